@@ -7,7 +7,7 @@
 
 use crate::{
     backtrace::BacktraceItem,
-    memory::{Address, Virtual},
+    memory::{self, Address, Virtual},
 };
 use aarch64_cpu::registers::*;
 use tock_registers::interfaces::Readable;
@@ -45,27 +45,16 @@ impl<'a> Iterator for StackFrameRecordIterator<'a> {
 
         // Need to abort if the pointer to the previous frame record is invalid.
         let prev_addr = Address::<Virtual>::new(previous as *const _ as usize);
-        if !prev_addr.is_valid_stack_addr() {
+        if !memory::is_valid_stack_addr(prev_addr) {
             // This allows to return the error and then stop on the next iteration.
             self.cur = &ABORT_FRAME;
             return Some(BacktraceItem::InvalidFramePointer(prev_addr));
         }
 
-        let ret = if !self.cur.link.is_valid_code_addr() {
+        let ret = if !memory::is_valid_code_addr(self.cur.link) {
             Some(BacktraceItem::InvalidLink(self.cur.link))
         } else {
-            // The link points to the instruction to be executed _after_ returning from a branch.
-            // However, we want to show the instruction that caused the branch, so subtract by one
-            // instruction.
-            //
-            // This might be called from panic!, so it must not panic itself on the subtraction.
-            let link = if self.cur.link >= Address::new(4) {
-                self.cur.link - 4
-            } else {
-                self.cur.link
-            };
-
-            Some(BacktraceItem::Link(link))
+            Some(BacktraceItem::Link(call_site(self.cur.link)))
         };
 
         // Advance the iterator.
@@ -75,9 +64,8 @@ impl<'a> Iterator for StackFrameRecordIterator<'a> {
     }
 }
 
-fn stack_frame_record_iterator<'a>() -> Option<StackFrameRecordIterator<'a>> {
-    let fp = Address::<Virtual>::new(FP.get() as usize);
-    if !fp.is_valid_stack_addr() {
+fn stack_frame_record_iterator<'a>(fp: Address<Virtual>) -> Option<StackFrameRecordIterator<'a>> {
+    if !memory::is_valid_stack_addr(fp) {
         return None;
     }
 
@@ -88,7 +76,28 @@ fn stack_frame_record_iterator<'a>() -> Option<StackFrameRecordIterator<'a>> {
 
 
 
+/// The link points to the instruction to be executed _after_ returning from a branch. However, we
+/// want to show the instruction that caused the branch, so subtract by one instruction.
+pub fn call_site(link: Address<Virtual>) -> Address<Virtual> {
+    // This might be called from panic!, so it must not panic itself on the subtraction.
+    if link >= Address::new(4) {
+        link - 4
+    } else {
+        link
+    }
+}
+
 /// Architectural implementation of the backtrace.
 pub fn backtrace(f: impl FnOnce(Option<&mut dyn Iterator<Item = BacktraceItem>>)) {
-    f(stack_frame_record_iterator().as_mut().map(|s| s as _))
+    let fp = Address::<Virtual>::new(FP.get() as usize);
+
+    backtrace_from(fp, f)
+}
+
+/// Architectural implementation of a backtrace that starts at the frame record pointed to by `fp`.
+pub fn backtrace_from(
+    fp: Address<Virtual>,
+    f: impl FnOnce(Option<&mut dyn Iterator<Item = BacktraceItem>>),
+) {
+    f(stack_frame_record_iterator(fp).as_mut().map(|s| s as _))
 }
@@ -7,7 +7,7 @@
 //!
 //! crate::cpu::arch_cpu
 
-use aarch64_cpu::asm;
+use aarch64_cpu::asm::{self, barrier};
 
 //--------------------------------------------------------------------------------------------------
 // Public Code
@@ -30,4 +30,22 @@ pub fn wait_forever() -> ! {
     loop {
         asm::wfe()
     }
-}
\ No newline at end of file
+}
+
+/// Clean and invalidate the data cache lines that cover the given range, to the point of coherency.
+///
+/// Needed around memory that is shared with bus masters which do not snoop the caches.
+#[inline(always)]
+pub fn clean_invalidate_dcache_range(start_addr: usize, size: usize) {
+    // The smallest data cache line size of the supported cores.
+    const CACHE_LINE_SIZE: usize = 64;
+
+    let end_addr_exclusive = start_addr + size;
+    let mut addr = start_addr & !(CACHE_LINE_SIZE - 1);
+    while addr < end_addr_exclusive {
+        unsafe { core::arch::asm!("dc civac, {}", in(reg) addr, options(nostack)) };
+        addr += CACHE_LINE_SIZE;
+    }
+
+    barrier::dsb(barrier::SY);
+}
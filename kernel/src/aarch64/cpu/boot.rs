@@ -5,7 +5,10 @@
 //!
 //! crate::cpu::boot::arch_boot
 
-use crate::{memory, memory::Address};
+use crate::{
+    memory,
+    memory::{Address, Physical},
+};
 use aarch64_cpu::{asm, registers::*};
 use core::{
     arch::global_asm,
@@ -20,6 +23,14 @@ global_asm!(
     CONST_CORE_ID_MASK = const 0b11
 );
 
+/// The physical address of the device tree blob that the firmware passes in `x0`. 0 if there is
+/// none.
+///
+/// Boot assembly code overwrites this value before any Rust code is executed. This given value
+/// here is just a (safe) dummy.
+#[no_mangle]
+static BOOT_DTB_PHYS_ADDR: u64 = 0;
+
 
 /// Prepares the transition from EL2 to EL1.
 ///
@@ -98,4 +109,13 @@ pub unsafe extern "C" fn _start_rust(
     // Use `eret` to "return" to EL1. Since virtual memory will already be enabled, this results in
     // execution of kernel_init() in EL1 from its _virtual address_.
     asm::eret()
-}
\ No newline at end of file
+}
+
+/// The physical address of the device tree blob that the firmware passed at boot, if any.
+pub fn boot_dtb_phys_addr() -> Option<Address<Physical>> {
+    // Read volatile is needed here to prevent the compiler from optimizing BOOT_DTB_PHYS_ADDR
+    // away.
+    let addr = unsafe { core::ptr::read_volatile(&BOOT_DTB_PHYS_ADDR) };
+
+    (addr != 0).then(|| Address::new(addr as usize))
+}
@@ -31,6 +31,9 @@
 // fn _start()
 //------------------------------------------------------------------------------
 _start:
+	// Keep the address of the device tree blob that the firmware passes in x0.
+	mov	x19, x0
+
 	// Only proceed if the core executes in EL2. Park it otherwise.
 	mrs	x0, CurrentEL
 	cmp	x0, {CONST_CURRENTEL_EL2}
@@ -84,6 +87,10 @@ _start:
 	b.eq	.L_parking_loop
 	str	w5, [x4]
 
+	// Store the address of the device tree blob in BOOT_DTB_PHYS_ADDR.
+	ADR_REL	x4, BOOT_DTB_PHYS_ADDR // provided by aarch64/cpu/boot.rs
+	str	x19, [x4]
+
 	// Jump to Rust code. x0, x1 and x2 hold the function arguments provided to _start_rust().
 	b	_start_rust
 
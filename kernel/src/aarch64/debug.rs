@@ -0,0 +1,455 @@
+//! Architectural self-hosted debug support.
+//!
+//! Since arch modules are imported into generic modules using the path attribute, the path of this
+//! file is:
+//!
+//! crate::debug::arch_debug
+//!
+//! # Resources
+//!
+//! - ARMv8-A Architecture Reference Manual, chapter D2 "AArch64 Self-hosted Debug".
+
+use crate::{
+    debug::WatchpointAccess,
+    info,
+    memory::{Address, Virtual},
+    synchronization,
+    synchronization::IRQSafeNullLock,
+};
+use aarch64_cpu::{asm::barrier, registers::*};
+use core::arch::asm;
+use tock_registers::{
+    interfaces::{ReadWriteable, Readable, Writeable},
+    register_bitfields,
+    registers::InMemoryRegister,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+// Debug registers.
+//
+// Descriptions taken from the ARMv8-A Architecture Reference Manual, chapter D13.
+register_bitfields! {u64,
+    /// Monitor Debug System Control Register.
+    MDSCR_EL1 [
+        /// Monitor debug events. Enables breakpoint, watchpoint and vector catch exceptions.
+        MDE OFFSET(15) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
+        /// Local (kernel) debug enable. Enables debug exceptions taken from the EL they target.
+        KDE OFFSET(13) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
+        /// Software step control.
+        SS OFFSET(0) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ]
+    ],
+
+    /// Debug Breakpoint Control Register n.
+    DBGBCR [
+        /// Breakpoint type.
+        BT OFFSET(20) NUMBITS(4) [
+            UnlinkedAddressMatch = 0b0000
+        ],
+
+        /// Byte address select. Must be all ones for A64 instructions.
+        BAS OFFSET(5) NUMBITS(4) [
+            A64 = 0b1111
+        ],
+
+        /// Privilege mode control. Together with HMC and SSC being zero, this selects the ELs in
+        /// which the breakpoint matches.
+        PMC OFFSET(1) NUMBITS(2) [
+            EL1 = 0b01
+        ],
+
+        /// Enable breakpoint.
+        E OFFSET(0) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ]
+    ],
+
+    /// Debug Watchpoint Control Register n.
+    DBGWCR [
+        /// Byte address select. Each bit selects one byte of the doubleword in DBGWVR.
+        BAS OFFSET(5) NUMBITS(8) [],
+
+        /// Load/store access control.
+        LSC OFFSET(3) NUMBITS(2) [
+            Load = 0b01,
+            Store = 0b10,
+            LoadStore = 0b11
+        ],
+
+        /// Privilege of access control. Selects the ELs in which the watchpoint matches.
+        PAC OFFSET(1) NUMBITS(2) [
+            EL1 = 0b01
+        ],
+
+        /// Enable watchpoint.
+        E OFFSET(0) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ]
+    ],
+
+    /// AArch64 Debug Feature Register 0.
+    ID_AA64DFR0_EL1 [
+        /// Number of watchpoints, minus 1.
+        WRPs OFFSET(20) NUMBITS(4) [],
+
+        /// Number of breakpoints, minus 1.
+        BRPs OFFSET(12) NUMBITS(4) []
+    ]
+}
+
+/// The architecture allows at most 16 breakpoints and 16 watchpoints.
+const MAX_SLOTS: usize = 16;
+
+/// A configured watchpoint.
+#[derive(Copy, Clone)]
+struct Watchpoint {
+    addr: Address<Virtual>,
+    len: usize,
+    access: WatchpointAccess,
+}
+
+struct HwDebugInner {
+    breakpoints: [Option<Address<Virtual>>; MAX_SLOTS],
+    watchpoints: [Option<Watchpoint>; MAX_SLOTS],
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static HW_DEBUG: IRQSafeNullLock<HwDebugInner> = IRQSafeNullLock::new(HwDebugInner::new());
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Generate a write accessor for system registers that exist in multiple numbered instances, like
+/// `DBGBVR<n>_EL1`. The register number is part of the instruction encoding, hence the match.
+macro_rules! indexed_sysreg_writer {
+    ($write_fn:ident, $prefix:literal, [$($i:literal),*]) => {
+        fn $write_fn(n: usize, value: u64) {
+            match n {
+                $($i => unsafe {
+                    asm!(
+                        concat!("msr ", $prefix, stringify!($i), "_EL1, {}"),
+                        in(reg) value,
+                        options(nomem, nostack)
+                    )
+                },)*
+                _ => panic!("Invalid debug register number {}", n),
+            }
+        }
+    };
+}
+
+indexed_sysreg_writer!(
+    write_dbgbvr,
+    "DBGBVR",
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+);
+indexed_sysreg_writer!(
+    write_dbgbcr,
+    "DBGBCR",
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+);
+indexed_sysreg_writer!(
+    write_dbgwvr,
+    "DBGWVR",
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+);
+indexed_sysreg_writer!(
+    write_dbgwcr,
+    "DBGWCR",
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+);
+
+fn read_mdscr() -> InMemoryRegister<u64, MDSCR_EL1::Register> {
+    let value: u64;
+    unsafe { asm!("mrs {}, MDSCR_EL1", out(reg) value, options(nomem, nostack)) };
+
+    InMemoryRegister::new(value)
+}
+
+fn write_mdscr(reg: InMemoryRegister<u64, MDSCR_EL1::Register>) {
+    unsafe { asm!("msr MDSCR_EL1, {}", in(reg) reg.get(), options(nomem, nostack)) };
+    barrier::isb(barrier::SY);
+}
+
+fn read_id_aa64dfr0() -> InMemoryRegister<u64, ID_AA64DFR0_EL1::Register> {
+    let value: u64;
+    unsafe { asm!("mrs {}, ID_AA64DFR0_EL1", out(reg) value, options(nomem, nostack)) };
+
+    InMemoryRegister::new(value)
+}
+
+fn breakpoint_control_value() -> u64 {
+    let ctrl = InMemoryRegister::<u64, DBGBCR::Register>::new(0);
+    ctrl.write(
+        DBGBCR::BT::UnlinkedAddressMatch + DBGBCR::BAS::A64 + DBGBCR::PMC::EL1 + DBGBCR::E::Enabled,
+    );
+
+    ctrl.get()
+}
+
+impl Watchpoint {
+    /// The doubleword-aligned address that goes into `DBGWVR<n>_EL1`.
+    fn aligned_addr(&self) -> u64 {
+        (self.addr.as_usize() & !0b111) as u64
+    }
+
+    fn control_value(&self) -> u64 {
+        let offset = self.addr.as_usize() & 0b111;
+        let bas = ((1u64 << self.len) - 1) << offset;
+
+        let lsc = match self.access {
+            WatchpointAccess::Load => DBGWCR::LSC::Load,
+            WatchpointAccess::Store => DBGWCR::LSC::Store,
+            WatchpointAccess::LoadStore => DBGWCR::LSC::LoadStore,
+        };
+
+        let ctrl = InMemoryRegister::<u64, DBGWCR::Register>::new(0);
+        ctrl.write(DBGWCR::BAS.val(bas) + lsc + DBGWCR::PAC::EL1 + DBGWCR::E::Enabled);
+
+        ctrl.get()
+    }
+}
+
+impl HwDebugInner {
+    const fn new() -> Self {
+        Self {
+            breakpoints: [None; MAX_SLOTS],
+            watchpoints: [None; MAX_SLOTS],
+        }
+    }
+
+    /// Write all configured breakpoints and watchpoints to the HW.
+    fn program_all(&self) {
+        for (n, bp) in self.breakpoints.iter().enumerate().take(num_breakpoints()) {
+            match bp {
+                None => write_dbgbcr(n, 0),
+                Some(addr) => {
+                    write_dbgbvr(n, addr.as_usize() as u64);
+                    write_dbgbcr(n, breakpoint_control_value());
+                }
+            }
+        }
+
+        for (n, wp) in self.watchpoints.iter().enumerate().take(num_watchpoints()) {
+            match wp {
+                None => write_dbgwcr(n, 0),
+                Some(wp) => {
+                    write_dbgwvr(n, wp.aligned_addr());
+                    write_dbgwcr(n, wp.control_value());
+                }
+            }
+        }
+
+        barrier::isb(barrier::SY);
+    }
+
+    /// Disable all breakpoints and watchpoints in HW, but keep their configuration.
+    fn disable_all(&self) {
+        for n in 0..num_breakpoints() {
+            write_dbgbcr(n, 0);
+        }
+
+        for n in 0..num_watchpoints() {
+            write_dbgwcr(n, 0);
+        }
+
+        barrier::isb(barrier::SY);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+use synchronization::interface::Mutex;
+
+/// The number of HW breakpoints implemented by the processing element.
+pub fn num_breakpoints() -> usize {
+    read_id_aa64dfr0().read(ID_AA64DFR0_EL1::BRPs) as usize + 1
+}
+
+/// The number of HW watchpoints implemented by the processing element.
+pub fn num_watchpoints() -> usize {
+    read_id_aa64dfr0().read(ID_AA64DFR0_EL1::WRPs) as usize + 1
+}
+
+/// Enable self-hosted debug exceptions on the executing core.
+///
+/// # Safety
+///
+/// - Changes the HW state of the executing core.
+/// - Must be called after exception handling was initialized, since debug exceptions might be
+///   generated from here on.
+pub unsafe fn init() {
+    // Debug exceptions are not generated while the OS lock is set, which it is out of reset.
+    OSLAR_EL1.write(OSLAR_EL1::OSLK::Unlocked);
+
+    HW_DEBUG.lock(|inner| inner.program_all());
+
+    let mdscr = read_mdscr();
+    mdscr.modify(MDSCR_EL1::MDE::Enabled + MDSCR_EL1::KDE::Enabled + MDSCR_EL1::SS::Disabled);
+    write_mdscr(mdscr);
+
+    // Boot code entered EL1 with debug exceptions masked.
+    DAIF.modify(DAIF::D::Unmasked);
+}
+
+/// Install a HW breakpoint on the instruction at `addr`.
+///
+/// Returns the number of the breakpoint register that was used.
+pub fn set_breakpoint(addr: Address<Virtual>) -> Result<usize, &'static str> {
+    if addr.as_usize() & 0b11 != 0 {
+        return Err("Breakpoint address not instruction aligned");
+    }
+
+    HW_DEBUG.lock(|inner| {
+        let num = num_breakpoints();
+        if inner.breakpoints[..num].contains(&Some(addr)) {
+            return Err("Breakpoint already set");
+        }
+
+        let n = inner.breakpoints[..num]
+            .iter()
+            .position(|bp| bp.is_none())
+            .ok_or("No free breakpoint register")?;
+
+        inner.breakpoints[n] = Some(addr);
+        inner.program_all();
+
+        Ok(n)
+    })
+}
+
+/// Remove the HW breakpoint at `addr`.
+pub fn clear_breakpoint(addr: Address<Virtual>) -> Result<(), &'static str> {
+    HW_DEBUG.lock(|inner| {
+        let n = inner
+            .breakpoints
+            .iter()
+            .position(|bp| *bp == Some(addr))
+            .ok_or("No breakpoint set at address")?;
+
+        inner.breakpoints[n] = None;
+        inner.program_all();
+
+        Ok(())
+    })
+}
+
+/// Install a HW watchpoint for accesses of type `access` to the `len` bytes starting at `addr`.
+///
+/// The watched bytes must be contained in a single naturally aligned doubleword.
+///
+/// Returns the number of the watchpoint register that was used.
+pub fn set_watchpoint(
+    addr: Address<Virtual>,
+    len: usize,
+    access: WatchpointAccess,
+) -> Result<usize, &'static str> {
+    if len == 0 || (addr.as_usize() & 0b111) + len > 8 {
+        return Err("Watched range must lie within one aligned doubleword");
+    }
+
+    HW_DEBUG.lock(|inner| {
+        let num = num_watchpoints();
+        if inner.watchpoints[..num]
+            .iter()
+            .flatten()
+            .any(|wp| wp.addr == addr)
+        {
+            return Err("Watchpoint already set");
+        }
+
+        let n = inner.watchpoints[..num]
+            .iter()
+            .position(|wp| wp.is_none())
+            .ok_or("No free watchpoint register")?;
+
+        inner.watchpoints[n] = Some(Watchpoint { addr, len, access });
+        inner.program_all();
+
+        Ok(n)
+    })
+}
+
+/// Remove the HW watchpoint at `addr`.
+pub fn clear_watchpoint(addr: Address<Virtual>) -> Result<(), &'static str> {
+    HW_DEBUG.lock(|inner| {
+        let n = inner
+            .watchpoints
+            .iter()
+            .position(|wp| matches!(wp, Some(wp) if wp.addr == addr))
+            .ok_or("No watchpoint set at address")?;
+
+        inner.watchpoints[n] = None;
+        inner.program_all();
+
+        Ok(())
+    })
+}
+
+/// Temporarily disable all HW breakpoints and watchpoints.
+///
+/// Used to step over the instruction that triggered one of them.
+pub fn suspend_breakpoints_and_watchpoints() {
+    HW_DEBUG.lock(|inner| inner.disable_all());
+}
+
+/// Re-enable all HW breakpoints and watchpoints after [`suspend_breakpoints_and_watchpoints`].
+pub fn resume_breakpoints_and_watchpoints() {
+    HW_DEBUG.lock(|inner| inner.program_all());
+}
+
+/// Enable or disable software stepping on the executing core.
+///
+/// The step itself only starts after an exception return with `PSTATE.SS` set. See
+/// `ExceptionContext::set_software_step()`.
+pub fn set_software_step(enable: bool) {
+    let mdscr = read_mdscr();
+    mdscr.modify(if enable {
+        MDSCR_EL1::SS::Enabled
+    } else {
+        MDSCR_EL1::SS::Disabled
+    });
+    write_mdscr(mdscr);
+}
+
+/// Print the configured breakpoints and watchpoints.
+pub fn print_state() {
+    HW_DEBUG.lock(|inner| {
+        info!("      Breakpoints ({} implemented):", num_breakpoints());
+        for (n, bp) in inner.breakpoints.iter().enumerate() {
+            if let Some(addr) = bp {
+                info!("            {: >2}. {}", n, addr);
+            }
+        }
+
+        info!("      Watchpoints ({} implemented):", num_watchpoints());
+        for (n, wp) in inner.watchpoints.iter().enumerate() {
+            if let Some(wp) = wp {
+                info!(
+                    "            {: >2}. {} | {} Byte | {:?}",
+                    n, wp.addr, wp.len, wp.access
+                );
+            }
+        }
+    });
+}
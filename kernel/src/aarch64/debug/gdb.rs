@@ -0,0 +1,148 @@
+//! Architectural part of the GDB stub.
+//!
+//! Since arch modules are imported into generic modules using the path attribute, the path of this
+//! file is:
+//!
+//! crate::debug::gdb::arch_gdb
+
+use crate::{
+    exception::ExceptionContext,
+    memory::{Address, Virtual},
+};
+use aarch64_cpu::asm::barrier;
+use core::arch::asm;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Target description that is handed to GDB through `qXfer:features:read`.
+///
+/// Only the core registers are described, so that the `g` packet does not have to carry the FP/SIMD
+/// registers, which are not part of the exception context.
+pub const TARGET_XML: &str = concat!(
+    r#"<?xml version="1.0"?>"#,
+    r#"<!DOCTYPE target SYSTEM "gdb-target.dtd">"#,
+    r#"<target version="1.0">"#,
+    r#"<architecture>aarch64</architecture>"#,
+    r#"<feature name="org.gnu.gdb.aarch64.core">"#,
+    r#"<reg name="x0" bitsize="64" type="int" regnum="0"/>"#,
+    r#"<reg name="x1" bitsize="64" type="int"/>"#,
+    r#"<reg name="x2" bitsize="64" type="int"/>"#,
+    r#"<reg name="x3" bitsize="64" type="int"/>"#,
+    r#"<reg name="x4" bitsize="64" type="int"/>"#,
+    r#"<reg name="x5" bitsize="64" type="int"/>"#,
+    r#"<reg name="x6" bitsize="64" type="int"/>"#,
+    r#"<reg name="x7" bitsize="64" type="int"/>"#,
+    r#"<reg name="x8" bitsize="64" type="int"/>"#,
+    r#"<reg name="x9" bitsize="64" type="int"/>"#,
+    r#"<reg name="x10" bitsize="64" type="int"/>"#,
+    r#"<reg name="x11" bitsize="64" type="int"/>"#,
+    r#"<reg name="x12" bitsize="64" type="int"/>"#,
+    r#"<reg name="x13" bitsize="64" type="int"/>"#,
+    r#"<reg name="x14" bitsize="64" type="int"/>"#,
+    r#"<reg name="x15" bitsize="64" type="int"/>"#,
+    r#"<reg name="x16" bitsize="64" type="int"/>"#,
+    r#"<reg name="x17" bitsize="64" type="int"/>"#,
+    r#"<reg name="x18" bitsize="64" type="int"/>"#,
+    r#"<reg name="x19" bitsize="64" type="int"/>"#,
+    r#"<reg name="x20" bitsize="64" type="int"/>"#,
+    r#"<reg name="x21" bitsize="64" type="int"/>"#,
+    r#"<reg name="x22" bitsize="64" type="int"/>"#,
+    r#"<reg name="x23" bitsize="64" type="int"/>"#,
+    r#"<reg name="x24" bitsize="64" type="int"/>"#,
+    r#"<reg name="x25" bitsize="64" type="int"/>"#,
+    r#"<reg name="x26" bitsize="64" type="int"/>"#,
+    r#"<reg name="x27" bitsize="64" type="int"/>"#,
+    r#"<reg name="x28" bitsize="64" type="int"/>"#,
+    r#"<reg name="x29" bitsize="64" type="int"/>"#,
+    r#"<reg name="x30" bitsize="64" type="int"/>"#,
+    r#"<reg name="sp" bitsize="64" type="data_ptr"/>"#,
+    r#"<reg name="pc" bitsize="64" type="code_ptr"/>"#,
+    r#"<reg name="cpsr" bitsize="32" type="int"/>"#,
+    r#"</feature>"#,
+    r#"</target>"#,
+);
+
+/// Number of registers in [`TARGET_XML`].
+pub const NUM_REGISTERS: usize = 34;
+
+/// Size of an instruction in bytes.
+pub const INSTRUCTION_SIZE: usize = 4;
+
+/// The instruction used for software breakpoints: `brk #0x400`.
+///
+/// A different immediate than the `brk #0` used in kernel code, so the two are distinguishable in a
+/// disassembly.
+pub const BREAKPOINT_INSTRUCTION: u32 = 0xd420_0000 | (0x400 << 5);
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const REG_SP: usize = 31;
+const REG_PC: usize = 32;
+const REG_CPSR: usize = 33;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// The size of register `n` in bytes.
+pub fn register_size(n: usize) -> usize {
+    if n == REG_CPSR {
+        4
+    } else {
+        8
+    }
+}
+
+/// Read register `n` from the exception context, using the numbering of [`TARGET_XML`].
+pub fn read_register(e: &ExceptionContext, n: usize) -> Option<u64> {
+    match n {
+        0..=30 => Some(e.gpr(n)),
+        REG_SP => Some(e.sp()),
+        REG_PC => Some(e.pc().as_usize() as u64),
+        REG_CPSR => Some(e.spsr() & 0xffff_ffff),
+        _ => None,
+    }
+}
+
+/// Write register `n` in the exception context, using the numbering of [`TARGET_XML`].
+pub fn write_register(e: &mut ExceptionContext, n: usize, value: u64) -> Result<(), &'static str> {
+    match n {
+        0..=30 => e.set_gpr(n, value),
+        // The stack pointer is implied by the location of the exception context.
+        REG_SP if value == e.sp() => (),
+        REG_SP => return Err("SP can not be changed"),
+        REG_PC => e.set_pc(Address::new(value as usize)),
+        REG_CPSR => e.set_spsr((e.spsr() & !0xffff_ffff) | (value & 0xffff_ffff)),
+        _ => return Err("Invalid register number"),
+    }
+
+    Ok(())
+}
+
+/// Check if `insn` is a `brk` instruction, irrespective of its immediate.
+pub fn is_breakpoint_instruction(insn: u32) -> bool {
+    (insn & 0xffe0_001f) == 0xd420_0000
+}
+
+/// Write an instruction and make it visible to instruction fetches.
+///
+/// # Safety
+///
+/// - `addr` must be a mapped, writable and 4 byte aligned kernel address.
+pub unsafe fn write_instruction(addr: Address<Virtual>, insn: u32) {
+    let ptr = addr.as_usize() as *mut u32;
+
+    core::ptr::write_volatile(ptr, insn);
+
+    // Clean the data cache line to the point of unification and invalidate the instruction cache
+    // line, so that the new instruction is fetched.
+    asm!("dc cvau, {}", in(reg) ptr, options(nostack));
+    barrier::dsb(barrier::ISH);
+    asm!("ic ivau, {}", in(reg) ptr, options(nostack));
+    barrier::dsb(barrier::ISH);
+    barrier::isb(barrier::SY);
+}
@@ -5,13 +5,13 @@
 //!
 //! crate::exception::arch_exception
 
-use crate::{exception, info, memory, println, symbols, warn};
+use crate::{backtrace, debug, exception, info, memory, println, symbols, warn};
 use aarch64_cpu::{asm::barrier, registers::*};
 use core::{arch::global_asm, cell::UnsafeCell, fmt};
 use core::arch::asm;
 use aarch64_cpu::asm::ret;
 use tock_registers::{
-    interfaces::{Readable, Writeable},
+    interfaces::{ReadWriteable, Readable, Writeable},
     registers::InMemoryRegister,
 };
 
@@ -23,6 +23,10 @@ global_asm!(
 );
 
 
+/// Size of the exception frame that `exception.s` puts on the stack. Includes the context and the
+/// frame record for backtracing.
+const EXCEPTION_FRAME_SIZE: usize = 16 * 18;
+
 /// Wrapper structs for memory copies of registers.
 #[repr(transparent)]
 struct SpsrEL1(InMemoryRegister<u64, SPSR_EL1::Register>);
@@ -30,7 +34,7 @@ struct EsrEL1(InMemoryRegister<u64, ESR_EL1::Register>);
 
 /// The exception context as it is stored on the stack on exception entry.
 #[repr(C)]
-struct ExceptionContext {
+pub struct ExceptionContext {
     /// General Purpose Registers.
     gpr: [u64; 30],
 
@@ -49,16 +53,41 @@ struct ExceptionContext {
 
 
 /// Prints verbose information about the exception and then panics.
-fn default_exception_handler(exc: &ExceptionContext) {
+fn default_exception_handler(exc: &mut ExceptionContext) {
+    // Give the debugger a chance to inspect the context first. Only panic if it lets go of the
+    // kernel.
+    #[cfg(feature = "gdb_stub")]
+    if debug::gdb::handle_exception(exc, gdb_signal(exc)) == debug::gdb::Exit::Resume {
+        return;
+    }
+
     println!("[***] Page fault ???");
     info!("[***] Page fault ???");
     panic!(
         "CPU Exception!\n\n\
+        {}\n\n\
         {}",
-        exc
+        exc,
+        exc.backtrace()
     );
 }
 
+/// Translate the exception class into the signal that is reported to GDB.
+#[cfg(feature = "gdb_stub")]
+fn gdb_signal(exc: &ExceptionContext) -> debug::gdb::Signal {
+    use debug::gdb::Signal;
+    use ESR_EL1::EC::Value::*;
+
+    match exc.exception_class() {
+        Some(InstrAbortLowerEL | InstrAbortCurrentEL | DataAbortLowerEL | DataAbortCurrentEL) => {
+            Signal::Segv
+        }
+        Some(PCAlignmentFault | SPAlignmentFault | SError) => Signal::Bus,
+        Some(Unknown | IllegalExecutionState) | None => Signal::Ill,
+        _ => Signal::Trap,
+    }
+}
+
 //------------------------------------------------------------------------------
 // Current, EL0
 //------------------------------------------------------------------------------
@@ -87,45 +116,58 @@ extern "C" fn current_elx_synchronous(e: &mut ExceptionContext) {
 
     if let Some(ESR_EL1::EC::Value::Brk64) = e.esr_el1.exception_class() {
         // This means the exception is due to a breakpoint instruction.
-        // Handler code goes here.
-        let elr = e.elr_el1; // Get Exception Link Register value
-
-        warn!("[!] EXCEPTION: BREAKPOINT\n");
-        warn!("\tInstruction Pointer (hex): {:#x}", elr);
-        warn!("\tStack Pointer (hex): {:#x}", get_fp());
-        info!("\tMoving onto next instruction...");
-        e.elr_el1 += 4;
+        // With the GDB stub, the debugger takes over. It also skips the `brk` on resume.
+        #[cfg(feature = "gdb_stub")]
+        debug::gdb::handle_exception(e, debug::gdb::Signal::Trap);
+
+        #[cfg(not(feature = "gdb_stub"))]
+        {
+            let elr = e.elr_el1; // Get Exception Link Register value
+
+            warn!("[!] EXCEPTION: BREAKPOINT\n");
+            warn!("\tInstruction Pointer (hex): {:#x}", elr);
+            warn!("\tStack Pointer (hex): {:#x}", get_fp());
+            info!("\tMoving onto next instruction...");
+            e.elr_el1 += 4;
+        }
         return;
     }
     else if let Some(ESR_EL1::EC::Value::DataAbortCurrentEL | ESR_EL1::EC::Value::DataAbortLowerEL) = e.esr_el1.exception_class() {
         // This means we have a data abort - i.e. a page fault.
-        // Call the appropriate function for handling this, or handle directly in here.
-
-        // Extract faulting address.
-        let elr: u64;
-        let far: u64;
-
-        unsafe {
-            asm!("mrs {:x}, ELR_EL1", out(reg) elr, options(nomem, nostack));
-            asm!("mrs {:x}, FAR_EL1", out(reg) far, options(nomem, nostack));
-        }
+        // With the GDB stub, the debugger inspects the fault.
+        #[cfg(feature = "gdb_stub")]
+        default_exception_handler(e);
 
-        // Here, elr contains the address of the faulting instruction,
-        // and far contains the faulting address.
+        #[cfg(not(feature = "gdb_stub"))]
+        {
+            // Extract faulting address.
+            let far: u64;
 
-        warn!("EXCEPTION: PAGE FAULT");
-        warn!("Accessed Address: {:#x}", far);
+            unsafe {
+                asm!("mrs {:x}, FAR_EL1", out(reg) far, options(nomem, nostack));
+            }
 
-        // if we want to survive this exception, we can just advance the exception link register for one instruction, so that execution can continue.
-        e.elr_el1 += 4;
-        return;
+            warn!("EXCEPTION: PAGE FAULT");
+            warn!("Accessed Address: {:#x}", far);
 
-        unsafe {
-            loop {
-                asm!("wfe");
-            }
+            // Advance the exception link register for one instruction, so that execution can
+            // continue.
+            e.elr_el1 += 4;
         }
     }
+    else if let Some(ESR_EL1::EC::Value::BreakpointCurrentEL) = e.esr_el1.exception_class() {
+        debug::handle_breakpoint(e);
+    }
+    else if let Some(ESR_EL1::EC::Value::WatchpointCurrentEL) = e.esr_el1.exception_class() {
+        // For watchpoints, FAR_EL1 holds the accessed data address.
+        let far = memory::Address::new(FAR_EL1.get() as usize);
+        let is_write = e.esr_el1.is_write();
+
+        debug::handle_watchpoint(e, far, is_write);
+    }
+    else if let Some(ESR_EL1::EC::Value::SoftwareStepCurrentEL) = e.esr_el1.exception_class() {
+        debug::handle_software_step(e);
+    }
     else {
         default_exception_handler(e);
     }
@@ -229,6 +271,15 @@ impl EsrEL1 {
     fn iss(&self) -> u64 {
         self.0.read(ESR_EL1::ISS)
     }
+
+    /// For data aborts and watchpoints, the WnR bit of the ISS tells if a write caused the
+    /// exception.
+    #[inline(always)]
+    fn is_write(&self) -> bool {
+        const ISS_WNR: u64 = 1 << 6;
+
+        (self.0.read(ESR_EL1::ISS) & ISS_WNR) != 0
+    }
 }
 
 /// Human readable ESR_EL1.
@@ -244,6 +295,9 @@ impl fmt::Display for EsrEL1 {
         // Exception class.
         let ec_translation = match self.exception_class() {
             Some(ESR_EL1::EC::Value::DataAbortCurrentEL) => "Data Abort, current EL",
+            Some(ESR_EL1::EC::Value::BreakpointCurrentEL) => "Breakpoint, current EL",
+            Some(ESR_EL1::EC::Value::SoftwareStepCurrentEL) => "Software Step, current EL",
+            Some(ESR_EL1::EC::Value::WatchpointCurrentEL) => "Watchpoint, current EL",
             _ => "N/A",
         };
         writeln!(f, " - {}", ec_translation)?;
@@ -259,6 +313,82 @@ impl ExceptionContext {
         self.esr_el1.exception_class()
     }
 
+    /// The address of the instruction that is executed on exception return.
+    #[inline(always)]
+    pub fn pc(&self) -> memory::Address<memory::Virtual> {
+        memory::Address::new(self.elr_el1 as usize)
+    }
+
+    /// Read general purpose register `x<n>`, with `n` in `0..=30`.
+    #[inline(always)]
+    pub fn gpr(&self, n: usize) -> u64 {
+        if n == 30 {
+            self.lr
+        } else {
+            self.gpr[n]
+        }
+    }
+
+    /// Overwrite general purpose register `x<n>`, with `n` in `0..=30`.
+    #[inline(always)]
+    pub fn set_gpr(&mut self, n: usize, value: u64) {
+        if n == 30 {
+            self.lr = value
+        } else {
+            self.gpr[n] = value
+        }
+    }
+
+    /// A backtrace of the interrupted context, starting at the instruction that was interrupted.
+    pub fn backtrace(&self) -> backtrace::BacktraceFrom {
+        backtrace::BacktraceFrom::new(
+            memory::Address::new(self.gpr[29] as usize),
+            self.pc(),
+            memory::Address::new(self.lr as usize),
+        )
+    }
+
+    /// The stack pointer of the interrupted context.
+    ///
+    /// Only valid for exceptions taken from the current EL, since the context is saved on the very
+    /// same stack.
+    #[inline(always)]
+    pub fn sp(&self) -> u64 {
+        self as *const Self as u64 + EXCEPTION_FRAME_SIZE as u64
+    }
+
+    /// Change the address of the instruction that is executed on exception return.
+    #[inline(always)]
+    pub fn set_pc(&mut self, pc: memory::Address<memory::Virtual>) {
+        self.elr_el1 = pc.as_usize() as u64;
+    }
+
+    /// The saved program status.
+    #[inline(always)]
+    pub fn spsr(&self) -> u64 {
+        self.spsr_el1.0.get()
+    }
+
+    /// Overwrite the saved program status.
+    #[inline(always)]
+    pub fn set_spsr(&mut self, value: u64) {
+        self.spsr_el1.0.set(value)
+    }
+
+    /// Set or clear `PSTATE.SS` for the exception return, so that exactly one instruction is
+    /// executed before a software step exception is taken.
+    ///
+    /// Debug exceptions must be unmasked in the interrupted context for the step to be taken.
+    pub fn set_software_step(&mut self, enable: bool) {
+        if enable {
+            self.spsr_el1
+                .0
+                .modify(SPSR_EL1::SS::SET + SPSR_EL1::D::Unmasked);
+        } else {
+            self.spsr_el1.0.modify(SPSR_EL1::SS::CLEAR);
+        }
+    }
+
     #[inline(always)]
     fn fault_address_valid(&self) -> bool {
         use ESR_EL1::EC::Value::*;
@@ -290,14 +420,10 @@ impl fmt::Display for ExceptionContext {
 
         writeln!(f, "{}", self.spsr_el1)?;
         writeln!(f, "ELR_EL1: {:#018x}", self.elr_el1)?;
-        writeln!(
-            f,
-            "      Symbol: {}",
-            match symbols::lookup_symbol(memory::Address::new(self.elr_el1 as usize)) {
-                Some(sym) => sym.name(),
-                _ => "Symbol not found",
-            }
-        )?;
+        writeln!(f, "      Symbol: {}", symbols::SymbolOffset::new(self.pc()))?;
+        if let Some(location) = symbols::lookup_source_location(self.pc()) {
+            writeln!(f, "      Source: {}", location)?;
+        }
         writeln!(f)?;
         writeln!(f, "General purpose register:")?;
 
@@ -322,6 +448,7 @@ fn get_sp() -> u64 {
     sp
 }
 
+#[cfg(not(feature = "gdb_stub"))]
 fn get_fp() -> u64 {
     let fp: u64;
     unsafe {
@@ -20,8 +20,8 @@ use crate::{
         Address, Physical, Virtual,
     },
 };
-use core::convert;
 use tock_registers::{
+    fields::FieldValue,
     interfaces::{Readable, Writeable},
     register_bitfields,
     registers::InMemoryRegister,
@@ -177,69 +177,69 @@ impl TableDescriptor {
 }
 
 /// Convert the kernel's generic memory attributes to HW-specific attributes of the MMU.
-impl convert::From<AttributeFields>
-for tock_registers::fields::FieldValue<u64, STAGE1_PAGE_DESCRIPTOR::Register>
-{
-    fn from(attribute_fields: AttributeFields) -> Self {
-        // Memory attributes.
-        let mut desc = match attribute_fields.mem_attributes {
-            MemAttributes::CacheableDRAM => {
-                STAGE1_PAGE_DESCRIPTOR::SH::InnerShareable
-                    + STAGE1_PAGE_DESCRIPTOR::AttrIndx.val(memory::mmu::arch_mmu::mair::NORMAL)
-            }
-            MemAttributes::Device => {
-                STAGE1_PAGE_DESCRIPTOR::SH::OuterShareable
-                    + STAGE1_PAGE_DESCRIPTOR::AttrIndx.val(memory::mmu::arch_mmu::mair::DEVICE)
-            }
-        };
-
-        // Access Permissions.
-        desc += match attribute_fields.acc_perms {
-            AccessPermissions::ReadOnly => STAGE1_PAGE_DESCRIPTOR::AP::RO_EL1,
-            AccessPermissions::ReadWrite => STAGE1_PAGE_DESCRIPTOR::AP::RW_EL1,
-        };
-
-        // The execute-never attribute is mapped to PXN in AArch64.
-        desc += if attribute_fields.execute_never {
-            STAGE1_PAGE_DESCRIPTOR::PXN::True
-        } else {
-            STAGE1_PAGE_DESCRIPTOR::PXN::False
-        };
-
-        // Always set unprivileged exectue-never as long as userspace is not implemented yet.
-        desc += STAGE1_PAGE_DESCRIPTOR::UXN::True;
-
-        desc
-    }
+fn attributes_to_descriptor_fields(
+    attribute_fields: &AttributeFields,
+) -> FieldValue<u64, STAGE1_PAGE_DESCRIPTOR::Register> {
+    // Memory attributes.
+    let mut desc = match attribute_fields.mem_attributes {
+        MemAttributes::CacheableDRAM => {
+            STAGE1_PAGE_DESCRIPTOR::SH::InnerShareable
+                + STAGE1_PAGE_DESCRIPTOR::AttrIndx.val(memory::mmu::arch_mmu::mair::NORMAL)
+        }
+        MemAttributes::Device => {
+            STAGE1_PAGE_DESCRIPTOR::SH::OuterShareable
+                + STAGE1_PAGE_DESCRIPTOR::AttrIndx.val(memory::mmu::arch_mmu::mair::DEVICE)
+        }
+        MemAttributes::WriteCombining => {
+            STAGE1_PAGE_DESCRIPTOR::SH::OuterShareable
+                + STAGE1_PAGE_DESCRIPTOR::AttrIndx
+                    .val(memory::mmu::arch_mmu::mair::NORMAL_NON_CACHEABLE)
+        }
+    };
+
+    // Access Permissions.
+    desc += match attribute_fields.acc_perms {
+        AccessPermissions::ReadOnly => STAGE1_PAGE_DESCRIPTOR::AP::RO_EL1,
+        AccessPermissions::ReadWrite => STAGE1_PAGE_DESCRIPTOR::AP::RW_EL1,
+    };
+
+    // The execute-never attribute is mapped to PXN in AArch64.
+    desc += if attribute_fields.execute_never {
+        STAGE1_PAGE_DESCRIPTOR::PXN::True
+    } else {
+        STAGE1_PAGE_DESCRIPTOR::PXN::False
+    };
+
+    // Always set unprivileged exectue-never as long as userspace is not implemented yet.
+    desc += STAGE1_PAGE_DESCRIPTOR::UXN::True;
+
+    desc
 }
 
 /// Convert the HW-specific attributes of the MMU to kernel's generic memory attributes.
-impl convert::TryFrom<InMemoryRegister<u64, STAGE1_PAGE_DESCRIPTOR::Register>> for AttributeFields {
-    type Error = &'static str;
-
-    fn try_from(
-        desc: InMemoryRegister<u64, STAGE1_PAGE_DESCRIPTOR::Register>,
-    ) -> Result<AttributeFields, Self::Error> {
-        let mem_attributes = match desc.read(STAGE1_PAGE_DESCRIPTOR::AttrIndx) {
-            memory::mmu::arch_mmu::mair::NORMAL => MemAttributes::CacheableDRAM,
-            memory::mmu::arch_mmu::mair::DEVICE => MemAttributes::Device,
-            _ => return Err("Unexpected memory attribute"),
-        };
-
-        let acc_perms = match desc.read_as_enum(STAGE1_PAGE_DESCRIPTOR::AP) {
-            Some(STAGE1_PAGE_DESCRIPTOR::AP::Value::RO_EL1) => AccessPermissions::ReadOnly,
-            Some(STAGE1_PAGE_DESCRIPTOR::AP::Value::RW_EL1) => AccessPermissions::ReadWrite,
-            _ => return Err("Unexpected access permission"),
-        };
-
-        let execute_never = desc.read(STAGE1_PAGE_DESCRIPTOR::PXN) > 0;
-
-        Ok(AttributeFields {
-            mem_attributes,
-            acc_perms,
-            execute_never,
-        })
-    }
+fn descriptor_to_attributes(
+    desc: InMemoryRegister<u64, STAGE1_PAGE_DESCRIPTOR::Register>,
+) -> Result<AttributeFields, &'static str> {
+    let mem_attributes = match desc.read(STAGE1_PAGE_DESCRIPTOR::AttrIndx) {
+        memory::mmu::arch_mmu::mair::NORMAL => MemAttributes::CacheableDRAM,
+        memory::mmu::arch_mmu::mair::DEVICE => MemAttributes::Device,
+        memory::mmu::arch_mmu::mair::NORMAL_NON_CACHEABLE => MemAttributes::WriteCombining,
+        _ => return Err("Unexpected memory attribute"),
+    };
+
+    let acc_perms = match desc.read_as_enum(STAGE1_PAGE_DESCRIPTOR::AP) {
+        Some(STAGE1_PAGE_DESCRIPTOR::AP::Value::RO_EL1) => AccessPermissions::ReadOnly,
+        Some(STAGE1_PAGE_DESCRIPTOR::AP::Value::RW_EL1) => AccessPermissions::ReadWrite,
+        _ => return Err("Unexpected access permission"),
+    };
+
+    let execute_never = desc.read(STAGE1_PAGE_DESCRIPTOR::PXN) > 0;
+
+    Ok(AttributeFields {
+        mem_attributes,
+        acc_perms,
+        execute_never,
+    })
 }
 
 impl PageDescriptor {
@@ -263,7 +263,7 @@ impl PageDescriptor {
                 + STAGE1_PAGE_DESCRIPTOR::AF::True
                 + STAGE1_PAGE_DESCRIPTOR::TYPE::Page
                 + STAGE1_PAGE_DESCRIPTOR::VALID::True
-                + (*attribute_fields).into(),
+                + attributes_to_descriptor_fields(attribute_fields),
         );
 
         Self { value: val.get() }
@@ -285,7 +285,7 @@ impl PageDescriptor {
 
     /// Returns the attributes.
     fn try_attributes(&self) -> Result<AttributeFields, &'static str> {
-        InMemoryRegister::<u64, STAGE1_PAGE_DESCRIPTOR::Register>::new(self.value).try_into()
+        descriptor_to_attributes(InMemoryRegister::new(self.value))
     }
 }
 
@@ -327,6 +327,11 @@ FixedSizeTranslationTable<NUM_TABLES, START_FROM_TOP>
         Self::_new(true)
     }
 
+    #[cfg(test)]
+    pub fn new_for_runtime() -> Self {
+        Self::_new(false)
+    }
+
     /// Helper to calculate the lvl2 and lvl3 indices from an address.
     #[inline(always)]
     fn lvl2_lvl3_index_from_page_addr(
@@ -470,9 +475,40 @@ for FixedSizeTranslationTable<NUM_TABLES, START_FROM_TOP>
         &self,
         virt_addr: Address<Virtual>,
     ) -> Result<Address<Physical>, &'static str> {
-        let virt_page = PageAddress::from(virt_addr.align_down_page());
+        let virt_page = PageAddress::from(virt_addr.align_down(Granule64KiB::SIZE));
         let phys_page = self.try_virt_page_addr_to_phys_page_addr(virt_page)?;
 
-        Ok(phys_page.into_inner() + virt_addr.offset_into_page())
+        Ok(phys_page.into_inner() + virt_addr.offset_into(Granule64KiB::SIZE))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+pub type MinSizeTranslationTable = FixedSizeTranslationTable<1, true>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// Check if the size of `struct TableDescriptor` is as expected.
+    #[kernel_test]
+    fn size_of_tabledescriptor_equals_64_bit() {
+        assert_eq!(
+            core::mem::size_of::<TableDescriptor>(),
+            core::mem::size_of::<u64>()
+        );
+    }
+
+    /// Check if the size of `struct PageDescriptor` is as expected.
+    #[kernel_test]
+    fn size_of_pagedescriptor_equals_64_bit() {
+        assert_eq!(
+            core::mem::size_of::<PageDescriptor>(),
+            core::mem::size_of::<u64>()
+        );
     }
 }
@@ -0,0 +1,34 @@
+//! Architectural semihosting code.
+//!
+//! # Orientation
+//!
+//! Since arch modules are imported into generic modules using the path attribute, the path of this
+//! file is:
+//!
+//! crate::semihosting::arch_semihosting
+
+use core::arch::asm;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Trap into the semihosting host.
+///
+/// # Safety
+///
+/// - `param` must be what the operation expects, usually the address of a parameter block.
+#[inline(always)]
+pub unsafe fn call(op: u32, param: usize) -> usize {
+    let ret: usize;
+
+    // The host may read and write memory that `param` points to, so no `nomem` or `readonly`.
+    asm!(
+        "hlt #0xf000",
+        inout("x0") op as usize => ret,
+        in("x1") param,
+        options(nostack)
+    );
+
+    ret
+}
@@ -4,7 +4,7 @@
 mod arch_backtrace;
 
 use crate::{
-    memory::{Address, Virtual},
+    memory::{self, Address, Virtual},
     symbols,
 };
 use core::fmt;
@@ -21,77 +21,170 @@ pub enum BacktraceItem {
 /// Pseudo-struct for printing a backtrace using its fmt::Display implementation.
 pub struct Backtrace;
 
+/// Pseudo-struct for printing the backtrace of an interrupted context, e.g. the code that caused an
+/// exception. Seeded from the context's frame pointer, program counter and link register instead of
+/// the current frame pointer.
+pub struct BacktraceFrom {
+    fp: Address<Virtual>,
+    pc: Address<Virtual>,
+    lr: Address<Virtual>,
+}
+
+
+fn same_function(a: Address<Virtual>, b: Address<Virtual>) -> bool {
+    match (symbols::lookup_symbol(a), symbols::lookup_symbol(b)) {
+        (Some(x), Some(y)) => core::ptr::eq(x, y),
+        _ => false,
+    }
+}
+
+fn fmt_backtrace(
+    f: &mut fmt::Formatter,
+    title: &str,
+    maybe_iter: Option<&mut dyn Iterator<Item = BacktraceItem>>,
+) -> fmt::Result {
+    writeln!(f, "{}", title)?;
+    writeln!(
+        f,
+        "      ----------------------------------------------------------------------------------------------"
+    )?;
+    writeln!(
+        f,
+        "          Address            Function containing address"
+    )?;
+    writeln!(
+        f,
+        "      ----------------------------------------------------------------------------------------------"
+    )?;
+
+    match maybe_iter {
+        None => writeln!(f, "ERROR! No valid stack frame found")?,
+        Some(iter) => {
+            for (i, backtrace_res) in iter.enumerate() {
+                match backtrace_res {
+                    BacktraceItem::InvalidFramePointer(addr) => {
+                        writeln!(
+                            f,
+                            "      {:>2}. ERROR! \
+                            Encountered invalid frame pointer ({}) during backtrace",
+                            i + 1,
+                            addr
+                        )?;
+                    }
+                    BacktraceItem::InvalidLink(addr) => {
+                        writeln!(
+                            f,
+                            "      {:>2}. ERROR! \
+                            Link address ({}) is not contained in kernel .text section",
+                            i + 1,
+                            addr
+                        )?;
+                    }
+                    BacktraceItem::Link(addr) => {
+                        writeln!(
+                            f,
+                            "      {:>2}. {:016x} | {}",
+                            i + 1,
+                            addr.as_usize(),
+                            symbols::SymbolOffset::new(addr)
+                        )?;
+
+                        if let Some(location) = symbols::lookup_source_location(addr) {
+                            writeln!(f, "                             at {}", location)?;
+                        }
+                    }
+                };
+            }
+        }
+    }
+
+    writeln!(
+        f,
+        "      ----------------------------------------------------------------------------------------------"
+    )
+}
+
 
 impl fmt::Display for Backtrace {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "Backtrace:")?;
-        writeln!(
-            f,
-            "      ----------------------------------------------------------------------------------------------"
-        )?;
-        writeln!(
-            f,
-            "          Address            Function containing address"
-        )?;
-        writeln!(
-            f,
-            "      ----------------------------------------------------------------------------------------------"
-        )?;
-
         let mut fmt_res: fmt::Result = Ok(());
         let trace_formatter =
             |maybe_iter: Option<&mut dyn Iterator<Item = BacktraceItem>>| match maybe_iter {
-                None => fmt_res = writeln!(f, "ERROR! No valid stack frame found"),
+                None => fmt_res = fmt_backtrace(f, "Backtrace:", None),
                 Some(iter) => {
                     // Since the backtrace is printed, the first function is always
                     // core::fmt::write. Skip 1 so it is excluded and doesn't bloat the output.
-                    for (i, backtrace_res) in iter.skip(1).enumerate() {
-                        match backtrace_res {
-                            BacktraceItem::InvalidFramePointer(addr) => {
-                                fmt_res = writeln!(
-                                    f,
-                                    "      {:>2}. ERROR! \
-                                    Encountered invalid frame pointer ({}) during backtrace",
-                                    i + 1,
-                                    addr
-                                );
-                            }
-                            BacktraceItem::InvalidLink(addr) => {
-                                fmt_res = writeln!(
-                                    f,
-                                    "      {:>2}. ERROR! \
-                                    Link address ({}) is not contained in kernel .text section",
-                                    i + 1,
-                                    addr
-                                );
-                            }
-                            BacktraceItem::Link(addr) => {
-                                fmt_res = writeln!(
-                                    f,
-                                    "      {:>2}. {:016x} | {:<50}",
-                                    i + 1,
-                                    addr.as_usize(),
-                                    match symbols::lookup_symbol(addr) {
-                                        Some(sym) => sym.name(),
-                                        _ => "Symbol not found",
-                                    }
-                                )
-                            }
-                        };
-
-                        if fmt_res.is_err() {
-                            break;
-                        }
-                    }
+                    fmt_res = fmt_backtrace(f, "Backtrace:", Some(&mut iter.skip(1)))
                 }
             };
 
         arch_backtrace::backtrace(trace_formatter);
-        fmt_res?;
+        fmt_res
+    }
+}
 
-        writeln!(
-            f,
-            "      ----------------------------------------------------------------------------------------------"
-        )
+impl BacktraceFrom {
+    /// Create an instance from the frame pointer, program counter and link register of the
+    /// interrupted context.
+    pub fn new(fp: Address<Virtual>, pc: Address<Virtual>, lr: Address<Virtual>) -> Self {
+        Self { fp, pc, lr }
     }
-}
\ No newline at end of file
+
+    fn code_item(addr: Address<Virtual>) -> BacktraceItem {
+        if memory::is_valid_code_addr(addr) {
+            BacktraceItem::Link(addr)
+        } else {
+            BacktraceItem::InvalidLink(addr)
+        }
+    }
+
+    /// The link register only adds information if the interrupted function did not yet push it into
+    /// its frame record, e.g. when it was stopped in its prologue. Omit it if it belongs to the
+    /// interrupted function itself, or if the frame records already contain it.
+    fn lr_item(&self, first_frame: Option<&BacktraceItem>) -> Option<BacktraceItem> {
+        let lr = arch_backtrace::call_site(self.lr);
+
+        if same_function(lr, self.pc) {
+            return None;
+        }
+
+        match first_frame {
+            Some(BacktraceItem::Link(addr)) if *addr == lr => None,
+            _ => Some(Self::code_item(lr)),
+        }
+    }
+}
+
+impl fmt::Display for BacktraceFrom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut fmt_res: fmt::Result = Ok(());
+        const TITLE: &str = "Backtrace of the interrupted context:";
+
+        let trace_formatter = |maybe_iter: Option<&mut dyn Iterator<Item = BacktraceItem>>| {
+            // The program counter points to the interrupted instruction itself.
+            let pc = core::iter::once(Self::code_item(self.pc));
+
+            fmt_res = match maybe_iter {
+                None => {
+                    let lr = self.lr_item(None);
+                    let error = BacktraceItem::InvalidFramePointer(self.fp);
+
+                    fmt_backtrace(
+                        f,
+                        TITLE,
+                        Some(&mut pc.chain(lr).chain(core::iter::once(error))),
+                    )
+                }
+                Some(iter) => {
+                    let mut frames = iter.peekable();
+                    let lr = self.lr_item(frames.peek());
+
+                    fmt_backtrace(f, TITLE, Some(&mut pc.chain(lr).chain(frames)))
+                }
+            }
+        };
+
+        arch_backtrace::backtrace_from(self.fp, trace_formatter);
+        fmt_res
+    }
+}
@@ -0,0 +1,102 @@
+//! Block devices.
+//!
+//! Storage that is read and written in whole blocks. Drivers register their devices by name during
+//! kernel init, filesystems look them up.
+
+use crate::{
+    info, shell,
+    synchronization::{interface::ReadWriteEx, InitStateLock},
+};
+use alloc::vec::Vec;
+
+pub use kernel_core::block::{check_request, interface};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+struct Entry {
+    name: &'static str,
+    device: &'static (dyn interface::BlockDevice + Sync),
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static BLOCK_DEVICES: InitStateLock<Vec<Entry>> = InitStateLock::new(Vec::new());
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn cmd_blocks(args: &[&str]) -> Result<(), &'static str> {
+    shell::no_args(args)?;
+    print_block_devices();
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Register a block device.
+///
+/// Only possible during kernel init.
+pub fn register_block_device(
+    name: &'static str,
+    device: &'static (dyn interface::BlockDevice + Sync),
+) -> Result<(), &'static str> {
+    if block_device(name).is_some() {
+        return Err("Block device already registered");
+    }
+
+    BLOCK_DEVICES.write(|devices| devices.push(Entry { name, device }));
+
+    Ok(())
+}
+
+/// Look up a block device by name.
+pub fn block_device(name: &str) -> Option<&'static (dyn interface::BlockDevice + Sync)> {
+    BLOCK_DEVICES.read(|devices| {
+        devices
+            .iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| entry.device)
+    })
+}
+
+/// The names of the registered block devices, in the order of registration.
+pub fn block_device_names() -> Vec<&'static str> {
+    BLOCK_DEVICES.read(|devices| devices.iter().map(|entry| entry.name).collect())
+}
+
+/// Print the registered block devices.
+pub fn print_block_devices() {
+    BLOCK_DEVICES.read(|devices| {
+        for entry in devices {
+            let size = entry.device.num_blocks() as usize * entry.device.block_size();
+            let (size, unit) = crate::common::size_human_readable_ceil(size);
+
+            info!(
+                "      {:<8} {:>10} blocks of {} Byte, {} {}",
+                entry.name,
+                entry.device.num_blocks(),
+                entry.device.block_size(),
+                size,
+                unit
+            );
+        }
+    })
+}
+
+/// Register the shell commands of the block layer.
+pub fn register_shell_commands() -> Result<(), &'static str> {
+    shell::register_command(shell::Command::new(
+        "blocks",
+        "blocks",
+        "List the block devices",
+        cmd_blocks,
+    ))
+}
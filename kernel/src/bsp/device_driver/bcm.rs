@@ -1,11 +1,23 @@
 //! BCM driver top level.
 
+mod bcm2xxx_emmc;
+mod bcm2xxx_framebuffer;
 mod bcm2xxx_gpio;
 #[cfg(feature = "bsp_rpi3")]
 mod bcm2xxx_interrupt_controller;
+mod bcm2xxx_mailbox;
+mod bcm2xxx_mini_uart;
 mod bcm2xxx_pl011_uart;
+mod bcm2xxx_rng;
+mod bcm2xxx_watchdog;
 
+pub use bcm2xxx_emmc::*;
+pub use bcm2xxx_framebuffer::*;
 pub use bcm2xxx_gpio::*;
 #[cfg(feature = "bsp_rpi3")]
 pub use bcm2xxx_interrupt_controller::*;
-pub use bcm2xxx_pl011_uart::*;
\ No newline at end of file
+pub use bcm2xxx_mailbox::*;
+pub use bcm2xxx_mini_uart::*;
+pub use bcm2xxx_pl011_uart::*;
+pub use bcm2xxx_rng::*;
+pub use bcm2xxx_watchdog::*;
\ No newline at end of file
@@ -0,0 +1,764 @@
+//! EMMC Driver.
+//!
+//! The SD host controller of the BCM2835 family, an Arasan SDHCI. Only SD memory cards are
+//! supported, in the default speed mode. Data goes through the buffer data port, with the
+//! completion of each step signaled by the controller's IRQ.
+//!
+//! The IRQ handler latches the interrupt bits, and the waiting caller picks them up. While IRQs are
+//! masked on the calling core, e.g. before the kernel unmasks them or in panic paths, the caller
+//! polls the interrupt register instead.
+
+use crate::{
+    block, bsp::device_driver::common::MMIODerefWrapper, common, driver, exception,
+    exception::asynchronous::IRQNumber, info, memory::Address, memory::Virtual, synchronization,
+    synchronization::IRQSafeNullLock, time,
+};
+use core::{
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    time::Duration,
+};
+use kernel_core::block::sd;
+use tock_registers::{
+    interfaces::{ReadWriteable, Readable, Writeable},
+    register_bitfields, register_structs,
+    registers::ReadWrite,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+// EMMC registers.
+//
+// Descriptions taken from
+// - https://github.com/raspberrypi/documentation/files/1888662/BCM2837-ARM-Peripherals.-.Revised.-.V2-1.pdf
+// - the SD Host Controller Simplified Specification, which the controller follows.
+register_bitfields! {
+    u32,
+
+    /// Command and Transfer Mode
+    CMDTM [
+        /// Index of the command.
+        CMD_INDEX OFFSET(24) NUMBITS(6) [],
+
+        /// Whether the command transfers data.
+        CMD_ISDATA OFFSET(21) NUMBITS(1) [],
+
+        /// Check that the response has the same index as the command.
+        CMD_IXCHK_EN OFFSET(20) NUMBITS(1) [],
+
+        /// Check the CRC of the response.
+        CMD_CRCCHK_EN OFFSET(19) NUMBITS(1) [],
+
+        /// The response type.
+        CMD_RSPNS_TYPE OFFSET(16) NUMBITS(2) [
+            None = 0b00,
+            Bits136 = 0b01,
+            Bits48 = 0b10,
+            Bits48Busy = 0b11
+        ],
+
+        /// Whether the transfer has more than one block.
+        TM_MULTI_BLOCK OFFSET(5) NUMBITS(1) [],
+
+        /// Direction of the data transfer.
+        TM_DAT_DIR OFFSET(4) NUMBITS(1) [
+            HostToCard = 0,
+            CardToHost = 1
+        ],
+
+        /// The command that is sent after the data transfer.
+        TM_AUTO_CMD_EN OFFSET(2) NUMBITS(2) [
+            None = 0b00,
+            Cmd12 = 0b01
+        ],
+
+        /// Enable the block counter.
+        TM_BLKCNT_EN OFFSET(1) NUMBITS(1) []
+    ],
+
+    /// Status
+    STATUS [
+        /// The data lines are in use.
+        DAT_INHIBIT OFFSET(1) NUMBITS(1) [],
+
+        /// The command line is in use.
+        CMD_INHIBIT OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// Host Configuration 0
+    CONTROL0 [
+        /// Use four data lines.
+        HCTL_DWIDTH OFFSET(1) NUMBITS(1) []
+    ],
+
+    /// Host Configuration 1
+    CONTROL1 [
+        /// Reset the data handling circuit.
+        SRST_DATA OFFSET(26) NUMBITS(1) [],
+
+        /// Reset the command handling circuit.
+        SRST_CMD OFFSET(25) NUMBITS(1) [],
+
+        /// Reset the complete host circuit.
+        SRST_HC OFFSET(24) NUMBITS(1) [],
+
+        /// Data timeout, in powers of two of the SD clock, starting at 2^13.
+        DATA_TOUNIT OFFSET(16) NUMBITS(4) [
+            Max = 0b1110
+        ],
+
+        /// Low bits of the SD clock divisor.
+        CLK_FREQ8 OFFSET(8) NUMBITS(8) [],
+
+        /// High bits of the SD clock divisor.
+        CLK_FREQ_MS2 OFFSET(6) NUMBITS(2) [],
+
+        /// Enable the SD clock.
+        CLK_EN OFFSET(2) NUMBITS(1) [],
+
+        /// The SD clock is stable.
+        CLK_STABLE OFFSET(1) NUMBITS(1) [],
+
+        /// Enable the internal clock.
+        CLK_INTLEN OFFSET(0) NUMBITS(1) []
+    ]
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    RegisterBlock {
+        (0x00 => _reserved1),
+        (0x04 => BLKSIZECNT: ReadWrite<u32>),
+        (0x08 => ARG1: ReadWrite<u32>),
+        (0x0C => CMDTM: ReadWrite<u32, CMDTM::Register>),
+        (0x10 => RESP: [ReadWrite<u32>; 4]),
+        (0x20 => DATA: ReadWrite<u32>),
+        (0x24 => STATUS: ReadWrite<u32, STATUS::Register>),
+        (0x28 => CONTROL0: ReadWrite<u32, CONTROL0::Register>),
+        (0x2C => CONTROL1: ReadWrite<u32, CONTROL1::Register>),
+        (0x30 => INTERRUPT: ReadWrite<u32>),
+        (0x34 => IRPT_MASK: ReadWrite<u32>),
+        (0x38 => IRPT_EN: ReadWrite<u32>),
+        (0x3C => CONTROL2: ReadWrite<u32>),
+        (0x40 => _reserved2),
+        (0x100 => @END),
+    }
+}
+
+/// Abstraction for the associated MMIO registers.
+type Registers = MMIODerefWrapper<RegisterBlock>;
+
+/// Bits of the INTERRUPT, IRPT_MASK and IRPT_EN registers.
+mod interrupt {
+    /// A command finished.
+    pub const CMD_DONE: u32 = 1 << 0;
+
+    /// A data transfer, or the busy phase of a command, finished.
+    pub const DATA_DONE: u32 = 1 << 1;
+
+    /// The buffer can take the next block.
+    pub const WRITE_READY: u32 = 1 << 4;
+
+    /// The buffer holds the next block.
+    pub const READ_READY: u32 = 1 << 5;
+
+    /// Any error, in the upper half, plus the summary bit.
+    pub const ERROR: u32 = 0xFFFF_8000;
+
+    /// Command or data timeout errors.
+    pub const TIMEOUT: u32 = (1 << 16) | (1 << 20);
+
+    /// The bits that the driver waits for.
+    pub const USED: u32 = CMD_DONE | DATA_DONE | WRITE_READY | READ_READY | ERROR;
+}
+
+#[derive(Copy, Clone)]
+enum Response {
+    None,
+    R1,
+    R1b,
+    R2,
+    R3,
+    R6,
+    R7,
+}
+
+#[derive(Copy, Clone)]
+struct Command {
+    index: u32,
+    response: Response,
+}
+
+/// The direction of a data transfer.
+#[derive(Copy, Clone)]
+enum Direction {
+    Read,
+    Write,
+}
+
+/// The memory side of a data transfer.
+enum Buffer<'a> {
+    Read(&'a mut [u8]),
+    Write(&'a [u8]),
+}
+
+const GO_IDLE_STATE: Command = Command::new(0, Response::None);
+const ALL_SEND_CID: Command = Command::new(2, Response::R2);
+const SEND_RELATIVE_ADDR: Command = Command::new(3, Response::R6);
+const SELECT_CARD: Command = Command::new(7, Response::R1b);
+const SEND_IF_COND: Command = Command::new(8, Response::R7);
+const SEND_CSD: Command = Command::new(9, Response::R2);
+const SET_BLOCKLEN: Command = Command::new(16, Response::R1);
+const READ_SINGLE_BLOCK: Command = Command::new(17, Response::R1);
+const READ_MULTIPLE_BLOCK: Command = Command::new(18, Response::R1);
+const WRITE_BLOCK: Command = Command::new(24, Response::R1);
+const WRITE_MULTIPLE_BLOCK: Command = Command::new(25, Response::R1);
+const APP_CMD: Command = Command::new(55, Response::R1);
+
+/// Application specific commands, to be preceded by `APP_CMD`.
+const SET_BUS_WIDTH: Command = Command::new(6, Response::R1);
+const SD_SEND_OP_COND: Command = Command::new(41, Response::R3);
+
+/// Check pattern and 2.7 V to 3.6 V supply voltage for `SEND_IF_COND`.
+const IF_COND_CHECK: u32 = 0x1AA;
+
+/// The supply voltage window for `SD_SEND_OP_COND`, 2.7 V to 3.6 V.
+const OCR_VOLTAGE_WINDOW: u32 = 0x00FF_8000;
+const OCR_HIGH_CAPACITY: u32 = 1 << 30;
+const OCR_POWERED_UP: u32 = 1 << 31;
+
+/// The clock while the card is identified, and for data transfers.
+const IDENTIFICATION_CLOCK_HZ: u32 = 400_000;
+const DEFAULT_SPEED_CLOCK_HZ: u32 = 25_000_000;
+
+/// The upper limit of the block counter.
+const MAX_BLOCKS_PER_TRANSFER: usize = u16::MAX as usize;
+
+/// How long a single step of the controller may take.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long the card may take to power up.
+const POWER_UP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The card after identification.
+#[derive(Copy, Clone)]
+struct Card {
+    high_capacity: bool,
+    num_blocks: u64,
+}
+
+struct EmmcInner {
+    registers: Registers,
+    card: Option<Card>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Representation of the EMMC controller.
+pub struct Emmc {
+    inner: IRQSafeNullLock<EmmcInner>,
+
+    /// Interrupt bits that arrived and were not consumed yet.
+    events: AtomicU32,
+
+    /// Whether the IRQ handler collects the events.
+    irq_driven: AtomicBool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Spin until `done` returns true, or fail after `TIMEOUT`.
+fn spin_until(mut done: impl FnMut() -> bool, err: &'static str) -> Result<(), &'static str> {
+    let deadline = time::time_manager().uptime() + TIMEOUT;
+
+    while !done() {
+        if time::time_manager().uptime() > deadline {
+            return Err(err);
+        }
+        core::hint::spin_loop();
+    }
+
+    Ok(())
+}
+
+impl Command {
+    const fn new(index: u32, response: Response) -> Self {
+        Self { index, response }
+    }
+
+    /// Whether the busy signal of the card is waited for after the response.
+    fn has_busy(&self) -> bool {
+        matches!(self.response, Response::R1b)
+    }
+
+    fn cmdtm(&self) -> u32 {
+        use CMDTM::*;
+
+        let response = match self.response {
+            Response::None => CMD_RSPNS_TYPE::None,
+            Response::R1 | Response::R6 | Response::R7 => {
+                CMD_RSPNS_TYPE::Bits48 + CMD_CRCCHK_EN::SET + CMD_IXCHK_EN::SET
+            }
+            Response::R1b => CMD_RSPNS_TYPE::Bits48Busy + CMD_CRCCHK_EN::SET + CMD_IXCHK_EN::SET,
+            Response::R2 => CMD_RSPNS_TYPE::Bits136 + CMD_CRCCHK_EN::SET,
+            Response::R3 => CMD_RSPNS_TYPE::Bits48,
+        };
+
+        (CMD_INDEX.val(self.index) + response).value
+    }
+}
+
+impl Buffer<'_> {
+    fn direction(&self) -> Direction {
+        match self {
+            Self::Read(_) => Direction::Read,
+            Self::Write(_) => Direction::Write,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Read(buf) => buf.len(),
+            Self::Write(buf) => buf.len(),
+        }
+    }
+}
+
+impl EmmcInner {
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO start address.
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            registers: Registers::new(mmio_start_addr),
+            card: None,
+        }
+    }
+
+    /// Reset the controller and enable the status bits that the driver uses.
+    fn reset(&mut self) -> Result<(), &'static str> {
+        self.registers.CONTROL1.write(CONTROL1::SRST_HC::SET);
+        spin_until(
+            || !self.registers.CONTROL1.is_set(CONTROL1::SRST_HC),
+            "EMMC controller reset timed out",
+        )?;
+
+        self.registers.CONTROL0.set(0);
+        self.registers.CONTROL2.set(0);
+        self.registers.IRPT_EN.set(0);
+        self.registers.IRPT_MASK.set(interrupt::USED);
+        self.registers.INTERRUPT.set(u32::MAX);
+        self.card = None;
+
+        Ok(())
+    }
+
+    /// Reset the command and data circuits after an error.
+    fn reset_lines(&mut self) {
+        self.registers
+            .CONTROL1
+            .modify(CONTROL1::SRST_CMD::SET + CONTROL1::SRST_DATA::SET);
+
+        // A controller that does not come out of reset fails the next command anyway.
+        let _ = spin_until(
+            || {
+                !self
+                    .registers
+                    .CONTROL1
+                    .matches_any(CONTROL1::SRST_CMD::SET + CONTROL1::SRST_DATA::SET)
+            },
+            "EMMC line reset timed out",
+        );
+        self.registers.INTERRUPT.set(u32::MAX);
+    }
+
+    fn set_clock(&mut self, base_clock_hz: u32, target_hz: u32) -> Result<u32, &'static str> {
+        let divisor = sd::clock_divisor(base_clock_hz, target_hz);
+
+        self.registers.CONTROL1.modify(CONTROL1::CLK_EN::CLEAR);
+        self.registers.CONTROL1.modify(
+            CONTROL1::CLK_INTLEN::SET
+                + CONTROL1::CLK_FREQ8.val(divisor & 0xFF)
+                + CONTROL1::CLK_FREQ_MS2.val(divisor >> 8)
+                + CONTROL1::DATA_TOUNIT::Max,
+        );
+        spin_until(
+            || self.registers.CONTROL1.is_set(CONTROL1::CLK_STABLE),
+            "SD clock did not stabilize",
+        )?;
+        self.registers.CONTROL1.modify(CONTROL1::CLK_EN::SET);
+
+        Ok(sd::divided_clock(base_clock_hz, divisor))
+    }
+
+    /// Start a command. `data` describes the blocks that it transfers.
+    fn send_command(
+        &mut self,
+        command: Command,
+        arg: u32,
+        data: Option<(Direction, usize)>,
+    ) -> Result<(), &'static str> {
+        let inhibit = if data.is_some() || command.has_busy() {
+            STATUS::CMD_INHIBIT::SET + STATUS::DAT_INHIBIT::SET
+        } else {
+            STATUS::CMD_INHIBIT::SET
+        };
+        spin_until(
+            || !self.registers.STATUS.matches_any(inhibit),
+            "SD card is busy",
+        )?;
+
+        let mut cmdtm = command.cmdtm();
+        if let Some((direction, num_blocks)) = data {
+            use CMDTM::*;
+
+            let direction = match direction {
+                Direction::Read => TM_DAT_DIR::CardToHost,
+                Direction::Write => TM_DAT_DIR::HostToCard,
+            };
+            let multi_block = if num_blocks > 1 {
+                TM_MULTI_BLOCK::SET + TM_BLKCNT_EN::SET + TM_AUTO_CMD_EN::Cmd12
+            } else {
+                TM_MULTI_BLOCK::CLEAR
+            };
+
+            cmdtm |= (CMD_ISDATA::SET + direction + multi_block).value;
+            self.registers
+                .BLKSIZECNT
+                .set(((num_blocks as u32) << 16) | sd::BLOCK_SIZE as u32);
+        }
+
+        self.registers.INTERRUPT.set(u32::MAX);
+        self.registers.ARG1.set(arg);
+        self.registers.CMDTM.set(cmdtm);
+
+        Ok(())
+    }
+
+    /// The 136 bit response of the last command. The controller drops the CRC byte, so the bits
+    /// are shifted up to their position in the register of the card.
+    fn response_136(&self) -> u128 {
+        let resp = &self.registers.RESP;
+        let raw = (resp[3].get() as u128) << 96
+            | (resp[2].get() as u128) << 64
+            | (resp[1].get() as u128) << 32
+            | resp[0].get() as u128;
+
+        raw << 8
+    }
+
+    fn read_buffer(&mut self, buf: &mut [u8]) {
+        for word in buf.chunks_exact_mut(4) {
+            word.copy_from_slice(&self.registers.DATA.get().to_le_bytes());
+        }
+    }
+
+    fn write_buffer(&mut self, buf: &[u8]) {
+        for word in buf.chunks_exact(4) {
+            self.registers
+                .DATA
+                .set(u32::from_le_bytes([word[0], word[1], word[2], word[3]]));
+        }
+    }
+
+    /// Read and acknowledge the pending interrupt bits.
+    fn take_interrupts(&mut self) -> u32 {
+        let pending = self.registers.INTERRUPT.get() & interrupt::USED;
+        self.registers.INTERRUPT.set(pending);
+
+        pending
+    }
+}
+
+impl Emmc {
+    /// Wait until all interrupt bits of `mask` arrived, and consume them.
+    ///
+    /// Once the IRQ handler is registered, it collects the bits. Until then, or while IRQs are
+    /// masked on the calling core, the interrupt register is polled.
+    fn wait_for(&self, mask: u32) -> Result<(), &'static str> {
+        let deadline = time::time_manager().uptime() + TIMEOUT;
+
+        loop {
+            if !self.irq_driven.load(Ordering::Relaxed)
+                || exception::asynchronous::is_local_irq_masked()
+            {
+                let pending = self.inner.lock(|inner| inner.take_interrupts());
+                self.events.fetch_or(pending, Ordering::Relaxed);
+            }
+
+            let events = self.events.load(Ordering::Relaxed);
+            let err = if events & interrupt::TIMEOUT != 0 {
+                Some("SD card did not respond")
+            } else if events & interrupt::ERROR != 0 {
+                Some("SD card transfer failed")
+            } else if events & mask == mask {
+                self.events.fetch_and(!mask, Ordering::Relaxed);
+                return Ok(());
+            } else if time::time_manager().uptime() > deadline {
+                Some("EMMC controller timed out")
+            } else {
+                None
+            };
+
+            if let Some(err) = err {
+                self.events.store(0, Ordering::Relaxed);
+                self.inner.lock(|inner| inner.reset_lines());
+                return Err(err);
+            }
+
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Send a command without data and return the 32 bits of its response that follow the index.
+    fn command(&self, command: Command, arg: u32) -> Result<u32, &'static str> {
+        self.events.store(0, Ordering::Relaxed);
+        self.inner
+            .lock(|inner| inner.send_command(command, arg, None))?;
+
+        self.wait_for(interrupt::CMD_DONE)?;
+        if command.has_busy() {
+            self.wait_for(interrupt::DATA_DONE)?;
+        }
+
+        Ok(self.inner.lock(|inner| inner.registers.RESP[0].get()))
+    }
+
+    /// Send an application specific command.
+    fn app_command(&self, command: Command, rca: u32, arg: u32) -> Result<u32, &'static str> {
+        self.command(APP_CMD, rca << 16)?;
+        self.command(command, arg)
+    }
+
+    /// Transfer at most `MAX_BLOCKS_PER_TRANSFER` blocks.
+    fn transfer_chunk(
+        &self,
+        card: &Card,
+        start_block: u64,
+        mut buf: Buffer,
+    ) -> Result<(), &'static str> {
+        let direction = buf.direction();
+        let num_blocks = buf.len() / sd::BLOCK_SIZE;
+        let command = match (direction, num_blocks) {
+            (Direction::Read, 1) => READ_SINGLE_BLOCK,
+            (Direction::Read, _) => READ_MULTIPLE_BLOCK,
+            (Direction::Write, 1) => WRITE_BLOCK,
+            (Direction::Write, _) => WRITE_MULTIPLE_BLOCK,
+        };
+
+        // Standard capacity cards are addressed in bytes.
+        let address = if card.high_capacity {
+            start_block
+        } else {
+            start_block * sd::BLOCK_SIZE as u64
+        };
+        let arg = u32::try_from(address).map_err(|_| "Block address out of range")?;
+
+        self.events.store(0, Ordering::Relaxed);
+        self.inner
+            .lock(|inner| inner.send_command(command, arg, Some((direction, num_blocks))))?;
+        self.wait_for(interrupt::CMD_DONE)?;
+
+        for i in 0..num_blocks {
+            let range = i * sd::BLOCK_SIZE..(i + 1) * sd::BLOCK_SIZE;
+
+            match &mut buf {
+                Buffer::Read(buf) => {
+                    self.wait_for(interrupt::READ_READY)?;
+                    self.inner.lock(|inner| inner.read_buffer(&mut buf[range]));
+                }
+                Buffer::Write(buf) => {
+                    self.wait_for(interrupt::WRITE_READY)?;
+                    self.inner.lock(|inner| inner.write_buffer(&buf[range]));
+                }
+            }
+        }
+
+        self.wait_for(interrupt::DATA_DONE)
+    }
+
+    fn card(&self) -> Result<Card, &'static str> {
+        self.inner
+            .lock(|inner| inner.card)
+            .ok_or("No SD card initialized")
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Emmc {
+    pub const COMPATIBLE: &'static str = "BCM EMMC";
+
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO start address.
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            inner: IRQSafeNullLock::new(EmmcInner::new(mmio_start_addr)),
+            events: AtomicU32::new(0),
+            irq_driven: AtomicBool::new(false),
+        }
+    }
+
+    /// Identify the inserted card and bring it into the transfer state.
+    ///
+    /// `base_clock_hz` is the clock that the controller derives the SD clock from.
+    pub fn init_card(&self, base_clock_hz: u32) -> Result<(), &'static str> {
+        self.inner.lock(|inner| -> Result<(), &'static str> {
+            inner.reset()?;
+            inner.set_clock(base_clock_hz, IDENTIFICATION_CLOCK_HZ)?;
+
+            Ok(())
+        })?;
+
+        self.command(GO_IDLE_STATE, 0)?;
+
+        // Version 1 cards do not know the command.
+        let version_2 = self
+            .command(SEND_IF_COND, IF_COND_CHECK)
+            .is_ok_and(|resp| resp & 0xFFF == IF_COND_CHECK);
+
+        let mut op_cond = OCR_VOLTAGE_WINDOW;
+        if version_2 {
+            op_cond |= OCR_HIGH_CAPACITY;
+        }
+        let deadline = time::time_manager().uptime() + POWER_UP_TIMEOUT;
+        let ocr = loop {
+            let ocr = self.app_command(SD_SEND_OP_COND, 0, op_cond)?;
+            if ocr & OCR_POWERED_UP != 0 {
+                break ocr;
+            }
+            if time::time_manager().uptime() > deadline {
+                return Err("SD card did not power up");
+            }
+            time::time_manager().spin_for(Duration::from_millis(10));
+        };
+
+        self.command(ALL_SEND_CID, 0)?;
+        let rca = self.command(SEND_RELATIVE_ADDR, 0)? >> 16;
+        self.command(SEND_CSD, rca << 16)?;
+        let csd = self.inner.lock(|inner| inner.response_136());
+        let num_blocks = sd::capacity_blocks(csd).ok_or("Unknown SD card CSD version")?;
+        self.command(SELECT_CARD, rca << 16)?;
+
+        let high_capacity = ocr & OCR_HIGH_CAPACITY != 0;
+        if !high_capacity {
+            self.command(SET_BLOCKLEN, sd::BLOCK_SIZE as u32)?;
+        }
+
+        // Four data lines are mandatory for SD memory cards.
+        self.app_command(SET_BUS_WIDTH, rca, 0b10)?;
+        let clock_hz = self.inner.lock(|inner| -> Result<u32, &'static str> {
+            inner.registers.CONTROL0.modify(CONTROL0::HCTL_DWIDTH::SET);
+            let clock_hz = inner.set_clock(base_clock_hz, DEFAULT_SPEED_CLOCK_HZ)?;
+            inner.card = Some(Card {
+                high_capacity,
+                num_blocks,
+            });
+
+            Ok(clock_hz)
+        })?;
+
+        let (size, unit) = common::size_human_readable_ceil(num_blocks as usize * sd::BLOCK_SIZE);
+        info!(
+            "      SD card: {} {}, {} capacity, {} kHz",
+            size,
+            unit,
+            if high_capacity { "high" } else { "standard" },
+            clock_hz / 1000
+        );
+
+        Ok(())
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+use synchronization::interface::Mutex;
+
+impl driver::interface::DeviceDriver for Emmc {
+    type IRQNumberType = IRQNumber;
+
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+
+    unsafe fn init(&self) -> Result<(), &'static str> {
+        self.inner.lock(|inner| inner.reset())
+    }
+
+    fn register_and_enable_irq_handler(
+        &'static self,
+        irq_number: &Self::IRQNumberType,
+    ) -> Result<(), &'static str> {
+        use exception::asynchronous::{irq_manager, IRQHandlerDescriptor};
+
+        let descriptor = IRQHandlerDescriptor::new(*irq_number, Self::COMPATIBLE, self);
+
+        irq_manager().register_handler(descriptor)?;
+        irq_manager().enable(irq_number);
+
+        self.inner
+            .lock(|inner| inner.registers.IRPT_EN.set(interrupt::USED));
+        self.irq_driven.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+}
+
+impl exception::asynchronous::interface::IRQHandler for Emmc {
+    fn handle(&self) -> Result<(), &'static str> {
+        let pending = self.inner.lock(|inner| inner.take_interrupts());
+        self.events.fetch_or(pending, Ordering::Relaxed);
+
+        Ok(())
+    }
+}
+
+impl block::interface::BlockDevice for Emmc {
+    fn block_size(&self) -> usize {
+        sd::BLOCK_SIZE
+    }
+
+    fn num_blocks(&self) -> u64 {
+        self.card().map_or(0, |card| card.num_blocks)
+    }
+
+    fn read_blocks(&self, start_block: u64, buf: &mut [u8]) -> Result<(), &'static str> {
+        let card = self.card()?;
+        block::check_request(self, start_block, buf.len())?;
+
+        let chunk_size = MAX_BLOCKS_PER_TRANSFER * sd::BLOCK_SIZE;
+        for (i, chunk) in buf.chunks_mut(chunk_size).enumerate() {
+            let block = start_block + (i * MAX_BLOCKS_PER_TRANSFER) as u64;
+            self.transfer_chunk(&card, block, Buffer::Read(chunk))?;
+        }
+
+        Ok(())
+    }
+
+    fn write_blocks(&self, start_block: u64, buf: &[u8]) -> Result<(), &'static str> {
+        let card = self.card()?;
+        block::check_request(self, start_block, buf.len())?;
+
+        let chunk_size = MAX_BLOCKS_PER_TRANSFER * sd::BLOCK_SIZE;
+        for (i, chunk) in buf.chunks(chunk_size).enumerate() {
+            let block = start_block + (i * MAX_BLOCKS_PER_TRANSFER) as u64;
+            self.transfer_chunk(&card, block, Buffer::Write(chunk))?;
+        }
+
+        Ok(())
+    }
+}
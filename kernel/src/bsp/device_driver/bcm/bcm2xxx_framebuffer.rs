@@ -0,0 +1,440 @@
+//! Framebuffer Driver.
+//!
+//! The firmware allocates the framebuffer in the VideoCore's part of the DRAM and scans it out to
+//! HDMI. The driver draws text into it with a bitmap font, which makes the display a console that
+//! understands the ANSI escape sequences for colors, cursor movement and erasing.
+//!
+//! # Resources
+//!
+//! - <https://github.com/raspberrypi/firmware/wiki/Mailbox-property-interface#frame-buffer>
+
+use super::{Mailbox, PropertyTag};
+use crate::{
+    console, driver,
+    exception::asynchronous::IRQNumber,
+    memory::{Address, Physical, Virtual},
+    synchronization,
+    synchronization::IRQSafeNullLock,
+};
+use alloc::vec::Vec;
+use core::fmt;
+use kernel_core::console::{
+    ansi::{self, Action, Attributes, Color, EraseMode},
+    psf,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Framebuffer property tags.
+mod tag {
+    pub const ALLOCATE_BUFFER: u32 = 0x0004_0001;
+    pub const GET_PITCH: u32 = 0x0004_0008;
+    pub const SET_PHYSICAL_SIZE: u32 = 0x0004_8003;
+    pub const SET_VIRTUAL_SIZE: u32 = 0x0004_8004;
+    pub const SET_DEPTH: u32 = 0x0004_8005;
+    pub const SET_PIXEL_ORDER: u32 = 0x0004_8006;
+}
+
+const BITS_PER_PIXEL: u32 = 32;
+const BYTES_PER_PIXEL: usize = 4;
+
+/// Alignment of the framebuffer that is requested from the firmware.
+const BUFFER_ALIGNMENT: u32 = 4096;
+
+/// The firmware hands out bus addresses. Clearing the alias bits gives the ARM physical address.
+const BUS_ADDR_TO_PHYS_MASK: u32 = 0x3FFF_FFFF;
+
+const TAB_WIDTH: usize = 8;
+
+/// Order of the color components in memory.
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum PixelOrder {
+    Bgr = 0,
+    Rgb = 1,
+}
+
+/// A character on the screen.
+#[derive(Copy, Clone)]
+struct Cell {
+    c: char,
+    attributes: Attributes,
+}
+
+struct FramebufferInner {
+    start_addr: Address<Virtual>,
+    mode: FramebufferMode,
+    font: psf::Font<'static>,
+    parser: ansi::Parser,
+    cells: Vec<Cell>,
+    columns: usize,
+    rows: usize,
+    cursor_row: usize,
+    cursor_column: usize,
+    chars_written: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A framebuffer, as allocated by the firmware.
+#[derive(Copy, Clone)]
+pub struct FramebufferMode {
+    /// Physical start address.
+    pub start_addr: Address<Physical>,
+
+    /// Size in bytes.
+    pub size: usize,
+
+    /// Width in pixels.
+    pub width: usize,
+
+    /// Height in pixels.
+    pub height: usize,
+
+    /// Distance between the starts of two lines in bytes.
+    pub pitch: usize,
+
+    pixel_order: PixelOrder,
+}
+
+/// Representation of the framebuffer, as a text console.
+pub struct Framebuffer {
+    inner: IRQSafeNullLock<FramebufferInner>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl Cell {
+    const BLANK: Self = Self {
+        c: ' ',
+        attributes: Attributes::DEFAULT,
+    };
+}
+
+impl FramebufferInner {
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide the start address of the mapped framebuffer.
+    unsafe fn new(start_addr: Address<Virtual>, mode: FramebufferMode) -> Self {
+        let font = psf::default_font();
+
+        Self {
+            start_addr,
+            mode,
+            font,
+            parser: ansi::Parser::new(),
+            cells: Vec::new(),
+            columns: mode.width / font.width(),
+            rows: mode.height / font.height(),
+            cursor_row: 0,
+            cursor_column: 0,
+            chars_written: 0,
+        }
+    }
+
+    /// Clear the whole framebuffer, including the margins that are too small for characters.
+    fn init(&mut self) {
+        self.cells = alloc::vec![Cell::BLANK; self.columns * self.rows];
+
+        let background = self.pixel(Attributes::DEFAULT.background);
+        for y in 0..self.mode.height {
+            for x in 0..self.mode.width {
+                self.write_pixel(x, y, background);
+            }
+        }
+
+        self.draw_cell(0, 0);
+    }
+
+    /// Convert a color to the pixel format of the framebuffer.
+    fn pixel(&self, color: Color) -> u32 {
+        let rgb = color.rgb();
+
+        match self.mode.pixel_order {
+            PixelOrder::Bgr => rgb,
+            PixelOrder::Rgb => ((rgb & 0xFF) << 16) | (rgb & 0xFF00) | ((rgb >> 16) & 0xFF),
+        }
+    }
+
+    fn write_pixel(&self, x: usize, y: usize, pixel: u32) {
+        let addr = self.start_addr.as_usize() + y * self.mode.pitch + x * BYTES_PER_PIXEL;
+
+        unsafe { core::ptr::write_volatile(addr as *mut u32, pixel) }
+    }
+
+    /// Draw a character cell. The cell under the cursor is drawn with swapped colors.
+    fn draw_cell(&self, row: usize, column: usize) {
+        let cell = self.cells[row * self.columns + column];
+        let (mut foreground, mut background) = cell.attributes.colors();
+        if (row, column) == (self.cursor_row, self.cursor_column) {
+            (foreground, background) = (background, foreground);
+        }
+        let (foreground, background) = (self.pixel(foreground), self.pixel(background));
+
+        let glyph = self.font.glyph(cell.c);
+        let (width, height) = (self.font.width(), self.font.height());
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = if glyph.is_set(x, y) {
+                    foreground
+                } else {
+                    background
+                };
+                self.write_pixel(column * width + x, row * height + y, pixel);
+            }
+        }
+    }
+
+    fn draw_all(&self) {
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                self.draw_cell(row, column);
+            }
+        }
+    }
+
+    fn move_cursor(&mut self, row: usize, column: usize) {
+        let old = (self.cursor_row, self.cursor_column);
+
+        self.cursor_row = row.min(self.rows - 1);
+        self.cursor_column = column.min(self.columns - 1);
+
+        self.draw_cell(old.0, old.1);
+        self.draw_cell(self.cursor_row, self.cursor_column);
+    }
+
+    /// Move the cursor to the start of the next line. Scrolls up at the bottom of the screen.
+    fn new_line(&mut self) {
+        if self.cursor_row + 1 < self.rows {
+            self.move_cursor(self.cursor_row + 1, 0);
+            return;
+        }
+
+        self.cells.copy_within(self.columns.., 0);
+        let last_row = (self.rows - 1) * self.columns;
+        self.cells[last_row..].fill(Cell {
+            c: ' ',
+            attributes: self.parser.attributes(),
+        });
+
+        self.cursor_column = 0;
+        self.draw_all();
+    }
+
+    /// Erase the cells from `start` up to, but excluding, `end_exclusive`, counted from the top
+    /// left.
+    fn erase(&mut self, start: usize, end_exclusive: usize) {
+        let blank = Cell {
+            c: ' ',
+            attributes: self.parser.attributes(),
+        };
+
+        for i in start..end_exclusive {
+            self.cells[i] = blank;
+            self.draw_cell(i / self.columns, i % self.columns);
+        }
+    }
+
+    fn erase_range(&mut self, mode: EraseMode, start: usize, end_exclusive: usize) {
+        let cursor = self.cursor_row * self.columns + self.cursor_column;
+
+        match mode {
+            EraseMode::ToEnd => self.erase(cursor, end_exclusive),
+            EraseMode::ToStart => self.erase(start, cursor + 1),
+            EraseMode::All => self.erase(start, end_exclusive),
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        self.cells[self.cursor_row * self.columns + self.cursor_column] = Cell {
+            c,
+            attributes: self.parser.attributes(),
+        };
+
+        if self.cursor_column + 1 < self.columns {
+            self.move_cursor(self.cursor_row, self.cursor_column + 1);
+        } else {
+            self.draw_cell(self.cursor_row, self.cursor_column);
+            self.new_line();
+        }
+    }
+
+    fn write_char(&mut self, c: char) {
+        self.chars_written += 1;
+
+        let Some(action) = self.parser.advance(c) else {
+            return;
+        };
+        let (row, column) = (self.cursor_row, self.cursor_column);
+
+        match action {
+            Action::Print(c) => self.print(c),
+            Action::LineFeed => self.new_line(),
+            Action::CarriageReturn => self.move_cursor(row, 0),
+            Action::Backspace => self.move_cursor(row, column.saturating_sub(1)),
+            Action::Tab => self.move_cursor(row, (column / TAB_WIDTH + 1) * TAB_WIDTH),
+            Action::CursorUp(n) => self.move_cursor(row.saturating_sub(n), column),
+            Action::CursorDown(n) => self.move_cursor(row.saturating_add(n), column),
+            Action::CursorForward(n) => self.move_cursor(row, column.saturating_add(n)),
+            Action::CursorBack(n) => self.move_cursor(row, column.saturating_sub(n)),
+            Action::CursorPosition { row, column } => self.move_cursor(row, column),
+            Action::EraseDisplay(mode) => self.erase_range(mode, 0, self.cells.len()),
+            Action::EraseLine(mode) => {
+                let line_start = row * self.columns;
+                self.erase_range(mode, line_start, line_start + self.columns)
+            }
+            // The parser keeps track of the attributes.
+            Action::SetAttributes(_) => (),
+        }
+    }
+
+    fn write_array(&mut self, a: &[char]) {
+        for c in a {
+            self.write_char(*c);
+        }
+    }
+}
+
+impl fmt::Write for FramebufferInner {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.write_char(c);
+        }
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Framebuffer {
+    pub const COMPATIBLE: &'static str = "BCM Framebuffer";
+
+    /// Ask the firmware for a framebuffer with 32 bits per pixel.
+    ///
+    /// The firmware might choose other dimensions than requested, e.g. those of the attached
+    /// display.
+    pub fn allocate(
+        mailbox: &Mailbox,
+        width: u32,
+        height: u32,
+    ) -> Result<FramebufferMode, &'static str> {
+        let mut physical_size = [width, height];
+        let mut virtual_size = [width, height];
+        let mut depth = [BITS_PER_PIXEL];
+        let mut pixel_order = [PixelOrder::Rgb as u32];
+        let mut buffer = [BUFFER_ALIGNMENT, 0];
+        let mut pitch = [0];
+
+        mailbox.properties(&mut [
+            PropertyTag::new(tag::SET_PHYSICAL_SIZE, &mut physical_size),
+            PropertyTag::new(tag::SET_VIRTUAL_SIZE, &mut virtual_size),
+            PropertyTag::new(tag::SET_DEPTH, &mut depth),
+            PropertyTag::new(tag::SET_PIXEL_ORDER, &mut pixel_order),
+            PropertyTag::new(tag::ALLOCATE_BUFFER, &mut buffer),
+            PropertyTag::new(tag::GET_PITCH, &mut pitch),
+        ])?;
+
+        if depth[0] != BITS_PER_PIXEL {
+            return Err("Firmware does not support 32 bits per pixel");
+        }
+        if buffer[0] == 0 || buffer[1] == 0 {
+            return Err("Firmware could not allocate a framebuffer");
+        }
+
+        let (width, height) = (virtual_size[0] as usize, virtual_size[1] as usize);
+        let pitch = pitch[0] as usize;
+        if width == 0 || height == 0 || pitch < width * BYTES_PER_PIXEL {
+            return Err("Firmware reported an invalid framebuffer geometry");
+        }
+        if (buffer[1] as usize) < pitch * height {
+            return Err("Firmware allocated a framebuffer that is too small");
+        }
+
+        Ok(FramebufferMode {
+            start_addr: Address::new((buffer[0] & BUS_ADDR_TO_PHYS_MASK) as usize),
+            size: buffer[1] as usize,
+            width,
+            height,
+            pitch,
+            pixel_order: if pixel_order[0] == PixelOrder::Bgr as u32 {
+                PixelOrder::Bgr
+            } else {
+                PixelOrder::Rgb
+            },
+        })
+    }
+
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide the start address of the mapped framebuffer.
+    pub unsafe fn new(start_addr: Address<Virtual>, mode: FramebufferMode) -> Self {
+        Self {
+            inner: IRQSafeNullLock::new(FramebufferInner::new(start_addr, mode)),
+        }
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+use synchronization::interface::Mutex;
+
+impl driver::interface::DeviceDriver for Framebuffer {
+    type IRQNumberType = IRQNumber;
+
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+
+    unsafe fn init(&self) -> Result<(), &'static str> {
+        self.inner.lock(|inner| {
+            if inner.columns == 0 || inner.rows == 0 {
+                return Err("Framebuffer is too small for the font");
+            }
+
+            inner.init();
+            Ok(())
+        })
+    }
+}
+
+impl console::interface::Write for Framebuffer {
+    fn write_char(&self, c: char) {
+        self.inner.lock(|inner| inner.write_char(c));
+    }
+
+    fn write_array(&self, a: &[char]) {
+        self.inner.lock(|inner| inner.write_array(a));
+    }
+
+    fn write_fmt(&self, args: core::fmt::Arguments) -> fmt::Result {
+        self.inner.lock(|inner| fmt::Write::write_fmt(inner, args))
+    }
+
+    fn flush(&self) {}
+}
+
+impl console::interface::Read for Framebuffer {
+    fn clear_rx(&self) {}
+}
+
+impl console::interface::Statistics for Framebuffer {
+    fn chars_written(&self) -> usize {
+        self.inner.lock(|inner| inner.chars_written)
+    }
+}
+
+impl console::interface::LineConfiguration for Framebuffer {}
+impl console::interface::All for Framebuffer {}
@@ -2,16 +2,19 @@
 
 use crate::{
     bsp::device_driver::common::MMIODerefWrapper,
-    driver,
+    driver, exception,
     exception::asynchronous::IRQNumber,
     memory::{Address, Virtual},
     synchronization,
     synchronization::IRQSafeNullLock,
+    time,
 };
+use core::time::Duration;
+use kernel_core::exception::asynchronous::PendingIRQs;
 use tock_registers::{
-    interfaces::{ReadWriteable, Writeable},
+    interfaces::{Readable, Writeable},
     register_bitfields, register_structs,
-    registers::ReadWrite,
+    registers::{ReadOnly, ReadWrite},
 };
 
 //--------------------------------------------------------------------------------------------------
@@ -26,24 +29,6 @@ use tock_registers::{
 register_bitfields! {
     u32,
 
-    /// GPIO Function Select 1
-    GPFSEL1 [
-        /// Pin 15
-        FSEL15 OFFSET(15) NUMBITS(3) [
-            Input = 0b000,
-            Output = 0b001,
-            AltFunc0 = 0b100  // PL011 UART RX
-
-        ],
-
-        /// Pin 14
-        FSEL14 OFFSET(12) NUMBITS(3) [
-            Input = 0b000,
-            Output = 0b001,
-            AltFunc0 = 0b100  // PL011 UART TX
-        ]
-    ],
-
     /// GPIO Pull-up/down Register
     ///
     /// BCM2837 only.
@@ -54,68 +39,129 @@ register_bitfields! {
             PullDown = 0b01,
             PullUp = 0b10
         ]
-    ],
-
-    /// GPIO Pull-up/down Clock Register 0
-    ///
-    /// BCM2837 only.
-    GPPUDCLK0 [
-        /// Pin 15
-        PUDCLK15 OFFSET(15) NUMBITS(1) [
-            NoEffect = 0,
-            AssertClock = 1
-        ],
-
-        /// Pin 14
-        PUDCLK14 OFFSET(14) NUMBITS(1) [
-            NoEffect = 0,
-            AssertClock = 1
-        ]
-    ],
-
-    /// GPIO Pull-up / Pull-down Register 0
-    ///
-    /// BCM2711 only.
-    GPIO_PUP_PDN_CNTRL_REG0 [
-        /// Pin 15
-        GPIO_PUP_PDN_CNTRL15 OFFSET(30) NUMBITS(2) [
-            NoResistor = 0b00,
-            PullUp = 0b01
-        ],
-
-        /// Pin 14
-        GPIO_PUP_PDN_CNTRL14 OFFSET(28) NUMBITS(2) [
-            NoResistor = 0b00,
-            PullUp = 0b01
-        ]
     ]
 }
 
 register_structs! {
     #[allow(non_snake_case)]
     RegisterBlock {
-        (0x00 => _reserved1),
-        (0x04 => GPFSEL1: ReadWrite<u32, GPFSEL1::Register>),
-        (0x08 => _reserved2),
+        /// Function Select, 3 bits per pin.
+        (0x00 => GPFSEL: [ReadWrite<u32>; 6]),
+        (0x18 => _reserved1),
+        /// Pin Output Set, 1 bit per pin.
+        (0x1C => GPSET: [ReadWrite<u32>; 2]),
+        (0x24 => _reserved2),
+        /// Pin Output Clear.
+        (0x28 => GPCLR: [ReadWrite<u32>; 2]),
+        (0x30 => _reserved3),
+        /// Pin Level.
+        (0x34 => GPLEV: [ReadOnly<u32>; 2]),
+        (0x3C => _reserved4),
+        /// Pin Event Detect Status. Bits are cleared by writing 1.
+        (0x40 => GPEDS: [ReadWrite<u32>; 2]),
+        (0x48 => _reserved5),
+        /// Pin Rising Edge Detect Enable.
+        (0x4C => GPREN: [ReadWrite<u32>; 2]),
+        (0x54 => _reserved6),
+        /// Pin Falling Edge Detect Enable.
+        (0x58 => GPFEN: [ReadWrite<u32>; 2]),
+        (0x60 => _reserved7),
+        /// Pin High Detect Enable.
+        (0x64 => GPHEN: [ReadWrite<u32>; 2]),
+        (0x6C => _reserved8),
+        /// Pin Low Detect Enable.
+        (0x70 => GPLEN: [ReadWrite<u32>; 2]),
+        (0x78 => _reserved9),
+        /// Pull-up/down Enable. BCM2837 only.
         (0x94 => GPPUD: ReadWrite<u32, GPPUD::Register>),
-        (0x98 => GPPUDCLK0: ReadWrite<u32, GPPUDCLK0::Register>),
-        (0x9C => _reserved3),
-        (0xE4 => GPIO_PUP_PDN_CNTRL_REG0: ReadWrite<u32, GPIO_PUP_PDN_CNTRL_REG0::Register>),
-        (0xE8 => @END),
+        /// Pull-up/down Enable Clock, 1 bit per pin. BCM2837 only.
+        (0x98 => GPPUDCLK: [ReadWrite<u32>; 2]),
+        (0xA0 => _reserved10),
+        /// Pull-up/down Control, 2 bits per pin. BCM2711 only.
+        (0xE4 => GPIO_PUP_PDN_CNTRL_REG: [ReadWrite<u32>; 4]),
+        (0xF4 => @END),
     }
 }
 
 /// Abstraction for the associated MMIO registers.
 type Registers = MMIODerefWrapper<RegisterBlock>;
 
+/// Pins 14 and 15 carry the TX and RX lines of the console UART.
+const UART_PINS: [usize; 2] = [14, 15];
+
+/// Alternate TX and RX pins of the PL011 UART, for when the mini UART uses pins 14 and 15.
+const PL011_UART_ALT_PINS: [usize; 2] = [36, 37];
+
+/// Alternate TX and RX pins of the mini UART, for when the PL011 UART uses pins 14 and 15.
+const MINI_UART_ALT_PINS: [usize; 2] = [32, 33];
+
 struct GPIOInner {
     registers: Registers,
+    pull_control: PullControl,
+    event_handlers: [Option<EventHandler>; NUM_PINS],
 }
 
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
 //--------------------------------------------------------------------------------------------------
 
+/// The number of GPIO pins.
+pub const NUM_PINS: usize = 54;
+
+/// The pin functions.
+///
+/// Which peripheral an alternate function connects depends on the pin, see the peripherals manual.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Function {
+    Input,
+    Output,
+    Alt0,
+    Alt1,
+    Alt2,
+    Alt3,
+    Alt4,
+    Alt5,
+}
+
+/// The internal pull resistors of a pin.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Pull {
+    None,
+    Up,
+    Down,
+}
+
+/// The way the pull resistors are programmed, which differs between the SoCs.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PullControl {
+    /// A global pull value that is clocked into the selected pins.
+    Bcm2837,
+    /// Direct pull fields per pin.
+    #[cfg_attr(feature = "bsp_rpi3", allow(dead_code))]
+    Bcm2711,
+}
+
+/// The pin events that can raise an interrupt.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    /// A transition from low to high.
+    RisingEdge,
+    /// A transition from high to low.
+    FallingEdge,
+    /// The pin is high.
+    ///
+    /// Fires again as long as the level persists, so the handler must remove the cause or disable
+    /// the event.
+    HighLevel,
+    /// The pin is low. Same caveat as for `HighLevel`.
+    LowLevel,
+}
+
+/// A callback for pin events. Receives the pin number and runs in interrupt context.
+pub type EventHandler = fn(usize);
+
 /// Representation of the GPIO HW.
 pub struct GPIO {
     inner: IRQSafeNullLock<GPIOInner>,
@@ -125,52 +171,182 @@ pub struct GPIO {
 // Private Code
 //--------------------------------------------------------------------------------------------------
 
+fn check_pin(pin: usize) -> Result<(), &'static str> {
+    if pin >= NUM_PINS {
+        return Err("GPIO pin out of range");
+    }
+
+    Ok(())
+}
+
+/// The register index and bit mask of a pin in the one bit per pin registers.
+fn bank_bit(pin: usize) -> (usize, u32) {
+    (pin / 32, 1 << (pin % 32))
+}
+
+impl Function {
+    fn from_bits(bits: u32) -> Self {
+        match bits & 0b111 {
+            0b000 => Self::Input,
+            0b001 => Self::Output,
+            0b100 => Self::Alt0,
+            0b101 => Self::Alt1,
+            0b110 => Self::Alt2,
+            0b111 => Self::Alt3,
+            0b011 => Self::Alt4,
+            _ => Self::Alt5,
+        }
+    }
+
+    fn bits(self) -> u32 {
+        match self {
+            Self::Input => 0b000,
+            Self::Output => 0b001,
+            Self::Alt0 => 0b100,
+            Self::Alt1 => 0b101,
+            Self::Alt2 => 0b110,
+            Self::Alt3 => 0b111,
+            Self::Alt4 => 0b011,
+            Self::Alt5 => 0b010,
+        }
+    }
+}
+
 impl GPIOInner {
     /// Create an instance.
     ///
     /// # Safety
     ///
     /// - The user must ensure to provide a correct MMIO start address.
-    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>, pull_control: PullControl) -> Self {
         Self {
             registers: Registers::new(mmio_start_addr),
+            pull_control,
+            event_handlers: [None; NUM_PINS],
         }
     }
 
-    /// Disable pull-up/down on pins 14 and 15.
-    #[cfg(feature = "bsp_rpi3")]
-    fn disable_pud_14_15_bcm2837(&mut self) {
-        use crate::time;
-        use core::time::Duration;
+    fn set_function(&mut self, pin: usize, function: Function) {
+        let reg = &self.registers.GPFSEL[pin / 10];
+        let shift = (pin % 10) * 3;
+
+        reg.set((reg.get() & !(0b111 << shift)) | (function.bits() << shift));
+    }
+
+    fn function(&self, pin: usize) -> Function {
+        Function::from_bits(self.registers.GPFSEL[pin / 10].get() >> ((pin % 10) * 3))
+    }
 
+    fn set_pull_bcm2837(&mut self, pin: usize, pull: Pull) {
         // The Linux 2837 GPIO driver waits 1 µs between the steps.
         const DELAY: Duration = Duration::from_micros(1);
 
-        self.registers.GPPUD.write(GPPUD::PUD::Off);
+        let (bank, bit) = bank_bit(pin);
+        let pud = match pull {
+            Pull::None => GPPUD::PUD::Off,
+            Pull::Up => GPPUD::PUD::PullUp,
+            Pull::Down => GPPUD::PUD::PullDown,
+        };
+
+        self.registers.GPPUD.write(pud);
         time::time_manager().spin_for(DELAY);
 
-        self.registers
-            .GPPUDCLK0
-            .write(GPPUDCLK0::PUDCLK15::AssertClock + GPPUDCLK0::PUDCLK14::AssertClock);
+        self.registers.GPPUDCLK[bank].set(bit);
         time::time_manager().spin_for(DELAY);
 
         self.registers.GPPUD.write(GPPUD::PUD::Off);
-        self.registers.GPPUDCLK0.set(0);
+        self.registers.GPPUDCLK[bank].set(0);
     }
 
-    /// Map PL011 UART as standard output.
-    ///
-    /// TX to pin 14
-    /// RX to pin 15
-    pub fn map_pl011_uart(&mut self) {
-        // Select the UART on pins 14 and 15.
-        self.registers
-            .GPFSEL1
-            .modify(GPFSEL1::FSEL15::AltFunc0 + GPFSEL1::FSEL14::AltFunc0);
+    fn set_pull_bcm2711(&mut self, pin: usize, pull: Pull) {
+        let reg = &self.registers.GPIO_PUP_PDN_CNTRL_REG[pin / 16];
+        let shift = (pin % 16) * 2;
+        let value = match pull {
+            Pull::None => 0b00,
+            Pull::Up => 0b01,
+            Pull::Down => 0b10,
+        };
+
+        reg.set((reg.get() & !(0b11 << shift)) | (value << shift));
+    }
+
+    fn set_pull(&mut self, pin: usize, pull: Pull) {
+        match self.pull_control {
+            PullControl::Bcm2837 => self.set_pull_bcm2837(pin, pull),
+            PullControl::Bcm2711 => self.set_pull_bcm2711(pin, pull),
+        }
+    }
+
+    fn write(&mut self, pin: usize, high: bool) {
+        let (bank, bit) = bank_bit(pin);
+
+        if high {
+            self.registers.GPSET[bank].set(bit);
+        } else {
+            self.registers.GPCLR[bank].set(bit);
+        }
+    }
+
+    fn read(&self, pin: usize) -> bool {
+        let (bank, bit) = bank_bit(pin);
+
+        self.registers.GPLEV[bank].get() & bit != 0
+    }
+
+    fn event_enable_register(&self, event: Event) -> &[ReadWrite<u32>; 2] {
+        match event {
+            Event::RisingEdge => &self.registers.GPREN,
+            Event::FallingEdge => &self.registers.GPFEN,
+            Event::HighLevel => &self.registers.GPHEN,
+            Event::LowLevel => &self.registers.GPLEN,
+        }
+    }
+
+    fn enable_event(&mut self, pin: usize, event: Event, handler: EventHandler) {
+        let (bank, bit) = bank_bit(pin);
+
+        self.event_handlers[pin] = Some(handler);
+
+        // Drop events that were latched before, then let the new one through.
+        self.registers.GPEDS[bank].set(bit);
+        let reg = &self.event_enable_register(event)[bank];
+        reg.set(reg.get() | bit);
+    }
+
+    fn disable_events(&mut self, pin: usize) {
+        let (bank, bit) = bank_bit(pin);
+
+        for event in [
+            Event::RisingEdge,
+            Event::FallingEdge,
+            Event::HighLevel,
+            Event::LowLevel,
+        ] {
+            let reg = &self.event_enable_register(event)[bank];
+            reg.set(reg.get() & !bit);
+        }
+        self.registers.GPEDS[bank].set(bit);
+
+        self.event_handlers[pin] = None;
+    }
+
+    /// Read and acknowledge the detected events of both banks.
+    fn take_pending_events(&mut self) -> PendingIRQs {
+        let low = self.registers.GPEDS[0].get();
+        let high = self.registers.GPEDS[1].get();
+
+        self.registers.GPEDS[0].set(low);
+        self.registers.GPEDS[1].set(high);
+
+        PendingIRQs::new(((high as u64) << 32) | low as u64)
+    }
 
-        // Disable pull-up/down on pins 14 and 15.
-        #[cfg(feature = "bsp_rpi3")]
-        self.disable_pud_14_15_bcm2837();
+    /// Connect TX and RX pins to a UART, without pull resistors.
+    fn map_uart(&mut self, pins: [usize; 2], function: Function) {
+        for pin in pins {
+            self.set_function(pin, function);
+            self.set_pull(pin, Pull::None);
+        }
     }
 }
 
@@ -186,15 +362,123 @@ impl GPIO {
     /// # Safety
     ///
     /// - The user must ensure to provide a correct MMIO start address.
-    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>, pull_control: PullControl) -> Self {
         Self {
-            inner: IRQSafeNullLock::new(GPIOInner::new(mmio_start_addr)),
+            inner: IRQSafeNullLock::new(GPIOInner::new(mmio_start_addr, pull_control)),
         }
     }
 
-    /// Concurrency safe version of `GPIOInner.map_pl011_uart()`
+    /// Select the function of a pin.
+    pub fn set_function(&self, pin: usize, function: Function) -> Result<(), &'static str> {
+        check_pin(pin)?;
+        self.inner.lock(|inner| inner.set_function(pin, function));
+
+        Ok(())
+    }
+
+    /// The currently selected function of a pin.
+    pub fn function(&self, pin: usize) -> Result<Function, &'static str> {
+        check_pin(pin)?;
+
+        Ok(self.inner.lock(|inner| inner.function(pin)))
+    }
+
+    /// Configure the pull resistors of a pin.
+    pub fn set_pull(&self, pin: usize, pull: Pull) -> Result<(), &'static str> {
+        check_pin(pin)?;
+        self.inner.lock(|inner| inner.set_pull(pin, pull));
+
+        Ok(())
+    }
+
+    /// Drive an output pin high.
+    pub fn set(&self, pin: usize) -> Result<(), &'static str> {
+        self.write(pin, true)
+    }
+
+    /// Drive an output pin low.
+    pub fn clear(&self, pin: usize) -> Result<(), &'static str> {
+        self.write(pin, false)
+    }
+
+    /// Drive an output pin high or low.
+    pub fn write(&self, pin: usize, high: bool) -> Result<(), &'static str> {
+        check_pin(pin)?;
+        self.inner.lock(|inner| inner.write(pin, high));
+
+        Ok(())
+    }
+
+    /// Whether a pin is high. Works for any function.
+    pub fn read(&self, pin: usize) -> Result<bool, &'static str> {
+        check_pin(pin)?;
+
+        Ok(self.inner.lock(|inner| inner.read(pin)))
+    }
+
+    /// Call `handler` when `event` is detected on a pin.
+    ///
+    /// Several events can be enabled on one pin. They share the handler that was given last.
+    pub fn enable_event(
+        &self,
+        pin: usize,
+        event: Event,
+        handler: EventHandler,
+    ) -> Result<(), &'static str> {
+        check_pin(pin)?;
+        self.inner
+            .lock(|inner| inner.enable_event(pin, event, handler));
+
+        Ok(())
+    }
+
+    /// Disable all event detection on a pin and forget its handler.
+    pub fn disable_events(&self, pin: usize) -> Result<(), &'static str> {
+        check_pin(pin)?;
+        self.inner.lock(|inner| inner.disable_events(pin));
+
+        Ok(())
+    }
+
+    /// Map PL011 UART as standard output.
+    ///
+    /// TX to pin 14
+    /// RX to pin 15
     pub fn map_pl011_uart(&self) {
-        self.inner.lock(|inner| inner.map_pl011_uart())
+        self.inner
+            .lock(|inner| inner.map_uart(UART_PINS, Function::Alt0))
+    }
+
+    /// Map the PL011 UART to its alternate pins, as a secondary console.
+    ///
+    /// TX to pin 36
+    /// RX to pin 37
+    ///
+    /// The pins are not on the 40 pin header of the Raspberry Pi 3.
+    pub fn map_pl011_uart_alt(&self) {
+        self.inner
+            .lock(|inner| inner.map_uart(PL011_UART_ALT_PINS, Function::Alt2))
+    }
+
+    /// Map the mini UART as standard output.
+    ///
+    /// TX to pin 14
+    /// RX to pin 15
+    pub fn map_mini_uart(&self) {
+        self.inner
+            .lock(|inner| inner.map_uart(UART_PINS, Function::Alt5))
+    }
+
+    /// Map the mini UART to its alternate pins, as a secondary console.
+    ///
+    /// TX to pin 32
+    /// RX to pin 33
+    ///
+    /// The pins are not on the 40 pin header. On the Raspberry Pi 3, they lead to the Bluetooth
+    /// module.
+    pub fn map_mini_uart_alt(&self) {
+        self.inner
+            .lock(|inner| inner.map_uart(MINI_UART_ALT_PINS, Function::Alt5))
     }
 }
 
@@ -209,4 +493,36 @@ impl driver::interface::DeviceDriver for GPIO {
     fn compatible(&self) -> &'static str {
         Self::COMPATIBLE
     }
-}
\ No newline at end of file
+
+    fn register_and_enable_irq_handler(
+        &'static self,
+        irq_number: &Self::IRQNumberType,
+    ) -> Result<(), &'static str> {
+        use exception::asynchronous::{irq_manager, IRQHandlerDescriptor};
+
+        let descriptor = IRQHandlerDescriptor::new(*irq_number, Self::COMPATIBLE, self);
+
+        irq_manager().register_handler(descriptor)?;
+        irq_manager().enable(irq_number);
+
+        Ok(())
+    }
+}
+
+impl exception::asynchronous::interface::IRQHandler for GPIO {
+    fn handle(&self) -> Result<(), &'static str> {
+        // All bank IRQs end up here, so serve the events of all pins. The handlers run without
+        // the lock held, so that they can use the GPIO themselves.
+        let (pending, handlers) = self
+            .inner
+            .lock(|inner| (inner.take_pending_events(), inner.event_handlers));
+
+        for pin in pending {
+            if let Some(handler) = handlers[pin] {
+                handler(pin);
+            }
+        }
+
+        Ok(())
+    }
+}
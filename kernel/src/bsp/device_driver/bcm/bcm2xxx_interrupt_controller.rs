@@ -9,12 +9,7 @@ use crate::{
     memory::{Address, Virtual},
 };
 use core::fmt;
-
-
-/// Wrapper struct for a bitmask indicating pending IRQ numbers.
-struct PendingIRQs {
-    bitmask: u64,
-}
+use kernel_core::exception::asynchronous::PendingIRQs;
 
 
 pub type LocalIRQ = BoundedUsize<{ InterruptController::MAX_LOCAL_IRQ_NUMBER }>;
@@ -34,26 +29,6 @@ pub struct InterruptController {
 }
 
 
-impl PendingIRQs {
-    pub fn new(bitmask: u64) -> Self {
-        Self { bitmask }
-    }
-}
-
-impl Iterator for PendingIRQs {
-    type Item = usize;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.bitmask == 0 {
-            return None;
-        }
-
-        let next = self.bitmask.trailing_zeros() as usize;
-        self.bitmask &= self.bitmask.wrapping_sub(1);
-        Some(next)
-    }
-}
-
 
 impl fmt::Display for IRQNumber {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -0,0 +1,469 @@
+//! Mailbox Driver.
+//!
+//! The mailboxes are the communication channel between the ARM cores and the VideoCore, which runs
+//! the firmware. Only the property interface is supported: The ARM places a message with a tag in
+//! memory, hands its bus address to the firmware, and the firmware writes its answer into the same
+//! memory.
+//!
+//! # Resources
+//!
+//! - <https://github.com/raspberrypi/firmware/wiki/Mailboxes>
+//! - <https://github.com/raspberrypi/firmware/wiki/Mailbox-property-interface>
+
+use crate::{
+    bsp::device_driver::common::MMIODerefWrapper,
+    cpu, driver,
+    exception::asynchronous::IRQNumber,
+    memory::{self, Address, Physical, Virtual},
+    synchronization,
+    synchronization::IRQSafeNullLock,
+};
+use alloc::{string::String, vec::Vec};
+use core::mem;
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_bitfields, register_structs,
+    registers::{ReadOnly, WriteOnly},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+// Mailbox registers.
+//
+// Mailbox 0 carries messages from the VideoCore to the ARM, mailbox 1 the other way round.
+register_bitfields! {
+    u32,
+
+    /// Status
+    STATUS [
+        /// No space for another message.
+        FULL OFFSET(31) NUMBITS(1) [],
+
+        /// No message to read.
+        EMPTY OFFSET(30) NUMBITS(1) []
+    ]
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    RegisterBlock {
+        (0x00 => MAIL0_RD: ReadOnly<u32>),
+        (0x04 => _reserved1),
+        (0x18 => MAIL0_STATUS: ReadOnly<u32, STATUS::Register>),
+        (0x1C => _reserved2),
+        (0x20 => MAIL1_WRT: WriteOnly<u32>),
+        (0x24 => _reserved3),
+        (0x38 => MAIL1_STATUS: ReadOnly<u32, STATUS::Register>),
+        (0x3C => _reserved4),
+        (0x40 => @END),
+    }
+}
+
+/// Abstraction for the associated MMIO registers.
+type Registers = MMIODerefWrapper<RegisterBlock>;
+
+/// The channel of the property interface, ARM to VideoCore.
+const CHANNEL_PROPERTY: u32 = 8;
+
+/// The lower four bits of a mailbox message carry the channel.
+const CHANNEL_MASK: u32 = 0xF;
+
+/// The VideoCore accesses ARM memory through this bus alias, which bypasses its L2 cache.
+const BUS_ADDR_UNCACHED_ALIAS: usize = 0xC000_0000;
+
+const REQUEST: u32 = 0x0000_0000;
+const RESPONSE_SUCCESS: u32 = 0x8000_0000;
+const TAG_RESPONSE: u32 = 0x8000_0000;
+const END_TAG: u32 = 0;
+
+/// Size of the property buffer in words.
+const BUFFER_WORDS: usize = 256;
+
+/// Message header: Size and code.
+const MESSAGE_HEADER_WORDS: usize = 2;
+
+/// Tag header: Tag, size of the value buffer and code.
+const TAG_HEADER_WORDS: usize = 3;
+
+/// Property tags.
+mod tag {
+    pub const GET_BOARD_REVISION: u32 = 0x0001_0002;
+    pub const GET_BOARD_SERIAL: u32 = 0x0001_0004;
+    pub const GET_ARM_MEMORY: u32 = 0x0001_0005;
+    pub const GET_VC_MEMORY: u32 = 0x0001_0006;
+    pub const GET_POWER_STATE: u32 = 0x0002_0001;
+    pub const SET_POWER_STATE: u32 = 0x0002_8001;
+    pub const GET_CLOCK_RATE: u32 = 0x0003_0002;
+    pub const GET_MAX_CLOCK_RATE: u32 = 0x0003_0004;
+    pub const GET_TEMPERATURE: u32 = 0x0003_0006;
+    pub const GET_MAX_TEMPERATURE: u32 = 0x0003_000A;
+    pub const GET_COMMAND_LINE: u32 = 0x0005_0001;
+}
+
+/// Power state bits.
+mod power_state {
+    /// The device is, or shall be, powered on.
+    pub const ON: u32 = 1 << 0;
+
+    /// Request: Return only once the device is in the new state.
+    pub const WAIT: u32 = 1 << 1;
+
+    /// Response: The device does not exist.
+    pub const NO_DEVICE: u32 = 1 << 1;
+}
+
+/// Memory for property messages.
+///
+/// The firmware requires 16 byte alignment, because the lower four bits of the address carry the
+/// channel. Large enough for the firmware's command line.
+#[repr(C, align(16))]
+struct PropertyBuffer([u32; BUFFER_WORDS]);
+
+struct MailboxInner {
+    registers: Registers,
+    buffer: PropertyBuffer,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Clocks that the firmware manages.
+#[allow(missing_docs)]
+#[derive(Copy, Clone)]
+pub enum Clock {
+    Emmc = 1,
+    Uart = 2,
+    Arm = 3,
+    Core = 4,
+    V3d = 5,
+    H264 = 6,
+    Isp = 7,
+    Sdram = 8,
+    Pixel = 9,
+    Pwm = 10,
+}
+
+/// Devices that the firmware can power on and off.
+#[allow(missing_docs)]
+#[derive(Copy, Clone)]
+pub enum PowerDevice {
+    SdCard = 0,
+    Uart0 = 1,
+    Uart1 = 2,
+    UsbHcd = 3,
+    I2c0 = 4,
+    I2c1 = 5,
+    I2c2 = 6,
+    Spi = 7,
+    Ccp2tx = 8,
+}
+
+/// A block of physical memory, as reported by the firmware.
+#[derive(Copy, Clone)]
+pub struct MemoryBlock {
+    /// Physical start address.
+    pub start_addr: Address<Physical>,
+
+    /// Size in bytes.
+    pub size: usize,
+}
+
+/// A property tag with its values, for messages that carry several tags.
+pub struct PropertyTag<'a> {
+    /// The tag.
+    pub tag: u32,
+
+    /// The request values on entry, the response values on return.
+    pub values: &'a mut [u32],
+
+    /// The size of the response in bytes, set on return. Can exceed the size of `values`.
+    pub response_size: usize,
+}
+
+/// Representation of the mailbox HW.
+pub struct Mailbox {
+    inner: IRQSafeNullLock<MailboxInner>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl MailboxInner {
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO start address.
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            registers: Registers::new(mmio_start_addr),
+            buffer: PropertyBuffer([0; BUFFER_WORDS]),
+        }
+    }
+
+    /// Hand the property buffer to the firmware and wait for its answer.
+    fn call(&mut self) -> Result<(), &'static str> {
+        let virt_addr = Address::<Virtual>::new(self.buffer.0.as_ptr() as usize);
+        let phys_addr = memory::mmu::try_kernel_virt_addr_to_phys_addr(virt_addr)?;
+        let bus_addr = u32::try_from(phys_addr.as_usize() | BUS_ADDR_UNCACHED_ALIAS)
+            .map_err(|_| "Property buffer is not reachable by the firmware")?;
+        let message = bus_addr | CHANNEL_PROPERTY;
+
+        // The firmware does not snoop the ARM's caches.
+        let buffer_size = mem::size_of::<PropertyBuffer>();
+        cpu::clean_invalidate_dcache_range(virt_addr.as_usize(), buffer_size);
+
+        while self.registers.MAIL1_STATUS.is_set(STATUS::FULL) {
+            cpu::nop();
+        }
+        self.registers.MAIL1_WRT.set(message);
+
+        // Answers on other channels are not expected, and dropped.
+        loop {
+            while self.registers.MAIL0_STATUS.is_set(STATUS::EMPTY) {
+                cpu::nop();
+            }
+
+            let answer = self.registers.MAIL0_RD.get();
+            if answer & CHANNEL_MASK == CHANNEL_PROPERTY {
+                if answer != message {
+                    return Err("Firmware answered with an unexpected buffer");
+                }
+                break;
+            }
+        }
+
+        cpu::clean_invalidate_dcache_range(virt_addr.as_usize(), buffer_size);
+
+        Ok(())
+    }
+
+    /// Send a message with several tags. See [`Mailbox::properties`].
+    fn properties(&mut self, tags: &mut [PropertyTag]) -> Result<(), &'static str> {
+        let num_words = MESSAGE_HEADER_WORDS
+            + tags
+                .iter()
+                .map(|t| TAG_HEADER_WORDS + t.values.len())
+                .sum::<usize>()
+            + 1;
+        if num_words > self.buffer.0.len() {
+            return Err("Property message does not fit into the buffer");
+        }
+
+        let buf = &mut self.buffer.0;
+        buf[0] = (num_words * 4) as u32;
+        buf[1] = REQUEST;
+        let mut i = MESSAGE_HEADER_WORDS;
+        for t in tags.iter() {
+            let len = t.values.len();
+            buf[i] = t.tag;
+            buf[i + 1] = (len * 4) as u32;
+            buf[i + 2] = REQUEST;
+            buf[i + 3..i + 3 + len].copy_from_slice(t.values);
+            i += TAG_HEADER_WORDS + len;
+        }
+        buf[i] = END_TAG;
+
+        self.call()?;
+
+        let buf = &self.buffer.0;
+        if buf[1] != RESPONSE_SUCCESS {
+            return Err("Firmware could not parse the property message");
+        }
+
+        let mut i = MESSAGE_HEADER_WORDS;
+        for t in tags.iter_mut() {
+            let len = t.values.len();
+            if buf[i + 2] & TAG_RESPONSE == 0 {
+                return Err("Firmware does not know the property tag");
+            }
+
+            t.response_size = (buf[i + 2] & !TAG_RESPONSE) as usize;
+            let num_response_words = len.min(t.response_size.div_ceil(4));
+            t.values[..num_response_words].copy_from_slice(&buf[i + 3..i + 3 + num_response_words]);
+            i += TAG_HEADER_WORDS + len;
+        }
+
+        Ok(())
+    }
+}
+
+impl MemoryBlock {
+    fn from_response(values: [u32; 2]) -> Self {
+        Self {
+            start_addr: Address::new(values[0] as usize),
+            size: values[1] as usize,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Mailbox {
+    pub const COMPATIBLE: &'static str = "BCM Mailbox";
+
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO start address.
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            inner: IRQSafeNullLock::new(MailboxInner::new(mmio_start_addr)),
+        }
+    }
+
+    /// Send a message with a single property tag to the firmware.
+    ///
+    /// `values` holds the request values on entry and the response values on return. Returns the
+    /// size of the response in bytes, which can exceed the size of `values`.
+    pub fn property(&self, tag: u32, values: &mut [u32]) -> Result<usize, &'static str> {
+        let mut tags = [PropertyTag::new(tag, values)];
+        self.properties(&mut tags)?;
+
+        Ok(tags[0].response_size)
+    }
+
+    /// Send a message with several property tags to the firmware.
+    ///
+    /// Needed where the firmware evaluates tags together, e.g. for allocating a framebuffer.
+    pub fn properties(&self, tags: &mut [PropertyTag]) -> Result<(), &'static str> {
+        self.inner.lock(|inner| inner.properties(tags))
+    }
+
+    /// The board revision code.
+    pub fn board_revision(&self) -> Result<u32, &'static str> {
+        let mut values = [0; 1];
+        self.property(tag::GET_BOARD_REVISION, &mut values)?;
+
+        Ok(values[0])
+    }
+
+    /// The board serial number.
+    pub fn board_serial(&self) -> Result<u64, &'static str> {
+        let mut values = [0; 2];
+        self.property(tag::GET_BOARD_SERIAL, &mut values)?;
+
+        Ok(((values[1] as u64) << 32) | values[0] as u64)
+    }
+
+    /// The part of the DRAM that belongs to the ARM cores.
+    pub fn arm_memory(&self) -> Result<MemoryBlock, &'static str> {
+        let mut values = [0; 2];
+        self.property(tag::GET_ARM_MEMORY, &mut values)?;
+
+        Ok(MemoryBlock::from_response(values))
+    }
+
+    /// The part of the DRAM that belongs to the VideoCore.
+    pub fn vc_memory(&self) -> Result<MemoryBlock, &'static str> {
+        let mut values = [0; 2];
+        self.property(tag::GET_VC_MEMORY, &mut values)?;
+
+        Ok(MemoryBlock::from_response(values))
+    }
+
+    /// The current rate of a clock in Hz.
+    pub fn clock_rate(&self, clock: Clock) -> Result<u32, &'static str> {
+        let mut values = [clock as u32, 0];
+        self.property(tag::GET_CLOCK_RATE, &mut values)?;
+
+        Ok(values[1])
+    }
+
+    /// The maximum rate of a clock in Hz.
+    pub fn max_clock_rate(&self, clock: Clock) -> Result<u32, &'static str> {
+        let mut values = [clock as u32, 0];
+        self.property(tag::GET_MAX_CLOCK_RATE, &mut values)?;
+
+        Ok(values[1])
+    }
+
+    /// The SoC temperature in thousandths of a degree Celsius.
+    pub fn temperature(&self) -> Result<u32, &'static str> {
+        let mut values = [0; 2];
+        self.property(tag::GET_TEMPERATURE, &mut values)?;
+
+        Ok(values[1])
+    }
+
+    /// The temperature in thousandths of a degree Celsius at which the firmware throttles the SoC.
+    pub fn max_temperature(&self) -> Result<u32, &'static str> {
+        let mut values = [0; 2];
+        self.property(tag::GET_MAX_TEMPERATURE, &mut values)?;
+
+        Ok(values[1])
+    }
+
+    /// Whether a device is powered on.
+    pub fn power_state(&self, device: PowerDevice) -> Result<bool, &'static str> {
+        let mut values = [device as u32, 0];
+        self.property(tag::GET_POWER_STATE, &mut values)?;
+
+        if values[1] & power_state::NO_DEVICE != 0 {
+            return Err("Device does not exist");
+        }
+
+        Ok(values[1] & power_state::ON != 0)
+    }
+
+    /// Power a device on or off. Returns once the device is in the new state.
+    pub fn set_power_state(&self, device: PowerDevice, on: bool) -> Result<(), &'static str> {
+        let state = if on { power_state::ON } else { 0 };
+        let mut values = [device as u32, state | power_state::WAIT];
+        self.property(tag::SET_POWER_STATE, &mut values)?;
+
+        if values[1] & power_state::NO_DEVICE != 0 {
+            return Err("Device does not exist");
+        }
+        if values[1] & power_state::ON != state {
+            return Err("Firmware could not change the power state");
+        }
+
+        Ok(())
+    }
+
+    /// The kernel command line that the firmware passes, e.g. from `cmdline.txt`.
+    pub fn command_line(&self) -> Result<String, &'static str> {
+        let mut values = [0; BUFFER_WORDS - MESSAGE_HEADER_WORDS - TAG_HEADER_WORDS - 1];
+        let size = self.property(tag::GET_COMMAND_LINE, &mut values)?;
+
+        let bytes: Vec<u8> = values.iter().flat_map(|x| x.to_le_bytes()).collect();
+        let bytes = &bytes[..size.min(bytes.len())];
+        let len = bytes.iter().position(|&c| c == 0).unwrap_or(bytes.len());
+
+        core::str::from_utf8(&bytes[..len])
+            .map(String::from)
+            .map_err(|_| "Command line is not valid UTF-8")
+    }
+}
+
+impl<'a> PropertyTag<'a> {
+    /// Create an instance.
+    pub fn new(tag: u32, values: &'a mut [u32]) -> Self {
+        Self {
+            tag,
+            values,
+            response_size: 0,
+        }
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+use synchronization::interface::Mutex;
+
+impl driver::interface::DeviceDriver for Mailbox {
+    type IRQNumberType = IRQNumber;
+
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+}
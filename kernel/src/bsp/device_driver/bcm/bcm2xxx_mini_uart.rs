@@ -0,0 +1,581 @@
+//! Mini UART driver.
+//!
+//! The mini UART is part of the auxiliary peripherals (AUX) block. Compared to the PL011, it has
+//! small FIFOs, no parity support and a baud rate that is derived from the core clock.
+//!
+//! Received characters are moved into a software RX buffer by the RX interrupt. Characters are
+//! sent synchronously.
+//!
+//! # Resources
+//!
+//! - <https://github.com/raspberrypi/documentation/files/1888662/BCM2837-ARM-Peripherals.-.Revised.-.V2-1.pdf>
+//! - <https://elinux.org/BCM2835_datasheet_errata>
+
+use crate::{
+    bsp::device_driver::common::MMIODerefWrapper,
+    common::RingBuffer,
+    console::{self, FlowControl, LineConfig, Parity},
+    cpu, driver,
+    exception::{self, asynchronous::IRQNumber},
+    memory::{Address, Virtual},
+    synchronization,
+    synchronization::IRQSafeNullLock,
+    vfs, watchdog,
+};
+use core::fmt;
+use tock_registers::{
+    interfaces::{ReadWriteable, Readable, Writeable},
+    register_bitfields, register_structs,
+    registers::{ReadOnly, ReadWrite},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+// Auxiliary peripherals and mini UART registers.
+//
+// Descriptions taken from the BCM2837 ARM Peripherals manual, corrected according to the errata.
+register_bitfields! {
+    u32,
+
+    /// Auxiliary Interrupt status.
+    AUX_IRQ [
+        /// If set the mini UART has an interrupt pending.
+        MINI_UART OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// Auxiliary enables.
+    AUX_ENABLES [
+        /// If set the mini UART is enabled. The UART will immediately start receiving data,
+        /// especially if the UART1_RX line is low. If clear the mini UART is disabled. That also
+        /// disables any mini UART register access.
+        MINI_UART OFFSET(0) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ]
+    ],
+
+    /// Mini UART Interrupt Enable.
+    AUX_MU_IER [
+        /// Not documented, but required to receive interrupts.
+        EDSSI OFFSET(3) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
+        /// Not documented, but required to receive interrupts.
+        ELSI OFFSET(2) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
+        /// If this bit is set the interrupt line is asserted whenever the transmit FIFO is empty.
+        TXIE OFFSET(1) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
+        /// If this bit is set the interrupt line is asserted whenever the receive FIFO holds at
+        /// least 1 byte.
+        RXIE OFFSET(0) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ]
+    ],
+
+    /// Mini UART Interrupt Identify.
+    AUX_MU_IIR [
+        /// On write: Clear the receive and/or transmit FIFO.
+        FIFO_CLEAR OFFSET(1) NUMBITS(2) [
+            Rx = 0b01,
+            Tx = 0b10,
+            All = 0b11
+        ]
+    ],
+
+    /// Mini UART Line Control.
+    AUX_MU_LCR [
+        /// The UART works in 7-bit mode or 8-bit mode. Bit 1 is not documented, but required for
+        /// 8-bit mode.
+        DATA_SIZE OFFSET(0) NUMBITS(2) [
+            SevenBit = 0b00,
+            EightBit = 0b11
+        ]
+    ],
+
+    /// Mini UART Line Status.
+    AUX_MU_LSR [
+        /// This bit is set if the transmit FIFO is empty and the transmitter is idle.
+        TX_IDLE OFFSET(6) NUMBITS(1) [],
+
+        /// This bit is set if the transmit FIFO can accept at least one byte.
+        TX_EMPTY OFFSET(5) NUMBITS(1) [],
+
+        /// This bit is set if there was a receiver overrun. That is: one or more characters arrived
+        /// whilst the receive FIFO was full. This bit is cleared each time this register is read.
+        RX_OVERRUN OFFSET(1) NUMBITS(1) [],
+
+        /// This bit is set if the receive FIFO holds at least 1 symbol.
+        DATA_READY OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// Mini UART Extra Control.
+    AUX_MU_CNTL [
+        /// If this bit is set the transmitter will stop if the CTS line is de-asserted.
+        TX_AUTO_FLOW OFFSET(3) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
+        /// If this bit is set the RTS line will de-assert if the receive FIFO reaches its 'auto
+        /// flow' level.
+        RX_AUTO_FLOW OFFSET(2) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
+        /// If this bit is set the mini UART transmitter is enabled.
+        TX_ENABLE OFFSET(1) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
+        /// If this bit is set the mini UART receiver is enabled.
+        RX_ENABLE OFFSET(0) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ]
+    ],
+
+    /// Mini UART Baudrate.
+    AUX_MU_BAUD [
+        /// Mini UART baudrate counter.
+        BAUDRATE OFFSET(0) NUMBITS(16) []
+    ]
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    pub RegisterBlock {
+        (0x00 => AUX_IRQ: ReadOnly<u32, AUX_IRQ::Register>),
+        (0x04 => AUX_ENABLES: ReadWrite<u32, AUX_ENABLES::Register>),
+        (0x08 => _reserved1),
+        (0x40 => AUX_MU_IO: ReadWrite<u32>),
+        (0x44 => AUX_MU_IER: ReadWrite<u32, AUX_MU_IER::Register>),
+        (0x48 => AUX_MU_IIR: ReadWrite<u32, AUX_MU_IIR::Register>),
+        (0x4C => AUX_MU_LCR: ReadWrite<u32, AUX_MU_LCR::Register>),
+        (0x50 => _reserved2),
+        (0x54 => AUX_MU_LSR: ReadOnly<u32, AUX_MU_LSR::Register>),
+        (0x58 => _reserved3),
+        (0x60 => AUX_MU_CNTL: ReadWrite<u32, AUX_MU_CNTL::Register>),
+        (0x64 => _reserved4),
+        (0x68 => AUX_MU_BAUD: ReadWrite<u32, AUX_MU_BAUD::Register>),
+        (0x6C => @END),
+    }
+}
+
+/// Abstraction for the associated MMIO registers.
+type Registers = MMIODerefWrapper<RegisterBlock>;
+
+const RX_BUFFER_SIZE: usize = 1024;
+
+/// The VPU core clock with `enable_uart=1` in config.txt, which fixes it.
+const DEFAULT_REFERENCE_CLOCK_HZ: u32 = 250_000_000;
+
+/// Maximum deviation from the requested baud rate, in per mille.
+const MAX_BAUD_RATE_ERROR: u64 = 25;
+
+struct MiniUartInner {
+    registers: Registers,
+    line_config: LineConfig,
+    reference_clock_hz: u32,
+    rx_buffer: RingBuffer<RX_BUFFER_SIZE>,
+    chars_written: usize,
+    chars_read: usize,
+    overrun_errors: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Representation of the mini UART.
+pub struct MiniUart {
+    inner: IRQSafeNullLock<MiniUartInner>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl MiniUartInner {
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO start address.
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            registers: Registers::new(mmio_start_addr),
+            line_config: LineConfig::DEFAULT,
+            reference_clock_hz: DEFAULT_REFERENCE_CLOCK_HZ,
+            rx_buffer: RingBuffer::new(),
+            chars_written: 0,
+            chars_read: 0,
+            overrun_errors: 0,
+        }
+    }
+
+    /// Enable the mini UART and set up baud rate and characteristics according to the line
+    /// configuration.
+    pub fn init(&mut self) {
+        // The enable bit also gates register access, so check it before flushing.
+        if self.registers.AUX_ENABLES.is_set(AUX_ENABLES::MINI_UART) {
+            self.flush();
+        }
+
+        // Other AUX peripherals share the enable register.
+        self.registers
+            .AUX_ENABLES
+            .modify(AUX_ENABLES::MINI_UART::Enabled);
+
+        // Turn RX and TX off temporarily.
+        self.registers.AUX_MU_CNTL.set(0);
+        self.registers.AUX_MU_IER.set(0);
+
+        // The configuration was validated when it was set, so computing the divisor can not fail.
+        let config = self.line_config;
+        let divisor = baud_rate_divisor(self.reference_clock_hz, config.baud_rate).unwrap();
+
+        let data_size = if config.data_bits == 7 {
+            AUX_MU_LCR::DATA_SIZE::SevenBit
+        } else {
+            AUX_MU_LCR::DATA_SIZE::EightBit
+        };
+        self.registers.AUX_MU_LCR.write(data_size);
+        self.registers
+            .AUX_MU_BAUD
+            .write(AUX_MU_BAUD::BAUDRATE.val(divisor));
+
+        self.registers.AUX_MU_IIR.write(AUX_MU_IIR::FIFO_CLEAR::All);
+
+        // Enable the RX IRQ.
+        self.registers.AUX_MU_IER.write(
+            AUX_MU_IER::RXIE::Enabled + AUX_MU_IER::ELSI::Enabled + AUX_MU_IER::EDSSI::Enabled,
+        );
+
+        let flow_control = match config.flow_control {
+            FlowControl::None => {
+                AUX_MU_CNTL::RX_AUTO_FLOW::Disabled + AUX_MU_CNTL::TX_AUTO_FLOW::Disabled
+            }
+            FlowControl::RtsCts => {
+                AUX_MU_CNTL::RX_AUTO_FLOW::Enabled + AUX_MU_CNTL::TX_AUTO_FLOW::Enabled
+            }
+        };
+
+        // Turn RX and TX on.
+        self.registers.AUX_MU_CNTL.write(
+            AUX_MU_CNTL::RX_ENABLE::Enabled + AUX_MU_CNTL::TX_ENABLE::Enabled + flow_control,
+        );
+    }
+
+    /// Validate and apply a new line configuration.
+    fn set_line_config(&mut self, config: LineConfig) -> Result<(), &'static str> {
+        if !(7..=8).contains(&config.data_bits)
+            || config.parity != Parity::None
+            || config.stop_bits != 1
+        {
+            return Err("The mini UART only supports 7N1 and 8N1");
+        }
+        baud_rate_divisor(self.reference_clock_hz, config.baud_rate)?;
+
+        self.line_config = config;
+        self.init();
+
+        Ok(())
+    }
+
+    /// Apply a new reference clock, keeping the line configuration.
+    fn set_reference_clock(&mut self, reference_clock_hz: u32) -> Result<(), &'static str> {
+        baud_rate_divisor(reference_clock_hz, self.line_config.baud_rate)?;
+
+        self.reference_clock_hz = reference_clock_hz;
+        self.init();
+
+        Ok(())
+    }
+
+    /// Send a character.
+    fn write_char(&mut self, c: char) {
+        // Spin until the TX FIFO can accept a character.
+        while !self.registers.AUX_MU_LSR.is_set(AUX_MU_LSR::TX_EMPTY) {
+            cpu::nop();
+        }
+
+        self.registers.AUX_MU_IO.set(c as u32);
+
+        self.chars_written += 1;
+    }
+
+    /// Send a slice of characters.
+    fn write_array(&mut self, a: &[char]) {
+        for c in a {
+            self.write_char(*c);
+        }
+    }
+
+    /// Block execution until the last buffered character has been physically put on the TX wire.
+    fn flush(&mut self) {
+        while !self.registers.AUX_MU_LSR.is_set(AUX_MU_LSR::TX_IDLE) {
+            cpu::nop();
+        }
+    }
+
+    /// Move received characters from the RX FIFO into the RX buffer.
+    fn receive(&mut self) {
+        loop {
+            // Reading clears the overrun flag.
+            let lsr = self.registers.AUX_MU_LSR.extract();
+
+            if lsr.is_set(AUX_MU_LSR::RX_OVERRUN) {
+                self.overrun_errors += 1;
+            }
+
+            if !lsr.is_set(AUX_MU_LSR::DATA_READY) {
+                break;
+            }
+
+            let c = self.registers.AUX_MU_IO.get() as u8;
+            if !self.rx_buffer.try_push(c) {
+                self.overrun_errors += 1;
+            }
+        }
+    }
+
+    /// Retrieve a character from the RX buffer.
+    ///
+    /// The RX FIFO is checked first, so that characters are picked up even while IRQs are masked.
+    fn read_char_converting(&mut self) -> Option<char> {
+        self.receive();
+
+        let mut ret = self.rx_buffer.pop()? as char;
+
+        // Convert carrige return to newline.
+        if ret == '\r' {
+            ret = '\n'
+        }
+
+        // Update statistics.
+        self.chars_read += 1;
+
+        Some(ret)
+    }
+}
+
+/// Compute the baud rate counter.
+///
+/// The baud rate is `reference_clock / (8 * (counter + 1))`.
+fn baud_rate_divisor(reference_clock_hz: u32, baud_rate: u32) -> Result<u32, &'static str> {
+    if baud_rate == 0 {
+        return Err("Invalid baud rate");
+    }
+
+    let clock = reference_clock_hz as u64 / 8;
+    let baud_rate = baud_rate as u64;
+    let divisor = (clock + baud_rate / 2) / baud_rate;
+
+    if divisor == 0 || divisor > 0x1_0000 {
+        return Err("Baud rate out of range for the UART reference clock");
+    }
+
+    let actual = clock / divisor;
+    if actual.abs_diff(baud_rate) * 1000 > baud_rate * MAX_BAUD_RATE_ERROR {
+        return Err("Baud rate can not be generated accurately from the UART reference clock");
+    }
+
+    Ok((divisor - 1) as u32)
+}
+
+/// Implementing `core::fmt::Write` enables usage of the `format_args!` macros.
+///
+/// See [`src/print.rs`].
+///
+/// [`src/print.rs`]: ../../print/index.html
+impl fmt::Write for MiniUartInner {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.write_char(c);
+        }
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl MiniUart {
+    pub const COMPATIBLE: &'static str = "BCM Mini UART";
+
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO start address.
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            inner: IRQSafeNullLock::new(MiniUartInner::new(mmio_start_addr)),
+        }
+    }
+
+    /// Change the UART reference clock, e.g. after querying the core clock from the firmware.
+    pub fn set_reference_clock(&self, reference_clock_hz: u32) -> Result<(), &'static str> {
+        self.inner
+            .lock(|inner| inner.set_reference_clock(reference_clock_hz))
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+use synchronization::interface::Mutex;
+
+impl driver::interface::DeviceDriver for MiniUart {
+    type IRQNumberType = IRQNumber;
+
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+
+    unsafe fn init(&self) -> Result<(), &'static str> {
+        self.inner.lock(|inner| inner.init());
+
+        Ok(())
+    }
+
+    fn register_and_enable_irq_handler(
+        &'static self,
+        irq_number: &Self::IRQNumberType,
+    ) -> Result<(), &'static str> {
+        use exception::asynchronous::{irq_manager, IRQHandlerDescriptor};
+
+        let descriptor = IRQHandlerDescriptor::new(*irq_number, Self::COMPATIBLE, self);
+
+        irq_manager().register_handler(descriptor)?;
+        irq_manager().enable(irq_number);
+
+        Ok(())
+    }
+
+    fn device_file(&'static self) -> Option<driver::DeviceFileDescriptor> {
+        Some(("uart", self))
+    }
+}
+
+/// Raw bytes. Received carriage returns are converted to newlines, as for the console.
+impl vfs::interface::DeviceFile for MiniUart {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, &'static str> {
+        self.inner.lock(|inner| {
+            let mut len = 0;
+            while len < buf.len() {
+                let Some(c) = inner.read_char_converting() else {
+                    break;
+                };
+                buf[len] = c as u8;
+                len += 1;
+            }
+
+            Ok(len)
+        })
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize, &'static str> {
+        self.inner.lock(|inner| {
+            for &c in data {
+                inner.write_char(c as char);
+            }
+        });
+
+        Ok(data.len())
+    }
+}
+
+impl console::interface::Write for MiniUart {
+    fn write_char(&self, c: char) {
+        self.inner.lock(|inner| inner.write_char(c));
+    }
+
+    fn write_array(&self, a: &[char]) {
+        self.inner.lock(|inner| inner.write_array(a));
+    }
+
+    fn write_fmt(&self, args: core::fmt::Arguments) -> fmt::Result {
+        self.inner.lock(|inner| fmt::Write::write_fmt(inner, args))
+    }
+
+    fn flush(&self) {
+        self.inner.lock(|inner| inner.flush());
+    }
+}
+
+impl console::interface::Read for MiniUart {
+    fn read_char(&self) -> char {
+        // Poll without holding the lock, so that IRQs can be served in between.
+        loop {
+            if let Some(c) = self.inner.lock(|inner| inner.read_char_converting()) {
+                return c;
+            }
+
+            watchdog::keepalive();
+            cpu::nop();
+        }
+    }
+
+    fn clear_rx(&self) {
+        self.inner.lock(|inner| {
+            inner.receive();
+            inner.rx_buffer.clear();
+        });
+    }
+}
+
+impl console::interface::Statistics for MiniUart {
+    fn chars_written(&self) -> usize {
+        self.inner.lock(|inner| inner.chars_written)
+    }
+
+    fn chars_read(&self) -> usize {
+        self.inner.lock(|inner| inner.chars_read)
+    }
+
+    fn overrun_errors(&self) -> usize {
+        self.inner.lock(|inner| inner.overrun_errors)
+    }
+}
+
+impl console::interface::LineConfiguration for MiniUart {
+    fn line_config(&self) -> Option<LineConfig> {
+        Some(self.inner.lock(|inner| inner.line_config))
+    }
+
+    fn set_line_config(&self, config: &LineConfig) -> Result<(), &'static str> {
+        self.inner.lock(|inner| inner.set_line_config(*config))
+    }
+}
+
+impl console::interface::All for MiniUart {}
+
+impl exception::asynchronous::interface::IRQHandler for MiniUart {
+    fn handle(&self) -> Result<(), &'static str> {
+        self.inner.lock(|inner| {
+            // The RX interrupt is cleared by emptying the RX FIFO.
+            if inner.registers.AUX_IRQ.is_set(AUX_IRQ::MINI_UART) {
+                inner.receive();
+            }
+        });
+
+        Ok(())
+    }
+}
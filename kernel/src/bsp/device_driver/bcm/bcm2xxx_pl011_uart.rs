@@ -1,20 +1,27 @@
 //! PL011 UART driver.
 //!
+//! Received characters are moved from the RX FIFO into a software RX buffer by the RX and RX timeout
+//! interrupts. Characters to be sent are queued in a software TX buffer, which the TX interrupt
+//! drains into the TX FIFO.
+//!
 //! # Resources
 //!
 //! - <https://github.com/raspberrypi/documentation/files/1888662/BCM2837-ARM-Peripherals.-.Revised.-.V2-1.pdf>
 
 use crate::{
     bsp::device_driver::common::MMIODerefWrapper,
-    console, cpu, driver,
+    common::RingBuffer,
+    console::{self, FlowControl, LineConfig, Parity},
+    cpu, driver,
     exception::{self, asynchronous::IRQNumber},
     memory::{Address, Virtual},
     synchronization,
     synchronization::IRQSafeNullLock,
+    vfs, watchdog,
 };
 use core::fmt;
 use tock_registers::{
-    interfaces::{Readable, Writeable},
+    interfaces::{ReadWriteable, Readable, Writeable},
     register_bitfields, register_structs,
     registers::{ReadOnly, ReadWrite, WriteOnly},
 };
@@ -29,6 +36,30 @@ use tock_registers::{
 register_bitfields! {
     u32,
 
+    /// Data Register.
+    DR [
+        /// Overrun error. This bit is set to 1 if data is received and the receive FIFO is already
+        /// full. The FIFO contents remain valid because no more data is written when the FIFO is
+        /// full, only the contents of the shift register are overwritten.
+        OE OFFSET(11) NUMBITS(1) [],
+
+        /// Break error. This bit is set to 1 if a break condition was detected, indicating that the
+        /// received data input was held LOW for longer than a full-word transmission time.
+        BE OFFSET(10) NUMBITS(1) [],
+
+        /// Parity error. When set to 1, it indicates that the parity of the received data character
+        /// does not match the parity that the EPS and SPS bits in the Line Control Register, LCR_H
+        /// select.
+        PE OFFSET(9) NUMBITS(1) [],
+
+        /// Framing error. When set to 1, it indicates that the received character did not have a
+        /// valid stop bit.
+        FE OFFSET(8) NUMBITS(1) [],
+
+        /// Data character.
+        DATA OFFSET(0) NUMBITS(8) []
+    ],
+
     /// Flag Register.
     FR [
         /// Transmit FIFO empty. The meaning of this bit depends on the state of the FEN bit in the
@@ -95,11 +126,47 @@ register_bitfields! {
         FEN  OFFSET(4) NUMBITS(1) [
             FifosDisabled = 0,
             FifosEnabled = 1
+        ],
+
+        /// Two stop bits select. If this bit is set to 1, two stop bits are transmitted at the end
+        /// of the frame. The receive logic does not check for two stop bits being received.
+        STP2 OFFSET(3) NUMBITS(1) [
+            OneStopBit = 0,
+            TwoStopBits = 1
+        ],
+
+        /// Even parity select. Controls the type of parity the UART uses during transmission and
+        /// reception. This bit has no effect when the PEN bit disables parity checking and
+        /// generation.
+        EPS OFFSET(2) NUMBITS(1) [
+            Odd = 0,
+            Even = 1
+        ],
+
+        /// Parity enable.
+        PEN OFFSET(1) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
         ]
     ],
 
     /// Control Register.
     CR [
+        /// CTS hardware flow control enable. If this bit is set to 1, CTS hardware flow control is
+        /// enabled. Data is only transmitted when the nUARTCTS signal is asserted.
+        CTSEN OFFSET(15) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
+        /// RTS hardware flow control enable. If this bit is set to 1, RTS hardware flow control is
+        /// enabled. Data is only requested when there is space in the receive FIFO for it to be
+        /// received.
+        RTSEN OFFSET(14) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
         /// Receive enable. If this bit is set to 1, the receive section of the UART is enabled.
         /// Data reception occurs for either UART signals or SIR signals depending on the setting of
         /// the SIREN bit. When the UART is disabled in the middle of reception, it completes the
@@ -143,6 +210,16 @@ register_bitfields! {
             OneHalf = 0b010,
             ThreeQuarters = 0b011,
             SevenEights = 0b100
+        ],
+
+        /// Transmit interrupt FIFO level select. The trigger points for the transmit interrupt are
+        /// as follows.
+        TXIFLSEL OFFSET(0) NUMBITS(3) [
+            OneEigth = 0b000,
+            OneQuarter = 0b001,
+            OneHalf = 0b010,
+            ThreeQuarters = 0b011,
+            SevenEights = 0b100
         ]
     ],
 
@@ -158,6 +235,15 @@ register_bitfields! {
             Enabled = 1
         ],
 
+        /// Transmit interrupt mask. A read returns the current mask for the UARTTXINTR interrupt.
+        ///
+        /// - On a write of 1, the mask of the UARTTXINTR interrupt is set.
+        /// - A write of 0 clears the mask.
+        TXIM OFFSET(5) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
         /// Receive interrupt mask. A read returns the current mask for the UARTRXINTR interrupt.
         ///
         /// - On a write of 1, the mask of the UARTRXINTR interrupt is set.
@@ -174,6 +260,10 @@ register_bitfields! {
         /// UARTRTINTR interrupt.
         RTMIS OFFSET(6) NUMBITS(1) [],
 
+        /// Transmit masked interrupt status. Returns the masked interrupt state of the UARTTXINTR
+        /// interrupt.
+        TXMIS OFFSET(5) NUMBITS(1) [],
+
         /// Receive masked interrupt status. Returns the masked interrupt state of the UARTRXINTR
         /// interrupt.
         RXMIS OFFSET(4) NUMBITS(1) []
@@ -189,7 +279,7 @@ register_bitfields! {
 register_structs! {
     #[allow(non_snake_case)]
     pub RegisterBlock {
-        (0x00 => DR: ReadWrite<u32>),
+        (0x00 => DR: ReadWrite<u32, DR::Register>),
         (0x04 => _reserved1),
         (0x18 => FR: ReadOnly<u32, FR::Register>),
         (0x1c => _reserved2),
@@ -209,16 +299,26 @@ register_structs! {
 /// Abstraction for the associated MMIO registers.
 type Registers = MMIODerefWrapper<RegisterBlock>;
 
-#[derive(PartialEq)]
-enum BlockingMode {
-    Blocking,
-    NonBlocking,
-}
+const RX_BUFFER_SIZE: usize = 1024;
+const TX_BUFFER_SIZE: usize = 4096;
+
+/// The UART reference clock as set with `init_uart_clock` in config.txt.
+const DEFAULT_REFERENCE_CLOCK_HZ: u32 = 48_000_000;
+
+/// Maximum deviation from the requested baud rate, in per mille.
+const MAX_BAUD_RATE_ERROR: u64 = 25;
 
 struct PL011UartInner {
     registers: Registers,
+    line_config: LineConfig,
+    reference_clock_hz: u32,
+    rx_buffer: RingBuffer<RX_BUFFER_SIZE>,
+    tx_buffer: RingBuffer<TX_BUFFER_SIZE>,
     chars_written: usize,
     chars_read: usize,
+    overrun_errors: usize,
+    framing_errors: usize,
+    parity_errors: usize,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -243,16 +343,23 @@ impl PL011UartInner {
     pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
         Self {
             registers: Registers::new(mmio_start_addr),
+            line_config: LineConfig::DEFAULT,
+            reference_clock_hz: DEFAULT_REFERENCE_CLOCK_HZ,
+            rx_buffer: RingBuffer::new(),
+            tx_buffer: RingBuffer::new(),
             chars_written: 0,
             chars_read: 0,
+            overrun_errors: 0,
+            framing_errors: 0,
+            parity_errors: 0,
         }
     }
 
-    /// Set up baud rate and characteristics.
+    /// Set up baud rate and characteristics according to the line configuration.
     ///
-    /// This results in 8N1 and 921_600 baud.
+    /// The default is 8N1 and 921_600 baud.
     ///
-    /// The calculation for the BRD is (we set the clock to 48 MHz in config.txt):
+    /// The calculation for the BRD is, for example (we set the clock to 48 MHz in config.txt):
     /// `(48_000_000 / 16) / 921_600 = 3.2552083`.
     ///
     /// This means the integer part is `3` and goes into the `IBRD`.
@@ -288,36 +395,153 @@ impl PL011UartInner {
         // updated on a single write strobe generated by a LCR_H write. So, to internally update the
         // contents of IBRD or FBRD, a LCR_H write must always be performed at the end.
         //
-        // Set the baud rate, 8N1 and FIFO enabled.
-        self.registers.IBRD.write(IBRD::BAUD_DIVINT.val(3));
-        self.registers.FBRD.write(FBRD::BAUD_DIVFRAC.val(16));
+        // Set the baud rate, frame format and FIFO enabled. The configuration was validated when
+        // it was set, so computing the divisors can not fail.
+        let config = self.line_config;
+        let (ibrd, fbrd) = baud_rate_divisors(self.reference_clock_hz, config.baud_rate).unwrap();
+
+        let wlen = match config.data_bits {
+            5 => LCR_H::WLEN::FiveBit,
+            6 => LCR_H::WLEN::SixBit,
+            7 => LCR_H::WLEN::SevenBit,
+            _ => LCR_H::WLEN::EightBit,
+        };
+        let parity = match config.parity {
+            Parity::None => LCR_H::PEN::Disabled,
+            Parity::Even => LCR_H::PEN::Enabled + LCR_H::EPS::Even,
+            Parity::Odd => LCR_H::PEN::Enabled + LCR_H::EPS::Odd,
+        };
+        let stop_bits = if config.stop_bits == 2 {
+            LCR_H::STP2::TwoStopBits
+        } else {
+            LCR_H::STP2::OneStopBit
+        };
+
+        self.registers.IBRD.write(IBRD::BAUD_DIVINT.val(ibrd));
+        self.registers.FBRD.write(FBRD::BAUD_DIVFRAC.val(fbrd));
         self.registers
             .LCR_H
-            .write(LCR_H::WLEN::EightBit + LCR_H::FEN::FifosEnabled);
+            .write(wlen + parity + stop_bits + LCR_H::FEN::FifosEnabled);
 
-        // Set RX FIFO fill level at 1/8.
-        self.registers.IFLS.write(IFLS::RXIFLSEL::OneEigth);
+        // Set RX and TX FIFO fill levels at 1/8.
+        self.registers
+            .IFLS
+            .write(IFLS::RXIFLSEL::OneEigth + IFLS::TXIFLSEL::OneEigth);
 
-        // Enable RX IRQ + RX timeout IRQ.
+        // Enable RX IRQ + RX timeout IRQ. The TX IRQ is only enabled while the TX buffer is not
+        // empty.
         self.registers
             .IMSC
-            .write(IMSC::RXIM::Enabled + IMSC::RTIM::Enabled);
+            .write(IMSC::RXIM::Enabled + IMSC::RTIM::Enabled + IMSC::TXIM::Disabled);
+
+        let flow_control = match config.flow_control {
+            FlowControl::None => CR::RTSEN::Disabled + CR::CTSEN::Disabled,
+            FlowControl::RtsCts => CR::RTSEN::Enabled + CR::CTSEN::Enabled,
+        };
 
         // Turn the UART on.
         self.registers
             .CR
-            .write(CR::UARTEN::Enabled + CR::TXE::Enabled + CR::RXE::Enabled);
+            .write(CR::UARTEN::Enabled + CR::TXE::Enabled + CR::RXE::Enabled + flow_control);
+    }
+
+    /// Validate and apply a new line configuration.
+    fn set_line_config(&mut self, config: LineConfig) -> Result<(), &'static str> {
+        if !(5..=8).contains(&config.data_bits) || !(1..=2).contains(&config.stop_bits) {
+            return Err("Unsupported frame format");
+        }
+        baud_rate_divisors(self.reference_clock_hz, config.baud_rate)?;
+
+        self.line_config = config;
+        self.init();
+
+        Ok(())
+    }
+
+    /// Apply a new reference clock, keeping the line configuration.
+    fn set_reference_clock(&mut self, reference_clock_hz: u32) -> Result<(), &'static str> {
+        baud_rate_divisors(reference_clock_hz, self.line_config.baud_rate)?;
+
+        self.reference_clock_hz = reference_clock_hz;
+        self.init();
+
+        Ok(())
+    }
+
+    /// Move characters from the TX buffer into the TX FIFO, as long as there is space.
+    ///
+    /// The TX IRQ is enabled if characters remain in the TX buffer. It fires once the TX FIFO
+    /// drained below its fill level.
+    fn transmit(&mut self) {
+        while !self.registers.FR.matches_all(FR::TXFF::SET) {
+            match self.tx_buffer.pop() {
+                None => break,
+                Some(c) => self.registers.DR.set(c as u32),
+            }
+        }
+
+        let txim = if self.tx_buffer.is_empty() {
+            IMSC::TXIM::Disabled
+        } else {
+            IMSC::TXIM::Enabled
+        };
+        self.registers.IMSC.modify(txim);
+    }
+
+    /// Move all characters from the TX buffer into the TX FIFO, spinning for free slots.
+    fn drain_tx_buffer(&mut self) {
+        while let Some(c) = self.tx_buffer.pop() {
+            // Spin while TX FIFO full is set, waiting for an empty slot.
+            while self.registers.FR.matches_all(FR::TXFF::SET) {
+                cpu::nop();
+            }
+
+            self.registers.DR.set(c as u32);
+        }
+
+        self.registers.IMSC.modify(IMSC::TXIM::Disabled);
+    }
+
+    /// Move received characters from the RX FIFO into the RX buffer.
+    ///
+    /// Characters with framing or parity errors are dropped. If the RX buffer is full, further
+    /// characters are dropped and counted as overruns.
+    fn receive(&mut self) {
+        while !self.registers.FR.matches_all(FR::RXFE::SET) {
+            let data = self.registers.DR.extract();
+
+            // An overrun means that a character after this one was lost. This one is still valid.
+            if data.is_set(DR::OE) {
+                self.overrun_errors += 1;
+            }
+
+            if data.matches_any(DR::FE::SET + DR::BE::SET) {
+                self.framing_errors += 1;
+                continue;
+            }
+
+            if data.is_set(DR::PE) {
+                self.parity_errors += 1;
+                continue;
+            }
+
+            if !self.rx_buffer.try_push(data.read(DR::DATA) as u8) {
+                self.overrun_errors += 1;
+            }
+        }
     }
 
     /// Send a character.
+    ///
+    /// The character is queued in the TX buffer. Only if the buffer is full, this spins until it is
+    /// drained.
     fn write_char(&mut self, c: char) {
-        // Spin while TX FIFO full is set, waiting for an empty slot.
-        while self.registers.FR.matches_all(FR::TXFF::SET) {
-            cpu::nop();
+        if self.tx_buffer.is_full() {
+            self.drain_tx_buffer();
         }
 
-        // Write the character to the buffer.
-        self.registers.DR.set(c as u32);
+        self.tx_buffer.try_push(c as u8);
+        self.transmit();
 
         self.chars_written += 1;
     }
@@ -330,30 +554,22 @@ impl PL011UartInner {
     }
 
     /// Block execution until the last buffered character has been physically put on the TX wire.
-    fn flush(&self) {
+    fn flush(&mut self) {
+        self.drain_tx_buffer();
+
         // Spin until the busy bit is cleared.
         while self.registers.FR.matches_all(FR::BUSY::SET) {
             cpu::nop();
         }
     }
 
-    /// Retrieve a character.
-    fn read_char_converting(&mut self, blocking_mode: BlockingMode) -> Option<char> {
-        // If RX FIFO is empty,
-        if self.registers.FR.matches_all(FR::RXFE::SET) {
-            // immediately return in non-blocking mode.
-            if blocking_mode == BlockingMode::NonBlocking {
-                return None;
-            }
-
-            // Otherwise, wait until a char was received.
-            while self.registers.FR.matches_all(FR::RXFE::SET) {
-                cpu::nop();
-            }
-        }
+    /// Retrieve a character from the RX buffer.
+    ///
+    /// The RX FIFO is checked first, so that characters are picked up even while IRQs are masked.
+    fn read_char_converting(&mut self) -> Option<char> {
+        self.receive();
 
-        // Read one character.
-        let mut ret = self.registers.DR.get() as u8 as char;
+        let mut ret = self.rx_buffer.pop()? as char;
 
         // Convert carrige return to newline.
         if ret == '\r' {
@@ -367,6 +583,32 @@ impl PL011UartInner {
     }
 }
 
+/// Compute the integer and fractional baud rate divisors for the `IBRD` and `FBRD` registers.
+///
+/// The divisor is `reference_clock / (16 * baud_rate)`, with the fractional part in 1/64ths. So in
+/// units of 1/64, it is `(4 * reference_clock) / baud_rate`, rounded to the nearest integer.
+fn baud_rate_divisors(reference_clock_hz: u32, baud_rate: u32) -> Result<(u32, u32), &'static str> {
+    if baud_rate == 0 {
+        return Err("Invalid baud rate");
+    }
+
+    let clock = 4 * reference_clock_hz as u64;
+    let baud_rate = baud_rate as u64;
+    let divisor = (clock + baud_rate / 2) / baud_rate;
+
+    let (ibrd, fbrd) = ((divisor >> 6) as u32, (divisor & 0x3f) as u32);
+    if ibrd == 0 || ibrd > 0xffff || (ibrd == 0xffff && fbrd != 0) {
+        return Err("Baud rate out of range for the UART reference clock");
+    }
+
+    let actual = clock / divisor;
+    if actual.abs_diff(baud_rate) * 1000 > baud_rate * MAX_BAUD_RATE_ERROR {
+        return Err("Baud rate can not be generated accurately from the UART reference clock");
+    }
+
+    Ok((ibrd, fbrd))
+}
+
 /// Implementing `core::fmt::Write` enables usage of the `format_args!` macros, which in turn are
 /// used to implement the `kernel`'s `print!` and `println!` macros. By implementing `write_str()`,
 /// we get `write_fmt()` automatically.
@@ -403,6 +645,30 @@ impl PL011Uart {
             inner: IRQSafeNullLock::new(PL011UartInner::new(mmio_start_addr)),
         }
     }
+
+    /// Change the UART reference clock, e.g. after querying it from the firmware.
+    pub fn set_reference_clock(&self, reference_clock_hz: u32) -> Result<(), &'static str> {
+        self.inner
+            .lock(|inner| inner.set_reference_clock(reference_clock_hz))
+    }
+
+    /// Lock the inner state for writing.
+    ///
+    /// The TX IRQ can only drain the TX buffer if IRQs are unmasked on the calling core. Otherwise,
+    /// e.g. in exception handlers or in the panic handler, the output is written synchronously.
+    fn lock_for_write<R>(&self, f: impl FnOnce(&mut PL011UartInner) -> R) -> R {
+        let synchronous = exception::asynchronous::is_local_irq_masked();
+
+        self.inner.lock(|inner| {
+            let ret = f(inner);
+
+            if synchronous {
+                inner.drain_tx_buffer();
+            }
+
+            ret
+        })
+    }
 }
 
 //------------------------------------------------------------------------------
@@ -436,23 +702,55 @@ impl driver::interface::DeviceDriver for PL011Uart {
 
         Ok(())
     }
+
+    fn device_file(&'static self) -> Option<driver::DeviceFileDescriptor> {
+        Some(("uart", self))
+    }
+}
+
+/// Raw bytes. Received carriage returns are converted to newlines, as for the console.
+impl vfs::interface::DeviceFile for PL011Uart {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, &'static str> {
+        self.inner.lock(|inner| {
+            let mut len = 0;
+            while len < buf.len() {
+                let Some(c) = inner.read_char_converting() else {
+                    break;
+                };
+                buf[len] = c as u8;
+                len += 1;
+            }
+
+            Ok(len)
+        })
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize, &'static str> {
+        self.lock_for_write(|inner| {
+            for &c in data {
+                inner.write_char(c as char);
+            }
+        });
+
+        Ok(data.len())
+    }
 }
 
 impl console::interface::Write for PL011Uart {
     /// Passthrough of `args` to the `core::fmt::Write` implementation, but guarded by a Mutex to
     /// serialize access.
     fn write_char(&self, c: char) {
-        self.inner.lock(|inner| inner.write_char(c));
+        self.lock_for_write(|inner| inner.write_char(c));
     }
 
     fn write_array(&self, a: &[char]) {
-        self.inner.lock(|inner| inner.write_array(a));
+        self.lock_for_write(|inner| inner.write_array(a));
     }
 
     fn write_fmt(&self, args: core::fmt::Arguments) -> fmt::Result {
         // Fully qualified syntax for the call to `core::fmt::Write::write_fmt()` to increase
         // readability.
-        self.inner.lock(|inner| fmt::Write::write_fmt(inner, args))
+        self.lock_for_write(|inner| fmt::Write::write_fmt(inner, args))
     }
 
     fn flush(&self) {
@@ -463,17 +761,22 @@ impl console::interface::Write for PL011Uart {
 
 impl console::interface::Read for PL011Uart {
     fn read_char(&self) -> char {
-        self.inner
-            .lock(|inner| inner.read_char_converting(BlockingMode::Blocking).unwrap())
+        // Poll without holding the lock, so that IRQs can be served in between.
+        loop {
+            if let Some(c) = self.inner.lock(|inner| inner.read_char_converting()) {
+                return c;
+            }
+
+            watchdog::keepalive();
+            cpu::nop();
+        }
     }
 
     fn clear_rx(&self) {
-        // Read from the RX FIFO until it is indicating empty.
-        while self
-            .inner
-            .lock(|inner| inner.read_char_converting(BlockingMode::NonBlocking))
-            .is_some()
-        {}
+        self.inner.lock(|inner| {
+            inner.receive();
+            inner.rx_buffer.clear();
+        });
     }
 }
 
@@ -485,6 +788,28 @@ impl console::interface::Statistics for PL011Uart {
     fn chars_read(&self) -> usize {
         self.inner.lock(|inner| inner.chars_read)
     }
+
+    fn overrun_errors(&self) -> usize {
+        self.inner.lock(|inner| inner.overrun_errors)
+    }
+
+    fn framing_errors(&self) -> usize {
+        self.inner.lock(|inner| inner.framing_errors)
+    }
+
+    fn parity_errors(&self) -> usize {
+        self.inner.lock(|inner| inner.parity_errors)
+    }
+}
+
+impl console::interface::LineConfiguration for PL011Uart {
+    fn line_config(&self) -> Option<LineConfig> {
+        Some(self.inner.lock(|inner| inner.line_config))
+    }
+
+    fn set_line_config(&self, config: &LineConfig) -> Result<(), &'static str> {
+        self.inner.lock(|inner| inner.set_line_config(*config))
+    }
 }
 
 impl console::interface::All for PL011Uart {}
@@ -499,10 +824,11 @@ impl exception::asynchronous::interface::IRQHandler for PL011Uart {
 
             // Check for any kind of RX interrupt.
             if pending.matches_any(MIS::RXMIS::SET + MIS::RTMIS::SET) {
-                // Echo any received characters.
-                while let Some(c) = inner.read_char_converting(BlockingMode::NonBlocking) {
-                    inner.write_char(c)
-                }
+                inner.receive();
+            }
+
+            if pending.matches_all(MIS::TXMIS::SET) {
+                inner.transmit();
             }
         });
 
@@ -0,0 +1,217 @@
+//! Hardware Random Number Generator Driver.
+//!
+//! The RNG of the BCM2835 family, which QEMU emulates. Its output is read by the kernel's random
+//! pool, and raw from `/dev/hwrng0`.
+
+use crate::{
+    bsp::device_driver::common::MMIODerefWrapper,
+    driver,
+    exception::asynchronous::IRQNumber,
+    memory::{Address, Virtual},
+    random, synchronization,
+    synchronization::IRQSafeNullLock,
+    time, vfs,
+};
+use core::time::Duration;
+use tock_registers::{
+    interfaces::{ReadWriteable, Readable, Writeable},
+    register_bitfields, register_structs,
+    registers::{ReadOnly, ReadWrite},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+// RNG registers.
+//
+// The BCM2837 peripherals manual does not document the RNG. Descriptions are taken from the Linux
+// bcm2835-rng driver.
+register_bitfields! {
+    u32,
+
+    /// Control
+    CTRL [
+        /// Random bit generator enable.
+        RBGEN OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// Status
+    STATUS [
+        /// Number of words in the FIFO.
+        WORDS_AVAILABLE OFFSET(24) NUMBITS(8) [],
+
+        /// Number of initial numbers that are discarded, because they are not random enough yet.
+        WARMUP_COUNT OFFSET(0) NUMBITS(20) []
+    ],
+
+    /// Interrupt Mask
+    INT_MASK [
+        /// Mask the interrupt.
+        INT_OFF OFFSET(0) NUMBITS(1) []
+    ]
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    RegisterBlock {
+        (0x00 => CTRL: ReadWrite<u32, CTRL::Register>),
+        (0x04 => STATUS: ReadWrite<u32, STATUS::Register>),
+        (0x08 => DATA: ReadOnly<u32>),
+        (0x0C => _reserved1),
+        (0x10 => INT_MASK: ReadWrite<u32, INT_MASK::Register>),
+        (0x14 => @END),
+    }
+}
+
+/// Abstraction for the associated MMIO registers.
+type Registers = MMIODerefWrapper<RegisterBlock>;
+
+/// Same warm-up as used by Linux.
+const WARMUP_COUNT: u32 = 0x40000;
+
+/// Upper limit for waiting on a word, including the warm-up.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Output per read of `/dev/hwrng0`, in bytes. The hardware is slow, so larger reads return
+/// short.
+const MAX_CHUNK_SIZE: usize = 256;
+
+struct RngInner {
+    registers: Registers,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Representation of the RNG.
+pub struct Rng {
+    inner: IRQSafeNullLock<RngInner>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl RngInner {
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO start address.
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            registers: Registers::new(mmio_start_addr),
+        }
+    }
+
+    /// Start generating, unless the firmware did already.
+    fn init(&mut self) {
+        // The driver polls.
+        self.registers.INT_MASK.modify(INT_MASK::INT_OFF::SET);
+
+        if self.registers.CTRL.is_set(CTRL::RBGEN) {
+            return;
+        }
+
+        self.registers
+            .STATUS
+            .write(STATUS::WARMUP_COUNT.val(WARMUP_COUNT));
+        self.registers.CTRL.write(CTRL::RBGEN::SET);
+    }
+
+    /// A word from the FIFO, if there is one.
+    fn try_read_word(&mut self) -> Option<u32> {
+        if self.registers.STATUS.read(STATUS::WORDS_AVAILABLE) == 0 {
+            return None;
+        }
+
+        Some(self.registers.DATA.get())
+    }
+}
+
+impl Rng {
+    /// Wait for the next word. The lock is only held for a single poll, so that IRQs are not
+    /// masked while the hardware catches up.
+    fn read_word(&self) -> Result<u32, &'static str> {
+        let deadline = time::time_manager().uptime() + TIMEOUT;
+
+        loop {
+            if let Some(word) = self.inner.lock(|inner| inner.try_read_word()) {
+                return Ok(word);
+            }
+            if time::time_manager().uptime() > deadline {
+                return Err("Timeout waiting for random numbers");
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    fn fill(&self, buf: &mut [u8]) -> Result<(), &'static str> {
+        for chunk in buf.chunks_mut(4) {
+            let word = self.read_word()?.to_le_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Rng {
+    pub const COMPATIBLE: &'static str = "BCM RNG";
+
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO start address.
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            inner: IRQSafeNullLock::new(RngInner::new(mmio_start_addr)),
+        }
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+use synchronization::interface::Mutex;
+
+impl driver::interface::DeviceDriver for Rng {
+    type IRQNumberType = IRQNumber;
+
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+
+    unsafe fn init(&self) -> Result<(), &'static str> {
+        self.inner.lock(|inner| inner.init());
+
+        Ok(())
+    }
+
+    fn device_file(&'static self) -> Option<driver::DeviceFileDescriptor> {
+        Some(("hwrng", self))
+    }
+}
+
+impl random::interface::EntropySource for Rng {
+    fn fill(&self, buf: &mut [u8]) -> Result<(), &'static str> {
+        Rng::fill(self, buf)
+    }
+}
+
+/// Raw output of the hardware.
+impl vfs::interface::DeviceFile for Rng {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, &'static str> {
+        let len = buf.len().min(MAX_CHUNK_SIZE);
+        self.fill(&mut buf[..len])?;
+
+        Ok(len)
+    }
+}
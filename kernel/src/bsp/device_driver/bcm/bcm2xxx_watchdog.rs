@@ -0,0 +1,257 @@
+//! Watchdog Driver.
+//!
+//! The watchdog is part of the power management (PM) block. When it expires, it resets the SoC
+//! according to the reset configuration in `PM_RSTC`.
+//!
+//! Besides the watchdog proper, it provides the reset and power-off of the board. Power-off is a
+//! reset into a partition that the firmware recognizes as halt: it stops the board until it is
+//! power cycled. QEMU powers off the machine instead.
+
+use crate::{
+    bsp::device_driver::common::MMIODerefWrapper,
+    cpu, driver,
+    exception::asynchronous::IRQNumber,
+    memory::{Address, Virtual},
+    synchronization,
+    synchronization::IRQSafeNullLock,
+    watchdog,
+};
+use core::time::Duration;
+use tock_registers::{
+    interfaces::{ReadWriteable, Readable, Writeable},
+    register_bitfields, register_structs,
+    registers::ReadWrite,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+// PM registers.
+//
+// The BCM2837 peripherals manual does not document the PM block. Descriptions are taken from the
+// Linux bcm2835_wdt driver.
+register_bitfields! {
+    u32,
+
+    /// Reset Control
+    PM_RSTC [
+        /// Must be written with every access, otherwise the write is ignored.
+        PASSWD OFFSET(24) NUMBITS(8) [
+            Magic = 0x5a
+        ],
+
+        /// Reset configuration, applied when the watchdog expires.
+        WRCFG OFFSET(4) NUMBITS(2) [
+            Clear = 0b00,
+            FullReset = 0b10
+        ]
+    ],
+
+    /// Reset Status
+    PM_RSTS [
+        /// Must be written with every access, otherwise the write is ignored.
+        PASSWD OFFSET(24) NUMBITS(8) [
+            Magic = 0x5a
+        ],
+
+        /// The partition to boot from after a reset, spread over the even bits. The firmware
+        /// halts when it finds partition 63.
+        PARTITION OFFSET(0) NUMBITS(11) [
+            Halt = 0x555
+        ]
+    ],
+
+    /// Watchdog
+    PM_WDOG [
+        /// Must be written with every access, otherwise the write is ignored.
+        PASSWD OFFSET(24) NUMBITS(8) [
+            Magic = 0x5a
+        ],
+
+        /// Remaining time in ticks of 1/65536 seconds.
+        TIME_SET OFFSET(0) NUMBITS(20) []
+    ]
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    RegisterBlock {
+        (0x00 => _reserved1),
+        (0x1C => PM_RSTC: ReadWrite<u32, PM_RSTC::Register>),
+        (0x20 => PM_RSTS: ReadWrite<u32, PM_RSTS::Register>),
+        (0x24 => PM_WDOG: ReadWrite<u32, PM_WDOG::Register>),
+        (0x28 => @END),
+    }
+}
+
+/// Abstraction for the associated MMIO registers.
+type Registers = MMIODerefWrapper<RegisterBlock>;
+
+/// Watchdog ticks per second.
+const TICKS_PER_SEC: u64 = 65536;
+
+/// The largest value of `PM_WDOG::TIME_SET`, almost 16 seconds.
+const MAX_TICKS: u64 = (1 << 20) - 1;
+
+struct WatchdogInner {
+    registers: Registers,
+
+    /// The timeout of the armed watchdog, `None` if it is stopped.
+    timeout_ticks: Option<u32>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Representation of the watchdog HW.
+pub struct Watchdog {
+    inner: IRQSafeNullLock<WatchdogInner>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl WatchdogInner {
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO start address.
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            registers: Registers::new(mmio_start_addr),
+            timeout_ticks: None,
+        }
+    }
+
+    /// Load the timeout, and reset the SoC when it expires.
+    fn arm(&mut self, ticks: u32) {
+        self.registers
+            .PM_WDOG
+            .write(PM_WDOG::PASSWD::Magic + PM_WDOG::TIME_SET.val(ticks));
+        self.registers
+            .PM_RSTC
+            .modify(PM_RSTC::PASSWD::Magic + PM_RSTC::WRCFG::FullReset);
+    }
+
+    fn start(&mut self, timeout: Duration) -> Result<(), &'static str> {
+        let ticks = timeout.as_micros() * TICKS_PER_SEC as u128 / 1_000_000;
+        if ticks == 0 {
+            return Err("Timeout too short");
+        }
+        if ticks > MAX_TICKS as u128 {
+            return Err("Timeout too long");
+        }
+
+        self.arm(ticks as u32);
+        self.timeout_ticks = Some(ticks as u32);
+
+        Ok(())
+    }
+
+    fn pet(&mut self) {
+        if let Some(ticks) = self.timeout_ticks {
+            self.arm(ticks);
+        }
+    }
+
+    fn stop(&mut self) {
+        self.registers
+            .PM_RSTC
+            .write(PM_RSTC::PASSWD::Magic + PM_RSTC::WRCFG::Clear);
+        self.timeout_ticks = None;
+    }
+
+    fn time_left(&self) -> Option<Duration> {
+        self.timeout_ticks?;
+
+        let ticks = self.registers.PM_WDOG.read(PM_WDOG::TIME_SET) as u64;
+        Some(Duration::from_micros(ticks * 1_000_000 / TICKS_PER_SEC))
+    }
+
+    /// Let the watchdog expire after a few ticks and trigger a full reset.
+    fn reset(&mut self) {
+        // Same timeout as used by Linux.
+        const RESET_TICKS: u32 = 10;
+
+        self.arm(RESET_TICKS);
+    }
+
+    /// Reset into the halt partition.
+    fn power_off(&mut self) {
+        self.registers
+            .PM_RSTS
+            .modify(PM_RSTS::PASSWD::Magic + PM_RSTS::PARTITION::Halt);
+        self.reset();
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Watchdog {
+    pub const COMPATIBLE: &'static str = "BCM Watchdog";
+
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO start address.
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            inner: IRQSafeNullLock::new(WatchdogInner::new(mmio_start_addr)),
+        }
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+use synchronization::interface::Mutex;
+
+impl driver::interface::DeviceDriver for Watchdog {
+    type IRQNumberType = IRQNumber;
+
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+}
+
+impl watchdog::interface::Watchdog for Watchdog {
+    fn max_timeout(&self) -> Duration {
+        Duration::from_micros(MAX_TICKS * 1_000_000 / TICKS_PER_SEC)
+    }
+
+    fn start(&self, timeout: Duration) -> Result<(), &'static str> {
+        self.inner.lock(|inner| inner.start(timeout))
+    }
+
+    fn pet(&self) {
+        self.inner.lock(|inner| inner.pet())
+    }
+
+    fn stop(&self) {
+        self.inner.lock(|inner| inner.stop())
+    }
+
+    fn time_left(&self) -> Option<Duration> {
+        self.inner.lock(|inner| inner.time_left())
+    }
+
+    fn reset(&self) -> ! {
+        self.inner.lock(|inner| inner.reset());
+
+        // The reset takes effect once the watchdog expires.
+        cpu::wait_forever()
+    }
+
+    fn power_off(&self) -> ! {
+        self.inner.lock(|inner| inner.power_off());
+
+        cpu::wait_forever()
+    }
+}
@@ -4,11 +4,137 @@ pub mod driver;
 pub mod cpu;
 pub mod memory;
 pub mod exception;
+pub mod shell_commands;
+
+use crate::{
+    cpu as generic_cpu, info,
+    memory::{self as generic_memory, mmu::MMIODescriptor, Address, Physical},
+    warn,
+};
+use alloc::string::String;
+use kernel_core::fdt;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Device trees of the Raspberry Pi are a few dozen KiB. Anything much larger is not one.
+const MAX_DEVICE_TREE_SIZE: usize = 1024 * 1024;
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Decode the model from a new-style board revision code.
+///
+/// See <https://www.raspberrypi.com/documentation/computers/raspberry-pi.html#raspberry-pi-revision-codes>.
+fn model_name(revision: u32) -> Option<&'static str> {
+    const NEW_STYLE: u32 = 1 << 23;
+
+    if revision & NEW_STYLE == 0 {
+        return None;
+    }
+
+    let name = match (revision >> 4) & 0xFF {
+        0x04 => "Raspberry Pi 2 Model B",
+        0x08 => "Raspberry Pi 3 Model B",
+        0x09 => "Raspberry Pi Zero",
+        0x0A => "Raspberry Pi Compute Module 3",
+        0x0C => "Raspberry Pi Zero W",
+        0x0D => "Raspberry Pi 3 Model B+",
+        0x0E => "Raspberry Pi 3 Model A+",
+        0x10 => "Raspberry Pi Compute Module 3+",
+        0x12 => "Raspberry Pi Zero 2 W",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+/// Map the device tree at `phys_addr`.
+///
+/// The mapping covers whole pages, so it usually holds the entire tree already when it is made
+/// for the header. Only a tree that extends beyond the pages of its header is mapped a second
+/// time, with its full size. The kernel can not unmap, so the mappings stay.
+unsafe fn map_device_tree(phys_addr: Address<Physical>) -> Result<&'static [u8], &'static str> {
+    let map = |size| {
+        generic_memory::mmu::kernel_map_ram("Device Tree", &MMIODescriptor::new(phys_addr, size))
+    };
+
+    let virt_addr = map(fdt::HEADER_SIZE)?;
+    let header = core::slice::from_raw_parts(virt_addr.as_usize() as *const u8, fdt::HEADER_SIZE);
+    let size = fdt::total_size(header)?;
+    if size > MAX_DEVICE_TREE_SIZE {
+        return Err("Device tree too large");
+    }
+
+    let mapped_end = (phys_addr + fdt::HEADER_SIZE).align_up(memory::mmu::KernelGranule::SIZE);
+    let virt_addr = if size <= mapped_end.as_usize() - phys_addr.as_usize() {
+        virt_addr
+    } else {
+        map(size)?
+    };
+
+    Ok(core::slice::from_raw_parts(
+        virt_addr.as_usize() as *const u8,
+        size,
+    ))
+}
+
+/// The `bootargs` of the device tree that the firmware passed at boot.
+unsafe fn device_tree_bootargs() -> Result<Option<String>, &'static str> {
+    let Some(phys_addr) = generic_cpu::boot_dtb_phys_addr() else {
+        return Ok(None);
+    };
+
+    let data = map_device_tree(phys_addr)?;
+    let bootargs = fdt::Fdt::new(data)?.property("/chosen", "bootargs")?;
+
+    bootargs
+        .map(|value| fdt::property_str(value).map(String::from))
+        .transpose()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
 
 /// Board identification.
+///
+/// The exact model is known once the firmware can be asked through the mailbox.
 pub fn board_name() -> &'static str {
+    let model = driver::mailbox()
+        .and_then(|mailbox| mailbox.board_revision().ok())
+        .and_then(model_name);
+
     #[cfg(feature = "bsp_rpi3")]
     {
-        "Raspberry Pi 3"
+        model.unwrap_or("Raspberry Pi 3")
+    }
+}
+
+/// The kernel command line, and where it came from.
+///
+/// The device tree is preferred over the firmware's property interface, which needs the mailbox
+/// driver to be instantiated ahead of the others. `None` if neither has a command line.
+///
+/// # Safety
+///
+/// - Only callable during kernel init, after the memory subsystem is up.
+pub unsafe fn command_line() -> Option<(&'static str, String)> {
+    match device_tree_bootargs() {
+        Ok(Some(line)) if !line.trim().is_empty() => return Some(("device tree", line)),
+        Ok(_) => (),
+        Err(x) => warn!("Ignoring the device tree: {}", x),
     }
-}
\ No newline at end of file
+
+    let line = match driver::early_mailbox().and_then(|mailbox| mailbox.command_line()) {
+        Ok(line) => line,
+        Err(x) => {
+            info!("No command line from the firmware: {}", x);
+            return None;
+        }
+    };
+
+    (!line.trim().is_empty()).then_some(("firmware", line))
+}
@@ -1,24 +1,89 @@
 //! BSP driver support.
 
-use super::{exception, memory::map::mmio};
+use super::{
+    exception,
+    memory::{self as bsp_memory, map::mmio},
+};
 use crate::{
+    block,
     bsp::device_driver,
-    console, driver as generic_driver,
+    console, driver as generic_driver, early_param,
     exception::{self as generic_exception},
-    memory,
+    log, memory,
     memory::mmu::MMIODescriptor,
+    random,
+    synchronization::{interface::ReadWriteEx, InitStateLock},
+    warn, watchdog,
 };
 use core::{
     mem::MaybeUninit,
     sync::atomic::{AtomicBool, Ordering},
 };
 
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The UARTs that can serve as the system console.
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum ConsoleUart {
+    PL011,
+    MiniUart,
+}
+
+/// The UARTs that serve as consoles.
+#[derive(Copy, Clone)]
+struct ConsoleSelection {
+    /// The UART that becomes the primary console, and the one that is connected to pins 14 and 15.
+    primary: ConsoleUart,
+
+    /// Whether the UART that is not the primary console is brought up as a secondary console, on
+    /// its alternate pins: GPIO 32 and 33 for the mini UART, GPIO 36 and 37 for the PL011 UART.
+    both: bool,
+}
+
+/// Selected at build time with the `CONSOLE` option of the Makefile, and overridden with the
+/// `console` boot parameter.
+const DEFAULT_CONSOLES: ConsoleSelection = ConsoleSelection {
+    primary: if cfg!(feature = "console_mini_uart") {
+        ConsoleUart::MiniUart
+    } else {
+        ConsoleUart::PL011
+    },
+    both: cfg!(feature = "console_both"),
+};
+
+/// The resolution that is requested for the framebuffer console.
+const FRAMEBUFFER_WIDTH: u32 = 1024;
+const FRAMEBUFFER_HEIGHT: u32 = 768;
+
+/// The pins of the SD card slot: clock, command and four data lines.
+const EMMC_CLK_PIN: usize = 48;
+const EMMC_PINS: core::ops::RangeInclusive<usize> = 48..=53;
+
+/// The EMMC base clock, for when the firmware can not be asked.
+const EMMC_DEFAULT_BASE_CLOCK_HZ: u32 = 50_000_000;
+
+/// The name of the SD card as a block device.
+const SD_CARD_NAME: &str = "sd0";
+
 //--------------------------------------------------------------------------------------------------
 // Global instances
 //--------------------------------------------------------------------------------------------------
 
 static mut PL011_UART: MaybeUninit<device_driver::PL011Uart> = MaybeUninit::uninit();
+static mut MINI_UART: MaybeUninit<device_driver::MiniUart> = MaybeUninit::uninit();
 static mut GPIO: MaybeUninit<device_driver::GPIO> = MaybeUninit::uninit();
+static mut WATCHDOG: MaybeUninit<device_driver::Watchdog> = MaybeUninit::uninit();
+static mut RNG: MaybeUninit<device_driver::Rng> = MaybeUninit::uninit();
+static mut MAILBOX: MaybeUninit<device_driver::Mailbox> = MaybeUninit::uninit();
+static mut FRAMEBUFFER: MaybeUninit<device_driver::Framebuffer> = MaybeUninit::uninit();
+static mut EMMC: MaybeUninit<device_driver::Emmc> = MaybeUninit::uninit();
+
+static CONSOLES: InitStateLock<ConsoleSelection> = InitStateLock::new(DEFAULT_CONSOLES);
+
+static GPIO_INSTANTIATED: AtomicBool = AtomicBool::new(false);
+static MAILBOX_INSTANTIATED: AtomicBool = AtomicBool::new(false);
 
 #[cfg(feature = "bsp_rpi3")]
 static mut INTERRUPT_CONTROLLER: MaybeUninit<device_driver::InterruptController> = MaybeUninit::uninit();
@@ -27,6 +92,46 @@ static mut INTERRUPT_CONTROLLER: MaybeUninit<device_driver::InterruptController>
 // Private Code
 //--------------------------------------------------------------------------------------------------
 
+impl core::str::FromStr for ConsoleUart {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pl011" => Ok(Self::PL011),
+            "mini_uart" => Ok(Self::MiniUart),
+            _ => Err("Unknown UART"),
+        }
+    }
+}
+
+impl ConsoleSelection {
+    /// Whether the UART is brought up as a console.
+    fn uses(self, uart: ConsoleUart) -> bool {
+        self.primary == uart || self.both
+    }
+}
+
+fn consoles() -> ConsoleSelection {
+    CONSOLES.read(|consoles| *consoles)
+}
+
+early_param!(
+    "console",
+    "Console UART, pl011 or mini_uart, optionally followed by a comma and the other one",
+    |value: &str| {
+        let mut uarts = value.split(',');
+        let primary: ConsoleUart = uarts.next().unwrap_or_default().parse()?;
+        let both = match (uarts.next(), uarts.next()) {
+            (None, _) => false,
+            (Some(other), None) if other.parse::<ConsoleUart>()? != primary => true,
+            _ => return Err("Expected two different UARTs at most"),
+        };
+
+        CONSOLES.write(|consoles| *consoles = ConsoleSelection { primary, both });
+        Ok(())
+    }
+);
+
 /// This must be called only after successful init of the memory subsystem.
 unsafe fn instantiate_uart() -> Result<(), &'static str> {
     let mmio_descriptor = MMIODescriptor::new(mmio::PL011_UART_START, mmio::PL011_UART_SIZE);
@@ -40,7 +145,41 @@ unsafe fn instantiate_uart() -> Result<(), &'static str> {
 
 /// This must be called only after successful init of the UART driver.
 unsafe fn post_init_uart() -> Result<(), &'static str> {
-    console::register_console(PL011_UART.assume_init_ref());
+    console::register_console(
+        device_driver::PL011Uart::COMPATIBLE,
+        PL011_UART.assume_init_ref(),
+        Some(log::Level::Trace),
+    )?;
+
+    if consoles().primary == ConsoleUart::PL011 {
+        console::set_primary_console(device_driver::PL011Uart::COMPATIBLE)?;
+    }
+
+    Ok(())
+}
+
+/// This must be called only after successful init of the memory subsystem.
+unsafe fn instantiate_mini_uart() -> Result<(), &'static str> {
+    let mmio_descriptor = MMIODescriptor::new(mmio::MINI_UART_START, mmio::MINI_UART_SIZE);
+    let virt_addr =
+        memory::mmu::kernel_map_mmio(device_driver::MiniUart::COMPATIBLE, &mmio_descriptor)?;
+
+    MINI_UART.write(device_driver::MiniUart::new(virt_addr));
+
+    Ok(())
+}
+
+/// This must be called only after successful init of the mini UART driver.
+unsafe fn post_init_mini_uart() -> Result<(), &'static str> {
+    console::register_console(
+        device_driver::MiniUart::COMPATIBLE,
+        MINI_UART.assume_init_ref(),
+        Some(log::Level::Trace),
+    )?;
+
+    if consoles().primary == ConsoleUart::MiniUart {
+        console::set_primary_console(device_driver::MiniUart::COMPATIBLE)?;
+    }
 
     Ok(())
 }
@@ -51,14 +190,191 @@ unsafe fn instantiate_gpio() -> Result<(), &'static str> {
     let virt_addr =
         memory::mmu::kernel_map_mmio(device_driver::GPIO::COMPATIBLE, &mmio_descriptor)?;
 
-    GPIO.write(device_driver::GPIO::new(virt_addr));
+    GPIO.write(device_driver::GPIO::new(
+        virt_addr,
+        device_driver::PullControl::Bcm2837,
+    ));
+    GPIO_INSTANTIATED.store(true, Ordering::Relaxed);
 
     Ok(())
 }
 
 /// This must be called only after successful init of the GPIO driver.
 unsafe fn post_init_gpio() -> Result<(), &'static str> {
-    GPIO.assume_init_ref().map_pl011_uart();
+    let gpio = GPIO.assume_init_ref();
+    let consoles = consoles();
+
+    // The primary console gets pins 14 and 15, the secondary one its alternate pins.
+    match consoles.primary {
+        ConsoleUart::PL011 => {
+            gpio.map_pl011_uart();
+            if consoles.both {
+                gpio.map_mini_uart_alt();
+            }
+        }
+        ConsoleUart::MiniUart => {
+            gpio.map_mini_uart();
+            if consoles.both {
+                gpio.map_pl011_uart_alt();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// This must be called only after successful init of the memory subsystem.
+unsafe fn instantiate_rng() -> Result<(), &'static str> {
+    let mmio_descriptor = MMIODescriptor::new(mmio::RNG_START, mmio::RNG_SIZE);
+    let virt_addr = memory::mmu::kernel_map_mmio(device_driver::Rng::COMPATIBLE, &mmio_descriptor)?;
+
+    RNG.write(device_driver::Rng::new(virt_addr));
+
+    Ok(())
+}
+
+/// This must be called only after successful init of the RNG driver.
+unsafe fn post_init_rng() -> Result<(), &'static str> {
+    // The random pool keeps going on timer jitter.
+    if let Err(x) =
+        random::register_entropy_source(device_driver::Rng::COMPATIBLE, RNG.assume_init_ref())
+    {
+        warn!("No hardware entropy: {}", x);
+    }
+
+    Ok(())
+}
+
+/// This must be called only after successful init of the memory subsystem.
+unsafe fn instantiate_watchdog() -> Result<(), &'static str> {
+    let mmio_descriptor = MMIODescriptor::new(mmio::WATCHDOG_START, mmio::WATCHDOG_SIZE);
+    let virt_addr =
+        memory::mmu::kernel_map_mmio(device_driver::Watchdog::COMPATIBLE, &mmio_descriptor)?;
+
+    WATCHDOG.write(device_driver::Watchdog::new(virt_addr));
+
+    Ok(())
+}
+
+/// This must be called only after successful init of the watchdog driver.
+unsafe fn post_init_watchdog() -> Result<(), &'static str> {
+    watchdog::register_watchdog(WATCHDOG.assume_init_ref())
+}
+
+/// This must be called only after successful init of the memory subsystem.
+///
+/// Might have been called early already, see [`early_mailbox()`].
+unsafe fn instantiate_mailbox() -> Result<(), &'static str> {
+    if MAILBOX_INSTANTIATED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+
+    let mmio_descriptor = MMIODescriptor::new(mmio::MAILBOX_START, mmio::MAILBOX_SIZE);
+    let virt_addr =
+        memory::mmu::kernel_map_mmio(device_driver::Mailbox::COMPATIBLE, &mmio_descriptor)?;
+
+    MAILBOX.write(device_driver::Mailbox::new(virt_addr));
+    MAILBOX_INSTANTIATED.store(true, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// This must be called only after successful init of the mailbox and UART drivers.
+unsafe fn post_init_mailbox() -> Result<(), &'static str> {
+    let mailbox = MAILBOX.assume_init_ref();
+
+    // The firmware might run the UART clocks at other rates than the drivers' defaults. If the
+    // rate can not be queried, or the baud rate not be derived from it, the default is kept.
+    if consoles().uses(ConsoleUart::PL011) {
+        let result = mailbox
+            .clock_rate(device_driver::Clock::Uart)
+            .and_then(|clock_hz| PL011_UART.assume_init_ref().set_reference_clock(clock_hz));
+        if let Err(x) = result {
+            warn!("PL011 UART keeps its default reference clock: {}", x);
+        }
+    }
+    if consoles().uses(ConsoleUart::MiniUart) {
+        let result = mailbox
+            .clock_rate(device_driver::Clock::Core)
+            .and_then(|clock_hz| MINI_UART.assume_init_ref().set_reference_clock(clock_hz));
+        if let Err(x) = result {
+            warn!("Mini UART keeps its default reference clock: {}", x);
+        }
+    }
+
+    match mailbox.arm_memory() {
+        Ok(arm_memory) => bsp_memory::set_phys_dram_size(arm_memory.size)?,
+        Err(x) => warn!("Size of the DRAM stays unknown: {}", x),
+    }
+
+    Ok(())
+}
+
+/// This must be called only after successful init of the mailbox driver.
+unsafe fn instantiate_framebuffer() -> Result<(), &'static str> {
+    let mode = device_driver::Framebuffer::allocate(
+        MAILBOX.assume_init_ref(),
+        FRAMEBUFFER_WIDTH,
+        FRAMEBUFFER_HEIGHT,
+    )?;
+    let descriptor = MMIODescriptor::new(mode.start_addr, mode.size);
+    let virt_addr = memory::mmu::kernel_map_write_combining(
+        device_driver::Framebuffer::COMPATIBLE,
+        &descriptor,
+    )?;
+
+    FRAMEBUFFER.write(device_driver::Framebuffer::new(virt_addr, mode));
+
+    Ok(())
+}
+
+/// This must be called only after successful init of the framebuffer driver.
+unsafe fn post_init_framebuffer() -> Result<(), &'static str> {
+    console::register_console(
+        device_driver::Framebuffer::COMPATIBLE,
+        FRAMEBUFFER.assume_init_ref(),
+        Some(log::Level::Info),
+    )
+}
+
+/// This must be called only after successful init of the memory subsystem.
+unsafe fn instantiate_emmc() -> Result<(), &'static str> {
+    let mmio_descriptor = MMIODescriptor::new(mmio::EMMC_START, mmio::EMMC_SIZE);
+    let virt_addr =
+        memory::mmu::kernel_map_mmio(device_driver::Emmc::COMPATIBLE, &mmio_descriptor)?;
+
+    EMMC.write(device_driver::Emmc::new(virt_addr));
+
+    Ok(())
+}
+
+/// This must be called only after successful init of the EMMC, GPIO and mailbox drivers.
+unsafe fn post_init_emmc() -> Result<(), &'static str> {
+    let gpio = GPIO.assume_init_ref();
+    for pin in EMMC_PINS {
+        gpio.set_function(pin, device_driver::Function::Alt3)?;
+        gpio.set_pull(
+            pin,
+            if pin == EMMC_CLK_PIN {
+                device_driver::Pull::None
+            } else {
+                device_driver::Pull::Up
+            },
+        )?;
+    }
+
+    let base_clock_hz = MAILBOX
+        .assume_init_ref()
+        .clock_rate(device_driver::Clock::Emmc)
+        .ok()
+        .filter(|&hz| hz != 0)
+        .unwrap_or(EMMC_DEFAULT_BASE_CLOCK_HZ);
+
+    // The board is usable without a card.
+    match EMMC.assume_init_ref().init_card(base_clock_hz) {
+        Ok(()) => block::register_block_device(SD_CARD_NAME, EMMC.assume_init_ref())?,
+        Err(x) => warn!("No SD card: {}", x),
+    }
+
     Ok(())
 }
 
@@ -98,6 +414,20 @@ unsafe fn driver_uart() -> Result<(), &'static str> {
     Ok(())
 }
 
+/// Function needs to ensure that driver registration happens only after correct instantiation.
+unsafe fn driver_mini_uart() -> Result<(), &'static str> {
+    instantiate_mini_uart()?;
+
+    let mini_uart_descriptor = generic_driver::DeviceDriverDescriptor::new(
+        MINI_UART.assume_init_ref(),
+        Some(post_init_mini_uart),
+        Some(exception::asynchronous::irq_map::MINI_UART),
+    );
+    generic_driver::driver_manager().register_driver(mini_uart_descriptor);
+
+    Ok(())
+}
+
 /// Function needs to ensure that driver registration happens only after correct instantiation.
 unsafe fn driver_gpio() -> Result<(), &'static str> {
     instantiate_gpio()?;
@@ -105,9 +435,81 @@ unsafe fn driver_gpio() -> Result<(), &'static str> {
     let gpio_descriptor = generic_driver::DeviceDriverDescriptor::new(
         GPIO.assume_init_ref(),
         Some(post_init_gpio),
+        Some(exception::asynchronous::irq_map::GPIO_BANK0),
+    )
+    .with_irq(exception::asynchronous::irq_map::GPIO_BANK1)
+    .with_irq(exception::asynchronous::irq_map::GPIO_BANK2);
+    generic_driver::driver_manager().register_driver(gpio_descriptor);
+
+    Ok(())
+}
+
+/// Function needs to ensure that driver registration happens only after correct instantiation.
+unsafe fn driver_watchdog() -> Result<(), &'static str> {
+    instantiate_watchdog()?;
+
+    let watchdog_descriptor = generic_driver::DeviceDriverDescriptor::new(
+        WATCHDOG.assume_init_ref(),
+        Some(post_init_watchdog),
         None,
     );
-    generic_driver::driver_manager().register_driver(gpio_descriptor);
+    generic_driver::driver_manager().register_driver(watchdog_descriptor);
+
+    Ok(())
+}
+
+/// Function needs to ensure that driver registration happens only after correct instantiation.
+unsafe fn driver_rng() -> Result<(), &'static str> {
+    instantiate_rng()?;
+
+    let rng_descriptor = generic_driver::DeviceDriverDescriptor::new(
+        RNG.assume_init_ref(),
+        Some(post_init_rng),
+        None,
+    );
+    generic_driver::driver_manager().register_driver(rng_descriptor);
+
+    Ok(())
+}
+
+/// Function needs to ensure that driver registration happens only after correct instantiation.
+unsafe fn driver_mailbox() -> Result<(), &'static str> {
+    instantiate_mailbox()?;
+
+    let mailbox_descriptor = generic_driver::DeviceDriverDescriptor::new(
+        MAILBOX.assume_init_ref(),
+        Some(post_init_mailbox),
+        None,
+    );
+    generic_driver::driver_manager().register_driver(mailbox_descriptor);
+
+    Ok(())
+}
+
+/// Function needs to ensure that driver registration happens only after correct instantiation.
+unsafe fn driver_framebuffer() -> Result<(), &'static str> {
+    instantiate_framebuffer()?;
+
+    let framebuffer_descriptor = generic_driver::DeviceDriverDescriptor::new(
+        FRAMEBUFFER.assume_init_ref(),
+        Some(post_init_framebuffer),
+        None,
+    );
+    generic_driver::driver_manager().register_driver(framebuffer_descriptor);
+
+    Ok(())
+}
+
+/// Function needs to ensure that driver registration happens only after correct instantiation.
+unsafe fn driver_emmc() -> Result<(), &'static str> {
+    instantiate_emmc()?;
+
+    let emmc_descriptor = generic_driver::DeviceDriverDescriptor::new(
+        EMMC.assume_init_ref(),
+        Some(post_init_emmc),
+        Some(exception::asynchronous::irq_map::EMMC),
+    );
+    generic_driver::driver_manager().register_driver(emmc_descriptor);
 
     Ok(())
 }
@@ -133,10 +535,55 @@ pub unsafe fn init() -> Result<(), &'static str> {
         return Err("Init already done");
     }
 
-    driver_uart()?;
+    if consoles().uses(ConsoleUart::PL011) {
+        driver_uart()?;
+    }
+    if consoles().uses(ConsoleUart::MiniUart) {
+        driver_mini_uart()?;
+    }
     driver_gpio()?;
+    driver_watchdog()?;
+    driver_rng()?;
+    driver_mailbox()?;
+    // The board is usable without a display.
+    if let Err(x) = driver_framebuffer() {
+        warn!("No framebuffer console: {}", x);
+    }
+    driver_emmc()?;
     driver_interrupt_controller()?;
 
     INIT_DONE.store(true, Ordering::Relaxed);
     Ok(())
 }
+
+/// The mailbox to the firmware, instantiated ahead of the other drivers if necessary.
+///
+/// Used to ask the firmware for information that is needed before the drivers are brought up,
+/// e.g. the kernel command line. The driver is registered later, in [`init()`].
+///
+/// # Safety
+///
+/// - Only callable during kernel init, after the memory subsystem is up.
+pub unsafe fn early_mailbox() -> Result<&'static device_driver::Mailbox, &'static str> {
+    instantiate_mailbox()?;
+
+    Ok(MAILBOX.assume_init_ref())
+}
+
+/// The mailbox to the firmware, if the driver was instantiated already.
+pub fn mailbox() -> Option<&'static device_driver::Mailbox> {
+    if !MAILBOX_INSTANTIATED.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    Some(unsafe { MAILBOX.assume_init_ref() })
+}
+
+/// The GPIO controller, if the driver was instantiated already.
+pub fn gpio() -> Option<&'static device_driver::GPIO> {
+    if !GPIO_INSTANTIATED.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    Some(unsafe { GPIO.assume_init_ref() })
+}
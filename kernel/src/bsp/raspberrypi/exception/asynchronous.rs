@@ -13,5 +13,10 @@ pub use bsp::device_driver::IRQNumber;
 pub(in crate::bsp) mod irq_map {
     use super::bsp::device_driver::{IRQNumber, PeripheralIRQ};
 
+    pub const MINI_UART: IRQNumber = IRQNumber::Peripheral(PeripheralIRQ::new(29));
+    pub const GPIO_BANK0: IRQNumber = IRQNumber::Peripheral(PeripheralIRQ::new(49));
+    pub const GPIO_BANK1: IRQNumber = IRQNumber::Peripheral(PeripheralIRQ::new(50));
+    pub const GPIO_BANK2: IRQNumber = IRQNumber::Peripheral(PeripheralIRQ::new(51));
     pub const PL011_UART: IRQNumber = IRQNumber::Peripheral(PeripheralIRQ::new(57));
+    pub const EMMC: IRQNumber = IRQNumber::Peripheral(PeripheralIRQ::new(62));
 }
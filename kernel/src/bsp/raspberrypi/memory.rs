@@ -17,6 +17,7 @@
 //! | .rodata                               |
 //! | .got                                  |
 //! | .kernel_symbols                       |
+//! | .kernel_initramfs                     |
 //! |                                       |
 //! +---------------------------------------+
 //! |                                       | data_start == code_end_exclusive
@@ -29,6 +30,11 @@
 //! |                                       |
 //! +---------------------------------------+
 //! |                                       | heap_end_exclusive
+//! | Unused                                |
+//! |                                       |
+//! +---------------------------------------+
+//! |                                       | phys_dram_size, as reported by the firmware
+//! | VideoCore memory                      |
 //! |                                       |
 //!
 //!
@@ -43,6 +49,7 @@
 //! | .rodata                               |
 //! | .got                                  |
 //! | .kernel_symbols                       |
+//! | .kernel_initramfs                     |
 //! |                                       |
 //! +---------------------------------------+
 //! |                                       | data_start == code_end_exclusive
@@ -73,7 +80,10 @@
 pub mod mmu;
 
 use crate::memory::{mmu::PageAddress, Address, Physical, Virtual};
-use core::cell::UnsafeCell;
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 // Symbols from the linker script.
 extern "Rust" {
@@ -107,18 +117,36 @@ pub(super) mod map {
         pub const PERIPHERAL_IC_START: Address<Physical> = Address::new(0x3F00_B200);
         pub const PERIPHERAL_IC_SIZE:  usize             =              0x24;
 
+        pub const MAILBOX_START:       Address<Physical> = Address::new(0x3F00_B880);
+        pub const MAILBOX_SIZE:        usize             =              0x40;
+
+        pub const WATCHDOG_START:      Address<Physical> = Address::new(0x3F10_0000);
+        pub const WATCHDOG_SIZE:       usize             =              0x28;
+
+        pub const RNG_START:           Address<Physical> = Address::new(0x3F10_4000);
+        pub const RNG_SIZE:            usize             =              0x14;
+
         pub const GPIO_START:          Address<Physical> = Address::new(0x3F20_0000);
-        pub const GPIO_SIZE:           usize             =              0xA0;
+        pub const GPIO_SIZE:           usize             =              0xF4;
 
         pub const PL011_UART_START:    Address<Physical> = Address::new(0x3F20_1000);
         pub const PL011_UART_SIZE:     usize             =              0x48;
 
+        pub const MINI_UART_START:     Address<Physical> = Address::new(0x3F21_5000);
+        pub const MINI_UART_SIZE:      usize             =              0x6C;
+
+        pub const EMMC_START:          Address<Physical> = Address::new(0x3F30_0000);
+        pub const EMMC_SIZE:           usize             =              0x100;
+
         pub const END:                 Address<Physical> = Address::new(0x4001_0000);
     }
 
     pub const END: Address<Physical> = mmio::END;
 }
 
+/// Size of the DRAM that the firmware assigned to the ARM cores, zero while unknown.
+static PHYS_DRAM_SIZE: AtomicUsize = AtomicUsize::new(0);
+
 
 /// Start page address of the code segment.
 ///
@@ -207,6 +235,32 @@ fn boot_core_stack_size() -> usize {
 }
 
 
+/// Record the size of the DRAM that the firmware assigned to the ARM cores.
+///
+/// Fails if the kernel binary and its heap do not fit into it.
+pub(super) fn set_phys_dram_size(size: usize) -> Result<(), &'static str> {
+    let phys_kernel_end_exclusive_addr = mmu::phys_kernel_end_exclusive_addr().into_inner();
+    if phys_kernel_end_exclusive_addr.as_usize() > size {
+        return Err("Kernel does not fit into the DRAM of the ARM cores");
+    }
+
+    PHYS_DRAM_SIZE.store(size, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Size of the DRAM that belongs to the ARM cores, starting at physical address zero.
+///
+/// Unknown until the mailbox driver asked the firmware. For information only: The layout above is
+/// fixed by the linker script and mapped before the firmware is asked, so the size does not bound
+/// the heap or the kernel's mappings.
+pub fn phys_dram_size() -> Option<usize> {
+    match PHYS_DRAM_SIZE.load(Ordering::Relaxed) {
+        0 => None,
+        size => Some(size),
+    }
+}
+
 /// Exclusive end address of the physical address space.
 #[inline(always)]
 pub fn phys_addr_space_end_exclusive_addr() -> PageAddress<Physical> {
@@ -120,6 +120,11 @@ pub fn virt_heap_region() -> MemoryRegion<Virtual> {
     MemoryRegion::new(start_page_addr, end_exclusive_page_addr)
 }
 
+/// Exclusive end address of the kernel binary and its heap in physical memory.
+pub fn phys_kernel_end_exclusive_addr() -> PageAddress<Physical> {
+    kernel_virt_to_phys_region(virt_heap_region()).end_exclusive_page_addr()
+}
+
 /// The boot core stack pages.
 pub fn virt_boot_core_stack_region() -> MemoryRegion<Virtual> {
     let num_pages = size_to_num_pages(super::boot_core_stack_size());
@@ -0,0 +1,97 @@
+//! BSP shell commands.
+
+use super::driver;
+use crate::{
+    bsp::device_driver::{Event, Function, Pull},
+    info, println,
+    shell::{register_command, Command},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn report_edge(pin: usize) {
+    let level = driver::gpio().and_then(|gpio| gpio.read(pin).ok());
+
+    info!(
+        "GPIO {}: edge, now {}",
+        pin,
+        if level == Some(true) { "high" } else { "low" }
+    );
+}
+
+/// Level events fire until the level changes, so report them once.
+fn report_level(pin: usize) {
+    if let Some(gpio) = driver::gpio() {
+        let _ = gpio.disable_events(pin);
+    }
+
+    info!("GPIO {}: level detected, watch ended", pin);
+}
+
+fn parse_function(s: &str) -> Option<Function> {
+    let function = match s {
+        "in" => Function::Input,
+        "out" => Function::Output,
+        "alt0" => Function::Alt0,
+        "alt1" => Function::Alt1,
+        "alt2" => Function::Alt2,
+        "alt3" => Function::Alt3,
+        "alt4" => Function::Alt4,
+        "alt5" => Function::Alt5,
+        _ => return None,
+    };
+
+    Some(function)
+}
+
+fn cmd_gpio(args: &[&str]) -> Result<(), &'static str> {
+    let gpio = driver::gpio().ok_or("GPIO driver not loaded")?;
+    let Some((pin, action)) = args.split_first() else {
+        return Err("Missing pin");
+    };
+    let pin = pin.parse().map_err(|_| "Invalid pin")?;
+
+    match action {
+        [] => {
+            let level = if gpio.read(pin)? { "high" } else { "low" };
+            println!("GPIO {}: {:?}, {}", pin, gpio.function(pin)?, level);
+        }
+        ["high"] => gpio.set(pin)?,
+        ["low"] => gpio.clear(pin)?,
+        ["up"] => gpio.set_pull(pin, Pull::Up)?,
+        ["down"] => gpio.set_pull(pin, Pull::Down)?,
+        ["nopull"] => gpio.set_pull(pin, Pull::None)?,
+        ["watch", event] => {
+            let (event, handler): (Event, fn(usize)) = match *event {
+                "rising" => (Event::RisingEdge, report_edge),
+                "falling" => (Event::FallingEdge, report_edge),
+                "high" => (Event::HighLevel, report_level),
+                "low" => (Event::LowLevel, report_level),
+                _ => return Err("Unknown event"),
+            };
+            gpio.enable_event(pin, event, handler)?;
+        }
+        ["unwatch"] => gpio.disable_events(pin)?,
+        [function] => gpio.set_function(pin, parse_function(function).ok_or("Unknown action")?)?,
+        _ => return Err("Wrong number of arguments"),
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Register the commands of the BSP.
+pub fn register() -> Result<(), &'static str> {
+    register_command(Command::new(
+        "gpio",
+        "gpio <pin> [action]",
+        "Show a pin, or: in, out, alt0-alt5, high, low, up, down, nopull, \
+         watch rising|falling|high|low, unwatch",
+        cmd_gpio,
+    ))
+}
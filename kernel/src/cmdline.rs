@@ -0,0 +1,214 @@
+//! Kernel command line.
+//!
+//! The BSP provides the command line, e.g. from the device tree or the firmware. Without one, the
+//! command line that was given at build time with the `CMDLINE` option of the Makefile is used.
+//!
+//! Subsystems declare parameters with [`early_param!`](crate::early_param). The declarations are
+//! collected in the `.kernel_params` linker section, and each parameter's value is parsed into
+//! its type and applied by [`init()`], early in kernel init, before the drivers are brought up.
+//! Parameters the kernel does not know, e.g. the ones the firmware adds for Linux, are ignored.
+
+use crate::{
+    debug, info, println, shell,
+    synchronization::{interface::ReadWriteEx, InitStateLock},
+    warn,
+};
+use alloc::string::String;
+use core::{cell::UnsafeCell, slice};
+use kernel_core::cmdline;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The command line that was given at build time.
+const DEFAULT_COMMAND_LINE: &str = match option_env!("KERNEL_CMDLINE") {
+    Some(line) => line,
+    None => "",
+};
+
+// Symbols from the linker script.
+extern "Rust" {
+    static __kernel_params_start: UnsafeCell<()>;
+    static __kernel_params_end_exclusive: UnsafeCell<()>;
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Signature of the function that parses and applies the value of a parameter.
+pub type ParamSetter = fn(value: Option<&'static str>) -> Result<(), &'static str>;
+
+/// A parameter, as declared with [`early_param!`](crate::early_param).
+pub struct EarlyParam {
+    name: &'static str,
+    help: &'static str,
+    set: ParamSetter,
+}
+
+/// Types that parameter values are parsed into.
+pub trait ParamValue: Sized {
+    /// Parse the value after the `=`. `None` if the parameter was given without one.
+    fn parse(value: Option<&'static str>) -> Result<Self, &'static str>;
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static COMMAND_LINE: InitStateLock<&'static str> = InitStateLock::new("");
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// The parameters of the `.kernel_params` section.
+fn params() -> &'static [EarlyParam] {
+    unsafe {
+        let start = __kernel_params_start.get() as *const EarlyParam;
+        let end = __kernel_params_end_exclusive.get() as usize;
+        let len = (end - start as usize) / core::mem::size_of::<EarlyParam>();
+
+        slice::from_raw_parts(start, len)
+    }
+}
+
+fn required(value: Option<&'static str>) -> Result<&'static str, &'static str> {
+    value.ok_or("Missing value")
+}
+
+fn cmd_cmdline(args: &[&str]) -> Result<(), &'static str> {
+    shell::no_args(args)?;
+
+    println!("{}", command_line());
+    println!();
+    println!("Parameters:");
+    print_params();
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl EarlyParam {
+    /// Used by [`early_param!`](crate::early_param).
+    #[doc(hidden)]
+    pub const fn new(name: &'static str, help: &'static str, set: ParamSetter) -> Self {
+        Self { name, help, set }
+    }
+}
+
+impl ParamValue for &'static str {
+    fn parse(value: Option<&'static str>) -> Result<Self, &'static str> {
+        required(value)
+    }
+}
+
+/// A bare name means `true`.
+impl ParamValue for bool {
+    fn parse(value: Option<&'static str>) -> Result<Self, &'static str> {
+        match value {
+            None | Some("1" | "on" | "yes" | "true") => Ok(true),
+            Some("0" | "off" | "no" | "false") => Ok(false),
+            Some(_) => Err("Expected a boolean"),
+        }
+    }
+}
+
+macro_rules! impl_param_value_for_int {
+    ($($ty:ty),*) => {
+        $(
+            impl ParamValue for $ty {
+                fn parse(value: Option<&'static str>) -> Result<Self, &'static str> {
+                    required(value)?.parse().map_err(|_| "Expected a number")
+                }
+            }
+        )*
+    };
+}
+
+impl_param_value_for_int!(u32, u64, usize, i32, i64);
+
+/// Declare a parameter of the kernel command line.
+///
+/// The closure gets the value, parsed into its argument's type, which must implement
+/// [`ParamValue`](crate::cmdline::ParamValue).
+///
+/// # Example
+///
+/// ```ignore
+/// early_param!("loglevel", "Default log level", |level: log::Level| {
+///     log::set_default_level(Some(level));
+///     Ok(())
+/// });
+/// ```
+#[macro_export]
+macro_rules! early_param {
+    ($name:literal, $help:literal, |$value:ident: $ty:ty| $body:block) => {
+        const _: () = {
+            fn set(value: Option<&'static str>) -> Result<(), &'static str> {
+                let $value: $ty = $crate::cmdline::ParamValue::parse(value)?;
+                $body
+            }
+
+            #[used]
+            #[link_section = ".kernel_params"]
+            static PARAM: $crate::cmdline::EarlyParam =
+                $crate::cmdline::EarlyParam::new($name, $help, set);
+        };
+    };
+}
+
+/// Get the command line and apply the parameters on it.
+///
+/// # Safety
+///
+/// - Only callable during kernel init, after the memory subsystem is up. Parameters take effect
+///   immediately, e.g. the selection of the console must happen before the drivers' init.
+pub unsafe fn init() {
+    let (source, line) = match crate::bsp::command_line() {
+        Some((source, line)) => (source, line),
+        None => ("build time default", String::from(DEFAULT_COMMAND_LINE)),
+    };
+
+    // Values are handed out as `&'static str`, so the command line lives for the rest of time.
+    let line: &'static str = line.leak();
+    COMMAND_LINE.write(|command_line| *command_line = line);
+    info!("Command line ({}): {}", source, line);
+
+    let params = params();
+    for arg in cmdline::args(line) {
+        let Some(param) = params.iter().find(|param| param.name == arg.name) else {
+            debug!("Ignoring unknown parameter {}", arg.name);
+            continue;
+        };
+
+        if let Err(x) = (param.set)(arg.value) {
+            warn!("Invalid parameter {}: {}", arg.name, x);
+        }
+    }
+
+    if let Err(x) = shell::register_command(shell::Command::new(
+        "cmdline",
+        "cmdline",
+        "Print the kernel command line and the known parameters",
+        cmd_cmdline,
+    )) {
+        panic!("Error registering the cmdline shell command: {}", x);
+    }
+}
+
+/// The command line the kernel was booted with.
+pub fn command_line() -> &'static str {
+    COMMAND_LINE.read(|command_line| *command_line)
+}
+
+/// Print the known parameters.
+pub fn print_params() {
+    for param in params() {
+        println!("      {:<16} {}", param.name, param.help);
+    }
+}
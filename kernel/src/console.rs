@@ -1,13 +1,58 @@
 //! System console.
+//!
+//! Output goes to all registered consoles, input comes from the primary one.
 
-mod buffer_console;
+mod multiplexer;
+mod null_console;
 
-use crate::synchronization;
+use crate::{
+    log::{self, Level, LevelFilter},
+    println, shell,
+};
+use core::fmt;
 
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
 //--------------------------------------------------------------------------------------------------
 
+/// Parity bit setting of a serial line.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+/// Flow control setting of a serial line.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FlowControl {
+    /// No flow control.
+    None,
+
+    /// Hardware flow control with the RTS and CTS signals.
+    RtsCts,
+}
+
+/// Configuration of a serial line.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LineConfig {
+    /// Baud rate in bit/s.
+    pub baud_rate: u32,
+
+    /// Number of data bits, 5 to 8.
+    pub data_bits: u8,
+
+    /// Parity bit setting.
+    pub parity: Parity,
+
+    /// Number of stop bits, 1 or 2.
+    pub stop_bits: u8,
+
+    /// Flow control setting.
+    pub flow_control: FlowControl,
+}
+
 /// Console interfaces.
 pub mod interface {
     use core::fmt;
@@ -49,40 +94,239 @@ pub mod interface {
         fn chars_read(&self) -> usize {
             0
         }
+
+        /// Return the number of received characters that were lost because the receiver could not
+        /// keep up.
+        fn overrun_errors(&self) -> usize {
+            0
+        }
+
+        /// Return the number of received characters that were dropped because of a framing error.
+        fn framing_errors(&self) -> usize {
+            0
+        }
+
+        /// Return the number of received characters that were dropped because of a parity error.
+        fn parity_errors(&self) -> usize {
+            0
+        }
+    }
+
+    /// Serial line configuration.
+    ///
+    /// The defaults are for consoles that are not connected to a serial line.
+    pub trait LineConfiguration {
+        /// Return the current line configuration.
+        fn line_config(&self) -> Option<super::LineConfig> {
+            None
+        }
+
+        /// Change the line configuration.
+        fn set_line_config(&self, _config: &super::LineConfig) -> Result<(), &'static str> {
+            Err("Console is not connected to a serial line")
+        }
     }
 
     /// Trait alias for a full-fledged console.
-    pub trait All: Write + Read + Statistics {}
+    pub trait All: Write + Read + Statistics + LineConfiguration {}
 }
 
 //--------------------------------------------------------------------------------------------------
-// Global instances
+// Private Code
 //--------------------------------------------------------------------------------------------------
 
-static CUR_CONSOLE: InitStateLock<&'static (dyn interface::All + Sync)> = InitStateLock::new(&buffer_console::BUFFER_CONSOLE);
+fn cmd_console(args: &[&str]) -> Result<(), &'static str> {
+    shell::no_args(args)?;
+
+    let con = console();
+    println!("Characters written: {}", con.chars_written());
+    println!("Characters read:    {}", con.chars_read());
+    println!("Overrun errors:     {}", con.overrun_errors());
+    println!("Framing errors:     {}", con.framing_errors());
+    println!("Parity errors:      {}", con.parity_errors());
+
+    Ok(())
+}
+
+fn cmd_consoles(args: &[&str]) -> Result<(), &'static str> {
+    match args {
+        [] => print_consoles(),
+        [name, level] => set_console_level(name, log::parse_level_filter(level)?)?,
+        _ => return Err("Wrong number of arguments"),
+    }
+
+    Ok(())
+}
+
+fn cmd_uart(args: &[&str]) -> Result<(), &'static str> {
+    let con = console();
+    let mut config = con
+        .line_config()
+        .ok_or("Console is not connected to a serial line")?;
+
+    let Some((baud_rate, settings)) = args.split_first() else {
+        println!("{}", config);
+        return Ok(());
+    };
+
+    config.baud_rate =
+        u32::try_from(shell::parse_number(baud_rate)?).map_err(|_| "Invalid baud rate")?;
+    for setting in settings {
+        config.flow_control = match *setting {
+            "rtscts" => FlowControl::RtsCts,
+            "noflow" => FlowControl::None,
+            format => {
+                config = config.with_frame_format(format)?;
+                continue;
+            }
+        };
+    }
+
+    // Announce at the old settings, so that the user knows what to switch the terminal to.
+    println!("Switching to {}", config);
+    con.set_line_config(&config)
+}
 
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
-use synchronization::{interface::ReadWriteEx, InitStateLock};
 
-/// Register a new console.
-pub fn register_console(new_console: &'static (dyn interface::All + Sync)) {
-    CUR_CONSOLE.write(|con| *con = new_console);
+impl LineConfig {
+    /// 921_600 baud, 8N1 and no flow control.
+    pub const DEFAULT: Self = Self {
+        baud_rate: 921_600,
+        data_bits: 8,
+        parity: Parity::None,
+        stop_bits: 1,
+        flow_control: FlowControl::None,
+    };
+
+    /// Apply a frame format in the usual short notation, e.g. `8N1` or `7E2`.
+    pub fn with_frame_format(mut self, format: &str) -> Result<Self, &'static str> {
+        let &[data_bits, parity, stop_bits] = format.as_bytes() else {
+            return Err("Frame format must look like 8N1");
+        };
+
+        self.data_bits = match data_bits {
+            b'5'..=b'8' => data_bits - b'0',
+            _ => return Err("Data bits must be 5 to 8"),
+        };
+        self.parity = match parity.to_ascii_uppercase() {
+            b'N' => Parity::None,
+            b'E' => Parity::Even,
+            b'O' => Parity::Odd,
+            _ => return Err("Parity must be N, E or O"),
+        };
+        self.stop_bits = match stop_bits {
+            b'1' | b'2' => stop_bits - b'0',
+            _ => return Err("Stop bits must be 1 or 2"),
+        };
+
+        Ok(self)
+    }
+}
 
-    static FIRST_SWITCH: InitStateLock<bool> = InitStateLock::new(true);
-    FIRST_SWITCH.write(|first| {
-        if *first {
-            *first = false;
+impl fmt::Display for LineConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Even => 'E',
+            Parity::Odd => 'O',
+        };
 
-            buffer_console::BUFFER_CONSOLE.dump();
+        write!(
+            f,
+            "{} baud, {}{}{}",
+            self.baud_rate, self.data_bits, parity, self.stop_bits
+        )?;
+
+        match self.flow_control {
+            FlowControl::None => Ok(()),
+            FlowControl::RtsCts => write!(f, ", RTS/CTS"),
         }
-    });
+    }
+}
+
+/// Register an additional console.
+///
+/// It receives all printed output, and log records down to `min_level`. The log records that were
+/// buffered before are replayed to it. The first registered console becomes the primary one.
+///
+/// Only possible during kernel init.
+pub fn register_console(
+    name: &'static str,
+    new_console: &'static (dyn interface::All + Sync),
+    min_level: LevelFilter,
+) -> Result<(), &'static str> {
+    multiplexer::CONSOLE_MULTIPLEXER.add_sink(name, new_console, min_level)?;
+    log::replay(new_console, min_level);
+
+    Ok(())
+}
+
+/// Select the console that input is read from.
+///
+/// Only possible during kernel init.
+pub fn set_primary_console(name: &str) -> Result<(), &'static str> {
+    multiplexer::CONSOLE_MULTIPLEXER.set_primary(name)
 }
 
-/// Return a reference to the currently registered console.
+/// Change the least severe level of log records that a console receives.
+pub fn set_console_level(name: &str, min_level: LevelFilter) -> Result<(), &'static str> {
+    multiplexer::CONSOLE_MULTIPLEXER.set_min_level(name, min_level)
+}
+
+/// Print the registered consoles.
+pub fn print_consoles() {
+    multiplexer::CONSOLE_MULTIPLEXER.print_sinks();
+}
+
+/// Register the shell commands of the console.
+pub fn register_shell_commands() -> Result<(), &'static str> {
+    let commands = [
+        shell::Command::new(
+            "console",
+            "console",
+            "Print the console statistics",
+            cmd_console,
+        ),
+        shell::Command::new(
+            "consoles",
+            "consoles [name level]",
+            "List the consoles, or set the least severe log level a console prints",
+            cmd_consoles,
+        ),
+        shell::Command::new(
+            "uart",
+            "uart [baud [8N1] [rtscts|noflow]]",
+            "Print or change the console line configuration",
+            cmd_uart,
+        ),
+    ];
+
+    for command in commands {
+        shell::register_command(command)?;
+    }
+
+    Ok(())
+}
+
+/// Return a reference to the system console.
 ///
-/// This is the global console used by all printing macros.
+/// This is the global console used by all printing macros. Output goes to all registered consoles,
+/// input and statistics come from the primary one.
 pub fn console() -> &'static dyn interface::All {
-    CUR_CONSOLE.read(|con| *con)
-}
\ No newline at end of file
+    &multiplexer::CONSOLE_MULTIPLEXER
+}
+
+/// Return a reference to the primary console only.
+///
+/// For protocols that must not be mirrored to the other consoles.
+pub fn primary_console() -> &'static dyn interface::All {
+    multiplexer::CONSOLE_MULTIPLEXER.primary()
+}
+
+/// Write a formatted log record to the consoles that want records of this level.
+pub fn write_log_record(level: Level, args: fmt::Arguments) {
+    multiplexer::CONSOLE_MULTIPLEXER.write_log_record(level, args);
+}
@@ -0,0 +1,229 @@
+//! Console multiplexer.
+//!
+//! Fans output out to all registered consoles, called sinks. Input is taken from the primary sink
+//! only.
+
+use super::{interface, null_console};
+use crate::{
+    log::{Level, LevelFilter},
+    println, synchronization,
+    synchronization::{IRQSafeNullLock, InitStateLock},
+};
+use alloc::vec::Vec;
+use core::fmt;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+struct Sink {
+    name: &'static str,
+    console: &'static (dyn interface::All + Sync),
+    min_level: IRQSafeNullLock<LevelFilter>,
+}
+
+struct Sinks {
+    sinks: Vec<Sink>,
+
+    /// Index of the sink that input is read from.
+    primary: Option<usize>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The multiplexer.
+pub struct ConsoleMultiplexer {
+    inner: InitStateLock<Sinks>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+pub static CONSOLE_MULTIPLEXER: ConsoleMultiplexer = ConsoleMultiplexer {
+    inner: InitStateLock::new(Sinks {
+        sinks: Vec::new(),
+        primary: None,
+    }),
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+use synchronization::interface::{Mutex, ReadWriteEx};
+
+impl Sinks {
+    fn position(&self, name: &str) -> Result<usize, &'static str> {
+        self.sinks
+            .iter()
+            .position(|sink| sink.name == name)
+            .ok_or("No console with this name")
+    }
+}
+
+impl ConsoleMultiplexer {
+    fn for_each_sink(&self, mut f: impl FnMut(&'static (dyn interface::All + Sync))) {
+        self.inner
+            .read(|inner| inner.sinks.iter().for_each(|sink| f(sink.console)))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl ConsoleMultiplexer {
+    /// Add a sink that receives all output, and log records down to `min_level`.
+    ///
+    /// The first sink becomes the primary one.
+    pub fn add_sink(
+        &self,
+        name: &'static str,
+        console: &'static (dyn interface::All + Sync),
+        min_level: LevelFilter,
+    ) -> Result<(), &'static str> {
+        self.inner.write(|inner| {
+            if inner.position(name).is_ok() {
+                return Err("Console already registered");
+            }
+
+            inner.sinks.push(Sink {
+                name,
+                console,
+                min_level: IRQSafeNullLock::new(min_level),
+            });
+            inner.primary.get_or_insert(inner.sinks.len() - 1);
+
+            Ok(())
+        })
+    }
+
+    /// Select the sink that input is read from.
+    pub fn set_primary(&self, name: &str) -> Result<(), &'static str> {
+        self.inner.write(|inner| {
+            inner.primary = Some(inner.position(name)?);
+
+            Ok(())
+        })
+    }
+
+    /// The sink that input is read from.
+    pub fn primary(&self) -> &'static (dyn interface::All + Sync) {
+        self.inner.read(|inner| match inner.primary {
+            None => &null_console::NULL_CONSOLE,
+            Some(i) => inner.sinks[i].console,
+        })
+    }
+
+    /// Change the least severe level of log records that a sink receives.
+    pub fn set_min_level(&self, name: &str, min_level: LevelFilter) -> Result<(), &'static str> {
+        self.inner.read(|inner| {
+            let i = inner.position(name)?;
+            inner.sinks[i].min_level.lock(|level| *level = min_level);
+
+            Ok(())
+        })
+    }
+
+    /// Write a formatted log record to all sinks that want records of this level.
+    pub fn write_log_record(&self, level: Level, args: fmt::Arguments) {
+        self.inner.read(|inner| {
+            for sink in &inner.sinks {
+                if sink.min_level.lock(|min_level| *min_level) >= Some(level) {
+                    let _ = sink.console.write_fmt(args);
+                }
+            }
+        })
+    }
+
+    /// Print the registered sinks.
+    pub fn print_sinks(&self) {
+        self.inner.read(|inner| {
+            for (i, sink) in inner.sinks.iter().enumerate() {
+                let min_level = sink.min_level.lock(|min_level| *min_level);
+
+                println!(
+                    "      {:<20} {:<6} {}",
+                    sink.name,
+                    min_level.map_or("off", Level::name),
+                    if inner.primary == Some(i) {
+                        "(primary)"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        })
+    }
+}
+
+impl interface::Write for ConsoleMultiplexer {
+    fn write_char(&self, c: char) {
+        self.for_each_sink(|con| con.write_char(c));
+    }
+
+    fn write_array(&self, a: &[char]) {
+        self.for_each_sink(|con| con.write_array(a));
+    }
+
+    fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result {
+        let mut result = Ok(());
+        self.for_each_sink(|con| {
+            if con.write_fmt(args).is_err() {
+                result = Err(fmt::Error);
+            }
+        });
+
+        result
+    }
+
+    fn flush(&self) {
+        self.for_each_sink(|con| con.flush());
+    }
+}
+
+impl interface::Read for ConsoleMultiplexer {
+    fn read_char(&self) -> char {
+        self.primary().read_char()
+    }
+
+    fn clear_rx(&self) {
+        self.primary().clear_rx()
+    }
+}
+
+impl interface::Statistics for ConsoleMultiplexer {
+    fn chars_written(&self) -> usize {
+        self.primary().chars_written()
+    }
+
+    fn chars_read(&self) -> usize {
+        self.primary().chars_read()
+    }
+
+    fn overrun_errors(&self) -> usize {
+        self.primary().overrun_errors()
+    }
+
+    fn framing_errors(&self) -> usize {
+        self.primary().framing_errors()
+    }
+
+    fn parity_errors(&self) -> usize {
+        self.primary().parity_errors()
+    }
+}
+
+impl interface::LineConfiguration for ConsoleMultiplexer {
+    fn line_config(&self) -> Option<super::LineConfig> {
+        self.primary().line_config()
+    }
+
+    fn set_line_config(&self, config: &super::LineConfig) -> Result<(), &'static str> {
+        self.primary().set_line_config(config)
+    }
+}
+
+impl interface::All for ConsoleMultiplexer {}
@@ -6,7 +6,8 @@ mod boot;
 //--------------------------------------------------------------------------------------------------
 // Architectural Public Reexports
 //--------------------------------------------------------------------------------------------------
-pub use arch_cpu::{nop, wait_forever};
+pub use arch_cpu::{clean_invalidate_dcache_range, nop, wait_forever};
+pub use boot::boot_dtb_phys_addr;
 
 #[cfg(feature = "bsp_rpi3")]
 pub use arch_cpu::spin_for_cycles;
\ No newline at end of file
@@ -1,4 +1,9 @@
 //! Boot code.
 
 #[path = "../aarch64/cpu/boot.rs"]
-mod arch_boot;
\ No newline at end of file
+mod arch_boot;
+
+//--------------------------------------------------------------------------------------------------
+// Architectural Public Reexports
+//--------------------------------------------------------------------------------------------------
+pub use arch_boot::boot_dtb_phys_addr;
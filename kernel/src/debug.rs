@@ -0,0 +1,149 @@
+//! Self-hosted debug support.
+//!
+//! HW breakpoints, HW watchpoints and single-stepping, handled by the kernel itself.
+
+#[path = "aarch64/debug.rs"]
+mod arch_debug;
+
+#[cfg(feature = "gdb_stub")]
+pub mod gdb;
+
+use crate::{
+    exception::ExceptionContext,
+    memory::{Address, Virtual},
+    symbols,
+    synchronization::{interface::Mutex, IRQSafeNullLock},
+    warn,
+};
+
+pub use arch_debug::{
+    clear_breakpoint, clear_watchpoint, init, num_breakpoints, num_watchpoints, print_state,
+    set_breakpoint, set_watchpoint,
+};
+
+/// Memory access types that can trigger a watchpoint.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WatchpointAccess {
+    Load,
+    Store,
+    LoadStore,
+}
+
+/// Bookkeeping for software stepping.
+struct StepState {
+    /// A breakpoint or watchpoint is being stepped over. They must be re-enabled after the step.
+    step_over: bool,
+
+    /// Number of instructions that are still to be traced.
+    trace_remaining: usize,
+}
+
+static STEP_STATE: IRQSafeNullLock<StepState> = IRQSafeNullLock::new(StepState {
+    step_over: false,
+    trace_remaining: 0,
+});
+
+/// Arm a single step for the exception return that leaves `e`.
+fn arm_software_step(e: &mut ExceptionContext) {
+    arch_debug::set_software_step(true);
+    e.set_software_step(true);
+}
+
+/// Execute the instruction that caused a breakpoint or watchpoint hit without it triggering again.
+///
+/// All breakpoints and watchpoints are disabled for the duration of a single step. They are
+/// re-enabled in the subsequent software step exception.
+fn step_over(e: &mut ExceptionContext) {
+    arch_debug::suspend_breakpoints_and_watchpoints();
+    STEP_STATE.lock(|state| state.step_over = true);
+
+    arm_software_step(e);
+}
+
+/// Trace the next `num_instructions` instructions after the next breakpoint or watchpoint hit.
+///
+/// The address and symbol of every traced instruction are printed.
+pub fn trace_instructions(num_instructions: usize) {
+    STEP_STATE.lock(|state| state.trace_remaining = num_instructions);
+}
+
+/// Handle a HW breakpoint exception.
+pub fn handle_breakpoint(e: &mut ExceptionContext) {
+    #[cfg(feature = "gdb_stub")]
+    if gdb::is_attached() {
+        gdb::handle_exception(e, gdb::Signal::Trap);
+        return;
+    }
+
+    let pc = e.pc();
+
+    warn!("[!] EXCEPTION: HW BREAKPOINT");
+    warn!("\tInstruction: {} | {}", pc, symbols::SymbolOffset::new(pc));
+
+    step_over(e);
+}
+
+/// Handle a HW watchpoint exception.
+///
+/// `data_addr` is the accessed address that matched the watchpoint.
+pub fn handle_watchpoint(e: &mut ExceptionContext, data_addr: Address<Virtual>, is_write: bool) {
+    #[cfg(feature = "gdb_stub")]
+    if gdb::is_attached() {
+        gdb::handle_watchpoint(e, data_addr, is_write);
+        return;
+    }
+
+    let pc = e.pc();
+
+    warn!("[!] EXCEPTION: WATCHPOINT");
+    warn!(
+        "\t{} of address {}",
+        if is_write { "Write" } else { "Read" },
+        data_addr
+    );
+    warn!("\tInstruction: {} | {}", pc, symbols::SymbolOffset::new(pc));
+
+    step_over(e);
+}
+
+/// Handle a software step exception.
+pub fn handle_software_step(e: &mut ExceptionContext) {
+    let (step_over, trace, keep_stepping) = STEP_STATE.lock(|state| {
+        let step_over = state.step_over;
+        state.step_over = false;
+
+        let trace = state.trace_remaining > 0;
+        if trace {
+            state.trace_remaining -= 1;
+        }
+
+        (step_over, trace, state.trace_remaining > 0)
+    });
+
+    if step_over {
+        arch_debug::resume_breakpoints_and_watchpoints();
+    }
+
+    // A step that was requested by the debugger.
+    #[cfg(feature = "gdb_stub")]
+    if gdb::is_attached() {
+        arch_debug::set_software_step(false);
+        e.set_software_step(false);
+
+        gdb::handle_exception(e, gdb::Signal::Trap);
+        return;
+    }
+
+    if trace {
+        let pc = e.pc();
+        warn!("\tStep: {} | {}", pc, symbols::SymbolOffset::new(pc));
+    }
+
+    if keep_stepping {
+        arm_software_step(e);
+    } else {
+        arch_debug::set_software_step(false);
+        e.set_software_step(false);
+    }
+}
@@ -0,0 +1,665 @@
+//! GDB remote serial protocol stub.
+//!
+//! When a `brk` instruction or a fatal exception hits, the stub takes over the console UART and
+//! serves the GDB remote serial protocol until the debugger resumes execution or detaches. While a
+//! debugger is attached, HW breakpoint, watchpoint and single-step exceptions are reported to it as
+//! well.
+//!
+//! Attach with `gdb-multiarch -ex "target remote /dev/ttyUSB0"` on real HW, or with
+//! `target remote localhost:1234` when QEMU was started with `-serial tcp::1234,server`.
+//!
+//! The kernel maps its code read-only. Software breakpoints in read-only pages are therefore
+//! transparently installed as HW breakpoints.
+//!
+//! # Resources
+//!
+//! - <https://sourceware.org/gdb/onlinedocs/gdb/Remote-Protocol.html>
+
+#[path = "../aarch64/debug/gdb.rs"]
+mod arch_gdb;
+
+use super::WatchpointAccess;
+use crate::{
+    bsp, console,
+    exception::ExceptionContext,
+    memory::{
+        self,
+        mmu::{AccessPermissions, PageAddress},
+        Address, Virtual,
+    },
+    synchronization::{interface::Mutex, IRQSafeNullLock},
+    warn,
+};
+use core::fmt::{self, Write};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Signals that are reported to GDB. The values are the ones used on the wire.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Signal {
+    Ill = 4,
+    Trap = 5,
+    Bus = 7,
+    Segv = 11,
+}
+
+/// How the debugger let go of the interrupted context.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Exit {
+    /// Execution was continued or single-stepped. The debugger stays attached.
+    Resume,
+
+    /// The debugger detached or killed the session.
+    Detach,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Maximum packet size, announced to GDB in the reply to `qSupported`.
+const PACKET_SIZE: usize = 1024;
+
+const MAX_SW_BREAKPOINTS: usize = 32;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// A software breakpoint, with the instruction that it replaced.
+#[derive(Copy, Clone)]
+struct SwBreakpoint {
+    addr: Address<Virtual>,
+    orig_insn: u32,
+}
+
+/// Why the interrupted context was stopped.
+#[derive(Copy, Clone)]
+enum StopReason {
+    Signal(Signal),
+    Watchpoint {
+        data_addr: Address<Virtual>,
+        is_write: bool,
+    },
+}
+
+/// Payload of a packet, without framing and checksum.
+struct PacketBuffer {
+    data: [u8; PACKET_SIZE],
+    len: usize,
+}
+
+struct GdbStubInner {
+    attached: bool,
+    error_messages: bool,
+    sw_breakpoints: [Option<SwBreakpoint>; MAX_SW_BREAKPOINTS],
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static GDB_STUB: IRQSafeNullLock<GdbStubInner> = IRQSafeNullLock::new(GdbStubInner::new());
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn getc() -> u8 {
+    console::primary_console().read_char() as u8
+}
+
+fn putc(c: u8) {
+    console::primary_console().write_char(c as char)
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Parse a big-endian hex number, as used for addresses and lengths.
+fn parse_hex(s: &[u8]) -> Result<usize, &'static str> {
+    if s.is_empty() {
+        return Err("Missing number");
+    }
+
+    s.iter().try_fold(0usize, |acc, c| {
+        let digit = hex_value(*c).ok_or("Invalid hex digit")?;
+
+        acc.checked_mul(16)
+            .map(|x| x + digit as usize)
+            .ok_or("Number too big")
+    })
+}
+
+/// Parse a hex encoded byte sequence.
+fn parse_hex_bytes(s: &[u8]) -> impl Iterator<Item = Result<u8, &'static str>> + '_ {
+    s.chunks(2).map(|pair| match pair {
+        [hi, lo] => match (hex_value(*hi), hex_value(*lo)) {
+            (Some(hi), Some(lo)) => Ok((hi << 4) | lo),
+            _ => Err("Invalid hex digit"),
+        },
+        _ => Err("Odd number of hex digits"),
+    })
+}
+
+/// Parse a register value, which is transferred in target byte order.
+fn parse_register(s: &[u8]) -> Result<u64, &'static str> {
+    parse_hex_bytes(s)
+        .enumerate()
+        .try_fold(0u64, |acc, (i, byte)| Ok(acc | ((byte? as u64) << (8 * i))))
+}
+
+fn split_once(s: &[u8], separator: u8) -> Result<(&[u8], &[u8]), &'static str> {
+    let pos = s
+        .iter()
+        .position(|c| *c == separator)
+        .ok_or("Missing separator")?;
+
+    Ok((&s[..pos], &s[pos + 1..]))
+}
+
+/// Parse the `addr,length` arguments of memory and breakpoint packets.
+fn parse_addr_len(s: &[u8]) -> Result<(usize, usize), &'static str> {
+    let (addr, len) = split_once(s, b',')?;
+
+    Ok((parse_hex(addr)?, parse_hex(len)?))
+}
+
+/// Check in the kernel translation tables that `len` bytes starting at `addr` are mapped, and
+/// writable if `write` is set.
+fn check_memory_access(addr: usize, len: usize, write: bool) -> Result<(), &'static str> {
+    if len == 0 {
+        return Ok(());
+    }
+
+    let page_size = bsp::memory::mmu::KernelGranule::SIZE;
+    let last = addr.checked_add(len - 1).ok_or("Address range overflows")?;
+    let last_page = PageAddress::from(Address::<Virtual>::new(last).align_down(page_size));
+    let mut page = PageAddress::from(Address::<Virtual>::new(addr).align_down(page_size));
+
+    loop {
+        let attributes = memory::mmu::try_kernel_page_attributes(page)?;
+
+        if write && attributes.acc_perms != AccessPermissions::ReadWrite {
+            return Err("Memory is read-only");
+        }
+
+        if page == last_page {
+            return Ok(());
+        }
+
+        // Can not overflow, because page < last_page.
+        page = page.checked_offset(1).unwrap();
+    }
+}
+
+fn read_instruction(addr: Address<Virtual>) -> Result<u32, &'static str> {
+    if addr.as_usize() % arch_gdb::INSTRUCTION_SIZE != 0 {
+        return Err("Instruction address not aligned");
+    }
+    check_memory_access(addr.as_usize(), arch_gdb::INSTRUCTION_SIZE, false)?;
+
+    Ok(unsafe { core::ptr::read_volatile(addr.as_usize() as *const u32) })
+}
+
+/// Send a packet and wait until GDB acknowledged it.
+fn send_packet(payload: &[u8]) {
+    let checksum = payload.iter().fold(0u8, |acc, c| acc.wrapping_add(*c));
+
+    loop {
+        putc(b'$');
+        payload.iter().for_each(|c| putc(*c));
+        putc(b'#');
+
+        putc(HEX_DIGITS[(checksum >> 4) as usize]);
+        putc(HEX_DIGITS[(checksum & 0xf) as usize]);
+
+        // Retransmit if GDB asks for it, otherwise assume the packet was received.
+        if getc() != b'-' {
+            return;
+        }
+    }
+}
+
+/// Receive a packet with a valid checksum into `packet` and acknowledge it.
+fn receive_packet(packet: &mut PacketBuffer) {
+    loop {
+        // Skip anything outside of a packet, like acknowledgements and interrupt requests.
+        while getc() != b'$' {}
+
+        packet.clear();
+        let mut checksum = 0u8;
+        let mut overflow = false;
+
+        loop {
+            let c = getc();
+            if c == b'#' {
+                break;
+            }
+
+            checksum = checksum.wrapping_add(c);
+            overflow |= packet.push(c).is_err();
+        }
+
+        let expected = match (hex_value(getc()), hex_value(getc())) {
+            (Some(hi), Some(lo)) => Some((hi << 4) | lo),
+            _ => None,
+        };
+
+        if !overflow && expected == Some(checksum) {
+            putc(b'+');
+            return;
+        }
+
+        putc(b'-');
+    }
+}
+
+impl PacketBuffer {
+    const fn new() -> Self {
+        Self {
+            data: [0; PACKET_SIZE],
+            len: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn push(&mut self, c: u8) -> Result<(), &'static str> {
+        if self.len == PACKET_SIZE {
+            return Err("Packet too long");
+        }
+
+        self.data[self.len] = c;
+        self.len += 1;
+
+        Ok(())
+    }
+
+    fn push_str(&mut self, s: &str) -> Result<(), &'static str> {
+        s.bytes().try_for_each(|c| self.push(c))
+    }
+
+    /// Append `value` as a register value, which is transferred in target byte order.
+    fn push_register(&mut self, value: u64, size: usize) -> Result<(), &'static str> {
+        (0..size).try_for_each(|i| self.push_hex_byte((value >> (8 * i)) as u8))
+    }
+
+    fn push_hex_byte(&mut self, byte: u8) -> Result<(), &'static str> {
+        self.push(HEX_DIGITS[(byte >> 4) as usize])?;
+        self.push(HEX_DIGITS[(byte & 0xf) as usize])
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+impl fmt::Write for PacketBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s).map_err(|_| fmt::Error)
+    }
+}
+
+impl GdbStubInner {
+    const fn new() -> Self {
+        Self {
+            attached: false,
+            error_messages: false,
+            sw_breakpoints: [None; MAX_SW_BREAKPOINTS],
+        }
+    }
+
+    /// Serve GDB until it resumes execution or detaches.
+    fn serve(&mut self, e: &mut ExceptionContext, stop: StopReason) -> Exit {
+        let mut packet = PacketBuffer::new();
+        let mut reply = PacketBuffer::new();
+
+        // An attached debugger is waiting for the stop reply to its last continue or step.
+        if self.attached {
+            write_stop_reply(&mut reply, stop).unwrap();
+            send_packet(reply.as_bytes());
+        } else {
+            warn!("[!] GDB stub active. Waiting for the debugger on the console UART...");
+        }
+        self.attached = true;
+
+        loop {
+            receive_packet(&mut packet);
+            reply.clear();
+
+            match self.handle_packet(e, stop, packet.as_bytes(), &mut reply) {
+                Ok(Some(exit)) => return exit,
+                Ok(None) => (),
+                Err(x) => {
+                    reply.clear();
+                    if self.error_messages {
+                        write!(reply, "E.{}", x).ok();
+                    } else {
+                        reply.push_str("E01").unwrap();
+                    }
+                }
+            }
+
+            send_packet(reply.as_bytes());
+        }
+    }
+
+    /// Handle a single packet. Returns `Some` if execution shall be resumed.
+    fn handle_packet(
+        &mut self,
+        e: &mut ExceptionContext,
+        stop: StopReason,
+        packet: &[u8],
+        reply: &mut PacketBuffer,
+    ) -> Result<Option<Exit>, &'static str> {
+        let (command, args) = match packet.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return Ok(None),
+        };
+
+        match command {
+            b'?' => write_stop_reply(reply, stop)?,
+            b'g' => read_registers(e, reply)?,
+            b'G' => {
+                write_registers(e, args)?;
+                reply.push_str("OK")?;
+            }
+            b'p' => {
+                let n = parse_hex(args)?;
+                let value = arch_gdb::read_register(e, n).ok_or("Invalid register number")?;
+
+                reply.push_register(value, arch_gdb::register_size(n))?;
+            }
+            b'P' => {
+                let (n, value) = split_once(args, b'=')?;
+
+                arch_gdb::write_register(e, parse_hex(n)?, parse_register(value)?)?;
+                reply.push_str("OK")?;
+            }
+            b'm' => read_memory(args, reply)?,
+            b'M' => {
+                write_memory(args)?;
+                reply.push_str("OK")?;
+            }
+            b'c' | b's' => {
+                if !args.is_empty() {
+                    e.set_pc(Address::new(parse_hex(args)?));
+                }
+
+                // Stepping over a `brk` that is compiled into the kernel is completed by skipping
+                // it.
+                if self.skip_compiled_breakpoint(e) && command == b's' {
+                    write_stop_reply(reply, StopReason::Signal(Signal::Trap))?;
+                    return Ok(None);
+                }
+
+                if command == b's' {
+                    super::arm_software_step(e);
+                }
+
+                return Ok(Some(Exit::Resume));
+            }
+            b'D' | b'k' => {
+                self.skip_compiled_breakpoint(e);
+                self.attached = false;
+
+                // A kill request has no reply.
+                if command == b'D' {
+                    send_packet(b"OK");
+                }
+
+                return Ok(Some(Exit::Detach));
+            }
+            b'Z' => self
+                .insert_breakpoint(args)
+                .and_then(|_| reply.push_str("OK"))?,
+            b'z' => self
+                .remove_breakpoint(args)
+                .and_then(|_| reply.push_str("OK"))?,
+            b'H' => reply.push_str("OK")?,
+            b'q' => self.query(args, reply)?,
+            // An empty reply tells GDB that the packet is not supported.
+            _ => (),
+        }
+
+        Ok(None)
+    }
+
+    fn query(&mut self, args: &[u8], reply: &mut PacketBuffer) -> Result<(), &'static str> {
+        const XFER_TARGET_XML: &[u8] = b"Xfer:features:read:target.xml:";
+
+        if args.starts_with(b"Supported") {
+            const ERROR_MESSAGE: &[u8] = b"error-message+";
+
+            self.error_messages = args
+                .windows(ERROR_MESSAGE.len())
+                .any(|x| x == ERROR_MESSAGE);
+
+            write!(reply, "PacketSize={:x};qXfer:features:read+", PACKET_SIZE)
+                .map_err(|_| "Reply too long")?;
+            if self.error_messages {
+                reply.push_str(";error-message+")?;
+            }
+        } else if let Some(range) = args.strip_prefix(XFER_TARGET_XML) {
+            let (offset, len) = parse_addr_len(range)?;
+            let xml = arch_gdb::TARGET_XML.as_bytes();
+
+            let start = offset.min(xml.len());
+            let end = start + len.min(PACKET_SIZE - 1).min(xml.len() - start);
+
+            // 'l' marks the last chunk.
+            reply.push(if end == xml.len() { b'l' } else { b'm' })?;
+            xml[start..end].iter().try_for_each(|c| reply.push(*c))?;
+        } else if args == b"Attached" {
+            // The kernel was not started by the debugger.
+            reply.push_str("1")?;
+        }
+
+        Ok(())
+    }
+
+    /// Handle `Ztype,addr,kind`.
+    fn insert_breakpoint(&mut self, args: &[u8]) -> Result<(), &'static str> {
+        let (kind, args) = split_once(args, b',')?;
+        let (addr, len) = parse_addr_len(args)?;
+        let addr = Address::new(addr);
+
+        // Code in read-only pages can not be patched. Fall back to a HW breakpoint there.
+        let writable =
+            check_memory_access(addr.as_usize(), arch_gdb::INSTRUCTION_SIZE, true).is_ok();
+
+        match kind {
+            b"0" if writable => self.insert_sw_breakpoint(addr),
+            b"0" | b"1" => super::set_breakpoint(addr).map(|_| ()),
+            b"2" => super::set_watchpoint(addr, len, WatchpointAccess::Store).map(|_| ()),
+            b"3" => super::set_watchpoint(addr, len, WatchpointAccess::Load).map(|_| ()),
+            b"4" => super::set_watchpoint(addr, len, WatchpointAccess::LoadStore).map(|_| ()),
+            _ => Err("Unsupported breakpoint type"),
+        }
+    }
+
+    /// Handle `ztype,addr,kind`.
+    fn remove_breakpoint(&mut self, args: &[u8]) -> Result<(), &'static str> {
+        let (kind, args) = split_once(args, b',')?;
+        let (addr, _) = parse_addr_len(args)?;
+        let addr = Address::new(addr);
+
+        match kind {
+            b"0" if self.find_sw_breakpoint(addr).is_some() => self.remove_sw_breakpoint(addr),
+            b"0" | b"1" => super::clear_breakpoint(addr),
+            b"2" | b"3" | b"4" => super::clear_watchpoint(addr),
+            _ => Err("Unsupported breakpoint type"),
+        }
+    }
+
+    fn find_sw_breakpoint(&self, addr: Address<Virtual>) -> Option<usize> {
+        self.sw_breakpoints
+            .iter()
+            .position(|x| matches!(x, Some(bp) if bp.addr == addr))
+    }
+
+    fn insert_sw_breakpoint(&mut self, addr: Address<Virtual>) -> Result<(), &'static str> {
+        if self.find_sw_breakpoint(addr).is_some() {
+            return Ok(());
+        }
+
+        let slot = self
+            .sw_breakpoints
+            .iter_mut()
+            .find(|x| x.is_none())
+            .ok_or("No free software breakpoint slot")?;
+        let orig_insn = read_instruction(addr)?;
+
+        unsafe { arch_gdb::write_instruction(addr, arch_gdb::BREAKPOINT_INSTRUCTION) };
+        *slot = Some(SwBreakpoint { addr, orig_insn });
+
+        Ok(())
+    }
+
+    fn remove_sw_breakpoint(&mut self, addr: Address<Virtual>) -> Result<(), &'static str> {
+        let i = self
+            .find_sw_breakpoint(addr)
+            .ok_or("No software breakpoint at this address")?;
+
+        if let Some(bp) = self.sw_breakpoints[i].take() {
+            unsafe { arch_gdb::write_instruction(bp.addr, bp.orig_insn) };
+        }
+
+        Ok(())
+    }
+
+    /// If the interrupted context is about to execute a `brk` that was not inserted by the
+    /// debugger, skip it. Otherwise, it would trap again immediately.
+    ///
+    /// Returns true if an instruction was skipped.
+    fn skip_compiled_breakpoint(&self, e: &mut ExceptionContext) -> bool {
+        let pc = e.pc();
+
+        // At a breakpoint of the debugger, the original instruction is executed on resume. GDB
+        // removes the breakpoint to step over it.
+        if self.find_sw_breakpoint(pc).is_some() {
+            return false;
+        }
+
+        match read_instruction(pc) {
+            Ok(insn) if arch_gdb::is_breakpoint_instruction(insn) => {
+                e.set_pc(pc + arch_gdb::INSTRUCTION_SIZE);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn write_stop_reply(reply: &mut PacketBuffer, stop: StopReason) -> Result<(), &'static str> {
+    match stop {
+        StopReason::Signal(signal) => write!(reply, "S{:02x}", signal as u8),
+        StopReason::Watchpoint {
+            data_addr,
+            is_write,
+        } => write!(
+            reply,
+            "T{:02x}{}:{:x};",
+            Signal::Trap as u8,
+            if is_write { "watch" } else { "rwatch" },
+            data_addr.as_usize()
+        ),
+    }
+    .map_err(|_| "Reply too long")
+}
+
+fn read_registers(e: &ExceptionContext, reply: &mut PacketBuffer) -> Result<(), &'static str> {
+    (0..arch_gdb::NUM_REGISTERS).try_for_each(|n| {
+        let value = arch_gdb::read_register(e, n).ok_or("Invalid register number")?;
+
+        reply.push_register(value, arch_gdb::register_size(n))
+    })
+}
+
+fn write_registers(e: &mut ExceptionContext, args: &[u8]) -> Result<(), &'static str> {
+    let mut rest = args;
+
+    for n in 0..arch_gdb::NUM_REGISTERS {
+        let num_digits = 2 * arch_gdb::register_size(n);
+        if rest.len() < num_digits {
+            return Err("Register data too short");
+        }
+
+        let (value, tail) = rest.split_at(num_digits);
+        arch_gdb::write_register(e, n, parse_register(value)?)?;
+        rest = tail;
+    }
+
+    Ok(())
+}
+
+/// Handle `maddr,length`.
+fn read_memory(args: &[u8], reply: &mut PacketBuffer) -> Result<(), &'static str> {
+    let (addr, len) = parse_addr_len(args)?;
+
+    // Each byte takes two hex digits. GDB reads the remainder with a follow-up request.
+    let len = len.min(PACKET_SIZE / 2);
+    check_memory_access(addr, len, false)?;
+
+    (addr..addr + len)
+        .try_for_each(|x| reply.push_hex_byte(unsafe { core::ptr::read_volatile(x as *const u8) }))
+}
+
+/// Handle `Maddr,length:XX...`.
+fn write_memory(args: &[u8]) -> Result<(), &'static str> {
+    let (range, data) = split_once(args, b':')?;
+    let (addr, len) = parse_addr_len(range)?;
+
+    if data.len() != 2 * len {
+        return Err("Length does not match data");
+    }
+    check_memory_access(addr, len, true)?;
+
+    // Validate everything before writing anything.
+    parse_hex_bytes(data).try_for_each(|x| x.map(|_| ()))?;
+
+    for (i, byte) in parse_hex_bytes(data).enumerate() {
+        unsafe { core::ptr::write_volatile((addr + i) as *mut u8, byte?) };
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Returns true if a debugger is attached to the stub.
+pub fn is_attached() -> bool {
+    GDB_STUB.lock(|stub| stub.attached)
+}
+
+/// Stop the interrupted context with `signal` and hand control to the debugger.
+///
+/// Returns after the debugger resumed execution or detached.
+pub fn handle_exception(e: &mut ExceptionContext, signal: Signal) -> Exit {
+    GDB_STUB.lock(|stub| stub.serve(e, StopReason::Signal(signal)))
+}
+
+/// Report a watchpoint hit on `data_addr` to the debugger.
+pub fn handle_watchpoint(e: &mut ExceptionContext, data_addr: Address<Virtual>, is_write: bool) {
+    GDB_STUB.lock(|stub| {
+        stub.serve(
+            e,
+            StopReason::Watchpoint {
+                data_addr,
+                is_write,
+            },
+        )
+    });
+}
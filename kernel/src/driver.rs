@@ -1,10 +1,11 @@
 //! Driver support.
 
 use crate::{
-    exception, info,
+    exception, info, shell,
     synchronization::{interface::ReadWriteEx, InitStateLock},
+    vfs,
 };
-use alloc::vec::Vec;
+use alloc::{format, string::String, vec::Vec};
 use core::fmt;
 
 /// Driver interfaces.
@@ -40,9 +41,21 @@ pub mod interface {
                 self.compatible()
             )
         }
+
+        /// The file that devfs exposes for the device.
+        fn device_file(&'static self) -> Option<super::DeviceFileDescriptor> {
+            None
+        }
     }
 }
 
+/// The class of a device, e.g. `uart`, and its device file. Devices of a class are numbered in the
+/// order of registration, e.g. `/dev/uart0`.
+pub type DeviceFileDescriptor = (
+    &'static str,
+    &'static (dyn vfs::interface::DeviceFile + Sync),
+);
+
 /// Type to be used as an optional callback after a driver's init() has run.
 pub type DeviceDriverPostInitCallback = unsafe fn() -> Result<(), &'static str>;
 
@@ -53,7 +66,7 @@ pub struct DeviceDriverDescriptor<T>
 {
     device_driver: &'static (dyn interface::DeviceDriver<IRQNumberType = T> + Sync),
     post_init_callback: Option<DeviceDriverPostInitCallback>,
-    irq_number: Option<T>,
+    irq_numbers: Vec<T>,
 }
 
 /// Provides device driver management functions.
@@ -70,6 +83,17 @@ pub struct DriverManager<T>
 
 static DRIVER_MANAGER: DriverManager<exception::asynchronous::IRQNumber> = DriverManager::new();
 
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn cmd_drivers(args: &[&str]) -> Result<(), &'static str> {
+    shell::no_args(args)?;
+    driver_manager().enumerate();
+
+    Ok(())
+}
+
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
@@ -84,9 +108,15 @@ impl<T> DeviceDriverDescriptor<T> {
         Self {
             device_driver,
             post_init_callback,
-            irq_number,
+            irq_numbers: irq_number.into_iter().collect(),
         }
     }
+
+    /// Register the driver for one more IRQ, for devices with several interrupt lines.
+    pub fn with_irq(mut self, irq_number: T) -> Self {
+        self.irq_numbers.push(irq_number);
+        self
+    }
 }
 
 /// Return a reference to the global DriverManager.
@@ -94,6 +124,16 @@ pub fn driver_manager() -> &'static DriverManager<exception::asynchronous::IRQNu
     &DRIVER_MANAGER
 }
 
+/// Register the shell commands of the driver subsystem.
+pub fn register_shell_commands() -> Result<(), &'static str> {
+    shell::register_command(shell::Command::new(
+        "drivers",
+        "drivers",
+        "List the loaded drivers",
+        cmd_drivers,
+    ))
+}
+
 impl<T> DriverManager<T>
     where
         T: fmt::Display,
@@ -143,7 +183,7 @@ impl<T> DriverManager<T>
             // 3. After all post-init callbacks were done, the interrupt controller should be
             //    registered and functional. So let drivers register with it now.
             for descriptor in descriptors {
-                if let Some(irq_number) = &descriptor.irq_number {
+                for irq_number in &descriptor.irq_numbers {
                     if let Err(x) = descriptor
                         .device_driver
                         .register_and_enable_irq_handler(irq_number)
@@ -159,6 +199,24 @@ impl<T> DriverManager<T>
         })
     }
 
+    /// The device files of the drivers, with their names.
+    pub fn device_files(&self) -> Vec<(String, &'static (dyn vfs::interface::DeviceFile + Sync))> {
+        self.descriptors.read(|descriptors| {
+            let mut classes = Vec::new();
+
+            descriptors
+                .iter()
+                .filter_map(|descriptor| descriptor.device_driver.device_file())
+                .map(|(class, file)| {
+                    let number = classes.iter().filter(|c| **c == class).count();
+                    classes.push(class);
+
+                    (format!("{}{}", class, number), file)
+                })
+                .collect()
+        })
+    }
+
     /// Enumerate all registered device drivers.
     pub fn enumerate(&self) {
         self.descriptors.read(|descriptors| {
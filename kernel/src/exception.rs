@@ -5,7 +5,7 @@ mod arch_exception;
 
 pub mod asynchronous;
 
-pub use arch_exception::{current_privilege_level, handling_init};
+pub use arch_exception::{current_privilege_level, handling_init, ExceptionContext};
 
 
 /// Kernel privilege levels.
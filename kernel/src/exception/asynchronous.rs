@@ -4,7 +4,7 @@
 mod arch_asynchronous;
 mod null_irq_manager;
 
-use crate::{bsp, synchronization};
+use crate::{bsp, shell, synchronization};
 use core::marker::PhantomData;
 
 pub use arch_asynchronous::{
@@ -144,6 +144,13 @@ impl<'irq_context> IRQContext<'irq_context> {
     }
 }
 
+fn cmd_irqs(args: &[&str]) -> Result<(), &'static str> {
+    shell::no_args(args)?;
+    irq_manager().print_handler();
+
+    Ok(())
+}
+
 /// Executes the provided closure while IRQs are masked on the executing core.
 ///
 /// While the function temporarily changes the HW state of the executing core, it restores it to the
@@ -169,4 +176,14 @@ pub fn register_irq_manager(
 /// This is the IRQ manager used by the architectural interrupt handling code.
 pub fn irq_manager() -> &'static dyn interface::IRQManager<IRQNumberType = IRQNumber> {
     CUR_IRQ_MANAGER.read(|manager| *manager)
-}
\ No newline at end of file
+}
+
+/// Register the shell commands of the IRQ handling.
+pub fn register_shell_commands() -> Result<(), &'static str> {
+    shell::register_command(shell::Command::new(
+        "irqs",
+        "irqs",
+        "List the registered IRQ handlers",
+        cmd_irqs,
+    ))
+}
@@ -0,0 +1,119 @@
+//! The `kernel` library.
+//!
+//! Used to compose the final kernel binary, and to run the unit and integration tests in QEMU.
+
+#![allow(clippy::upper_case_acronyms)]
+#![feature(alloc_error_handler)]
+#![feature(asm_const)]
+#![feature(const_option)]
+#![feature(core_intrinsics)]
+#![feature(format_args_nl)]
+#![feature(nonzero_min_max)]
+#![feature(panic_info_message)]
+#![feature(trait_alias)]
+#![feature(unchecked_math)]
+#![allow(incomplete_features)]
+#![feature(generic_const_exprs)]
+#![feature(int_roundings)]
+#![feature(linkage)]
+#![no_std]
+// Testing
+#![cfg_attr(test, no_main)]
+#![feature(custom_test_frameworks)]
+#![reexport_test_harness_main = "test_main"]
+#![test_runner(crate::test_runner)]
+
+extern crate alloc;
+
+mod panic_wait;
+
+pub mod backtrace;
+pub mod block;
+pub mod bsp;
+pub mod cmdline;
+pub mod common;
+pub mod console;
+pub mod cpu;
+pub mod debug;
+pub mod driver;
+pub mod exception;
+pub mod log;
+pub mod memory;
+pub mod print;
+pub mod random;
+#[cfg(feature = "semihosting")]
+pub mod semihosting;
+pub mod shell;
+pub mod state;
+pub mod symbols;
+pub mod synchronization;
+pub mod time;
+pub mod vfs;
+pub mod watchdog;
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+/// Only tests whose name contains this are run.
+static TEST_FILTER: synchronization::InitStateLock<&'static str> =
+    synchronization::InitStateLock::new("");
+
+early_param!(
+    "test",
+    "Run only the tests whose name contains this",
+    |filter: &'static str| {
+        use synchronization::interface::ReadWriteEx;
+
+        TEST_FILTER.write(|test_filter| *test_filter = filter);
+        Ok(())
+    }
+);
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// The default runner for unit tests.
+///
+/// A failing test panics, which ends the run. The `test` boot parameter selects tests by name.
+pub fn test_runner(tests: &[&test_types::UnitTest]) {
+    use synchronization::interface::ReadWriteEx;
+
+    let filter = TEST_FILTER.read(|test_filter| *test_filter);
+    let selected = || tests.iter().filter(|test| test.name.contains(filter));
+
+    // This line will be printed as the test header.
+    println!("Running {} tests", selected().count());
+
+    for (i, test) in selected().enumerate() {
+        print!("{:>3}. {:.<58}", i + 1, test.name);
+
+        // Run the actual test.
+        (test.test_func)();
+
+        // Failed tests call panic!(). Execution reaches here only if the test has passed.
+        println!("[ok]")
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+/// The `kernel_init()` for unit tests.
+#[cfg(test)]
+#[no_mangle]
+unsafe fn kernel_init() -> ! {
+    exception::handling_init();
+    memory::init();
+
+    if let Err(x) = semihosting::register_console() {
+        panic!("Error registering the semihosting console: {}", x);
+    }
+    cmdline::init();
+
+    test_main();
+
+    semihosting::exit_success()
+}
@@ -0,0 +1,556 @@
+//! Kernel log.
+//!
+//! Log records have a [`Level`] and pass through a filter that can be set per module at runtime.
+//! Records that pass are kept in a fixed-size ring buffer in `.bss`, which works from the first
+//! instruction on. Nothing is lost before a console exists: When a console is registered, the
+//! buffer is replayed to it. Afterwards, records are printed as they come in.
+//!
+//! The buffer can be read back, `dmesg` style, with a [`Reader`].
+//!
+//! The `loglevel` boot parameter sets the default filter, e.g. `loglevel=debug`.
+
+mod record_buffer;
+
+use crate::{
+    cmdline, console, early_param, println, shell, synchronization,
+    synchronization::IRQSafeNullLock, time,
+};
+use core::{fmt, time::Duration};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Messages are truncated to this length.
+const MAX_MESSAGE_LEN: usize = 512;
+
+const MAX_MODULE_FILTERS: usize = 16;
+const MAX_MODULE_LEN: usize = 64;
+
+/// The level filter for modules without a filter of their own, at boot.
+const DEFAULT_LEVEL: Level = if cfg!(feature = "debug_prints") {
+    Level::Debug
+} else {
+    Level::Info
+};
+
+#[derive(Copy, Clone)]
+struct ModuleFilter {
+    module: [u8; MAX_MODULE_LEN],
+    module_len: usize,
+    level: LevelFilter,
+}
+
+struct Filters {
+    default: LevelFilter,
+    modules: [Option<ModuleFilter>; MAX_MODULE_FILTERS],
+}
+
+/// Collects a formatted message, truncating it if it does not fit.
+struct MessageBuffer {
+    buf: [u8; MAX_MESSAGE_LEN],
+    len: usize,
+}
+
+/// A record, formatted the way it is shown on the console.
+struct DisplayRecord<'a> {
+    level: Level,
+    timestamp: Duration,
+    message: &'a str,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Severity of a log record, from most to least severe.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// The least severe level that passes a filter. `None` lets no record pass.
+pub type LevelFilter = Option<Level>;
+
+/// A log record, as read back from the ring buffer.
+pub struct Record {
+    seq: u64,
+    level: Level,
+    timestamp: Duration,
+    message: [u8; MAX_MESSAGE_LEN],
+    message_len: usize,
+}
+
+/// Reads the records in the ring buffer, oldest first.
+pub struct Reader {
+    cursor: Option<record_buffer::Cursor>,
+    lost: u64,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static RECORD_BUFFER: IRQSafeNullLock<record_buffer::RecordBuffer> =
+    IRQSafeNullLock::new(record_buffer::RecordBuffer::new());
+
+static FILTERS: IRQSafeNullLock<Filters> = IRQSafeNullLock::new(Filters {
+    default: Some(DEFAULT_LEVEL),
+    modules: [None; MAX_MODULE_FILTERS],
+});
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl Level {
+    const ALL: [Self; 5] = [
+        Self::Error,
+        Self::Warn,
+        Self::Info,
+        Self::Debug,
+        Self::Trace,
+    ];
+
+    /// Inverse of `level as u8`.
+    fn from_u8(raw: u8) -> Self {
+        Self::ALL.get(raw as usize).copied().unwrap_or(Self::Error)
+    }
+
+    /// Tag that is shown in front of the timestamp.
+    fn tag(self) -> char {
+        match self {
+            Self::Error => 'E',
+            Self::Warn => 'W',
+            Self::Info => ' ',
+            Self::Debug => 'D',
+            Self::Trace => 'T',
+        }
+    }
+}
+
+early_param!(
+    "loglevel",
+    "Log level of modules without a filter of their own",
+    |level: Level| {
+        set_default_level(Some(level));
+        Ok(())
+    }
+);
+
+/// Strip the crate name from a module path.
+fn module_without_crate(module_path: &str) -> &str {
+    module_path
+        .split_once("::")
+        .map_or("", |(_crate_name, module)| module)
+}
+
+/// Whether `module` is `filter_module` or one of its submodules.
+fn module_matches(module: &str, filter_module: &str) -> bool {
+    match module.strip_prefix(filter_module) {
+        None => false,
+        Some(rest) => filter_module.is_empty() || rest.is_empty() || rest.starts_with("::"),
+    }
+}
+
+impl ModuleFilter {
+    fn module(&self) -> &str {
+        // Only ever filled from a str, and cut at a char boundary.
+        core::str::from_utf8(&self.module[..self.module_len]).unwrap_or("")
+    }
+}
+
+impl Filters {
+    /// The filter of the most specific matching module filter, or the default one.
+    fn level_for(&self, module: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .flatten()
+            .filter(|filter| module_matches(module, filter.module()))
+            .max_by_key(|filter| filter.module_len)
+            .map_or(self.default, |filter| filter.level)
+    }
+}
+
+impl MessageBuffer {
+    const fn new() -> Self {
+        Self {
+            buf: [0; MAX_MESSAGE_LEN],
+            len: 0,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl fmt::Write for MessageBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut n = s.len().min(MAX_MESSAGE_LEN - self.len);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for DisplayRecord<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{} {:>3}.{:06}] {}",
+            self.level.tag(),
+            self.timestamp.as_secs(),
+            self.timestamp.subsec_micros(),
+            self.message
+        )
+    }
+}
+
+fn cmd_dmesg(args: &[&str]) -> Result<(), &'static str> {
+    let clear_after = match args {
+        [] => false,
+        ["-c"] => true,
+        _ => return Err("Unexpected arguments"),
+    };
+
+    let mut reader = Reader::new();
+    for record in &mut reader {
+        println!("{}", record);
+    }
+    if reader.lost() > 0 {
+        println!("({} records were overwritten while reading)", reader.lost());
+    }
+
+    if clear_after {
+        clear();
+    }
+
+    Ok(())
+}
+
+fn cmd_loglevel(args: &[&str]) -> Result<(), &'static str> {
+    match args {
+        [] => print_filters(),
+        [level] => set_default_level(parse_level_filter(level)?),
+        [module, "default"] => clear_module_level(module)?,
+        [module, level] => set_module_level(module, parse_level_filter(level)?)?,
+        _ => return Err("Wrong number of arguments"),
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+use synchronization::interface::Mutex;
+
+impl Level {
+    /// The level's name in lower case.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+            Self::Trace => "trace",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl core::str::FromStr for Level {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|level| level.name().eq_ignore_ascii_case(s))
+            .ok_or("Unknown log level")
+    }
+}
+
+impl cmdline::ParamValue for Level {
+    fn parse(value: Option<&'static str>) -> Result<Self, &'static str> {
+        value.ok_or("Missing value")?.parse()
+    }
+}
+
+impl Record {
+    /// Sequence number. Increments by one for each record that was logged.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// The record's level.
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Uptime at which the record was logged.
+    pub fn timestamp(&self) -> Duration {
+        self.timestamp
+    }
+
+    /// The message.
+    pub fn message(&self) -> &str {
+        core::str::from_utf8(&self.message[..self.message_len]).unwrap_or("")
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        DisplayRecord {
+            level: self.level,
+            timestamp: self.timestamp,
+            message: self.message(),
+        }
+        .fmt(f)
+    }
+}
+
+impl Reader {
+    /// Create a reader that starts at the oldest record.
+    pub const fn new() -> Self {
+        Self {
+            cursor: None,
+            lost: 0,
+        }
+    }
+
+    /// Number of records that were dropped from the buffer before this reader got to them.
+    pub fn lost(&self) -> u64 {
+        self.lost
+    }
+}
+
+impl Iterator for Reader {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Record> {
+        RECORD_BUFFER.lock(|buf| {
+            let cursor = match self.cursor {
+                Some(cursor) if !buf.is_dropped(cursor) => self.cursor.as_mut().unwrap(),
+                _ => {
+                    let first = buf.first();
+                    self.lost += first.seq - self.cursor.map_or(first.seq, |cursor| cursor.seq);
+
+                    self.cursor.insert(first)
+                }
+            };
+
+            buf.read(cursor)
+        })
+    }
+}
+
+#[doc(hidden)]
+pub fn _log(level: Level, module_path: &str, args: fmt::Arguments) {
+    if !enabled(level, module_path) {
+        return;
+    }
+
+    let timestamp = time::time_manager().uptime();
+    let mut message = MessageBuffer::new();
+    let _ = fmt::Write::write_fmt(&mut message, args);
+
+    RECORD_BUFFER.lock(|buf| buf.push(level, timestamp, message.as_bytes()));
+
+    let record = DisplayRecord {
+        level,
+        timestamp,
+        message: core::str::from_utf8(message.as_bytes()).unwrap_or(""),
+    };
+    console::write_log_record(level, format_args_nl!("{}", record));
+}
+
+/// Whether a record of the given level, logged from the given module, passes the filters.
+pub fn enabled(level: Level, module_path: &str) -> bool {
+    let module = module_without_crate(module_path);
+
+    FILTERS.lock(|filters| filters.level_for(module)) >= Some(level)
+}
+
+/// Set the filter for modules that do not have one of their own.
+pub fn set_default_level(level: LevelFilter) {
+    FILTERS.lock(|filters| filters.default = level);
+}
+
+/// Set the filter for a module and its submodules, e.g. `memory::mmu`.
+///
+/// Overrides an existing filter for the same module.
+pub fn set_module_level(module: &str, level: LevelFilter) -> Result<(), &'static str> {
+    if module.len() > MAX_MODULE_LEN {
+        return Err("Module path too long");
+    }
+
+    FILTERS.lock(|filters| {
+        let slot = match filters
+            .modules
+            .iter()
+            .position(|filter| matches!(filter, Some(filter) if filter.module() == module))
+        {
+            Some(i) => &mut filters.modules[i],
+            None => filters
+                .modules
+                .iter_mut()
+                .find(|filter| filter.is_none())
+                .ok_or("Too many module filters")?,
+        };
+
+        let mut new_filter = ModuleFilter {
+            module: [0; MAX_MODULE_LEN],
+            module_len: module.len(),
+            level,
+        };
+        new_filter.module[..module.len()].copy_from_slice(module.as_bytes());
+        *slot = Some(new_filter);
+
+        Ok(())
+    })
+}
+
+/// Remove the filter of a module.
+pub fn clear_module_level(module: &str) -> Result<(), &'static str> {
+    FILTERS.lock(|filters| {
+        let slot = filters
+            .modules
+            .iter_mut()
+            .find(|filter| matches!(filter, Some(filter) if filter.module() == module))
+            .ok_or("No filter for this module")?;
+        *slot = None;
+
+        Ok(())
+    })
+}
+
+/// Print the active filters.
+pub fn print_filters() {
+    let level_name = |level: LevelFilter| level.map_or("off", Level::name);
+
+    // Copy, to not hold the lock while printing.
+    let (default, modules) = FILTERS.lock(|filters| (filters.default, filters.modules));
+
+    println!("      {:<40} {}", "(default)", level_name(default));
+    for filter in modules.iter().flatten() {
+        println!("      {:<40} {}", filter.module(), level_name(filter.level));
+    }
+}
+
+/// Parse a level name, or `off`.
+pub fn parse_level_filter(s: &str) -> Result<LevelFilter, &'static str> {
+    match s {
+        "off" => Ok(None),
+        _ => s.parse().map(Some),
+    }
+}
+
+/// Register the shell commands of the kernel log.
+pub fn register_shell_commands() -> Result<(), &'static str> {
+    shell::register_command(shell::Command::new(
+        "dmesg",
+        "dmesg [-c]",
+        "Print the kernel log, and optionally clear it",
+        cmd_dmesg,
+    ))?;
+    shell::register_command(shell::Command::new(
+        "loglevel",
+        "loglevel [module] [level]",
+        "Print or set log filters. Levels: off, error, warn, info, debug, trace, default",
+        cmd_loglevel,
+    ))
+}
+
+/// Drop all records from the ring buffer.
+pub fn clear() {
+    RECORD_BUFFER.lock(|buf| buf.clear());
+}
+
+/// Write the buffered records down to `min_level` to a console.
+///
+/// Used to catch up a console that was registered after records were logged already.
+pub fn replay(con: &dyn console::interface::Write, min_level: LevelFilter) {
+    let mut reader = Reader::new();
+    let first = reader.next();
+
+    if let Some(record) = &first {
+        if record.seq > 0 {
+            let _ = con.write_fmt(format_args_nl!(
+                "[  ...] {} earlier log records were lost",
+                record.seq
+            ));
+        }
+    }
+
+    for record in first.into_iter().chain(reader) {
+        if min_level >= Some(record.level) {
+            let _ = con.write_fmt(format_args_nl!("{}", record));
+        }
+    }
+}
+
+/// Log an error, with a newline.
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => ($crate::log::_log(
+        $crate::log::Level::Error,
+        module_path!(),
+        format_args!($($arg)*)
+    ));
+}
+
+/// Log a warning, with a newline.
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => ($crate::log::_log(
+        $crate::log::Level::Warn,
+        module_path!(),
+        format_args!($($arg)*)
+    ));
+}
+
+/// Log an info, with a newline.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => ($crate::log::_log(
+        $crate::log::Level::Info,
+        module_path!(),
+        format_args!($($arg)*)
+    ));
+}
+
+/// Log a debug message, with a newline.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => ($crate::log::_log(
+        $crate::log::Level::Debug,
+        module_path!(),
+        format_args!($($arg)*)
+    ));
+}
+
+/// Log a trace message, with a newline.
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => ($crate::log::_log(
+        $crate::log::Level::Trace,
+        module_path!(),
+        format_args!($($arg)*)
+    ));
+}
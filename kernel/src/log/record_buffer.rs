@@ -0,0 +1,181 @@
+//! Ring buffer of log records.
+//!
+//! Records are stored back to back as a header followed by the message bytes, wrapping around at
+//! the end of the buffer. When there is not enough room for a new record, the oldest ones are
+//! dropped.
+
+use super::{Level, Record, MAX_MESSAGE_LEN};
+use core::time::Duration;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Level (1 Byte), timestamp in nanoseconds (8 Byte), message length (2 Byte).
+const HEADER_SIZE: usize = 1 + 8 + 2;
+
+struct Header {
+    level: Level,
+    timestamp: Duration,
+    message_len: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The ring buffer.
+pub struct RecordBuffer {
+    data: [u8; BUFFER_SIZE],
+
+    /// Offset of the oldest record.
+    head: usize,
+
+    /// Number of bytes in use.
+    used: usize,
+
+    /// Sequence number of the oldest record.
+    first_seq: u64,
+
+    /// Sequence number that the next record gets.
+    next_seq: u64,
+}
+
+/// Position of a record in the buffer.
+///
+/// Stays valid as long as the record has not been dropped, which is the case when `seq` is not
+/// smaller than the sequence number of the oldest record.
+#[derive(Copy, Clone)]
+pub struct Cursor {
+    pub seq: u64,
+    offset: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl RecordBuffer {
+    fn copy_in(&mut self, offset: usize, bytes: &[u8]) {
+        for (i, b) in bytes.iter().enumerate() {
+            self.data[(offset + i) % BUFFER_SIZE] = *b;
+        }
+    }
+
+    fn copy_out(&self, offset: usize, bytes: &mut [u8]) {
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = self.data[(offset + i) % BUFFER_SIZE];
+        }
+    }
+
+    fn read_header(&self, offset: usize) -> Header {
+        let mut raw = [0; HEADER_SIZE];
+        self.copy_out(offset, &mut raw);
+
+        let mut timestamp = [0; 8];
+        timestamp.copy_from_slice(&raw[1..9]);
+
+        Header {
+            level: Level::from_u8(raw[0]),
+            timestamp: Duration::from_nanos(u64::from_le_bytes(timestamp)),
+            message_len: u16::from_le_bytes([raw[9], raw[10]]) as usize,
+        }
+    }
+
+    fn drop_oldest(&mut self) {
+        let size = HEADER_SIZE + self.read_header(self.head).message_len;
+
+        self.head = (self.head + size) % BUFFER_SIZE;
+        self.used -= size;
+        self.first_seq += 1;
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl RecordBuffer {
+    /// Create an instance.
+    pub const fn new() -> Self {
+        Self {
+            data: [0; BUFFER_SIZE],
+            head: 0,
+            used: 0,
+            first_seq: 0,
+            next_seq: 0,
+        }
+    }
+
+    /// Append a record, dropping old ones as needed.
+    ///
+    /// The message must not be longer than `MAX_MESSAGE_LEN`.
+    pub fn push(&mut self, level: Level, timestamp: Duration, message: &[u8]) {
+        let size = HEADER_SIZE + message.len();
+
+        while BUFFER_SIZE - self.used < size {
+            self.drop_oldest();
+        }
+
+        let mut header = [0; HEADER_SIZE];
+        header[0] = level as u8;
+        header[1..9].copy_from_slice(&(timestamp.as_nanos() as u64).to_le_bytes());
+        header[9..11].copy_from_slice(&(message.len() as u16).to_le_bytes());
+
+        let offset = (self.head + self.used) % BUFFER_SIZE;
+        self.copy_in(offset, &header);
+        self.copy_in(offset + HEADER_SIZE, message);
+
+        self.used += size;
+        self.next_seq += 1;
+    }
+
+    /// Drop all records.
+    pub fn clear(&mut self) {
+        self.head = (self.head + self.used) % BUFFER_SIZE;
+        self.used = 0;
+        self.first_seq = self.next_seq;
+    }
+
+    /// Cursor to the oldest record.
+    pub fn first(&self) -> Cursor {
+        Cursor {
+            seq: self.first_seq,
+            offset: self.head,
+        }
+    }
+
+    /// Whether the record at `cursor` has been dropped already.
+    pub fn is_dropped(&self, cursor: Cursor) -> bool {
+        cursor.seq < self.first_seq
+    }
+
+    /// Read the record at `cursor` and advance the cursor.
+    ///
+    /// Returns `None` if there are no more records, or if the record has been dropped.
+    pub fn read(&self, cursor: &mut Cursor) -> Option<Record> {
+        if cursor.seq >= self.next_seq || self.is_dropped(*cursor) {
+            return None;
+        }
+
+        let header = self.read_header(cursor.offset);
+        let mut record = Record {
+            seq: cursor.seq,
+            level: header.level,
+            timestamp: header.timestamp,
+            message: [0; MAX_MESSAGE_LEN],
+            message_len: header.message_len,
+        };
+        self.copy_out(
+            cursor.offset + HEADER_SIZE,
+            &mut record.message[..header.message_len],
+        );
+
+        cursor.seq += 1;
+        cursor.offset = (cursor.offset + HEADER_SIZE + header.message_len) % BUFFER_SIZE;
+
+        Some(record)
+    }
+}
@@ -1,98 +1,19 @@
-#![allow(clippy::upper_case_acronyms)]
-#![feature(alloc_error_handler)]
-#![feature(asm_const)]
-#![feature(const_option)]
-#![feature(core_intrinsics)]
-#![feature(format_args_nl)]
-#![feature(nonzero_min_max)]
-#![feature(panic_info_message)]
-#![feature(trait_alias)]
-#![feature(unchecked_math)]
-#![feature(step_trait)]
-#![allow(incomplete_features)]
-#![feature(generic_const_exprs)]
-#![feature(int_roundings)]
-#![feature(is_sorted)]
-#![feature(linkage)]
+//! The `kernel` binary.
 
-#![no_std]
 #![no_main]
+#![no_std]
 
 extern crate alloc;
 
-mod bsp;
-mod console;
-mod cpu;
-mod print;
-mod exception;
-mod time;
-mod synchronization;
-mod driver;
-mod memory;
-mod common;
-mod state;
-mod symbols;
-mod backtrace;
-
 use alloc::boxed::Box;
-use core::arch::asm;
-use core::panic::PanicInfo;
-use core::ptr::{read_volatile, write_volatile};
+use core::ptr::write_volatile;
 use core::time::Duration;
-
-/// Stop immediately if called a second time.
-///
-/// # Note
-///
-/// Using atomics here relieves us from needing to use `unsafe` for the static variable.
-///
-/// On `AArch64`, which is the only implemented architecture at the time of writing this,
-/// [`AtomicBool::load`] and [`AtomicBool::store`] are lowered to ordinary load and store
-/// instructions. They are therefore safe to use even with MMU + caching deactivated.
-///
-/// [`AtomicBool::load`]: core::sync::atomic::AtomicBool::load
-/// [`AtomicBool::store`]: core::sync::atomic::AtomicBool::store
-fn panic_prevent_reenter() {
-    use core::sync::atomic::{AtomicBool, Ordering};
-
-    static PANIC_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
-
-    if !PANIC_IN_PROGRESS.load(Ordering::Relaxed) {
-        PANIC_IN_PROGRESS.store(true, Ordering::Relaxed);
-
-        return;
-    }
-
-    cpu::wait_forever()
-}
-
-#[panic_handler]
-fn panic(info: &PanicInfo) -> ! {
-    // Protect against panic infinite loops if any of the following code panics itself.
-    panic_prevent_reenter();
-
-    let timestamp = time::time_manager().uptime();
-    let (location, line, column) = match info.location() {
-        Some(loc) => (loc.file(), loc.line(), loc.column()),
-        _ => ("???", 0, 0),
-    };
-
-    println!(
-        "[  {:>3}.{:06}] Kernel panic!\n\n\
-    Panic location:\n      File '{}', line {}, column {}\n\n\
-    {}\n\n\
-    {}",
-        timestamp.as_secs(),
-        timestamp.subsec_micros(),
-        location,
-        line,
-        column,
-        info.message().unwrap_or(&format_args!("")),
-        backtrace::Backtrace
-    );
-
-    cpu::wait_forever()
-}
+#[cfg(feature = "semihosting")]
+use libkernel::semihosting;
+use libkernel::{
+    block, bsp, cmdline, common, console, debug, driver, exception, info, log, memory, random,
+    shell, state, symbols, time, vfs, warn, watchdog,
+};
 
 /// Early init code.
 ///
@@ -108,8 +29,20 @@ unsafe fn kernel_init() -> ! {
     use memory::mmu::interface::MMU;
 
     exception::handling_init();
+    debug::init();
     memory::init();
 
+    #[cfg(feature = "semihosting")]
+    if let Err(x) = semihosting::register_console() {
+        panic!("Error registering the semihosting console: {}", x);
+    }
+
+    // Apply the boot parameters, some of which decide how the drivers are brought up.
+    cmdline::init();
+
+    // Seed the random pool from timer jitter. The hardware adds to it once its driver is up.
+    random::init();
+
     // Initialize the BSP driver subsystem.
     if let Err(x) = bsp::driver::init() {
         panic!("Error initializing BSP driver subsystem: {}", x);
@@ -118,6 +51,30 @@ unsafe fn kernel_init() -> ! {
     // Initialize all device drivers.
     driver::driver_manager().init_drivers_and_irqs();
 
+    if let Err(x) = vfs::init() {
+        panic!("Error assembling the filesystem tree: {}", x);
+    }
+
+    if let Err(x) = shell::init() {
+        panic!("Error initializing the shell: {}", x);
+    }
+
+    // The subsystems that have no init of their own.
+    for register_shell_commands in [
+        block::register_shell_commands,
+        console::register_shell_commands,
+        driver::register_shell_commands,
+        exception::asynchronous::register_shell_commands,
+        log::register_shell_commands,
+        symbols::register_shell_commands,
+        time::register_shell_commands,
+        watchdog::register_shell_commands,
+    ] {
+        if let Err(x) = register_shell_commands() {
+            panic!("Error registering shell commands: {}", x);
+        }
+    }
+
     bsp::memory::mmu::kernel_add_mapping_records_for_precomputed();
 
     // Unmask interrupts on the boot CPU core.
@@ -134,6 +91,11 @@ unsafe fn kernel_init() -> ! {
 fn kernel_main() -> ! {
     info!("Booting on: {}", bsp::board_name());
 
+    if let Some(size) = bsp::memory::phys_dram_size() {
+        let (size, unit) = common::size_human_readable_ceil(size);
+        info!("DRAM of the ARM cores: {} {}", size, unit);
+    }
+
     info!("MMU online:");
     memory::mmu::kernel_print_mappings();
 
@@ -143,6 +105,9 @@ fn kernel_main() -> ! {
     info!("Exception handling state:");
     exception::asynchronous::print_state();
 
+    info!("HW debug state:");
+    debug::print_state();
+
     info!(
         "Architectural timer resolution: {} ns",
         time::time_manager().resolution().as_nanos()
@@ -157,31 +122,51 @@ fn kernel_main() -> ! {
     // info!("Timer test, spinning for 5 seconds");
     // time::time_manager().spin_for(Duration::from_secs(5));
 
-    // trigger a page fault
-    unsafe {
-        *(0xdeadbeef as *mut u8) = 42;
-    };
+    // The exception handler skips over these faults. With the GDB stub, they would stop the kernel
+    // in the debugger instead.
+    #[cfg(not(feature = "gdb_stub"))]
+    {
+        use core::{arch::asm, ptr::read_volatile};
+
+        // trigger a page fault
+        unsafe {
+            *(0xdeadbeef as *mut u8) = 42;
+        };
+
+        info!("************************************************");
+        info!("Whoa! We recovered from a synchronous exception!");
+        info!("************************************************");
+        info!("");
+        info!("Let's try again");
+
+        // Cause an exception by accessing a virtual address for which no translation was set up.
+        // This code accesses the address 8 GiB, which is outside the mapped address space.
+        //
+        // For demo purposes, the exception handler will catch the faulting 8 GiB address and allow
+        // execution to continue.
+        info!("");
+        info!("Trying to read from address 8 GiB...");
+        let mut big_addr: u64 = 8 * 1024 * 1024 * 1024;
+        unsafe { read_volatile(big_addr as *mut u64) };
+
+        // invoke a breakpoint exception
+        unsafe {
+            asm!("brk #0")
+        }
+    }
 
-    info!("************************************************");
-    info!("Whoa! We recovered from a synchronous exception!");
-    info!("************************************************");
-    info!("");
-    info!("Let's try again");
+    // Catch a write to a variable with a HW watchpoint, and trace what happens right after.
+    static mut WATCHED_VALUE: u64 = 0;
+    let watched_addr = memory::Address::new(core::ptr::addr_of!(WATCHED_VALUE) as usize);
 
-    // Cause an exception by accessing a virtual address for which no translation was set up.
-    // This code accesses the address 8 GiB, which is outside the mapped address space.
-    //
-    // For demo purposes, the exception handler will catch the faulting 8 GiB address and allow
-    // execution to continue.
     info!("");
-    info!("Trying to read from address 8 GiB...");
-    let mut big_addr: u64 = 8 * 1024 * 1024 * 1024;
-    unsafe { read_volatile(big_addr as *mut u64) };
-
-    // invoke a breakpoint exception
-    unsafe {
-        asm!("brk #0")
+    info!("Setting a watchpoint on {}...", watched_addr);
+    if let Err(x) = debug::set_watchpoint(watched_addr, 8, debug::WatchpointAccess::Store) {
+        warn!("Could not set watchpoint: {}", x);
     }
+    debug::trace_instructions(2);
+    unsafe { write_volatile(core::ptr::addr_of_mut!(WATCHED_VALUE), 42) };
+    debug::clear_watchpoint(watched_addr).ok();
 
     info!("Kernel heap:");
     memory::heap_alloc::kernel_heap_allocator().print_usage();
@@ -193,6 +178,6 @@ fn kernel_main() -> ! {
     info!("Kernel heap:");
     memory::heap_alloc::kernel_heap_allocator().print_usage();
 
-    info!("Echoing input now");
-    cpu::wait_forever();
+    info!("Starting the kernel monitor. Type 'help' for a list of commands.");
+    shell::run()
 }
@@ -1,156 +1,135 @@
 pub mod heap_alloc;
 pub mod mmu;
 
-use crate::{bsp, common};
-use core::{
-    fmt,
-    marker::PhantomData,
-    ops::{Add, Sub},
-};
-
-
-/// Metadata trait for marking the type of an address.
-pub trait AddressType: Copy + Clone + PartialOrd + PartialEq + Ord + Eq {}
-
-/// Zero-sized type to mark a physical address.
-#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq)]
-pub enum Physical {}
-
-/// Zero-sized type to mark a virtual address.
-#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq)]
-pub enum Virtual {}
-
-/// Generic address type.
-#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq)]
-pub struct Address<ATYPE: AddressType> {
-    value: usize,
-    _address_type: PhantomData<fn() -> ATYPE>,
-}
+use crate::{bsp, println, shell};
+use core::mem::size_of;
+use mmu::{AccessPermissions, PageAddress};
 
+pub use kernel_core::memory::{Address, AddressType, Physical, Virtual};
 
-impl AddressType for Physical {}
-impl AddressType for Virtual {}
+/// Upper limit for the number of words printed by a single `peek`.
+const MAX_PEEK_COUNT: usize = 256;
 
-impl<ATYPE: AddressType> Address<ATYPE> {
-    /// Create an instance.
-    pub const fn new(value: usize) -> Self {
-        Self {
-            value,
-            _address_type: PhantomData,
-        }
+/// Check that a word sized access to `addr` will not fault.
+fn check_access(addr: Address<Virtual>, write: bool) -> Result<(), &'static str> {
+    if !addr.as_usize().is_multiple_of(size_of::<u64>()) {
+        return Err("Address is not 8 byte aligned");
     }
 
-    /// Convert to usize.
-    pub const fn as_usize(self) -> usize {
-        self.value
-    }
+    mmu::try_kernel_virt_addr_to_phys_addr(addr)?;
 
-    /// Align down to page size.
-    #[must_use]
-    pub const fn align_down_page(self) -> Self {
-        let aligned = common::align_down(self.value, bsp::memory::mmu::KernelGranule::SIZE);
+    if write {
+        let attributes = mmu::try_kernel_page_attributes(PageAddress::from(addr))?;
 
-        Self::new(aligned)
+        if attributes.acc_perms != AccessPermissions::ReadWrite {
+            return Err("Address is read-only");
+        }
     }
 
-    /// Align up to page size.
-    #[must_use]
-    pub const fn align_up_page(self) -> Self {
-        let aligned = common::align_up(self.value, bsp::memory::mmu::KernelGranule::SIZE);
+    Ok(())
+}
 
-        Self::new(aligned)
-    }
+fn cmd_mappings(args: &[&str]) -> Result<(), &'static str> {
+    shell::no_args(args)?;
+    mmu::kernel_print_mappings();
 
-    /// Checks if the address is page aligned.
-    pub const fn is_page_aligned(&self) -> bool {
-        common::is_aligned(self.value, bsp::memory::mmu::KernelGranule::SIZE)
-    }
+    Ok(())
+}
 
-    /// Return the address' offset into the corresponding page.
-    pub const fn offset_into_page(&self) -> usize {
-        self.value & bsp::memory::mmu::KernelGranule::MASK
-    }
+fn cmd_heap(args: &[&str]) -> Result<(), &'static str> {
+    shell::no_args(args)?;
+    heap_alloc::kernel_heap_allocator().print_usage();
+
+    Ok(())
 }
 
-impl<ATYPE: AddressType> Add<usize> for Address<ATYPE> {
-    type Output = Self;
+fn cmd_peek(args: &[&str]) -> Result<(), &'static str> {
+    let (addr, count) = match args {
+        [addr] => (shell::parse_number(addr)?, 1),
+        [addr, count] => (shell::parse_number(addr)?, shell::parse_number(count)?),
+        _ => return Err("Wrong number of arguments"),
+    };
 
-    #[inline(always)]
-    fn add(self, rhs: usize) -> Self::Output {
-        match self.value.checked_add(rhs) {
-            None => panic!("Overflow on Address::add"),
-            Some(x) => Self::new(x),
-        }
+    if count > MAX_PEEK_COUNT {
+        return Err("Count too big");
     }
-}
 
-impl<ATYPE: AddressType> Sub<usize> for Address<ATYPE> {
-    type Output = Self;
+    for i in 0..count {
+        let addr = i
+            .checked_mul(size_of::<u64>())
+            .and_then(|offset| addr.checked_add(offset))
+            .map(Address::<Virtual>::new)
+            .ok_or("Address overflow")?;
+        check_access(addr, false)?;
 
-    #[inline(always)]
-    fn sub(self, rhs: usize) -> Self::Output {
-        match self.value.checked_sub(rhs) {
-            None => panic!("Overflow on Address::sub"),
-            Some(x) => Self::new(x),
-        }
+        let value = unsafe { core::ptr::read_volatile(addr.as_usize() as *const u64) };
+        println!("{}: {:#018x}", addr, value);
     }
+
+    Ok(())
 }
 
-impl<ATYPE: AddressType> Sub<Address<ATYPE>> for Address<ATYPE> {
-    type Output = Self;
+fn cmd_poke(args: &[&str]) -> Result<(), &'static str> {
+    let [addr, value] = args else {
+        return Err("Wrong number of arguments");
+    };
 
-    #[inline(always)]
-    fn sub(self, rhs: Address<ATYPE>) -> Self::Output {
-        match self.value.checked_sub(rhs.value) {
-            None => panic!("Overflow on Address::sub"),
-            Some(x) => Self::new(x),
-        }
-    }
+    let addr = Address::<Virtual>::new(shell::parse_number(addr)?);
+    let value = shell::parse_number(value)? as u64;
+    check_access(addr, true)?;
+
+    unsafe { core::ptr::write_volatile(addr.as_usize() as *mut u64, value) };
+
+    Ok(())
 }
 
-impl Address<Virtual> {
-    /// Checks if the address is part of the boot core stack region.
-    pub fn is_valid_stack_addr(&self) -> bool {
-        bsp::memory::mmu::virt_boot_core_stack_region().contains(*self)
+fn register_shell_commands() -> Result<(), &'static str> {
+    let commands = [
+        shell::Command::new(
+            "mappings",
+            "mappings",
+            "Print the kernel's memory mappings",
+            cmd_mappings,
+        ),
+        shell::Command::new("heap", "heap", "Print the kernel heap usage", cmd_heap),
+        shell::Command::new(
+            "peek",
+            "peek <addr> [count]",
+            "Read 64 bit words from memory",
+            cmd_peek,
+        ),
+        shell::Command::new(
+            "poke",
+            "poke <addr> <value>",
+            "Write a 64 bit word to memory",
+            cmd_poke,
+        ),
+    ];
+
+    for command in commands {
+        shell::register_command(command)?;
     }
 
-    /// Checks if the address is part of the kernel code region.
-    pub fn is_valid_code_addr(&self) -> bool {
-        bsp::memory::mmu::virt_code_region().contains(*self)
-    }
+    Ok(())
 }
 
-impl fmt::Display for Address<Physical> {
-    // Don't expect to see physical addresses greater than 40 bit.
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let q3: u8 = ((self.value >> 32) & 0xff) as u8;
-        let q2: u16 = ((self.value >> 16) & 0xffff) as u16;
-        let q1: u16 = (self.value & 0xffff) as u16;
-
-        write!(f, "0x")?;
-        write!(f, "{:02x}_", q3)?;
-        write!(f, "{:04x}_", q2)?;
-        write!(f, "{:04x}", q1)
-    }
+/// Checks if the address is part of the boot core stack region.
+pub fn is_valid_stack_addr(addr: Address<Virtual>) -> bool {
+    bsp::memory::mmu::virt_boot_core_stack_region().contains(addr)
 }
 
-impl fmt::Display for Address<Virtual> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let q4: u16 = ((self.value >> 48) & 0xffff) as u16;
-        let q3: u16 = ((self.value >> 32) & 0xffff) as u16;
-        let q2: u16 = ((self.value >> 16) & 0xffff) as u16;
-        let q1: u16 = (self.value & 0xffff) as u16;
-
-        write!(f, "0x")?;
-        write!(f, "{:04x}_", q4)?;
-        write!(f, "{:04x}_", q3)?;
-        write!(f, "{:04x}_", q2)?;
-        write!(f, "{:04x}", q1)
-    }
+/// Checks if the address is part of the kernel code region.
+pub fn is_valid_code_addr(addr: Address<Virtual>) -> bool {
+    bsp::memory::mmu::virt_code_region().contains(addr)
 }
 
 /// Initialize the memory subsystem.
 pub fn init() {
     mmu::kernel_init_mmio_va_allocator();
     heap_alloc::kernel_init_heap_allocator();
+
+    // Needs the heap.
+    if let Err(x) = register_shell_commands() {
+        panic!("Error registering the memory shell commands: {}", x);
+    }
 }
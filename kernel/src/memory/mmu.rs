@@ -6,7 +6,6 @@ mod arch_mmu;
 mod mapping_record;
 mod page_alloc;
 mod translation_table;
-mod types;
 
 use crate::{
     bsp,
@@ -15,7 +14,17 @@ use crate::{
 };
 use core::{fmt, num::NonZeroUsize};
 
-pub use types::*;
+pub use kernel_core::memory::mmu::{
+    AccessPermissions, AttributeFields, MMIODescriptor, MemAttributes, TranslationGranule,
+};
+
+/// A page address, in pages of the BSP's translation granule.
+pub type PageAddress<ATYPE> =
+    kernel_core::memory::mmu::PageAddress<ATYPE, { bsp::memory::mmu::KernelGranule::SIZE }>;
+
+/// A region of memory, in pages of the BSP's translation granule.
+pub type MemoryRegion<ATYPE> =
+    kernel_core::memory::mmu::MemoryRegion<ATYPE, { bsp::memory::mmu::KernelGranule::SIZE }>;
 
 
 /// MMU enable errors variants.
@@ -47,9 +56,6 @@ pub mod interface {
     }
 }
 
-/// Describes the characteristics of a translation granule.
-pub struct TranslationGranule<const GRANULE_SIZE: usize>;
-
 /// Describes properties of an address space.
 pub struct AddressSpace<const AS_SIZE: usize>;
 
@@ -95,14 +101,36 @@ unsafe fn kernel_map_at_unchecked(
     Ok(())
 }
 
-/// Try to translate a kernel virtual address to a physical address.
+/// Allocate virtual pages from the MMIO remap reservation and map the physical region there.
 ///
-/// Will only succeed if there exists a valid mapping for the input address.
-fn try_kernel_virt_addr_to_phys_addr(
-    virt_addr: Address<Virtual>,
-) -> Result<Address<Physical>, &'static str> {
-    bsp::memory::mmu::kernel_translation_tables()
-        .read(|tables| tables.try_virt_addr_to_phys_addr(virt_addr))
+/// # why is this function unsafe ???
+///
+/// - Same as `kernel_map_at_unchecked()`, minus the aliasing part.
+unsafe fn kernel_map_remapped(
+    name: &'static str,
+    phys_region: &MemoryRegion<Physical>,
+    mem_attributes: MemAttributes,
+) -> Result<MemoryRegion<Virtual>, &'static str> {
+    let num_pages = match NonZeroUsize::new(phys_region.num_pages()) {
+        None => return Err("Requested 0 pages"),
+        Some(x) => x,
+    };
+
+    let virt_region =
+        page_alloc::kernel_mmio_va_allocator().lock(|allocator| allocator.alloc(num_pages))?;
+
+    kernel_map_at_unchecked(
+        name,
+        &virt_region,
+        phys_region,
+        &AttributeFields {
+            mem_attributes,
+            acc_perms: AccessPermissions::ReadWrite,
+            execute_never: true,
+        },
+    )?;
+
+    Ok(virt_region)
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -118,23 +146,6 @@ impl fmt::Display for MMUEnableError {
     }
 }
 
-impl<const GRANULE_SIZE: usize> TranslationGranule<GRANULE_SIZE> {
-    /// The granule's size.
-    pub const SIZE: usize = Self::size_checked();
-
-    /// The granule's mask.
-    pub const MASK: usize = Self::SIZE - 1;
-
-    /// The granule's shift, aka log2(size).
-    pub const SHIFT: usize = Self::SIZE.trailing_zeros() as usize;
-
-    const fn size_checked() -> usize {
-        assert!(GRANULE_SIZE.is_power_of_two());
-
-        GRANULE_SIZE
-    }
-}
-
 impl<const AS_SIZE: usize> AddressSpace<AS_SIZE> {
     /// The address space size.
     pub const SIZE: usize = Self::size_checked();
@@ -182,7 +193,9 @@ pub unsafe fn kernel_map_mmio(
     mmio_descriptor: &MMIODescriptor,
 ) -> Result<Address<Virtual>, &'static str> {
     let phys_region = MemoryRegion::from(*mmio_descriptor);
-    let offset_into_start_page = mmio_descriptor.start_addr().offset_into_page();
+    let offset_into_start_page = mmio_descriptor
+        .start_addr()
+        .offset_into(bsp::memory::mmu::KernelGranule::SIZE);
 
     // Check if an identical region has been mapped for another driver. If so, reuse it.
     let virt_addr = if let Some(addr) =
@@ -191,31 +204,56 @@ pub unsafe fn kernel_map_mmio(
         addr
         // Otherwise, allocate a new region and map it.
     } else {
-        let num_pages = match NonZeroUsize::new(phys_region.num_pages()) {
-            None => return Err("Requested 0 pages"),
-            Some(x) => x,
-        };
-
-        let virt_region =
-            page_alloc::kernel_mmio_va_allocator().lock(|allocator| allocator.alloc(num_pages))?;
-
-        kernel_map_at_unchecked(
-            name,
-            &virt_region,
-            &phys_region,
-            &AttributeFields {
-                mem_attributes: MemAttributes::Device,
-                acc_perms: AccessPermissions::ReadWrite,
-                execute_never: true,
-            },
-        )?;
-
-        virt_region.start_addr()
+        kernel_map_remapped(name, &phys_region, MemAttributes::Device)?.start_addr()
     };
 
     Ok(virt_addr + offset_into_start_page)
 }
 
+/// Write-combining remapping in the kernel translation tables.
+///
+/// For memory that the CPU mostly writes to and that is read by other bus masters, e.g.
+/// framebuffers. Unlike MMIO mappings, identical regions are not shared.
+///
+/// # why is this function unsafe ???
+///
+/// - Same as `kernel_map_mmio()`.
+pub unsafe fn kernel_map_write_combining(
+    name: &'static str,
+    descriptor: &MMIODescriptor,
+) -> Result<Address<Virtual>, &'static str> {
+    let phys_region = MemoryRegion::from(*descriptor);
+    let offset_into_start_page = descriptor
+        .start_addr()
+        .offset_into(bsp::memory::mmu::KernelGranule::SIZE);
+
+    let virt_region = kernel_map_remapped(name, &phys_region, MemAttributes::WriteCombining)?;
+
+    Ok(virt_region.start_addr() + offset_into_start_page)
+}
+
+/// Cacheable remapping of RAM in the kernel translation tables.
+///
+/// For RAM outside of the kernel's own regions, e.g. data that the firmware left behind. The
+/// mapping allows unaligned accesses, unlike MMIO mappings.
+///
+/// # why is this function unsafe ???
+///
+/// - Same as `kernel_map_mmio()`.
+pub unsafe fn kernel_map_ram(
+    name: &'static str,
+    descriptor: &MMIODescriptor,
+) -> Result<Address<Virtual>, &'static str> {
+    let phys_region = MemoryRegion::from(*descriptor);
+    let offset_into_start_page = descriptor
+        .start_addr()
+        .offset_into(bsp::memory::mmu::KernelGranule::SIZE);
+
+    let virt_region = kernel_map_remapped(name, &phys_region, MemAttributes::CacheableDRAM)?;
+
+    Ok(virt_region.start_addr() + offset_into_start_page)
+}
+
 /// Try to translate a kernel virtual page address to a physical page address.
 ///
 /// Will only succeed if there exists a valid mapping for the input page.
@@ -226,6 +264,16 @@ pub fn try_kernel_virt_page_addr_to_phys_page_addr(
         .read(|tables| tables.try_virt_page_addr_to_phys_page_addr(virt_page_addr))
 }
 
+/// Try to translate a kernel virtual address to a physical address.
+///
+/// Will only succeed if there exists a valid mapping for the input address.
+pub fn try_kernel_virt_addr_to_phys_addr(
+    virt_addr: Address<Virtual>,
+) -> Result<Address<Physical>, &'static str> {
+    bsp::memory::mmu::kernel_translation_tables()
+        .read(|tables| tables.try_virt_addr_to_phys_addr(virt_addr))
+}
+
 /// Try to get the attributes of a kernel page.
 ///
 /// Will only succeed if there exists a valid mapping for the input page.
@@ -1,157 +1,88 @@
 //! A record of mapped pages.
+//!
+//! The record itself is implemented in the `kernel-core` library.
 
 use super::{
     AccessPermissions, Address, AttributeFields, MMIODescriptor, MemAttributes, MemoryRegion,
     Physical, Virtual,
 };
 use crate::{bsp, common, info, synchronization, synchronization::InitStateLock};
-use alloc::{vec, vec::Vec};
+use kernel_core::memory::mmu::mapping_record;
 
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
 
-/// Type describing a virtual memory mapping.
-#[allow(missing_docs)]
-struct MappingRecordEntry {
-    pub users: Vec<&'static str>,
-    pub phys_start_addr: Address<Physical>,
-    pub virt_start_addr: Address<Virtual>,
-    pub num_pages: usize,
-    pub attribute_fields: AttributeFields,
-}
-
-struct MappingRecord {
-    inner: Vec<MappingRecordEntry>,
-}
-
-
-static KERNEL_MAPPING_RECORD: InitStateLock<MappingRecord> = InitStateLock::new(MappingRecord::new());
-
-
-impl MappingRecordEntry {
-    pub fn new(
-        name: &'static str,
-        virt_region: &MemoryRegion<Virtual>,
-        phys_region: &MemoryRegion<Physical>,
-        attr: &AttributeFields,
-    ) -> Self {
-        Self {
-            users: vec![name],
-            phys_start_addr: phys_region.start_addr(),
-            virt_start_addr: virt_region.start_addr(),
-            num_pages: phys_region.num_pages(),
-            attribute_fields: *attr,
-        }
-    }
-
-    pub fn add_user(&mut self, user: &'static str) {
-        self.users.push(user);
-    }
-}
+type MappingRecord = mapping_record::MappingRecord<{ bsp::memory::mmu::KernelGranule::SIZE }>;
 
-impl MappingRecord {
-    pub const fn new() -> Self {
-        Self { inner: Vec::new() }
-    }
-
-    fn sort(&mut self) {
-        if !self.inner.is_sorted_by_key(|item| item.virt_start_addr) {
-            self.inner.sort_unstable_by_key(|item| item.virt_start_addr)
-        }
-    }
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
 
-    fn find_duplicate(
-        &mut self,
-        phys_region: &MemoryRegion<Physical>,
-    ) -> Option<&mut MappingRecordEntry> {
-        self.inner
-            .iter_mut()
-            .filter(|x| x.attribute_fields.mem_attributes == MemAttributes::Device)
-            .find(|x| {
-                if x.phys_start_addr != phys_region.start_addr() {
-                    return false;
-                }
-
-                if x.num_pages != phys_region.num_pages() {
-                    return false;
-                }
-
-                true
-            })
-    }
+static KERNEL_MAPPING_RECORD: InitStateLock<MappingRecord> =
+    InitStateLock::new(MappingRecord::new());
 
-    pub fn add(
-        &mut self,
-        name: &'static str,
-        virt_region: &MemoryRegion<Virtual>,
-        phys_region: &MemoryRegion<Physical>,
-        attr: &AttributeFields,
-    ) {
-        self.inner.push(MappingRecordEntry::new(
-            name,
-            virt_region,
-            phys_region,
-            attr,
-        ));
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
 
-        self.sort();
-    }
+fn print(mr: &MappingRecord) {
+    info!("      -------------------------------------------------------------------------------------------------------------------------------------------");
+    info!(
+        "      {:^44}     {:^30}   {:^7}   {:^9}   {:^35}",
+        "Virtual", "Physical", "Size", "Attr", "Entity"
+    );
+    info!("      -------------------------------------------------------------------------------------------------------------------------------------------");
+
+    for i in mr.iter() {
+        let size = i.size();
+        let virt_start = i.virt_start_addr;
+        let virt_end_inclusive = virt_start + (size - 1);
+        let phys_start = i.phys_start_addr;
+        let phys_end_inclusive = phys_start + (size - 1);
+
+        let (size, unit) = common::size_human_readable_ceil(size);
+
+        let attr = match i.attribute_fields.mem_attributes {
+            MemAttributes::CacheableDRAM => "C",
+            MemAttributes::Device => "Dev",
+            MemAttributes::WriteCombining => "WC",
+        };
+
+        let acc_p = match i.attribute_fields.acc_perms {
+            AccessPermissions::ReadOnly => "RO",
+            AccessPermissions::ReadWrite => "RW",
+        };
+
+        let xn = if i.attribute_fields.execute_never {
+            "XN"
+        } else {
+            "X"
+        };
 
-    pub fn print(&self) {
-        info!("      -------------------------------------------------------------------------------------------------------------------------------------------");
         info!(
-            "      {:^44}     {:^30}   {:^7}   {:^9}   {:^35}",
-            "Virtual", "Physical", "Size", "Attr", "Entity"
+            "      {}..{} --> {}..{} | {:>3} {} | {:<3} {} {:<2} | {}",
+            virt_start,
+            virt_end_inclusive,
+            phys_start,
+            phys_end_inclusive,
+            size,
+            unit,
+            attr,
+            acc_p,
+            xn,
+            i.users[0]
         );
-        info!("      -------------------------------------------------------------------------------------------------------------------------------------------");
-
-        for i in self.inner.iter() {
-            let size = i.num_pages * bsp::memory::mmu::KernelGranule::SIZE;
-            let virt_start = i.virt_start_addr;
-            let virt_end_inclusive = virt_start + (size - 1);
-            let phys_start = i.phys_start_addr;
-            let phys_end_inclusive = phys_start + (size - 1);
-
-            let (size, unit) = common::size_human_readable_ceil(size);
-
-            let attr = match i.attribute_fields.mem_attributes {
-                MemAttributes::CacheableDRAM => "C",
-                MemAttributes::Device => "Dev",
-            };
-
-            let acc_p = match i.attribute_fields.acc_perms {
-                AccessPermissions::ReadOnly => "RO",
-                AccessPermissions::ReadWrite => "RW",
-            };
-
-            let xn = if i.attribute_fields.execute_never {
-                "XN"
-            } else {
-                "X"
-            };
 
+        for k in &i.users[1..] {
             info!(
-                "      {}..{} --> {}..{} | {:>3} {} | {:<3} {} {:<2} | {}",
-                virt_start,
-                virt_end_inclusive,
-                phys_start,
-                phys_end_inclusive,
-                size,
-                unit,
-                attr,
-                acc_p,
-                xn,
-                i.users[0]
+                "                                                                                                            | {}",
+                k
             );
-
-            for k in &i.users[1..] {
-                info!(
-                        "                                                                                                            | {}",
-                        k
-                    );
-            }
         }
-
-        info!("      -------------------------------------------------------------------------------------------------------------------------------------------");
     }
+
+    info!("      -------------------------------------------------------------------------------------------------------------------------------------------");
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -175,16 +106,10 @@ pub fn kernel_find_and_insert_mmio_duplicate(
 ) -> Option<Address<Virtual>> {
     let phys_region: MemoryRegion<Physical> = (*mmio_descriptor).into();
 
-    KERNEL_MAPPING_RECORD.write(|mr| {
-        let dup = mr.find_duplicate(&phys_region)?;
-
-        dup.add_user(new_user);
-
-        Some(dup.virt_start_addr)
-    })
+    KERNEL_MAPPING_RECORD.write(|mr| mr.find_and_insert_mmio_duplicate(&phys_region, new_user))
 }
 
 /// Human-readable print of all recorded kernel mappings.
 pub fn kernel_print() {
-    KERNEL_MAPPING_RECORD.read(|mr| mr.print());
-}
\ No newline at end of file
+    KERNEL_MAPPING_RECORD.read(print);
+}
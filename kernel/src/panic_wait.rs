@@ -0,0 +1,220 @@
+//! The panic handler, and what happens after a panic.
+//!
+//! After the panic message is printed and the console is flushed, the panic policy decides what
+//! comes next:
+//!
+//! - `halt`: Wait forever. The default. An armed watchdog still resets the board.
+//! - `reboot`: Reboot through the watchdog, after a few seconds.
+//! - `monitor`: Drop into a minimal serial monitor that polls the console.
+//! - `exit`: Exit QEMU with a failure status. Needs semihosting, and is the default with it.
+//!
+//! The policy is selected at build time with the `PANIC` option of the Makefile, and at boot with
+//! the `panic` boot parameter, e.g. `panic=monitor`. A number, e.g. `panic=10`, reboots after that
+//! many seconds, and `panic=0` halts.
+
+mod monitor;
+
+use crate::{
+    backtrace, console, cpu, early_param, exception, println,
+    synchronization::{interface::ReadWriteEx, InitStateLock},
+    time, watchdog,
+};
+use core::{
+    panic::PanicInfo,
+    sync::atomic::{AtomicU8, Ordering},
+    time::Duration,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// What happens after a panic.
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Policy {
+    Halt,
+    Reboot { delay_secs: u64 },
+    Monitor,
+    Exit,
+}
+
+/// The delay of `panic=reboot`, to give a chance to read the panic message.
+const DEFAULT_REBOOT_DELAY_SECS: u64 = 10;
+
+const DEFAULT_POLICY: Policy = if cfg!(feature = "panic_monitor") {
+    Policy::Monitor
+} else if cfg!(feature = "panic_reboot") {
+    Policy::Reboot {
+        delay_secs: DEFAULT_REBOOT_DELAY_SECS,
+    }
+} else if cfg!(feature = "semihosting") {
+    Policy::Exit
+} else {
+    Policy::Halt
+};
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static POLICY: InitStateLock<Policy> = InitStateLock::new(DEFAULT_POLICY);
+
+/// Number of panics so far. More than one means that the panic handling panicked itself.
+///
+/// # Note
+///
+/// Using atomics here relieves us from needing to use `unsafe` for the static variable.
+///
+/// On `AArch64`, which is the only implemented architecture at the time of writing this,
+/// [`AtomicU8::load`] and [`AtomicU8::store`] are lowered to ordinary load and store instructions.
+/// They are therefore safe to use even with MMU + caching deactivated. Read-modify-write
+/// operations are not.
+static PANIC_COUNT: AtomicU8 = AtomicU8::new(0);
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl core::str::FromStr for Policy {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "halt" | "0" => Ok(Self::Halt),
+            "reboot" => Ok(Self::Reboot {
+                delay_secs: DEFAULT_REBOOT_DELAY_SECS,
+            }),
+            "monitor" => Ok(Self::Monitor),
+            "exit" if cfg!(feature = "semihosting") => Ok(Self::Exit),
+            "exit" => Err("Exiting needs semihosting"),
+            _ => s
+                .parse()
+                .map(|delay_secs| Self::Reboot { delay_secs })
+                .map_err(|_| "Expected halt, reboot, monitor, exit or a number of seconds"),
+        }
+    }
+}
+
+impl crate::cmdline::ParamValue for Policy {
+    fn parse(value: Option<&'static str>) -> Result<Self, &'static str> {
+        value.ok_or("Missing value")?.parse()
+    }
+}
+
+early_param!(
+    "panic",
+    "After a panic: halt, reboot, monitor, exit, or reboot after a number of seconds",
+    |policy: Policy| {
+        POLICY.write(|p| *p = policy);
+        Ok(())
+    }
+);
+
+/// Stop everything that could interfere with the panic output or the policy.
+fn stop_the_world() {
+    exception::asynchronous::local_irq_mask();
+
+    // Give the panic output a full timeout of an armed watchdog.
+    watchdog::pet().ok();
+
+    // Only the boot core runs kernel code. The other cores stay parked in `_start`, so there is
+    // nothing to stop on them.
+}
+
+fn reboot_after(delay_secs: u64) -> ! {
+    if delay_secs > 0 {
+        println!("Rebooting in {} s", delay_secs);
+        console::console().flush();
+        time::time_manager().spin_for(Duration::from_secs(delay_secs));
+    }
+
+    watchdog::reboot()
+}
+
+fn halt() -> ! {
+    // Nothing pets the watchdog anymore.
+    if let Some(left) = watchdog::time_left() {
+        println!("The watchdog resets the board in {} s", left.as_secs());
+        console::console().flush();
+    }
+
+    cpu::wait_forever()
+}
+
+/// The point of exit for `libkernel`. Carries out the panic policy.
+///
+/// It is linked weakly, so that the integration tests can overload its standard behavior.
+#[linkage = "weak"]
+#[no_mangle]
+fn _panic_exit() -> ! {
+    let mut policy = POLICY.read(|p| *p);
+
+    // The monitor runs a fair amount of code. If it panicked, do not enter it again.
+    if policy == Policy::Monitor && PANIC_COUNT.load(Ordering::Relaxed) > 1 {
+        policy = Policy::Halt;
+    }
+
+    match policy {
+        Policy::Halt => halt(),
+        Policy::Reboot { delay_secs } => reboot_after(delay_secs),
+        Policy::Monitor => monitor::run(),
+        Policy::Exit => {
+            #[cfg(feature = "semihosting")]
+            crate::semihosting::exit_failure();
+
+            #[cfg(not(feature = "semihosting"))]
+            halt()
+        }
+    }
+}
+
+/// Count the panic, and deal with panics during the panic handling.
+///
+/// The first nested panic is reported in a single line, before the policy is carried out. If even
+/// that panics, the core halts.
+fn panic_prevent_reenter() {
+    let count = PANIC_COUNT.load(Ordering::Relaxed);
+    PANIC_COUNT.store(count.saturating_add(1), Ordering::Relaxed);
+
+    match count {
+        0 => (),
+        1 => {
+            println!("\nKernel panic while handling a panic");
+            console::console().flush();
+
+            _panic_exit()
+        }
+        _ => cpu::wait_forever(),
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    // Protect against panic infinite loops if any of the following code panics itself.
+    panic_prevent_reenter();
+
+    stop_the_world();
+
+    let timestamp = time::time_manager().uptime();
+    let (location, line, column) = match info.location() {
+        Some(loc) => (loc.file(), loc.line(), loc.column()),
+        _ => ("???", 0, 0),
+    };
+
+    println!(
+        "[  {:>3}.{:06}] Kernel panic!\n\n\
+    Panic location:\n      File '{}', line {}, column {}\n\n\
+    {}\n\n\
+    {}",
+        timestamp.as_secs(),
+        timestamp.subsec_micros(),
+        location,
+        line,
+        column,
+        info.message().unwrap_or(&format_args!("")),
+        backtrace::Backtrace
+    );
+    console::console().flush();
+
+    _panic_exit()
+}
@@ -0,0 +1,152 @@
+//! A minimal serial monitor for after a panic.
+//!
+//! It polls the console with interrupts masked and does not allocate, because neither the
+//! interrupt handling nor the heap can be trusted after a panic.
+
+use crate::{
+    console, cpu, log,
+    memory::{self, Address, Virtual},
+    print, println, watchdog,
+};
+use core::mem::size_of;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const MAX_LINE_LEN: usize = 64;
+
+/// Upper limit for the number of words printed by a single `peek`.
+const MAX_PEEK_COUNT: usize = 256;
+
+const PROMPT: &str = "panic> ";
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Read a line into `buf`, with echo and backspace. Returns the length.
+fn read_line(buf: &mut [u8; MAX_LINE_LEN]) -> usize {
+    let con = console::console();
+    let mut len = 0;
+
+    loop {
+        match con.read_char() {
+            '\r' | '\n' => {
+                println!();
+                return len;
+            }
+            '\x08' | '\x7f' if len > 0 => {
+                len -= 1;
+                print!("\x08 \x08");
+            }
+            c if (c.is_ascii_graphic() || c == ' ') && len < buf.len() => {
+                buf[len] = c as u8;
+                len += 1;
+                print!("{}", c);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Parse a decimal number, or a hexadecimal one with a `0x` prefix.
+fn parse_number(s: &str) -> Result<usize, &'static str> {
+    let (digits, radix) = match s.strip_prefix("0x") {
+        Some(hex) => (hex, 16),
+        None => (s, 10),
+    };
+
+    usize::from_str_radix(digits, radix).map_err(|_| "Invalid number")
+}
+
+fn cmd_dmesg() {
+    for record in log::Reader::new() {
+        println!("{}", record);
+    }
+}
+
+fn cmd_peek(addr: &str, count: Option<&str>) -> Result<(), &'static str> {
+    let addr = parse_number(addr)?;
+    let count = count.map_or(Ok(1), parse_number)?;
+
+    if count > MAX_PEEK_COUNT {
+        return Err("Count too big");
+    }
+    if addr % size_of::<u64>() != 0 {
+        return Err("Address is not 8 byte aligned");
+    }
+
+    for i in 0..count {
+        let addr = i
+            .checked_mul(size_of::<u64>())
+            .and_then(|offset| addr.checked_add(offset))
+            .map(Address::<Virtual>::new)
+            .ok_or("Address overflow")?;
+        memory::mmu::try_kernel_virt_addr_to_phys_addr(addr)?;
+
+        let value = unsafe { core::ptr::read_volatile(addr.as_usize() as *const u64) };
+        println!("{}: {:#018x}", addr, value);
+    }
+
+    Ok(())
+}
+
+fn print_help() {
+    println!("Commands:");
+    println!("      dmesg                   Print the kernel log");
+    println!("      peek <addr> [count]     Print 64 bit words of memory");
+    println!("      reboot                  Reset the board");
+    println!("      poweroff                Power off the board");
+    println!("      halt                    Stop the core");
+}
+
+fn execute(line: &str) -> Result<(), &'static str> {
+    let mut words = line.split_whitespace();
+    let Some(command) = words.next() else {
+        return Ok(());
+    };
+    let args = (words.next(), words.next(), words.next());
+
+    match (command, args) {
+        ("help", (None, ..)) => print_help(),
+        ("dmesg", (None, ..)) => cmd_dmesg(),
+        ("peek", (Some(addr), count, None)) => cmd_peek(addr, count)?,
+        ("reboot", (None, ..)) => watchdog::reboot(),
+        ("poweroff", (None, ..)) => watchdog::poweroff(),
+        ("halt", (None, ..)) => {
+            println!("Halted");
+            console::console().flush();
+            cpu::wait_forever()
+        }
+        ("help" | "dmesg" | "peek" | "reboot" | "poweroff" | "halt", _) => {
+            return Err("Wrong number of arguments")
+        }
+        _ => return Err("Unknown command, try help"),
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Run the monitor. Does not return.
+pub fn run() -> ! {
+    let mut buf = [0; MAX_LINE_LEN];
+
+    println!("\nPanic monitor, type help for a list of commands");
+    console::console().clear_rx();
+
+    loop {
+        print!("{}", PROMPT);
+        let len = read_line(&mut buf);
+
+        // Only ASCII gets into the buffer.
+        let line = core::str::from_utf8(&buf[..len]).unwrap_or("");
+        if let Err(x) = execute(line) {
+            println!("Error: {}", x);
+        }
+    }
+}
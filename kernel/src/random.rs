@@ -0,0 +1,228 @@
+//! Random numbers.
+//!
+//! Entropy is collected in a pool, which hands out output of a ChaCha20 based generator. The pool
+//! is seeded from timer jitter during kernel init, and from a hardware random number generator
+//! once its driver registers. It reseeds from the hardware regularly. The output is also readable
+//! from `/dev/random`, and writing there mixes the data into the pool.
+
+use crate::{
+    cpu, info, print, println, shell,
+    synchronization::{
+        interface::{Mutex, ReadWriteEx},
+        IRQSafeNullLock, InitStateLock,
+    },
+    time, vfs, warn,
+};
+use kernel_core::chacha::{self, ChaChaRng};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Number of timer samples for the jitter entropy.
+const JITTER_SAMPLES: usize = 256;
+
+/// Output after which the pool reseeds from the hardware, in bytes.
+const RESEED_INTERVAL: usize = 1024 * 1024;
+
+/// Output per lock of the pool, so that long requests do not keep IRQs masked for long.
+const MAX_CHUNK_SIZE: usize = 256;
+
+/// Upper limit for the number of bytes printed by a single `random`.
+const MAX_RANDOM_BYTES: usize = 256;
+
+struct Pool {
+    rng: ChaChaRng,
+    output_since_reseed: usize,
+}
+
+struct EntropySourceEntry {
+    name: &'static str,
+    source: &'static (dyn interface::EntropySource + Sync),
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Random number interfaces.
+pub mod interface {
+    /// A source of entropy, e.g. a hardware random number generator.
+    pub trait EntropySource {
+        /// Fill `buf` with random bytes.
+        fn fill(&self, buf: &mut [u8]) -> Result<(), &'static str>;
+    }
+}
+
+/// The `/dev/random` device file.
+pub struct RandomFile;
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static POOL: IRQSafeNullLock<Pool> = IRQSafeNullLock::new(Pool {
+    rng: ChaChaRng::new(),
+    output_since_reseed: 0,
+});
+
+static ENTROPY_SOURCE: InitStateLock<Option<EntropySourceEntry>> = InitStateLock::new(None);
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Sample the timer around work of varying length. How long the work takes depends on caches,
+/// the pipeline and the bus, which makes the low bits of the durations hard to predict.
+fn timer_jitter() -> [u8; JITTER_SAMPLES] {
+    let now = || time::time_manager().uptime().as_nanos() as u64;
+
+    let mut samples = [0; JITTER_SAMPLES];
+    let mut prev = now();
+    for sample in &mut samples {
+        for _ in 0..(prev & 0xff) + 64 {
+            cpu::nop();
+        }
+
+        let current = now();
+        *sample = current.wrapping_sub(prev) as u8;
+        prev = current;
+    }
+
+    samples
+}
+
+/// Mix fresh output of the entropy source into the pool.
+fn reseed() -> Result<(), &'static str> {
+    let mut seed = [0; chacha::KEY_SIZE];
+
+    ENTROPY_SOURCE.read(|entry| match entry {
+        Some(entry) => entry.source.fill(&mut seed),
+        None => Err("No entropy source"),
+    })?;
+
+    POOL.lock(|pool| {
+        pool.rng.mix(&seed);
+        pool.output_since_reseed = 0;
+    });
+
+    Ok(())
+}
+
+fn cmd_random(args: &[&str]) -> Result<(), &'static str> {
+    let len = match args {
+        [] => 16,
+        [len] => shell::parse_number(len)?,
+        _ => return Err("Wrong number of arguments"),
+    };
+
+    if len > MAX_RANDOM_BYTES {
+        return Err("Count too big");
+    }
+
+    let mut buf = [0; MAX_RANDOM_BYTES];
+    fill_bytes(&mut buf[..len]);
+
+    for line in buf[..len].chunks(16) {
+        for byte in line {
+            print!("{:02x} ", byte);
+        }
+        println!();
+    }
+    println!(
+        "Entropy source: {}",
+        entropy_source_name().unwrap_or("timer jitter only")
+    );
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Seed the pool from timer jitter.
+pub fn init() {
+    add_entropy(&timer_jitter());
+
+    if let Err(x) = shell::register_command(shell::Command::new(
+        "random",
+        "random [bytes]",
+        "Print bytes from the kernel random pool",
+        cmd_random,
+    )) {
+        panic!("Error registering the random shell command: {}", x);
+    }
+}
+
+/// Register a source of entropy, and seed the pool from it.
+///
+/// Only possible during kernel init.
+pub fn register_entropy_source(
+    name: &'static str,
+    source: &'static (dyn interface::EntropySource + Sync),
+) -> Result<(), &'static str> {
+    if ENTROPY_SOURCE.read(|entry| entry.is_some()) {
+        return Err("Entropy source already registered");
+    }
+    ENTROPY_SOURCE.write(|entry| *entry = Some(EntropySourceEntry { name, source }));
+
+    reseed()?;
+    info!("Random pool seeded from {}", name);
+
+    Ok(())
+}
+
+/// Mix data into the pool.
+pub fn add_entropy(data: &[u8]) {
+    POOL.lock(|pool| pool.rng.mix(data));
+}
+
+/// Fill `buf` with random bytes.
+pub fn fill_bytes(buf: &mut [u8]) {
+    for chunk in buf.chunks_mut(MAX_CHUNK_SIZE) {
+        if POOL.lock(|pool| pool.output_since_reseed >= RESEED_INTERVAL) {
+            if let Err(x) = reseed() {
+                // Without a source, keep going on what the pool has.
+                POOL.lock(|pool| pool.output_since_reseed = 0);
+                warn!("Could not reseed the random pool: {}", x);
+            }
+        }
+
+        POOL.lock(|pool| {
+            pool.rng.fill_bytes(chunk);
+            pool.output_since_reseed += chunk.len();
+        });
+    }
+}
+
+/// A random `u64`.
+pub fn random_u64() -> u64 {
+    let mut bytes = [0; 8];
+    fill_bytes(&mut bytes);
+
+    u64::from_le_bytes(bytes)
+}
+
+/// The name of the registered entropy source, if any.
+pub fn entropy_source_name() -> Option<&'static str> {
+    ENTROPY_SOURCE.read(|entry| entry.as_ref().map(|entry| entry.name))
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+
+impl vfs::interface::DeviceFile for RandomFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, &'static str> {
+        fill_bytes(buf);
+
+        Ok(buf.len())
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize, &'static str> {
+        add_entropy(data);
+
+        Ok(data.len())
+    }
+}
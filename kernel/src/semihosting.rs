@@ -0,0 +1,216 @@
+//! ARM semihosting.
+//!
+//! Lets the kernel use services of the host when running under QEMU with `-semihosting`, or under a
+//! debugger that supports it: A console, access to host files, and exiting QEMU with a status code.
+//!
+//! On hardware without an attached debugger, semihosting calls end in an exception. Therefore, this
+//! module is only built with the `semihosting` feature.
+
+#[path = "aarch64/semihosting.rs"]
+mod arch_semihosting;
+
+mod console;
+
+use crate::{console as kernel_console, log};
+use alloc::vec::Vec;
+
+pub use console::SEMIHOSTING_CONSOLE;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Semihosting operation numbers.
+mod op {
+    pub const SYS_OPEN: u32 = 0x01;
+    pub const SYS_CLOSE: u32 = 0x02;
+    pub const SYS_WRITEC: u32 = 0x03;
+    pub const SYS_WRITE0: u32 = 0x04;
+    pub const SYS_WRITE: u32 = 0x05;
+    pub const SYS_READ: u32 = 0x06;
+    pub const SYS_READC: u32 = 0x07;
+    pub const SYS_SEEK: u32 = 0x0A;
+    pub const SYS_FLEN: u32 = 0x0C;
+    pub const SYS_EXIT: u32 = 0x18;
+}
+
+/// Reason code of `SYS_EXIT` for a regular exit of the application.
+const ADP_STOPPED_APPLICATION_EXIT: usize = 0x2_0026;
+
+/// Host paths are copied to a buffer of this size to add the terminating NUL.
+const MAX_PATH_LEN: usize = 255;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// How a host file is opened. All modes are binary.
+#[derive(Copy, Clone)]
+pub enum OpenMode {
+    /// Read only.
+    Read,
+
+    /// Write only. The file is created, or truncated if it exists.
+    Write,
+
+    /// Write only, at the end of the file. The file is created if it does not exist.
+    Append,
+}
+
+/// A file on the host.
+///
+/// Closed when dropped.
+pub struct HostFile {
+    handle: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn call(op: u32, param: usize) -> usize {
+    unsafe { arch_semihosting::call(op, param) }
+}
+
+/// Call an operation that takes a parameter block.
+fn call_with_block(op: u32, block: &[usize]) -> usize {
+    call(op, block.as_ptr() as usize)
+}
+
+impl OpenMode {
+    /// The mode number, as in the index into the `fopen()` mode strings
+    /// `r, rb, r+, r+b, w, wb, w+, w+b, a, ab, a+, a+b`.
+    fn number(self) -> usize {
+        match self {
+            Self::Read => 1,
+            Self::Write => 5,
+            Self::Append => 9,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl HostFile {
+    /// Open a file on the host. Relative paths are relative to the working directory of QEMU.
+    ///
+    /// The special path `:tt` opens the host's console.
+    pub fn open(path: &str, mode: OpenMode) -> Result<Self, &'static str> {
+        if path.len() > MAX_PATH_LEN {
+            return Err("Path too long");
+        }
+
+        let mut c_path = [0u8; MAX_PATH_LEN + 1];
+        c_path[..path.len()].copy_from_slice(path.as_bytes());
+
+        let handle = call_with_block(
+            op::SYS_OPEN,
+            &[c_path.as_ptr() as usize, mode.number(), path.len()],
+        );
+        if handle as isize == -1 {
+            return Err("Host could not open the file");
+        }
+
+        Ok(Self { handle })
+    }
+
+    /// Read into `buf`, returning the number of bytes read. Zero means end of file.
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize, &'static str> {
+        let not_read = call_with_block(
+            op::SYS_READ,
+            &[self.handle, buf.as_mut_ptr() as usize, buf.len()],
+        );
+        if not_read > buf.len() {
+            return Err("Host could not read the file");
+        }
+
+        Ok(buf.len() - not_read)
+    }
+
+    /// Write all of `buf`.
+    pub fn write(&self, buf: &[u8]) -> Result<(), &'static str> {
+        let not_written = call_with_block(
+            op::SYS_WRITE,
+            &[self.handle, buf.as_ptr() as usize, buf.len()],
+        );
+        if not_written != 0 {
+            return Err("Host could not write the file");
+        }
+
+        Ok(())
+    }
+
+    /// Move to an absolute position in the file.
+    pub fn seek(&self, pos: usize) -> Result<(), &'static str> {
+        if call_with_block(op::SYS_SEEK, &[self.handle, pos]) != 0 {
+            return Err("Host could not seek in the file");
+        }
+
+        Ok(())
+    }
+
+    /// The size of the file in bytes.
+    pub fn size(&self) -> Result<usize, &'static str> {
+        let len = call_with_block(op::SYS_FLEN, &[self.handle]);
+        if len as isize == -1 {
+            return Err("Host could not determine the file length");
+        }
+
+        Ok(len)
+    }
+
+    /// Read the whole file.
+    pub fn read_to_end(&self) -> Result<Vec<u8>, &'static str> {
+        let mut data = alloc::vec![0; self.size()?];
+
+        let mut filled = 0;
+        while filled < data.len() {
+            match self.read(&mut data[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        data.truncate(filled);
+
+        Ok(data)
+    }
+}
+
+impl Drop for HostFile {
+    fn drop(&mut self) {
+        call_with_block(op::SYS_CLOSE, &[self.handle]);
+    }
+}
+
+/// Register the semihosting console with the kernel's console multiplexer.
+pub fn register_console() -> Result<(), &'static str> {
+    kernel_console::register_console("Semihosting", &SEMIHOSTING_CONSOLE, Some(log::Level::Trace))
+}
+
+/// Read a whole file from the host.
+pub fn read_file(path: &str) -> Result<Vec<u8>, &'static str> {
+    HostFile::open(path, OpenMode::Read)?.read_to_end()
+}
+
+/// End the program, e.g. exit QEMU, with the given status code.
+pub fn exit(status: u32) -> ! {
+    call_with_block(
+        op::SYS_EXIT,
+        &[ADP_STOPPED_APPLICATION_EXIT, status as usize],
+    );
+
+    // Only reached if the host ignores the request.
+    crate::cpu::wait_forever()
+}
+
+/// Exit with status code 0.
+pub fn exit_success() -> ! {
+    exit(0)
+}
+
+/// Exit with status code 1.
+pub fn exit_failure() -> ! {
+    exit(1)
+}
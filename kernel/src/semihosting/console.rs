@@ -0,0 +1,114 @@
+//! Semihosting console.
+
+use super::{call, op};
+use crate::console::interface;
+use core::fmt;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Output is passed to the host in chunks of this size.
+const CHUNK_SIZE: usize = 128;
+
+/// Collects output into a NUL-terminated chunk for `SYS_WRITE0`.
+struct ChunkWriter {
+    buf: [u8; CHUNK_SIZE + 1],
+    len: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A console on the host's terminal.
+pub struct SemihostingConsole;
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+pub static SEMIHOSTING_CONSOLE: SemihostingConsole = SemihostingConsole {};
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl ChunkWriter {
+    const fn new() -> Self {
+        Self {
+            buf: [0; CHUNK_SIZE + 1],
+            len: 0,
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.len == 0 {
+            return;
+        }
+
+        self.buf[self.len] = 0;
+        call(op::SYS_WRITE0, self.buf.as_ptr() as usize);
+        self.len = 0;
+    }
+}
+
+impl fmt::Write for ChunkWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for b in s.bytes() {
+            // A NUL would end the string early.
+            if b == 0 {
+                continue;
+            }
+
+            if self.len == CHUNK_SIZE {
+                self.flush();
+            }
+            self.buf[self.len] = b;
+            self.len += 1;
+        }
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl interface::Write for SemihostingConsole {
+    fn write_char(&self, c: char) {
+        let mut buf = [0; 4];
+        for b in c.encode_utf8(&mut buf).bytes() {
+            call(op::SYS_WRITEC, &b as *const u8 as usize);
+        }
+    }
+
+    fn write_array(&self, a: &[char]) {
+        for c in a {
+            self.write_char(*c);
+        }
+    }
+
+    fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result {
+        let mut writer = ChunkWriter::new();
+        fmt::Write::write_fmt(&mut writer, args)?;
+        writer.flush();
+
+        Ok(())
+    }
+
+    fn flush(&self) {}
+}
+
+impl interface::Read for SemihostingConsole {
+    fn read_char(&self) -> char {
+        call(op::SYS_READC, 0) as u8 as char
+    }
+
+    fn clear_rx(&self) {}
+}
+
+impl interface::Statistics for SemihostingConsole {}
+impl interface::LineConfiguration for SemihostingConsole {}
+impl interface::All for SemihostingConsole {}
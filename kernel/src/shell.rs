@@ -0,0 +1,146 @@
+//! Kernel monitor shell.
+//!
+//! A line-edited command shell on the system console, with history and tab completion of command
+//! names. Subsystems add their own commands with [`register_command`] during kernel init.
+
+mod builtin_commands;
+mod line_editor;
+
+use crate::{
+    bsp, println,
+    synchronization::{interface::ReadWriteEx, InitStateLock},
+};
+use alloc::vec::Vec;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Signature of a command handler. `args` contains the words following the command name.
+pub type CommandHandler = fn(args: &[&str]) -> Result<(), &'static str>;
+
+/// A shell command.
+pub struct Command {
+    name: &'static str,
+    usage: &'static str,
+    help: &'static str,
+    handler: CommandHandler,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static COMMANDS: InitStateLock<Vec<Command>> = InitStateLock::new(Vec::new());
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+const PROMPT: &str = "kernel> ";
+
+fn find_command(name: &str) -> Option<&'static Command> {
+    COMMANDS.read(|commands| commands.iter().find(|cmd| cmd.name == name))
+}
+
+/// Names of all registered commands that start with `prefix`.
+fn command_names(prefix: &str) -> impl Iterator<Item = &'static str> + '_ {
+    COMMANDS
+        .read(|commands| commands.iter())
+        .map(|cmd| cmd.name)
+        .filter(move |name| name.starts_with(prefix))
+}
+
+fn execute(line: &str) {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let Some((name, args)) = words.split_first() else {
+        return;
+    };
+
+    match find_command(name) {
+        None => println!(
+            "Unknown command '{}'. Type 'help' for a list of commands.",
+            name
+        ),
+        Some(cmd) => {
+            if let Err(x) = (cmd.handler)(args) {
+                println!("{}: {}", cmd.name, x);
+                println!("Usage: {}", cmd.usage);
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Command {
+    /// Create an instance.
+    ///
+    /// `usage` shows the command together with its arguments, e.g. `peek <addr> [count]`.
+    pub const fn new(
+        name: &'static str,
+        usage: &'static str,
+        help: &'static str,
+        handler: CommandHandler,
+    ) -> Self {
+        Self {
+            name,
+            usage,
+            help,
+            handler,
+        }
+    }
+}
+
+/// Register a command with the shell.
+///
+/// Only possible during kernel init.
+pub fn register_command(command: Command) -> Result<(), &'static str> {
+    if find_command(command.name).is_some() {
+        return Err("Command already registered");
+    }
+
+    COMMANDS.write(|commands| commands.push(command));
+
+    Ok(())
+}
+
+/// Parse a decimal number, or a hexadecimal one with a `0x` prefix.
+pub fn parse_number(s: &str) -> Result<usize, &'static str> {
+    let (digits, radix) = match s.strip_prefix("0x") {
+        Some(hex) => (hex, 16),
+        None => (s, 10),
+    };
+
+    usize::from_str_radix(&digits.replace('_', ""), radix).map_err(|_| "Invalid number")
+}
+
+/// Fail if a command that takes no arguments was given some.
+pub fn no_args(args: &[&str]) -> Result<(), &'static str> {
+    if !args.is_empty() {
+        return Err("Unexpected arguments");
+    }
+
+    Ok(())
+}
+
+/// Register the commands of the shell itself and those of the BSP.
+///
+/// The other subsystems register their commands during their own init.
+pub fn init() -> Result<(), &'static str> {
+    builtin_commands::register()?;
+    bsp::shell_commands::register()
+}
+
+/// Read and execute commands from the console, forever.
+pub fn run() -> ! {
+    let mut editor = line_editor::LineEditor::new();
+
+    loop {
+        let line = editor.read_line(PROMPT);
+
+        execute(&line);
+    }
+}
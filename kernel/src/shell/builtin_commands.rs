@@ -0,0 +1,40 @@
+//! Commands of the shell itself.
+
+use super::{register_command, Command, COMMANDS};
+use crate::{println, synchronization::interface::ReadWriteEx};
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn cmd_help(_args: &[&str]) -> Result<(), &'static str> {
+    COMMANDS.read(|commands| {
+        for cmd in commands {
+            println!("  {:<24} {}", cmd.usage, cmd.help);
+        }
+    });
+
+    Ok(())
+}
+
+/// The shell splits arguments at whitespace, so they are printed with single spaces.
+fn cmd_echo(args: &[&str]) -> Result<(), &'static str> {
+    println!("{}", args.join(" "));
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Register the commands of the shell itself.
+pub fn register() -> Result<(), &'static str> {
+    register_command(Command::new("help", "help", "List all commands", cmd_help))?;
+    register_command(Command::new(
+        "echo",
+        "echo [text...]",
+        "Print the arguments",
+        cmd_echo,
+    ))
+}
@@ -0,0 +1,298 @@
+//! Line editing for the shell.
+//!
+//! Understands the usual VT100 cursor keys, Emacs style control keys, history browsing and tab
+//! completion of command names.
+
+use crate::{console, print, println};
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const MAX_LINE_LEN: usize = 256;
+const HISTORY_SIZE: usize = 32;
+
+#[derive(Copy, Clone)]
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Tab,
+    Cancel,
+    ClearLine,
+    Ignore,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A line editor with a history of previously entered lines.
+pub struct LineEditor {
+    history: VecDeque<String>,
+    line: Vec<char>,
+    cursor: usize,
+
+    /// Index of the history entry being shown, if any.
+    history_pos: Option<usize>,
+
+    /// The line that was being edited before browsing the history.
+    draft: Vec<char>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn read_char() -> char {
+    console::console().read_char()
+}
+
+/// Decode the rest of a `ESC [` or `ESC O` sequence.
+fn read_escape_sequence() -> Key {
+    if !matches!(read_char(), '[' | 'O') {
+        return Key::Ignore;
+    }
+
+    match read_char() {
+        'A' => Key::Up,
+        'B' => Key::Down,
+        'C' => Key::Right,
+        'D' => Key::Left,
+        'H' => Key::Home,
+        'F' => Key::End,
+        // Sequences of the form `ESC [ <number> ~`.
+        first @ '0'..='9' => {
+            let mut number = first.to_digit(10).unwrap();
+
+            loop {
+                match read_char() {
+                    '~' => break,
+                    c @ '0'..='9' => number = number * 10 + c.to_digit(10).unwrap(),
+                    _ => return Key::Ignore,
+                }
+            }
+
+            match number {
+                1 | 7 => Key::Home,
+                3 => Key::Delete,
+                4 | 8 => Key::End,
+                _ => Key::Ignore,
+            }
+        }
+        _ => Key::Ignore,
+    }
+}
+
+fn read_key() -> Key {
+    match read_char() {
+        '\n' | '\r' => Key::Enter,
+        '\x7f' | '\x08' => Key::Backspace,
+        '\t' => Key::Tab,
+        '\x01' => Key::Home,
+        '\x02' => Key::Left,
+        '\x03' => Key::Cancel,
+        '\x04' => Key::Delete,
+        '\x05' => Key::End,
+        '\x06' => Key::Right,
+        '\x0e' => Key::Down,
+        '\x10' => Key::Up,
+        '\x15' => Key::ClearLine,
+        '\x1b' => read_escape_sequence(),
+        c if c == ' ' || c.is_ascii_graphic() => Key::Char(c),
+        _ => Key::Ignore,
+    }
+}
+
+/// Length of the common prefix of two strings.
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.bytes().zip(b.bytes()).take_while(|(x, y)| x == y).count()
+}
+
+impl LineEditor {
+    /// Print the prompt and the line, then put the terminal cursor where the edit cursor is.
+    fn redraw(&self, prompt: &str) {
+        let line: String = self.line.iter().collect();
+        print!("\r{}{}\x1b[K", prompt, line);
+
+        let chars_after_cursor = self.line.len() - self.cursor;
+        if chars_after_cursor > 0 {
+            print!("\x1b[{}D", chars_after_cursor);
+        }
+    }
+
+    fn insert(&mut self, s: &str) {
+        for c in s.chars() {
+            if self.line.len() >= MAX_LINE_LEN {
+                print!("\x07");
+                break;
+            }
+
+            self.line.insert(self.cursor, c);
+            self.cursor += 1;
+        }
+    }
+
+    fn replace_line(&mut self, line: Vec<char>) {
+        self.line = line;
+        self.cursor = self.line.len();
+    }
+
+    fn history_up(&mut self) {
+        let pos = match self.history_pos {
+            None => match self.history.len().checked_sub(1) {
+                None => return,
+                Some(pos) => {
+                    self.draft = core::mem::take(&mut self.line);
+                    pos
+                }
+            },
+            Some(pos) => pos.saturating_sub(1),
+        };
+
+        self.history_pos = Some(pos);
+        self.replace_line(self.history[pos].chars().collect());
+    }
+
+    fn history_down(&mut self) {
+        let Some(pos) = self.history_pos else {
+            return;
+        };
+
+        if pos + 1 < self.history.len() {
+            self.history_pos = Some(pos + 1);
+            self.replace_line(self.history[pos + 1].chars().collect());
+        } else {
+            self.history_pos = None;
+            let draft = core::mem::take(&mut self.draft);
+            self.replace_line(draft);
+        }
+    }
+
+    fn add_to_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.back().map(String::as_str) == Some(line) {
+            return;
+        }
+
+        if self.history.len() == HISTORY_SIZE {
+            self.history.pop_front();
+        }
+        self.history.push_back(String::from(line));
+    }
+
+    /// Complete the command name in front of the cursor.
+    ///
+    /// A unique match is completed fully. Otherwise, the common prefix of all matches is inserted,
+    /// and if there is none, the matches are listed.
+    fn complete(&mut self) {
+        if self.line[..self.cursor].iter().any(|c| *c == ' ') {
+            print!("\x07");
+            return;
+        }
+
+        let prefix: String = self.line[..self.cursor].iter().collect();
+        let matches: Vec<&str> = super::command_names(&prefix).collect();
+
+        match matches.as_slice() {
+            [] => print!("\x07"),
+            [name] => {
+                self.insert(&name[prefix.len()..]);
+                self.insert(" ");
+            }
+            [first, others @ ..] => {
+                let common_len = others.iter().fold(first.len(), |len, name| {
+                    len.min(common_prefix_len(first, name))
+                });
+
+                if common_len > prefix.len() {
+                    self.insert(&first[prefix.len()..common_len]);
+                } else {
+                    println!();
+                    for name in &matches {
+                        print!("{}  ", name);
+                    }
+                    println!();
+                }
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl LineEditor {
+    /// Create an instance.
+    pub const fn new() -> Self {
+        Self {
+            history: VecDeque::new(),
+            line: Vec::new(),
+            cursor: 0,
+            history_pos: None,
+            draft: Vec::new(),
+        }
+    }
+
+    /// Print `prompt` and read a line, blocking until it is completed with enter.
+    pub fn read_line(&mut self, prompt: &str) -> String {
+        self.line.clear();
+        self.cursor = 0;
+        self.history_pos = None;
+
+        print!("{}", prompt);
+
+        loop {
+            match read_key() {
+                Key::Enter => {
+                    println!();
+                    break;
+                }
+                Key::Char(c) => {
+                    let mut buf = [0; 4];
+                    self.insert(c.encode_utf8(&mut buf));
+                }
+                Key::Backspace if self.cursor > 0 => {
+                    self.cursor -= 1;
+                    self.line.remove(self.cursor);
+                }
+                Key::Delete if self.cursor < self.line.len() => {
+                    self.line.remove(self.cursor);
+                }
+                Key::Left if self.cursor > 0 => self.cursor -= 1,
+                Key::Right if self.cursor < self.line.len() => self.cursor += 1,
+                Key::Home => self.cursor = 0,
+                Key::End => self.cursor = self.line.len(),
+                Key::Up => self.history_up(),
+                Key::Down => self.history_down(),
+                Key::Tab => self.complete(),
+                Key::Cancel => {
+                    println!("^C");
+                    self.line.clear();
+                    self.cursor = 0;
+                    self.history_pos = None;
+                }
+                Key::ClearLine => {
+                    self.line.clear();
+                    self.cursor = 0;
+                }
+                _ => continue,
+            }
+
+            self.redraw(prompt);
+        }
+
+        let line: String = self.line.iter().collect();
+        self.add_to_history(&line);
+
+        line
+    }
+}
@@ -1,13 +1,22 @@
 //! Debug symbol support.
 
-use crate::memory::{Address, Virtual};
-use core::{cell::UnsafeCell, slice};
-use debug_symbol_types::Symbol;
+use crate::{
+    memory::{Address, Virtual},
+    println, shell,
+};
+use core::{cell::UnsafeCell, fmt, slice};
+use debug_symbol_types::{
+    line_info::{LineInfo, SourceLocation},
+    Symbol,
+};
 
 
-// Symbol from the linker script.
+// Symbols from the linker script.
 extern "Rust" {
     static __kernel_symbols_start: UnsafeCell<()>;
+
+    static __kernel_line_info_start: UnsafeCell<()>;
+    static __kernel_line_info_end_exclusive: UnsafeCell<()>;
 }
 
 
@@ -35,10 +44,104 @@ fn kernel_symbols_slice() -> &'static [Symbol] {
     unsafe { slice::from_raw_parts(ptr, num_kernel_symbols()) }
 }
 
+/// The line info section is patched by the "kernel symbols tool" after linking. If that did not
+/// happen, the magic number does not match and no table is returned.
+fn kernel_line_info() -> Option<LineInfo<'static>> {
+    let data = unsafe {
+        let start = __kernel_line_info_start.get() as usize;
+        let end = __kernel_line_info_end_exclusive.get() as usize;
+
+        slice::from_raw_parts(start as *const u8, end - start)
+    };
+
+    LineInfo::from_bytes(data)
+}
+
+fn cmd_sym(args: &[&str]) -> Result<(), &'static str> {
+    let [arg] = args else {
+        return Err("Wrong number of arguments");
+    };
+
+    // Symbol names never start with a digit.
+    if arg.starts_with(|c: char| c.is_ascii_digit()) {
+        let addr = Address::<Virtual>::new(shell::parse_number(arg)?);
+
+        println!("{}: {}", addr, SymbolOffset::new(addr));
+        if let Some(location) = lookup_source_location(addr) {
+            println!("      at {}", location);
+        }
+    } else {
+        let sym = lookup_symbol_by_name(arg).ok_or("Symbol not found")?;
+
+        println!(
+            "{}: {} ({} Byte)",
+            Address::<Virtual>::new(sym.start()),
+            sym.name(),
+            sym.size()
+        );
+    }
+
+    Ok(())
+}
+
+
+/// Pseudo-struct for printing an address relative to the symbol that contains it, in the form
+/// `name+offset/size`.
+pub struct SymbolOffset(Address<Virtual>);
+
 
 /// Retrieve the symbol corresponding to a virtual address, if any.
+///
+/// The symbol table is sorted by address, so a binary search finds the candidate.
 pub fn lookup_symbol(addr: Address<Virtual>) -> Option<&'static Symbol> {
-    kernel_symbols_slice()
-        .iter()
-        .find(|&i| i.contains(addr.as_usize()))
+    let symbols = kernel_symbols_slice();
+
+    // Index of the first symbol that starts after addr. The one before is the only candidate.
+    let i = symbols.partition_point(|sym| sym.start() <= addr.as_usize());
+
+    symbols[..i]
+        .last()
+        .filter(|sym| sym.contains(addr.as_usize()))
+}
+
+/// Retrieve the symbol with the given name, if any.
+pub fn lookup_symbol_by_name(name: &str) -> Option<&'static Symbol> {
+    kernel_symbols_slice().iter().find(|sym| sym.name() == name)
+}
+
+/// Retrieve the source file and line of the instruction at a virtual address, if any.
+pub fn lookup_source_location(addr: Address<Virtual>) -> Option<SourceLocation<'static>> {
+    kernel_line_info()?.lookup(addr.as_usize())
+}
+
+/// Register the shell commands of the symbol lookup.
+pub fn register_shell_commands() -> Result<(), &'static str> {
+    shell::register_command(shell::Command::new(
+        "sym",
+        "sym <addr|name>",
+        "Look up the symbol of an address, or the address of a symbol",
+        cmd_sym,
+    ))
+}
+
+impl SymbolOffset {
+    /// Create an instance.
+    pub fn new(addr: Address<Virtual>) -> Self {
+        Self(addr)
+    }
+}
+
+impl fmt::Display for SymbolOffset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match lookup_symbol(self.0) {
+            Some(sym) => write!(
+                f,
+                "{}+{:#x}/{:#x}",
+                sym.name(),
+                self.0.as_usize() - sym.start(),
+                sym.size()
+            ),
+            _ => write!(f, "Symbol not found"),
+        }
+    }
 }
@@ -50,6 +50,17 @@ pub struct IRQSafeNullLock<T>
     data: UnsafeCell<T>,
 }
 
+/// A pseudo-lock like [IRQSafeNullLock], but without masking IRQs.
+///
+/// For data that is held across slow operations, e.g. block I/O, and that is never used by IRQ
+/// handlers. Masking IRQs for that long would hold up the timer and the consoles.
+pub struct NullLock<T>
+    where
+        T: ?Sized,
+{
+    data: UnsafeCell<T>,
+}
+
 /// A pseudo-lock that is RW during the single-core kernel init phase and RO afterwards.
 ///
 /// Intended to encapsulate data that is populated during kernel init when no concurrency exists.
@@ -72,6 +83,18 @@ impl<T> IRQSafeNullLock<T> {
     }
 }
 
+unsafe impl<T> Send for NullLock<T> where T: ?Sized + Send {}
+unsafe impl<T> Sync for NullLock<T> where T: ?Sized + Send {}
+
+impl<T> NullLock<T> {
+    /// Create an instance.
+    pub const fn new(data: T) -> Self {
+        Self {
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
 unsafe impl<T> Send for InitStateLock<T> where T: ?Sized + Send {}
 unsafe impl<T> Sync for InitStateLock<T> where T: ?Sized + Send {}
 
@@ -102,6 +125,17 @@ impl<T> interface::Mutex for IRQSafeNullLock<T> {
     }
 }
 
+impl<T> interface::Mutex for NullLock<T> {
+    type Data = T;
+
+    fn lock<'a, R>(&'a self, f: impl FnOnce(&'a mut Self::Data) -> R) -> R {
+        // Same as above, minus the IRQ masking.
+        let data = unsafe { &mut *self.data.get() };
+
+        f(data)
+    }
+}
+
 impl<T> interface::ReadWriteEx for InitStateLock<T> {
     type Data = T;
 
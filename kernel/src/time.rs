@@ -3,6 +3,7 @@
 #[path = "aarch64/time.rs"]
 mod arch_time;
 
+use crate::{println, shell};
 use core::time::Duration;
 
 
@@ -13,11 +14,31 @@ pub struct TimeManager;
 static TIME_MANAGER: TimeManager = TimeManager::new();
 
 
+fn cmd_uptime(args: &[&str]) -> Result<(), &'static str> {
+    shell::no_args(args)?;
+
+    let uptime = time_manager().uptime();
+    println!("{}.{:06} s", uptime.as_secs(), uptime.subsec_micros());
+
+    Ok(())
+}
+
+
 /// Return a reference to the global TimeManager.
 pub fn time_manager() -> &'static TimeManager {
     &TIME_MANAGER
 }
 
+/// Register the shell commands of the time subsystem.
+pub fn register_shell_commands() -> Result<(), &'static str> {
+    shell::register_command(shell::Command::new(
+        "uptime",
+        "uptime",
+        "Print the time since power-on",
+        cmd_uptime,
+    ))
+}
+
 impl TimeManager {
     /// Create an instance.
     pub const fn new() -> Self {
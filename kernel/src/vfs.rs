@@ -0,0 +1,631 @@
+//! Virtual filesystem.
+//!
+//! One directory tree, assembled from the filesystems of the mount table. Filesystems provide
+//! inodes, i.e. files, directories and devices, and the VFS resolves paths to dentries, i.e. inodes
+//! together with the path they were found at, across mount points.
+//!
+//! At kernel init, a ramfs is mounted at `/`, the devfs at `/dev`, the first FAT volume of the
+//! block devices, if any, at `/boot` and the initramfs of the kernel image, if any, at
+//! `/initramfs`.
+
+mod devfs;
+mod fat;
+mod initramfs;
+mod ramfs;
+
+use crate::{
+    info, print, println, shell,
+    synchronization::{interface::Mutex, NullLock},
+    warn,
+};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use kernel_core::fs::path;
+
+pub use devfs::DevFs;
+pub use fat::FatFs;
+pub use initramfs::InitRamFs;
+pub use ramfs::RamFs;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+struct Mount {
+    point: String,
+    fs: Arc<dyn interface::FileSystem>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// VFS interfaces.
+pub mod interface {
+    use super::{DirEntry, Metadata, NodeKind};
+    use alloc::{sync::Arc, vec::Vec};
+
+    /// A file, directory or device of a filesystem.
+    ///
+    /// The defaults are for inodes that do not support an operation.
+    pub trait Inode: Send + Sync {
+        /// Kind and size.
+        fn metadata(&self) -> Result<Metadata, &'static str>;
+
+        /// Read at `offset`, and return the number of bytes read. 0 at the end of the file.
+        fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> Result<usize, &'static str> {
+            Err("Not a file")
+        }
+
+        /// Write at `offset`, and return the number of bytes written.
+        fn write_at(&self, _offset: u64, _data: &[u8]) -> Result<usize, &'static str> {
+            Err("Not a file")
+        }
+
+        /// Change the size of a file.
+        fn truncate(&self, _len: u64) -> Result<(), &'static str> {
+            Err("Not a file")
+        }
+
+        /// Look up an entry of a directory.
+        fn lookup(&self, _name: &str) -> Result<Arc<dyn Inode>, &'static str> {
+            Err("Not a directory")
+        }
+
+        /// The entries of a directory, except for `.` and `..`.
+        fn read_dir(&self) -> Result<Vec<DirEntry>, &'static str> {
+            Err("Not a directory")
+        }
+
+        /// Create an empty file or directory in a directory.
+        fn create(&self, _name: &str, _kind: NodeKind) -> Result<Arc<dyn Inode>, &'static str> {
+            Err("Not a directory")
+        }
+
+        /// Remove a file or an empty directory from a directory.
+        fn remove(&self, _name: &str) -> Result<(), &'static str> {
+            Err("Not a directory")
+        }
+    }
+
+    /// A filesystem that can be mounted.
+    pub trait FileSystem: Send + Sync {
+        /// The type of the filesystem, e.g. `ramfs`.
+        fn name(&self) -> &'static str;
+
+        /// The root directory.
+        fn root(&self) -> Arc<dyn Inode>;
+    }
+
+    /// A device that devfs exposes as a file. Devices are streams, so there are no offsets.
+    pub trait DeviceFile {
+        /// Read what is available, without blocking, and return the number of bytes read.
+        fn read(&self, _buf: &mut [u8]) -> Result<usize, &'static str> {
+            Err("Device is not readable")
+        }
+
+        /// Write, and return the number of bytes written.
+        fn write(&self, _data: &[u8]) -> Result<usize, &'static str> {
+            Err("Device is not writable")
+        }
+    }
+}
+
+/// The kind of an inode.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NodeKind {
+    /// A regular file.
+    File,
+
+    /// A directory.
+    Directory,
+
+    /// A device file.
+    Device,
+}
+
+/// Information about an inode.
+#[derive(Copy, Clone, Debug)]
+pub struct Metadata {
+    /// The kind.
+    pub kind: NodeKind,
+
+    /// The size in bytes. 0 for directories and devices.
+    pub size: u64,
+}
+
+/// An entry of a directory listing.
+#[derive(Clone, Debug)]
+pub struct DirEntry {
+    /// The name within the directory.
+    pub name: String,
+
+    /// Kind and size.
+    pub metadata: Metadata,
+}
+
+/// An inode, together with the normalized path that it was found at.
+#[derive(Clone)]
+pub struct Dentry {
+    path: String,
+    inode: Arc<dyn interface::Inode>,
+}
+
+/// Where to seek to.
+#[derive(Copy, Clone, Debug)]
+pub enum SeekFrom {
+    /// An offset from the start of the file.
+    Start(u64),
+
+    /// An offset from the end of the file.
+    End(i64),
+
+    /// An offset from the current position.
+    Current(i64),
+}
+
+/// Options for opening a file, in the style of `std::fs::OpenOptions`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+}
+
+/// An open file. Closed when dropped.
+pub struct File {
+    dentry: Dentry,
+    options: OpenOptions,
+    position: u64,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static MOUNTS: NullLock<Vec<Mount>> = NullLock::new(Vec::new());
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// The root of the filesystem that is mounted closest to `path`, and the rest of `path` below it.
+fn mount_for(path: &str) -> Result<(Arc<dyn interface::Inode>, &str), &'static str> {
+    MOUNTS.lock(|mounts| {
+        mounts
+            .iter()
+            .filter_map(|mount| path::strip_prefix(path, &mount.point).map(|rest| (mount, rest)))
+            .max_by_key(|(mount, _)| mount.point.len())
+            .map(|(mount, rest)| (mount.fs.root(), rest))
+            .ok_or("Nothing mounted at /")
+    })
+}
+
+fn is_mount_point(path: &str) -> bool {
+    MOUNTS.lock(|mounts| mounts.iter().any(|mount| mount.point == path))
+}
+
+/// The parent directory of a path that is to be created or removed, and the name within it.
+fn resolve_parent(path: &str) -> Result<(Dentry, String), &'static str> {
+    let path = path::normalize(path);
+    let (parent, name) = path::split(&path).ok_or("Invalid path")?;
+
+    Ok((resolve(parent)?, String::from(name)))
+}
+
+fn cmd_ls(args: &[&str]) -> Result<(), &'static str> {
+    let path = match args {
+        [] => "/",
+        [path] => path,
+        _ => return Err("Unexpected arguments"),
+    };
+
+    let node = metadata(path)?;
+    if node.kind != NodeKind::Directory {
+        println!("{:>10}  {}", node.size, path);
+        return Ok(());
+    }
+
+    for entry in read_dir(path)? {
+        match entry.metadata.kind {
+            NodeKind::File => println!("{:>10}  {}", entry.metadata.size, entry.name),
+            NodeKind::Directory => println!("{:>10}  {}/", "<dir>", entry.name),
+            NodeKind::Device => println!("{:>10}  {}", "<dev>", entry.name),
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_cat(args: &[&str]) -> Result<(), &'static str> {
+    let [path] = args else {
+        return Err("Expected a path");
+    };
+
+    let data = read(path)?;
+    print!("{}", String::from_utf8_lossy(&data));
+    if !data.is_empty() && !data.ends_with(b"\n") {
+        println!();
+    }
+
+    Ok(())
+}
+
+/// The shell splits arguments at whitespace, so the text is joined with single spaces.
+fn cmd_write(args: &[&str]) -> Result<(), &'static str> {
+    let (append, args) = match args.split_first() {
+        Some((&"-a", rest)) => (true, rest),
+        _ => (false, args),
+    };
+    let Some((path, words)) = args.split_first() else {
+        return Err("Expected a path");
+    };
+
+    let mut text = words.join(" ");
+    text.push('\n');
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)?;
+    file.write(text.as_bytes())?;
+
+    Ok(())
+}
+
+fn cmd_mkdir(args: &[&str]) -> Result<(), &'static str> {
+    let [path] = args else {
+        return Err("Expected a path");
+    };
+
+    create_dir(path)
+}
+
+fn cmd_rm(args: &[&str]) -> Result<(), &'static str> {
+    let [path] = args else {
+        return Err("Expected a path");
+    };
+
+    remove(path)
+}
+
+fn cmd_mounts(args: &[&str]) -> Result<(), &'static str> {
+    shell::no_args(args)?;
+    print_mounts();
+
+    Ok(())
+}
+
+fn register_shell_commands() -> Result<(), &'static str> {
+    let commands = [
+        shell::Command::new("ls", "ls [path]", "List a directory", cmd_ls),
+        shell::Command::new("cat", "cat <path>", "Print a file", cmd_cat),
+        shell::Command::new(
+            "write",
+            "write [-a] <path> [text...]",
+            "Write a line of text to a file, or append it with -a",
+            cmd_write,
+        ),
+        shell::Command::new("mkdir", "mkdir <path>", "Create a directory", cmd_mkdir),
+        shell::Command::new(
+            "rm",
+            "rm <path>",
+            "Remove a file or an empty directory",
+            cmd_rm,
+        ),
+        shell::Command::new(
+            "mounts",
+            "mounts",
+            "List the mounted filesystems",
+            cmd_mounts,
+        ),
+    ];
+
+    for command in commands {
+        shell::register_command(command)?;
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Dentry {
+    /// The normalized path.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The inode.
+    pub fn inode(&self) -> &Arc<dyn interface::Inode> {
+        &self.inode
+    }
+}
+
+impl OpenOptions {
+    /// Create an instance with all options off.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open for reading.
+    pub fn read(mut self, read: bool) -> Self {
+        self.read = read;
+        self
+    }
+
+    /// Open for writing.
+    pub fn write(mut self, write: bool) -> Self {
+        self.write = write;
+        self
+    }
+
+    /// Write at the end of the file. Implies writing.
+    pub fn append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+
+    /// Truncate a file to 0 bytes when opening it. Needs writing.
+    pub fn truncate(mut self, truncate: bool) -> Self {
+        self.truncate = truncate;
+        self
+    }
+
+    /// Create a file if it does not exist. Needs writing.
+    pub fn create(mut self, create: bool) -> Self {
+        self.create = create;
+        self
+    }
+
+    /// Open a file with these options.
+    pub fn open(&self, path: &str) -> Result<File, &'static str> {
+        let writable = self.write || self.append;
+        if (self.truncate || self.create) && !writable {
+            return Err("Invalid open options");
+        }
+
+        let dentry = match resolve(path) {
+            Ok(dentry) => dentry,
+            Err(_) if self.create => {
+                let (parent, name) = resolve_parent(path)?;
+                let inode = parent.inode.create(&name, NodeKind::File)?;
+
+                Dentry {
+                    path: path::normalize(path),
+                    inode,
+                }
+            }
+            Err(x) => return Err(x),
+        };
+
+        if dentry.inode.metadata()?.kind == NodeKind::Directory {
+            return Err("Is a directory");
+        }
+        if self.truncate {
+            dentry.inode.truncate(0)?;
+        }
+
+        Ok(File {
+            dentry,
+            options: *self,
+            position: 0,
+        })
+    }
+}
+
+impl File {
+    /// Open a file for reading.
+    pub fn open(path: &str) -> Result<Self, &'static str> {
+        OpenOptions::new().read(true).open(path)
+    }
+
+    /// Open a file for writing. It is created if it does not exist, and truncated if it does.
+    pub fn create(path: &str) -> Result<Self, &'static str> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+    }
+
+    /// The dentry the file was opened at.
+    pub fn dentry(&self) -> &Dentry {
+        &self.dentry
+    }
+
+    /// Read at the current position, and advance it. Returns 0 at the end of the file.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, &'static str> {
+        if !self.options.read {
+            return Err("File is not open for reading");
+        }
+
+        let len = self.dentry.inode.read_at(self.position, buf)?;
+        self.position += len as u64;
+
+        Ok(len)
+    }
+
+    /// Write at the current position, or at the end of the file if opened for appending, and
+    /// advance the position.
+    pub fn write(&mut self, data: &[u8]) -> Result<usize, &'static str> {
+        if !self.options.write && !self.options.append {
+            return Err("File is not open for writing");
+        }
+        if self.options.append {
+            self.position = self.dentry.inode.metadata()?.size;
+        }
+
+        let len = self.dentry.inode.write_at(self.position, data)?;
+        self.position += len as u64;
+
+        Ok(len)
+    }
+
+    /// Read from the current position to the end of the file.
+    pub fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize, &'static str> {
+        let start = buf.len();
+        let mut chunk = [0; 512];
+
+        loop {
+            match self.read(&mut chunk)? {
+                0 => return Ok(buf.len() - start),
+                len => buf.extend_from_slice(&chunk[..len]),
+            }
+        }
+    }
+
+    /// Move the position, and return the new one. Devices have no position.
+    pub fn seek(&mut self, pos: SeekFrom) -> Result<u64, &'static str> {
+        let metadata = self.dentry.inode.metadata()?;
+        if metadata.kind == NodeKind::Device {
+            return Err("Device is not seekable");
+        }
+
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => metadata.size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or("Invalid seek")?;
+
+        Ok(self.position)
+    }
+
+    /// Close the file. The same as dropping it.
+    pub fn close(self) {}
+}
+
+/// Mount a filesystem at a directory. `/` must be mounted first.
+pub fn mount(point: &str, fs: Arc<dyn interface::FileSystem>) -> Result<(), &'static str> {
+    let point = path::normalize(point);
+
+    if point != "/" && resolve(&point)?.inode.metadata()?.kind != NodeKind::Directory {
+        return Err("Mount point is not a directory");
+    }
+    if is_mount_point(&point) {
+        return Err("Mount point is busy");
+    }
+
+    MOUNTS.lock(|mounts| mounts.push(Mount { point, fs }));
+
+    Ok(())
+}
+
+/// Unmount the filesystem at a directory. Files that are still open keep working.
+pub fn unmount(point: &str) -> Result<(), &'static str> {
+    let point = path::normalize(point);
+    if point == "/" {
+        return Err("Cannot unmount /");
+    }
+
+    MOUNTS.lock(|mounts| {
+        let i = mounts
+            .iter()
+            .position(|mount| mount.point == point)
+            .ok_or("Not a mount point")?;
+        mounts.remove(i);
+
+        Ok(())
+    })
+}
+
+/// Resolve a path to a dentry.
+pub fn resolve(path: &str) -> Result<Dentry, &'static str> {
+    let path = path::normalize(path);
+    let (mut inode, rest) = mount_for(&path)?;
+
+    for name in path::components(rest) {
+        inode = inode.lookup(name)?;
+    }
+
+    Ok(Dentry { path, inode })
+}
+
+/// Kind and size of a file or directory.
+pub fn metadata(path: &str) -> Result<Metadata, &'static str> {
+    resolve(path)?.inode.metadata()
+}
+
+/// The entries of a directory.
+pub fn read_dir(path: &str) -> Result<Vec<DirEntry>, &'static str> {
+    resolve(path)?.inode.read_dir()
+}
+
+/// The contents of a file.
+pub fn read(path: &str) -> Result<Vec<u8>, &'static str> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+
+    Ok(data)
+}
+
+/// Replace the contents of a file. It is created if it does not exist.
+pub fn write(path: &str, data: &[u8]) -> Result<(), &'static str> {
+    let mut file = File::create(path)?;
+
+    let mut done = 0;
+    while done < data.len() {
+        match file.write(&data[done..])? {
+            0 => return Err("Write made no progress"),
+            len => done += len,
+        }
+    }
+
+    Ok(())
+}
+
+/// Create a directory. The parent directory must exist.
+pub fn create_dir(path: &str) -> Result<(), &'static str> {
+    let (parent, name) = resolve_parent(path)?;
+
+    parent.inode.create(&name, NodeKind::Directory).map(|_| ())
+}
+
+/// Remove a file or an empty directory.
+pub fn remove(path: &str) -> Result<(), &'static str> {
+    let (parent, name) = resolve_parent(path)?;
+    if is_mount_point(&path::normalize(path)) {
+        return Err("Mount point is busy");
+    }
+
+    parent.inode.remove(&name)
+}
+
+/// Print the mount table.
+pub fn print_mounts() {
+    MOUNTS.lock(|mounts| {
+        for mount in mounts.iter() {
+            println!("      {:<12} {}", mount.point, mount.fs.name());
+        }
+    });
+}
+
+/// Assemble the directory tree.
+///
+/// Needs the drivers to be initialized, so that devfs and the block devices are populated.
+pub fn init() -> Result<(), &'static str> {
+    mount("/", Arc::new(RamFs::new()))?;
+
+    for dir in ["/dev", "/boot", "/initramfs", "/tmp"] {
+        create_dir(dir)?;
+    }
+    mount("/dev", Arc::new(DevFs::new()))?;
+
+    match FatFs::probe() {
+        Some(fs) => mount("/boot", Arc::new(fs))?,
+        None => warn!("No FAT volume found, /boot stays empty"),
+    }
+
+    match InitRamFs::probe() {
+        Some(fs) => mount("/initramfs", Arc::new(fs))?,
+        None => info!("No initramfs in the kernel image"),
+    }
+
+    register_shell_commands()
+}
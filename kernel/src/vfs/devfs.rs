@@ -0,0 +1,131 @@
+//! Device files.
+//!
+//! The files of the kernel itself, e.g. `random`, and those of the drivers of the `DriverManager`
+//! that provide one, named by their class and number, e.g. `uart0`. The directory is generated on
+//! each access.
+
+use super::{interface, DirEntry, Metadata, NodeKind};
+use crate::{driver, random};
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+struct DevDir;
+
+struct DevNode {
+    file: &'static (dyn interface::DeviceFile + Sync),
+}
+
+const DEVICE_METADATA: Metadata = Metadata {
+    kind: NodeKind::Device,
+    size: 0,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The device filesystem.
+pub struct DevFs {
+    root: Arc<DevDir>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn device_files() -> Vec<(String, &'static (dyn interface::DeviceFile + Sync))> {
+    let kernel_files: [(&str, &'static (dyn interface::DeviceFile + Sync)); 1] =
+        [("random", &random::RandomFile)];
+
+    kernel_files
+        .into_iter()
+        .map(|(name, file)| (name.to_string(), file))
+        .chain(driver::driver_manager().device_files())
+        .collect()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl DevFs {
+    /// Create an instance.
+    pub fn new() -> Self {
+        Self {
+            root: Arc::new(DevDir),
+        }
+    }
+}
+
+impl Default for DevFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+
+impl interface::FileSystem for DevFs {
+    fn name(&self) -> &'static str {
+        "devfs"
+    }
+
+    fn root(&self) -> Arc<dyn interface::Inode> {
+        self.root.clone()
+    }
+}
+
+impl interface::Inode for DevDir {
+    fn metadata(&self) -> Result<Metadata, &'static str> {
+        Ok(Metadata {
+            kind: NodeKind::Directory,
+            size: 0,
+        })
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn interface::Inode>, &'static str> {
+        device_files()
+            .into_iter()
+            .find(|(file_name, _)| file_name == name)
+            .map(|(_, file)| Arc::new(DevNode { file }) as Arc<dyn interface::Inode>)
+            .ok_or("No such device")
+    }
+
+    fn read_dir(&self) -> Result<Vec<DirEntry>, &'static str> {
+        Ok(device_files()
+            .into_iter()
+            .map(|(name, _)| DirEntry {
+                name,
+                metadata: DEVICE_METADATA,
+            })
+            .collect())
+    }
+}
+
+impl interface::Inode for DevNode {
+    fn metadata(&self) -> Result<Metadata, &'static str> {
+        Ok(DEVICE_METADATA)
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> Result<usize, &'static str> {
+        self.file.read(buf)
+    }
+
+    fn write_at(&self, _offset: u64, data: &[u8]) -> Result<usize, &'static str> {
+        self.file.write(data)
+    }
+
+    /// Opening a device for writing truncates it, which is a no-op.
+    fn truncate(&self, _len: u64) -> Result<(), &'static str> {
+        Ok(())
+    }
+}
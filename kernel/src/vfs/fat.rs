@@ -0,0 +1,229 @@
+//! FAT32 volumes of block devices.
+//!
+//! Inodes refer to their files by path, so they stay valid while the directory changes. The lock
+//! of a volume is held across block I/O, so it does not mask IRQs.
+
+use super::{interface, DirEntry, Metadata, NodeKind};
+use crate::{
+    block, info,
+    synchronization::{interface::Mutex, NullLock},
+    warn,
+};
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use kernel_core::{
+    block::partition::{self, Partition, PartitionDevice, PartitionType},
+    fs::fat,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+type Volume = fat::FileSystem<PartitionDevice<&'static (dyn block::interface::BlockDevice + Sync)>>;
+
+struct FatInode {
+    volume: Arc<NullLock<Volume>>,
+    path: String,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A mounted FAT32 volume.
+pub struct FatFs {
+    volume: Arc<NullLock<Volume>>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Try the FAT partitions of a device, or the whole device if it has no partition table.
+fn mount_device(name: &str) -> Option<Volume> {
+    let device = block::block_device(name)?;
+    let partitions = match partition::scan(&device) {
+        Ok(partitions) => partitions,
+        Err(x) => {
+            warn!("{}: {}", name, x);
+            return None;
+        }
+    };
+
+    let candidates: Vec<Partition> = if partitions.is_empty() {
+        // Number 0 stands for the whole device.
+        [Partition {
+            number: 0,
+            start_block: 0,
+            num_blocks: device.num_blocks(),
+            partition_type: PartitionType::Mbr(0),
+        }]
+        .into()
+    } else {
+        partitions
+            .into_iter()
+            .filter(|p| p.partition_type.is_fat())
+            .collect()
+    };
+
+    candidates.iter().find_map(|partition| {
+        let volume = fat::FileSystem::mount(PartitionDevice::new(device, partition)).ok()?;
+        let (size, unit) = crate::common::size_human_readable_ceil(volume.size() as usize);
+
+        if partition.number == 0 {
+            info!("FAT volume {}, {} {}", name, size, unit);
+        } else {
+            info!(
+                "FAT volume {} partition {}, {} {}",
+                name, partition.number, size, unit
+            );
+        }
+
+        Some(volume)
+    })
+}
+
+fn metadata(entry: &fat::DirEntry) -> Metadata {
+    if entry.is_dir() {
+        Metadata {
+            kind: NodeKind::Directory,
+            size: 0,
+        }
+    } else {
+        Metadata {
+            kind: NodeKind::File,
+            size: entry.size(),
+        }
+    }
+}
+
+impl FatInode {
+    fn child_path(&self, name: &str) -> String {
+        format!("{}/{}", self.path, name)
+    }
+
+    fn child(&self, path: String) -> Arc<dyn interface::Inode> {
+        Arc::new(FatInode {
+            volume: self.volume.clone(),
+            path,
+        })
+    }
+
+    fn with_entry<R>(
+        &self,
+        f: impl FnOnce(&mut Volume, fat::DirEntry) -> Result<R, &'static str>,
+    ) -> Result<R, &'static str> {
+        self.volume.lock(|volume| {
+            let entry = volume.open(&self.path)?;
+            f(volume, entry)
+        })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl FatFs {
+    /// Mount the first FAT32 volume of the registered block devices.
+    pub fn probe() -> Option<Self> {
+        let volume = block::block_device_names()
+            .into_iter()
+            .find_map(mount_device)?;
+
+        Some(Self {
+            volume: Arc::new(NullLock::new(volume)),
+        })
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+
+impl interface::FileSystem for FatFs {
+    fn name(&self) -> &'static str {
+        "fat32"
+    }
+
+    fn root(&self) -> Arc<dyn interface::Inode> {
+        Arc::new(FatInode {
+            volume: self.volume.clone(),
+            path: String::new(),
+        })
+    }
+}
+
+impl interface::Inode for FatInode {
+    fn metadata(&self) -> Result<Metadata, &'static str> {
+        self.with_entry(|_, entry| Ok(metadata(&entry)))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, &'static str> {
+        self.with_entry(|volume, entry| volume.read(&entry, offset, buf))
+    }
+
+    fn write_at(&self, offset: u64, data: &[u8]) -> Result<usize, &'static str> {
+        self.with_entry(|volume, mut entry| volume.write(&mut entry, offset, data))
+    }
+
+    /// Growing fills with zeros.
+    fn truncate(&self, len: u64) -> Result<(), &'static str> {
+        self.with_entry(|volume, mut entry| {
+            if len <= entry.size() {
+                return volume.truncate(&mut entry, len);
+            }
+
+            let zeros = [0; 512];
+            while entry.size() < len {
+                let size = entry.size();
+                let chunk = (len - size).min(zeros.len() as u64) as usize;
+                volume.write(&mut entry, size, &zeros[..chunk])?;
+            }
+
+            Ok(())
+        })
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn interface::Inode>, &'static str> {
+        let path = self.child_path(name);
+        self.volume.lock(|volume| volume.open(&path))?;
+
+        Ok(self.child(path))
+    }
+
+    fn read_dir(&self) -> Result<Vec<DirEntry>, &'static str> {
+        self.with_entry(|volume, entry| {
+            Ok(volume
+                .read_dir(&entry)?
+                .into_iter()
+                .map(|entry| DirEntry {
+                    metadata: metadata(&entry),
+                    name: String::from(entry.name()),
+                })
+                .collect())
+        })
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        kind: NodeKind,
+    ) -> Result<Arc<dyn interface::Inode>, &'static str> {
+        let path = self.child_path(name);
+
+        self.volume.lock(|volume| match kind {
+            NodeKind::File => volume.create(&path),
+            NodeKind::Directory => volume.create_dir(&path),
+            NodeKind::Device => Err("Devices live in devfs"),
+        })?;
+
+        Ok(self.child(path))
+    }
+
+    fn remove(&self, name: &str) -> Result<(), &'static str> {
+        let path = self.child_path(name);
+
+        self.volume.lock(|volume| volume.remove(&path))
+    }
+}
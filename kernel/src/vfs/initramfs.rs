@@ -0,0 +1,216 @@
+//! The initramfs, a cpio archive that the kernel symbols tool patches into the kernel image.
+//!
+//! The archive is parsed once at mount time. File contents are not copied, they are read straight
+//! from the linker section.
+
+use super::{interface, DirEntry, Metadata, NodeKind};
+use crate::{info, warn};
+use alloc::{boxed::Box, collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use core::{cell::UnsafeCell, slice};
+use kernel_core::fs::{cpio, path};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+// Symbols from the linker script.
+extern "Rust" {
+    static __kernel_initramfs_start: UnsafeCell<()>;
+    static __kernel_initramfs_end_exclusive: UnsafeCell<()>;
+}
+
+enum Node {
+    File(&'static [u8]),
+    Directory(BTreeMap<String, Node>),
+}
+
+struct InitRamInode {
+    node: &'static Node,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The read-only filesystem of the initramfs archive.
+pub struct InitRamFs {
+    root: &'static Node,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// The whole linker section. Only the start is used if the archive is smaller.
+fn archive() -> &'static [u8] {
+    unsafe {
+        let start = __kernel_initramfs_start.get() as usize;
+        let end = __kernel_initramfs_end_exclusive.get() as usize;
+
+        slice::from_raw_parts(start as *const u8, end - start)
+    }
+}
+
+impl Node {
+    fn metadata(&self) -> Metadata {
+        match self {
+            Node::File(data) => Metadata {
+                kind: NodeKind::File,
+                size: data.len() as u64,
+            },
+            Node::Directory(_) => Metadata {
+                kind: NodeKind::Directory,
+                size: 0,
+            },
+        }
+    }
+
+    /// Insert an entry, and create missing parent directories on the way.
+    fn insert(&mut self, entry: &cpio::Entry<'static>) -> Result<(), &'static str> {
+        let mut dir = self;
+        let mut components = path::components(entry.path).peekable();
+
+        while let Some(name) = components.next() {
+            let Node::Directory(entries) = dir else {
+                return Err("initramfs path runs through a file");
+            };
+
+            if components.peek().is_some() {
+                dir = entries
+                    .entry(String::from(name))
+                    .or_insert_with(|| Node::Directory(BTreeMap::new()));
+                continue;
+            }
+
+            match entry.kind {
+                cpio::EntryKind::File => {
+                    entries.insert(String::from(name), Node::File(entry.data));
+                }
+                cpio::EntryKind::Directory => {
+                    entries
+                        .entry(String::from(name))
+                        .or_insert_with(|| Node::Directory(BTreeMap::new()));
+                }
+                cpio::EntryKind::Other => (),
+            }
+            break;
+        }
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl InitRamFs {
+    /// Parse the archive of the kernel image.
+    ///
+    /// `None` if the build did not provide one. The directory tree is built once and lives as long
+    /// as the kernel.
+    pub fn probe() -> Option<Self> {
+        let archive = archive();
+        if !archive.starts_with(cpio::MAGIC) {
+            return None;
+        }
+
+        let mut root = Node::Directory(BTreeMap::new());
+        let mut num_files = 0;
+        for entry in cpio::entries(archive) {
+            let result = entry.and_then(|entry| {
+                if entry.kind == cpio::EntryKind::File {
+                    num_files += 1;
+                }
+                root.insert(&entry)
+            });
+
+            if let Err(x) = result {
+                warn!("initramfs: {}", x);
+                return None;
+            }
+        }
+        info!("initramfs with {} files", num_files);
+
+        Some(Self {
+            root: Box::leak(Box::new(root)),
+        })
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+
+impl interface::FileSystem for InitRamFs {
+    fn name(&self) -> &'static str {
+        "initramfs"
+    }
+
+    fn root(&self) -> Arc<dyn interface::Inode> {
+        Arc::new(InitRamInode { node: self.root })
+    }
+}
+
+impl interface::Inode for InitRamInode {
+    fn metadata(&self) -> Result<Metadata, &'static str> {
+        Ok(self.node.metadata())
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, &'static str> {
+        let Node::File(data) = self.node else {
+            return Err("Is a directory");
+        };
+
+        let start = (offset as usize).min(data.len());
+        let len = buf.len().min(data.len() - start);
+        buf[..len].copy_from_slice(&data[start..start + len]);
+
+        Ok(len)
+    }
+
+    fn write_at(&self, _offset: u64, _data: &[u8]) -> Result<usize, &'static str> {
+        Err("Read-only filesystem")
+    }
+
+    fn truncate(&self, _len: u64) -> Result<(), &'static str> {
+        Err("Read-only filesystem")
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn interface::Inode>, &'static str> {
+        let Node::Directory(entries) = self.node else {
+            return Err("Not a directory");
+        };
+
+        entries
+            .get(name)
+            .map(|node| Arc::new(InitRamInode { node }) as Arc<dyn interface::Inode>)
+            .ok_or("No such file or directory")
+    }
+
+    fn read_dir(&self) -> Result<Vec<DirEntry>, &'static str> {
+        let Node::Directory(entries) = self.node else {
+            return Err("Not a directory");
+        };
+
+        Ok(entries
+            .iter()
+            .map(|(name, node)| DirEntry {
+                name: name.clone(),
+                metadata: node.metadata(),
+            })
+            .collect())
+    }
+
+    fn create(
+        &self,
+        _name: &str,
+        _kind: NodeKind,
+    ) -> Result<Arc<dyn interface::Inode>, &'static str> {
+        Err("Read-only filesystem")
+    }
+
+    fn remove(&self, _name: &str) -> Result<(), &'static str> {
+        Err("Read-only filesystem")
+    }
+}
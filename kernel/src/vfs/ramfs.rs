@@ -0,0 +1,215 @@
+//! A filesystem on the heap.
+//!
+//! Contents are lost on reset. Names are case-sensitive.
+
+use super::{interface, DirEntry, Metadata, NodeKind};
+use crate::synchronization::{interface::Mutex, IRQSafeNullLock};
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+enum Content {
+    File(Vec<u8>),
+    Directory(BTreeMap<String, Arc<RamInode>>),
+}
+
+struct RamInode {
+    content: IRQSafeNullLock<Content>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A filesystem on the heap.
+pub struct RamFs {
+    root: Arc<RamInode>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl RamInode {
+    fn new(kind: NodeKind) -> Self {
+        let content = match kind {
+            NodeKind::Directory => Content::Directory(BTreeMap::new()),
+            _ => Content::File(Vec::new()),
+        };
+
+        Self {
+            content: IRQSafeNullLock::new(content),
+        }
+    }
+
+    fn with_file<R>(
+        &self,
+        f: impl FnOnce(&mut Vec<u8>) -> Result<R, &'static str>,
+    ) -> Result<R, &'static str> {
+        self.content.lock(|content| match content {
+            Content::File(data) => f(data),
+            Content::Directory(_) => Err("Is a directory"),
+        })
+    }
+
+    fn with_dir<R>(
+        &self,
+        f: impl FnOnce(&mut BTreeMap<String, Arc<RamInode>>) -> Result<R, &'static str>,
+    ) -> Result<R, &'static str> {
+        self.content.lock(|content| match content {
+            Content::Directory(entries) => f(entries),
+            Content::File(_) => Err("Not a directory"),
+        })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl RamFs {
+    /// Create an empty instance.
+    pub fn new() -> Self {
+        Self {
+            root: Arc::new(RamInode::new(NodeKind::Directory)),
+        }
+    }
+}
+
+impl Default for RamFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+
+impl interface::FileSystem for RamFs {
+    fn name(&self) -> &'static str {
+        "ramfs"
+    }
+
+    fn root(&self) -> Arc<dyn interface::Inode> {
+        self.root.clone()
+    }
+}
+
+impl interface::Inode for RamInode {
+    fn metadata(&self) -> Result<Metadata, &'static str> {
+        let metadata = self.content.lock(|content| match content {
+            Content::File(data) => Metadata {
+                kind: NodeKind::File,
+                size: data.len() as u64,
+            },
+            Content::Directory(_) => Metadata {
+                kind: NodeKind::Directory,
+                size: 0,
+            },
+        });
+
+        Ok(metadata)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, &'static str> {
+        self.with_file(|data| {
+            let start = (offset as usize).min(data.len());
+            let len = buf.len().min(data.len() - start);
+            buf[..len].copy_from_slice(&data[start..start + len]);
+
+            Ok(len)
+        })
+    }
+
+    /// Writing beyond the end leaves a hole of zeros.
+    fn write_at(&self, offset: u64, data: &[u8]) -> Result<usize, &'static str> {
+        let end = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| offset.checked_add(data.len()))
+            .ok_or("File too large")?;
+
+        self.with_file(|file| {
+            if file.len() < end {
+                file.resize(end, 0);
+            }
+            file[end - data.len()..end].copy_from_slice(data);
+
+            Ok(data.len())
+        })
+    }
+
+    fn truncate(&self, len: u64) -> Result<(), &'static str> {
+        let len = usize::try_from(len).map_err(|_| "File too large")?;
+
+        self.with_file(|file| {
+            file.resize(len, 0);
+            file.shrink_to_fit();
+
+            Ok(())
+        })
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn interface::Inode>, &'static str> {
+        self.with_dir(|entries| {
+            entries
+                .get(name)
+                .map(|inode| inode.clone() as Arc<dyn interface::Inode>)
+                .ok_or("No such file or directory")
+        })
+    }
+
+    fn read_dir(&self) -> Result<Vec<DirEntry>, &'static str> {
+        self.with_dir(|entries| {
+            entries
+                .iter()
+                .map(|(name, inode)| {
+                    Ok(DirEntry {
+                        name: name.clone(),
+                        metadata: interface::Inode::metadata(inode.as_ref())?,
+                    })
+                })
+                .collect()
+        })
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        kind: NodeKind,
+    ) -> Result<Arc<dyn interface::Inode>, &'static str> {
+        if kind == NodeKind::Device {
+            return Err("Devices live in devfs");
+        }
+
+        self.with_dir(|entries| {
+            if entries.contains_key(name) {
+                return Err("File exists");
+            }
+
+            let inode = Arc::new(RamInode::new(kind));
+            entries.insert(String::from(name), inode.clone());
+
+            Ok(inode as Arc<dyn interface::Inode>)
+        })
+    }
+
+    fn remove(&self, name: &str) -> Result<(), &'static str> {
+        self.with_dir(|entries| {
+            let inode = entries.get(name).ok_or("No such file or directory")?;
+            let is_empty_or_file = inode.content.lock(|content| match content {
+                Content::Directory(children) => children.is_empty(),
+                Content::File(_) => true,
+            });
+            if !is_empty_or_file {
+                return Err("Directory not empty");
+            }
+
+            entries.remove(name);
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,250 @@
+//! Watchdog, reboot and power-off.
+//!
+//! Once armed, the watchdog resets the board unless it is petted in time. The kernel pets it from
+//! its idle loops, i.e. while the console waits for input. A board that hangs, or that halts after
+//! a panic, therefore resets by itself. The `watchdog` boot parameter arms it during kernel init,
+//! e.g. `watchdog=10`.
+
+use crate::{
+    cpu, early_param, info, println, shell,
+    synchronization::{interface::ReadWriteEx, InitStateLock},
+    time, warn,
+};
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Watchdog interfaces.
+pub mod interface {
+    use core::time::Duration;
+
+    /// A watchdog that also resets and powers off the board.
+    pub trait Watchdog {
+        /// The longest supported timeout.
+        fn max_timeout(&self) -> Duration;
+
+        /// Arm the watchdog. It resets the board unless it is petted within `timeout`.
+        fn start(&self, timeout: Duration) -> Result<(), &'static str>;
+
+        /// Restart the timeout of the armed watchdog.
+        fn pet(&self);
+
+        /// Disarm the watchdog.
+        fn stop(&self);
+
+        /// The time until the reset, `None` if the watchdog is not armed.
+        fn time_left(&self) -> Option<Duration>;
+
+        /// Reset the board.
+        fn reset(&self) -> !;
+
+        /// Power off the board.
+        fn power_off(&self) -> !;
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static WATCHDOG: InitStateLock<Option<&'static (dyn interface::Watchdog + Sync)>> =
+    InitStateLock::new(None);
+
+/// The timeout that the `watchdog` boot parameter asks for, in seconds. 0 leaves it disarmed.
+static BOOT_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(0);
+
+/// Interval of the keepalive from the idle loops, in milliseconds. 0 if disarmed.
+///
+/// Only loads and stores, so that the keepalive also works from the panic monitor.
+static KEEPALIVE_INTERVAL_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Uptime at which the keepalive pets the watchdog next, in milliseconds.
+static NEXT_KEEPALIVE_MS: AtomicU64 = AtomicU64::new(0);
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+early_param!(
+    "watchdog",
+    "Arm the watchdog with this timeout in seconds, 0 leaves it disarmed",
+    |secs: u64| {
+        BOOT_TIMEOUT_SECS.store(secs, Ordering::Relaxed);
+        Ok(())
+    }
+);
+
+fn watchdog() -> Option<&'static (dyn interface::Watchdog + Sync)> {
+    WATCHDOG.read(|watchdog| *watchdog)
+}
+
+fn uptime_ms() -> u64 {
+    time::time_manager().uptime().as_millis() as u64
+}
+
+/// Pet twice per timeout, so that the idle loops can be late by up to half of it.
+fn schedule_keepalive(timeout: Duration) {
+    let interval = (timeout.as_millis() as u64 / 2).max(1);
+
+    NEXT_KEEPALIVE_MS.store(uptime_ms() + interval, Ordering::Relaxed);
+    KEEPALIVE_INTERVAL_MS.store(interval, Ordering::Relaxed);
+}
+
+fn cmd_watchdog(args: &[&str]) -> Result<(), &'static str> {
+    match args {
+        [] => {
+            let max_timeout = max_timeout().ok_or("No watchdog")?;
+
+            match time_left() {
+                Some(left) => println!("Armed, reset in {} ms", left.as_millis()),
+                None => println!("Disarmed"),
+            }
+            println!("Longest timeout: {} ms", max_timeout.as_millis());
+        }
+        ["start", secs] => start(Duration::from_secs(shell::parse_number(secs)? as u64))?,
+        ["pet"] => pet()?,
+        ["stop"] => stop()?,
+        _ => return Err("Expected start <secs>, pet or stop"),
+    }
+
+    Ok(())
+}
+
+fn cmd_reboot(args: &[&str]) -> Result<(), &'static str> {
+    shell::no_args(args)?;
+    reboot()
+}
+
+fn cmd_poweroff(args: &[&str]) -> Result<(), &'static str> {
+    shell::no_args(args)?;
+    poweroff()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Register the watchdog, and arm it if the `watchdog` boot parameter asks for it.
+///
+/// Only possible during kernel init.
+pub fn register_watchdog(
+    new_watchdog: &'static (dyn interface::Watchdog + Sync),
+) -> Result<(), &'static str> {
+    if watchdog().is_some() {
+        return Err("Watchdog already registered");
+    }
+    WATCHDOG.write(|watchdog| *watchdog = Some(new_watchdog));
+
+    let secs = BOOT_TIMEOUT_SECS.load(Ordering::Relaxed);
+    if secs > 0 {
+        match start(Duration::from_secs(secs)) {
+            Ok(()) => info!("Watchdog armed, timeout {} s", secs),
+            Err(x) => warn!("Could not arm the watchdog: {}", x),
+        }
+    }
+
+    Ok(())
+}
+
+/// Register the shell commands of the watchdog.
+pub fn register_shell_commands() -> Result<(), &'static str> {
+    let commands = [
+        shell::Command::new(
+            "watchdog",
+            "watchdog [start <secs>|pet|stop]",
+            "Print the watchdog state, or arm, pet or disarm it. The kernel pets it while idle",
+            cmd_watchdog,
+        ),
+        shell::Command::new("reboot", "reboot", "Reset the board", cmd_reboot),
+        shell::Command::new("poweroff", "poweroff", "Power off the board", cmd_poweroff),
+    ];
+
+    for command in commands {
+        shell::register_command(command)?;
+    }
+
+    Ok(())
+}
+
+/// Arm the watchdog. The kernel pets it from its idle loops from now on.
+pub fn start(timeout: Duration) -> Result<(), &'static str> {
+    let watchdog = watchdog().ok_or("No watchdog")?;
+
+    watchdog.start(timeout)?;
+    schedule_keepalive(timeout);
+
+    Ok(())
+}
+
+/// Restart the timeout of the armed watchdog.
+pub fn pet() -> Result<(), &'static str> {
+    watchdog().ok_or("No watchdog")?.pet();
+
+    Ok(())
+}
+
+/// Disarm the watchdog.
+pub fn stop() -> Result<(), &'static str> {
+    let watchdog = watchdog().ok_or("No watchdog")?;
+
+    KEEPALIVE_INTERVAL_MS.store(0, Ordering::Relaxed);
+    watchdog.stop();
+
+    Ok(())
+}
+
+/// The time until the watchdog resets the board, `None` if it is not armed.
+pub fn time_left() -> Option<Duration> {
+    watchdog()?.time_left()
+}
+
+/// The longest supported timeout, `None` without a watchdog.
+pub fn max_timeout() -> Option<Duration> {
+    Some(watchdog()?.max_timeout())
+}
+
+/// Pet the armed watchdog if it is due.
+///
+/// Called from loops that wait for something, e.g. for console input. Cheap enough to be called
+/// on every iteration.
+pub fn keepalive() {
+    let interval = KEEPALIVE_INTERVAL_MS.load(Ordering::Relaxed);
+    if interval == 0 {
+        return;
+    }
+
+    let now = uptime_ms();
+    if now < NEXT_KEEPALIVE_MS.load(Ordering::Relaxed) {
+        return;
+    }
+
+    if let Some(watchdog) = watchdog() {
+        watchdog.pet();
+    }
+    NEXT_KEEPALIVE_MS.store(now + interval, Ordering::Relaxed);
+}
+
+/// Reset the board.
+///
+/// Spins forever if no watchdog was registered yet.
+pub fn reboot() -> ! {
+    match watchdog() {
+        Some(watchdog) => watchdog.reset(),
+        None => cpu::wait_forever(),
+    }
+}
+
+/// Power off the board.
+///
+/// Spins forever if no watchdog was registered yet.
+pub fn poweroff() -> ! {
+    match watchdog() {
+        Some(watchdog) => watchdog.power_off(),
+        None => cpu::wait_forever(),
+    }
+}
@@ -0,0 +1,71 @@
+//! Page faults and breakpoints are handled, and execution continues where it was interrupted.
+
+#![feature(custom_test_frameworks)]
+#![no_main]
+#![no_std]
+#![reexport_test_harness_main = "test_main"]
+#![test_runner(libkernel::test_runner)]
+
+use core::arch::asm;
+use core::ptr::read_volatile;
+use libkernel::{cmdline, exception, memory, semihosting};
+use test_macros::kernel_test;
+
+/// An address outside of the kernel's virtual address space.
+const UNMAPPED_ADDRESS: usize = 8 * 1024 * 1024 * 1024;
+
+#[no_mangle]
+unsafe fn kernel_init() -> ! {
+    exception::handling_init();
+    memory::init();
+
+    if let Err(x) = semihosting::register_console() {
+        panic!("Error registering the semihosting console: {}", x);
+    }
+    cmdline::init();
+
+    test_main();
+
+    semihosting::exit_success()
+}
+
+/// Computes a value around a page fault, so that a corrupted register or return address would
+/// show in the result.
+#[inline(never)]
+fn compute_around_page_fault(x: u64) -> u64 {
+    let y = x * 2;
+    let _ = unsafe { read_volatile(UNMAPPED_ADDRESS as *const u64) };
+
+    y + 1
+}
+
+/// Computes a value around a breakpoint.
+#[inline(never)]
+fn compute_around_breakpoint(x: u64) -> u64 {
+    let y = x + 2;
+    unsafe { asm!("brk #0") };
+
+    y + 1
+}
+
+/// A page fault is recovered from, and the interrupted function returns the right value.
+#[kernel_test]
+fn page_fault_is_recovered() {
+    let sum: u64 = (0..4).map(compute_around_page_fault).sum();
+
+    assert_eq!(sum, 16);
+}
+
+/// A breakpoint without an attached debugger is stepped over.
+#[kernel_test]
+fn breakpoint_is_recovered() {
+    assert_eq!(compute_around_breakpoint(39), 42);
+}
+
+/// The kernel runs in EL1 after boot.
+#[kernel_test]
+fn runs_in_el1() {
+    let (level, _) = exception::current_privilege_level();
+
+    assert!(level == exception::PrivilegeLevel::Kernel);
+}
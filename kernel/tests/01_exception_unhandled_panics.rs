@@ -0,0 +1,26 @@
+//! An exception that the kernel does not handle ends in a kernel panic.
+
+#![no_main]
+#![no_std]
+
+mod panic_exit_success;
+
+use core::arch::asm;
+use libkernel::{exception, info, memory, semihosting};
+
+#[no_mangle]
+unsafe fn kernel_init() -> ! {
+    exception::handling_init();
+    memory::init();
+
+    if let Err(x) = semihosting::register_console() {
+        panic!("Error registering the semihosting console: {}", x);
+    }
+
+    info!("Executing a permanently undefined instruction");
+    asm!("udf #0");
+
+    // If execution reaches here, the exception was not caught and the test failed.
+    info!("Undefined instruction was not handled");
+    semihosting::exit_failure()
+}
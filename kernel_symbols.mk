@@ -98,6 +98,13 @@ symbols:
 	@$(DOCKER_TOOLS) $(EXEC_SYMBOLS_TOOL) --patch_data $(KERNEL_SYMBOLS_OUTPUT_ELF) \
                 $(KERNEL_SYMBOLS_STRIPPED)
 
+	@$(DOCKER_TOOLS) $(EXEC_SYMBOLS_TOOL) --patch_line_info $(KERNEL_SYMBOLS_OUTPUT_ELF)
+
+ifneq ($(wildcard $(INITRAMFS_DIR)),)
+	@$(DOCKER_TOOLS) $(EXEC_SYMBOLS_TOOL) --patch_initramfs $(KERNEL_SYMBOLS_OUTPUT_ELF) \
+                $(INITRAMFS_DIR)
+endif
+
 # Note: The following is the only _trivial_ way I could think of that works out of the box on both
 # Linux and macOS. Since macOS does not have the %N nanosecond format string option, the
 # resolution is restricted to whole seconds.
@@ -1,6 +1,8 @@
 //! Types for implementing debug symbol support.
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
+
+pub mod line_info;
 
 use core::ops::Range;
 
@@ -29,6 +31,11 @@ impl Symbol {
         self.addr_range.contains(&addr)
     }
 
+    /// Returns the symbol's start address.
+    pub fn start(&self) -> usize {
+        self.addr_range.start
+    }
+
     /// Returns the symbol's name.
     pub fn name(&self) -> &'static str {
         self.name
@@ -0,0 +1,286 @@
+//! Compact address to source line table.
+//!
+//! The table is generated from the DWARF line programs of the kernel ELF by the kernel symbols
+//! tool. It does not contain pointers, so that it can be patched into the kernel binary as an
+//! opaque blob. All integers are little endian.
+//!
+//! ```text
+//! Header  | magic: u32 | num_rows: u32 | num_files: u32 | strings_size: u32 | base_addr: u64 |
+//! Rows    | addr_offset: u32 | file: u32 | line: u32 |  x num_rows, sorted by addr_offset
+//! Files   | offset: u32 | len: u32 |                     x num_files, offsets into Strings
+//! Strings | UTF-8 file names, not terminated                strings_size bytes
+//! ```
+//!
+//! A row covers the addresses from its own `addr_offset` up to the one of the next row. A line of
+//! zero marks addresses without line information.
+
+use core::fmt;
+
+/// Marks a valid table: "LINE" in little endian.
+pub const MAGIC: u32 = 0x454e_494c;
+
+/// Size of the header in bytes.
+pub const HEADER_SIZE: usize = 24;
+
+/// Size of a row in bytes.
+pub const ROW_SIZE: usize = 12;
+
+/// Size of a file entry in bytes.
+pub const FILE_SIZE: usize = 8;
+
+/// A read-only view on a line table.
+#[derive(Copy, Clone)]
+pub struct LineInfo<'a> {
+    data: &'a [u8],
+    num_rows: usize,
+    num_files: usize,
+    base_addr: usize,
+}
+
+/// A source code location.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SourceLocation<'a> {
+    file: &'a str,
+    line: u32,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(8)?)?;
+
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+impl<'a> LineInfo<'a> {
+    /// Create a view on the table in `data`.
+    ///
+    /// Returns `None` if `data` does not contain a complete table, e.g. because the section was not
+    /// patched.
+    pub fn from_bytes(data: &'a [u8]) -> Option<Self> {
+        if read_u32(data, 0)? != MAGIC {
+            return None;
+        }
+
+        let num_rows = read_u32(data, 4)? as usize;
+        let num_files = read_u32(data, 8)? as usize;
+        let strings_size = read_u32(data, 12)? as usize;
+        let base_addr = read_u64(data, 16)? as usize;
+
+        let size = HEADER_SIZE
+            .checked_add(num_rows.checked_mul(ROW_SIZE)?)?
+            .checked_add(num_files.checked_mul(FILE_SIZE)?)?
+            .checked_add(strings_size)?;
+        if data.len() < size {
+            return None;
+        }
+
+        Some(Self {
+            data,
+            num_rows,
+            num_files,
+            base_addr,
+        })
+    }
+
+    /// The number of rows in the table.
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    fn row_addr_offset(&self, i: usize) -> u32 {
+        read_u32(self.data, HEADER_SIZE + i * ROW_SIZE).unwrap_or(u32::MAX)
+    }
+
+    fn file_name(&self, i: usize) -> Option<&'a str> {
+        if i >= self.num_files {
+            return None;
+        }
+
+        let files_start = HEADER_SIZE + self.num_rows * ROW_SIZE;
+        let strings_start = files_start + self.num_files * FILE_SIZE;
+
+        let offset = read_u32(self.data, files_start + i * FILE_SIZE)? as usize;
+        let len = read_u32(self.data, files_start + i * FILE_SIZE + 4)? as usize;
+        let start = strings_start.checked_add(offset)?;
+
+        core::str::from_utf8(self.data.get(start..start.checked_add(len)?)?).ok()
+    }
+
+    /// Retrieve the source location of the instruction at `addr`, if any.
+    pub fn lookup(&self, addr: usize) -> Option<SourceLocation<'a>> {
+        let offset = u32::try_from(addr.checked_sub(self.base_addr)?).ok()?;
+
+        // Binary search for the last row that starts at or before offset.
+        let (mut lo, mut hi) = (0, self.num_rows);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+
+            if self.row_addr_offset(mid) <= offset {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        let row = lo.checked_sub(1)?;
+
+        let row_start = HEADER_SIZE + row * ROW_SIZE;
+        let file = read_u32(self.data, row_start + 4)? as usize;
+        let line = read_u32(self.data, row_start + 8)?;
+        if line == 0 {
+            return None;
+        }
+
+        Some(SourceLocation {
+            file: self.file_name(file)?,
+            line,
+        })
+    }
+}
+
+impl<'a> SourceLocation<'a> {
+    /// Returns the file name.
+    pub fn file(&self) -> &'a str {
+        self.file
+    }
+
+    /// Returns the line number.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+}
+
+impl fmt::Display for SourceLocation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE_ADDR: u64 = 0x8_0000;
+
+    /// Serialize a table the way the kernel symbols tool does.
+    fn table(rows: &[(u32, u32, u32)], files: &[&str]) -> Vec<u8> {
+        let strings: String = files.concat();
+
+        let mut data = Vec::new();
+        for field in [
+            MAGIC,
+            rows.len() as u32,
+            files.len() as u32,
+            strings.len() as u32,
+        ] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(&BASE_ADDR.to_le_bytes());
+
+        for &(addr_offset, file, line) in rows {
+            for field in [addr_offset, file, line] {
+                data.extend_from_slice(&field.to_le_bytes());
+            }
+        }
+
+        let mut offset = 0;
+        for file in files {
+            data.extend_from_slice(&(offset as u32).to_le_bytes());
+            data.extend_from_slice(&(file.len() as u32).to_le_bytes());
+            offset += file.len();
+        }
+        data.extend_from_slice(strings.as_bytes());
+
+        data
+    }
+
+    fn sample() -> Vec<u8> {
+        table(
+            &[(0x0, 0, 10), (0x10, 1, 20), (0x18, 0, 0), (0x40, 1, 30)],
+            &["main.rs", "lib.rs"],
+        )
+    }
+
+    fn addr(offset: u64) -> usize {
+        (BASE_ADDR + offset) as usize
+    }
+
+    #[test]
+    fn rows_are_found() {
+        let data = sample();
+        let info = LineInfo::from_bytes(&data).unwrap();
+
+        assert_eq!(info.num_rows(), 4);
+
+        let loc = info.lookup(addr(0x0)).unwrap();
+        assert_eq!((loc.file(), loc.line()), ("main.rs", 10));
+        let loc = info.lookup(addr(0xc)).unwrap();
+        assert_eq!((loc.file(), loc.line()), ("main.rs", 10));
+        let loc = info.lookup(addr(0x10)).unwrap();
+        assert_eq!(loc.to_string(), "lib.rs:20");
+        let loc = info.lookup(addr(0x1000)).unwrap();
+        assert_eq!((loc.file(), loc.line()), ("lib.rs", 30));
+    }
+
+    #[test]
+    fn addresses_without_line_info_are_none() {
+        let data = sample();
+        let info = LineInfo::from_bytes(&data).unwrap();
+
+        // A row with line zero.
+        assert_eq!(info.lookup(addr(0x18)), None);
+        assert_eq!(info.lookup(addr(0x3c)), None);
+
+        // Below the base address, and too far above it for the 32 bit offsets.
+        assert_eq!(info.lookup(addr(0) - 4), None);
+        assert_eq!(info.lookup(addr(1 << 32)), None);
+
+        let data = table(&[], &[]);
+        let info = LineInfo::from_bytes(&data).unwrap();
+        assert_eq!(info.lookup(addr(0)), None);
+    }
+
+    #[test]
+    fn truncated_tables_are_rejected() {
+        let data = sample();
+
+        for len in 0..data.len() {
+            assert!(LineInfo::from_bytes(&data[..len]).is_none(), "len {}", len);
+        }
+
+        // An unpatched section is all zeros.
+        assert!(LineInfo::from_bytes(&[0; 64]).is_none());
+    }
+
+    #[test]
+    fn corrupt_tables_do_not_panic() {
+        let mut data = sample();
+        let files_start = HEADER_SIZE + 4 * ROW_SIZE;
+
+        // A row with a file index out of range.
+        data[HEADER_SIZE + 4..HEADER_SIZE + 8].copy_from_slice(&7u32.to_le_bytes());
+        // A file name out of the strings.
+        data[files_start + FILE_SIZE..files_start + FILE_SIZE + 4]
+            .copy_from_slice(&1000u32.to_le_bytes());
+
+        let info = LineInfo::from_bytes(&data).unwrap();
+        assert_eq!(info.lookup(addr(0x0)), None);
+        assert_eq!(info.lookup(addr(0x10)), None);
+
+        // Counts that overflow the size computation.
+        let mut data = sample();
+        data[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        data[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(LineInfo::from_bytes(&data).is_none());
+
+        // A file name that is not UTF-8.
+        let mut data = table(&[(0, 0, 1)], &["x"]);
+        *data.last_mut().unwrap() = 0xff;
+        let info = LineInfo::from_bytes(&data).unwrap();
+        assert_eq!(info.lookup(addr(0)), None);
+    }
+}
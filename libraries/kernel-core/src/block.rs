@@ -0,0 +1,135 @@
+//! Block device support.
+
+pub mod partition;
+pub mod sd;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Block device interfaces.
+pub mod interface {
+    /// A device that is accessed in blocks.
+    pub trait BlockDevice {
+        /// The size of a block in bytes.
+        fn block_size(&self) -> usize;
+
+        /// The number of blocks of the device.
+        fn num_blocks(&self) -> u64;
+
+        /// Read the consecutive blocks starting at `start_block`. The length of `buf` must be a
+        /// multiple of the block size.
+        fn read_blocks(&self, start_block: u64, buf: &mut [u8]) -> Result<(), &'static str>;
+
+        /// Write the consecutive blocks starting at `start_block`. The length of `buf` must be a
+        /// multiple of the block size.
+        fn write_blocks(&self, start_block: u64, buf: &[u8]) -> Result<(), &'static str>;
+    }
+
+    impl<T: BlockDevice + ?Sized> BlockDevice for &T {
+        fn block_size(&self) -> usize {
+            (**self).block_size()
+        }
+
+        fn num_blocks(&self) -> u64 {
+            (**self).num_blocks()
+        }
+
+        fn read_blocks(&self, start_block: u64, buf: &mut [u8]) -> Result<(), &'static str> {
+            (**self).read_blocks(start_block, buf)
+        }
+
+        fn write_blocks(&self, start_block: u64, buf: &[u8]) -> Result<(), &'static str> {
+            (**self).write_blocks(start_block, buf)
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Check a request of `len` bytes at `start_block` against the geometry of a device, and return
+/// the number of blocks it covers.
+///
+/// For implementations of `read_blocks()` and `write_blocks()`.
+pub fn check_request(
+    device: &(impl interface::BlockDevice + ?Sized),
+    start_block: u64,
+    len: usize,
+) -> Result<u64, &'static str> {
+    if !len.is_multiple_of(device.block_size()) {
+        return Err("Buffer is not a multiple of the block size");
+    }
+
+    let num_blocks = (len / device.block_size()) as u64;
+    match start_block.checked_add(num_blocks) {
+        Some(end) if end <= device.num_blocks() => Ok(num_blocks),
+        _ => Err("Blocks out of range"),
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+/// A block device in memory, for the tests of the users of block devices.
+#[cfg(test)]
+pub(crate) mod ram_disk {
+    use super::{check_request, interface::BlockDevice};
+    use std::cell::RefCell;
+
+    pub struct RamDisk {
+        pub data: RefCell<Vec<u8>>,
+    }
+
+    impl RamDisk {
+        pub fn new(num_blocks: usize) -> Self {
+            Self {
+                data: RefCell::new(vec![0; num_blocks * 512]),
+            }
+        }
+    }
+
+    impl BlockDevice for RamDisk {
+        fn block_size(&self) -> usize {
+            512
+        }
+
+        fn num_blocks(&self) -> u64 {
+            (self.data.borrow().len() / 512) as u64
+        }
+
+        fn read_blocks(&self, start_block: u64, buf: &mut [u8]) -> Result<(), &'static str> {
+            check_request(self, start_block, buf.len())?;
+            let start = start_block as usize * 512;
+            buf.copy_from_slice(&self.data.borrow()[start..start + buf.len()]);
+
+            Ok(())
+        }
+
+        fn write_blocks(&self, start_block: u64, buf: &[u8]) -> Result<(), &'static str> {
+            check_request(self, start_block, buf.len())?;
+            let start = start_block as usize * 512;
+            self.data.borrow_mut()[start..start + buf.len()].copy_from_slice(buf);
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_request, ram_disk::RamDisk};
+
+    #[test]
+    fn requests_are_checked() {
+        let disk = RamDisk::new(4);
+
+        assert_eq!(check_request(&disk, 0, 2048), Ok(4));
+        assert_eq!(check_request(&disk, 3, 512), Ok(1));
+        assert!(check_request(&disk, 3, 1024).is_err());
+        assert!(check_request(&disk, 0, 100).is_err());
+        assert!(check_request(&disk, u64::MAX, 512).is_err());
+    }
+}
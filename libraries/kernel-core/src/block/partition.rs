@@ -0,0 +1,372 @@
+//! Partition tables.
+//!
+//! Primary partitions of an MBR, and the partitions of a GPT behind a protective MBR. The CRCs of
+//! the GPT are not checked.
+
+use super::{check_request, interface::BlockDevice};
+use alloc::{vec, vec::Vec};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const SECTOR_SIZE: usize = 512;
+
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+const MBR_ENTRIES_OFFSET: usize = 446;
+const MBR_ENTRY_SIZE: usize = 16;
+const MBR_NUM_ENTRIES: usize = 4;
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const GPT_MAX_ENTRIES: usize = 1024;
+
+/// `EBD0A0A2-B9E5-4433-87C0-68B6B72699C7`, in the mixed-endian encoding of the GPT.
+const GPT_TYPE_BASIC_DATA: [u8; 16] = [
+    0xA2, 0xA0, 0xD0, 0xEB, 0xE5, 0xB9, 0x33, 0x44, 0x87, 0xC0, 0x68, 0xB6, 0xB7, 0x26, 0x99, 0xC7,
+];
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The type of a partition, as given by the partition table.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PartitionType {
+    /// The system ID of an MBR entry.
+    Mbr(u8),
+
+    /// The partition type GUID of a GPT entry, in its on-disk encoding.
+    Gpt([u8; 16]),
+}
+
+/// A partition of a block device.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Partition {
+    /// Position in the partition table, starting at 1.
+    pub number: usize,
+
+    /// The first block.
+    pub start_block: u64,
+
+    /// The size in blocks.
+    pub num_blocks: u64,
+
+    /// The type.
+    pub partition_type: PartitionType,
+}
+
+/// A partition, accessed as a block device of its own.
+pub struct PartitionDevice<D> {
+    device: D,
+    start_block: u64,
+    num_blocks: u64,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn read_u32(data: &[u8], offset: usize) -> u64 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as u64
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// Whether the first sector carries a FAT boot sector instead of an MBR. Both end with the same
+/// signature.
+fn is_fat_boot_sector(sector: &[u8]) -> bool {
+    sector[54..57] == *b"FAT" || sector[82..87] == *b"FAT32"
+}
+
+fn read_sector(device: &impl BlockDevice, lba: u64) -> Result<Vec<u8>, &'static str> {
+    let mut buf = vec![0; device.block_size()];
+    device.read_blocks(lba, &mut buf)?;
+
+    Ok(buf)
+}
+
+fn scan_mbr(sector: &[u8]) -> Option<Vec<Partition>> {
+    let entries = (0..MBR_NUM_ENTRIES)
+        .map(|i| &sector[MBR_ENTRIES_OFFSET + i * MBR_ENTRY_SIZE..][..MBR_ENTRY_SIZE]);
+
+    // The boot indicator is the only field that tells a partition table apart from boot code.
+    if entries.clone().any(|entry| entry[0] & 0x7F != 0) {
+        return None;
+    }
+
+    let partitions = entries
+        .enumerate()
+        .map(|(i, entry)| Partition {
+            number: i + 1,
+            start_block: read_u32(entry, 8),
+            num_blocks: read_u32(entry, 12),
+            partition_type: PartitionType::Mbr(entry[4]),
+        })
+        .filter(|partition| partition.partition_type != PartitionType::Mbr(0))
+        .collect();
+
+    Some(partitions)
+}
+
+fn scan_gpt(device: &impl BlockDevice) -> Result<Vec<Partition>, &'static str> {
+    let header = read_sector(device, 1)?;
+    if header[0..8] != *GPT_SIGNATURE {
+        return Err("GPT header is missing");
+    }
+
+    let entries_lba = read_u64(&header, 72);
+    let num_entries = read_u32(&header, 80) as usize;
+    let entry_size = read_u32(&header, 84) as usize;
+    if num_entries > GPT_MAX_ENTRIES
+        || entry_size < 128
+        || !device.block_size().is_multiple_of(entry_size)
+    {
+        return Err("GPT header is invalid");
+    }
+
+    let entries_per_block = device.block_size() / entry_size;
+    let mut partitions = Vec::new();
+    let mut block = Vec::new();
+    for i in 0..num_entries {
+        if i % entries_per_block == 0 {
+            block = read_sector(
+                device,
+                entries_lba.saturating_add((i / entries_per_block) as u64),
+            )?;
+        }
+
+        let entry = &block[(i % entries_per_block) * entry_size..][..entry_size];
+        let type_guid: [u8; 16] = entry[0..16].try_into().unwrap();
+        if type_guid == [0; 16] {
+            continue;
+        }
+
+        let first_lba = read_u64(entry, 32);
+        let last_lba = read_u64(entry, 40);
+        if last_lba < first_lba {
+            continue;
+        }
+
+        partitions.push(Partition {
+            number: i + 1,
+            start_block: first_lba,
+            num_blocks: last_lba - first_lba + 1,
+            partition_type: PartitionType::Gpt(type_guid),
+        });
+    }
+
+    Ok(partitions)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl PartitionType {
+    /// Whether the type announces a FAT filesystem.
+    pub fn is_fat(&self) -> bool {
+        match self {
+            Self::Mbr(id) => matches!(id, 0x01 | 0x04 | 0x06 | 0x0B | 0x0C | 0x0E),
+            Self::Gpt(guid) => *guid == GPT_TYPE_BASIC_DATA,
+        }
+    }
+}
+
+/// Read the partition table of a device.
+///
+/// An empty list means that the device has no partition table, e.g. because a filesystem starts
+/// right at the first block. Partitions that do not fit the device are left out.
+pub fn scan(device: &impl BlockDevice) -> Result<Vec<Partition>, &'static str> {
+    if device.block_size() != SECTOR_SIZE {
+        return Err("Partition tables need 512 byte blocks");
+    }
+    if device.num_blocks() == 0 {
+        return Ok(Vec::new());
+    }
+
+    let sector = read_sector(device, 0)?;
+    if sector[510..512] != MBR_SIGNATURE || is_fat_boot_sector(&sector) {
+        return Ok(Vec::new());
+    }
+
+    let Some(mut partitions) = scan_mbr(&sector) else {
+        return Ok(Vec::new());
+    };
+    if partitions
+        .iter()
+        .any(|p| p.partition_type == PartitionType::Mbr(MBR_TYPE_GPT_PROTECTIVE))
+    {
+        partitions = scan_gpt(device)?;
+    }
+
+    partitions.retain(|p| {
+        p.num_blocks != 0
+            && p.start_block
+                .checked_add(p.num_blocks)
+                .is_some_and(|end| end <= device.num_blocks())
+    });
+
+    Ok(partitions)
+}
+
+impl<D: BlockDevice> PartitionDevice<D> {
+    /// Create an instance.
+    pub fn new(device: D, partition: &Partition) -> Self {
+        Self {
+            device,
+            start_block: partition.start_block,
+            num_blocks: partition.num_blocks,
+        }
+    }
+}
+
+impl<D: BlockDevice> BlockDevice for PartitionDevice<D> {
+    fn block_size(&self) -> usize {
+        self.device.block_size()
+    }
+
+    fn num_blocks(&self) -> u64 {
+        self.num_blocks
+    }
+
+    fn read_blocks(&self, start_block: u64, buf: &mut [u8]) -> Result<(), &'static str> {
+        check_request(self, start_block, buf.len())?;
+        self.device.read_blocks(self.start_block + start_block, buf)
+    }
+
+    fn write_blocks(&self, start_block: u64, buf: &[u8]) -> Result<(), &'static str> {
+        check_request(self, start_block, buf.len())?;
+        self.device
+            .write_blocks(self.start_block + start_block, buf)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::ram_disk::RamDisk;
+    use proptest::prelude::*;
+
+    fn write_mbr_entry(disk: &RamDisk, i: usize, id: u8, start: u32, len: u32) {
+        let mut data = disk.data.borrow_mut();
+        let entry = &mut data[MBR_ENTRIES_OFFSET + i * MBR_ENTRY_SIZE..][..MBR_ENTRY_SIZE];
+
+        entry[4] = id;
+        entry[8..12].copy_from_slice(&start.to_le_bytes());
+        entry[12..16].copy_from_slice(&len.to_le_bytes());
+        data[510..512].copy_from_slice(&MBR_SIGNATURE);
+    }
+
+    #[test]
+    fn mbr_partitions() {
+        let disk = RamDisk::new(64);
+        write_mbr_entry(&disk, 0, 0x0C, 8, 32);
+        write_mbr_entry(&disk, 2, 0x83, 40, 24);
+        write_mbr_entry(&disk, 3, 0x83, 60, 24);
+
+        let partitions = scan(&disk).unwrap();
+
+        assert_eq!(
+            partitions.len(),
+            2,
+            "the partition beyond the end is dropped"
+        );
+        assert_eq!(
+            partitions[0],
+            Partition {
+                number: 1,
+                start_block: 8,
+                num_blocks: 32,
+                partition_type: PartitionType::Mbr(0x0C),
+            }
+        );
+        assert!(partitions[0].partition_type.is_fat());
+        assert_eq!(partitions[1].number, 3);
+        assert!(!partitions[1].partition_type.is_fat());
+    }
+
+    #[test]
+    fn gpt_partitions() {
+        let disk = RamDisk::new(64);
+        write_mbr_entry(&disk, 0, MBR_TYPE_GPT_PROTECTIVE, 1, 63);
+        {
+            let mut data = disk.data.borrow_mut();
+            let header = &mut data[512..1024];
+            header[0..8].copy_from_slice(GPT_SIGNATURE);
+            header[72..80].copy_from_slice(&2u64.to_le_bytes());
+            header[80..84].copy_from_slice(&128u32.to_le_bytes());
+            header[84..88].copy_from_slice(&128u32.to_le_bytes());
+
+            // The sixth entry, in the second block of entries.
+            let entry = &mut data[3 * 512 + 128..][..128];
+            entry[0..16].copy_from_slice(&GPT_TYPE_BASIC_DATA);
+            entry[32..40].copy_from_slice(&34u64.to_le_bytes());
+            entry[40..48].copy_from_slice(&63u64.to_le_bytes());
+        }
+
+        let partitions = scan(&disk).unwrap();
+
+        assert_eq!(
+            partitions,
+            [Partition {
+                number: 6,
+                start_block: 34,
+                num_blocks: 30,
+                partition_type: PartitionType::Gpt(GPT_TYPE_BASIC_DATA),
+            }]
+        );
+        assert!(partitions[0].partition_type.is_fat());
+    }
+
+    #[test]
+    fn unpartitioned_devices() {
+        let disk = RamDisk::new(8);
+        assert_eq!(scan(&disk).unwrap(), []);
+
+        // A FAT boot sector has the MBR signature, too.
+        write_mbr_entry(&disk, 0, 0x0C, 1, 4);
+        disk.data.borrow_mut()[82..87].copy_from_slice(b"FAT32");
+        assert_eq!(scan(&disk).unwrap(), []);
+    }
+
+    #[test]
+    fn partition_device_is_offset() {
+        let disk = RamDisk::new(8);
+        let partition = Partition {
+            number: 1,
+            start_block: 2,
+            num_blocks: 4,
+            partition_type: PartitionType::Mbr(0x0C),
+        };
+        let device = PartitionDevice::new(&disk, &partition);
+
+        device.write_blocks(1, &[0xAB; 512]).unwrap();
+
+        assert_eq!(disk.data.borrow()[3 * 512], 0xAB);
+        assert_eq!(device.num_blocks(), 4);
+        assert!(device.write_blocks(4, &[0; 512]).is_err());
+    }
+
+    proptest! {
+        /// Scanning arbitrary first sectors never panics, and yields partitions within the device.
+        #[test]
+        fn scan_arbitrary_tables(sectors in proptest::collection::vec(any::<u8>(), 4 * 512)) {
+            let disk = RamDisk::new(4);
+            disk.data.borrow_mut().copy_from_slice(&sectors);
+            disk.data.borrow_mut()[510..512].copy_from_slice(&MBR_SIGNATURE);
+
+            if let Ok(partitions) = scan(&disk) {
+                for p in partitions {
+                    prop_assert!(p.start_block + p.num_blocks <= 4);
+                }
+            }
+        }
+    }
+}
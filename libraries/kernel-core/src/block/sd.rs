@@ -0,0 +1,147 @@
+//! SD card register decoding.
+//!
+//! See the SD Physical Layer Simplified Specification and the SD Host Controller Simplified
+//! Specification.
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The largest divisor of the 10-bit divided clock mode of SDHCI 3.0.
+const MAX_CLOCK_DIVISOR: u32 = 0x3FF;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The block size that the kernel uses for SD cards. High capacity cards support no other.
+pub const BLOCK_SIZE: usize = 512;
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// The bits `high..=low` of a 128 bit register.
+fn field(reg: u128, high: u32, low: u32) -> u64 {
+    ((reg >> low) & ((1 << (high - low + 1)) - 1)) as u64
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// The capacity in blocks of [`BLOCK_SIZE`] bytes, decoded from the Card-Specific Data register.
+///
+/// Returns `None` for an unknown CSD structure version.
+pub fn capacity_blocks(csd: u128) -> Option<u64> {
+    match field(csd, 127, 126) {
+        // Standard capacity.
+        0 => {
+            let c_size = field(csd, 73, 62);
+            let c_size_mult = field(csd, 49, 47);
+            let read_bl_len = field(csd, 83, 80);
+            let bytes = (c_size + 1) << (c_size_mult + 2 + read_bl_len);
+
+            Some(bytes / BLOCK_SIZE as u64)
+        }
+        // High and extended capacity, in units of 512 KiB.
+        1 => Some((field(csd, 69, 48) + 1) * 1024),
+        // Ultra capacity.
+        2 => Some((field(csd, 75, 48) + 1) * 1024),
+        _ => None,
+    }
+}
+
+/// The divisor `N` of the SDHCI divided clock mode, `base_hz / (2 * N)`, that comes closest to
+/// `target_hz` without exceeding it. `N = 0` selects the undivided base clock.
+///
+/// Saturates at the largest divisor if the base clock is too fast for the target.
+pub fn clock_divisor(base_hz: u32, target_hz: u32) -> u32 {
+    if target_hz >= base_hz {
+        return 0;
+    }
+
+    let divisor = base_hz.div_ceil(2 * target_hz.max(1));
+
+    divisor.min(MAX_CLOCK_DIVISOR)
+}
+
+/// The SD clock that results from a divisor of [`clock_divisor`].
+pub fn divided_clock(base_hz: u32, divisor: u32) -> u32 {
+    if divisor == 0 {
+        return base_hz;
+    }
+
+    base_hz / (2 * divisor)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn set_field(reg: &mut u128, high: u32, low: u32, value: u64) {
+        assert_eq!(field(value as u128, high - low, 0), value);
+        *reg |= (value as u128) << low;
+    }
+
+    /// A 1 GiB standard capacity card with 1024 byte read blocks.
+    #[test]
+    fn standard_capacity() {
+        let mut csd = 0;
+        set_field(&mut csd, 83, 80, 10);
+        set_field(&mut csd, 73, 62, 4095);
+        set_field(&mut csd, 49, 47, 6);
+
+        assert_eq!(capacity_blocks(csd), Some(1024 * 1024 * 1024 / 512));
+    }
+
+    /// A 16 GiB high capacity card.
+    #[test]
+    fn high_capacity() {
+        let mut csd = 0;
+        set_field(&mut csd, 127, 126, 1);
+        set_field(&mut csd, 69, 48, 16 * 2048 - 1);
+
+        assert_eq!(capacity_blocks(csd), Some(16 * 1024 * 1024 * 2));
+    }
+
+    #[test]
+    fn unknown_structure() {
+        assert_eq!(capacity_blocks(3 << 126), None);
+    }
+
+    /// The identification clock of 400 kHz and the default speed clock of 25 MHz.
+    #[test]
+    fn common_clocks() {
+        assert_eq!(clock_divisor(50_000_000, 400_000), 63);
+        assert_eq!(divided_clock(50_000_000, 63), 396_825);
+        assert_eq!(clock_divisor(50_000_000, 25_000_000), 1);
+        assert_eq!(clock_divisor(20_000_000, 25_000_000), 0);
+        assert_eq!(clock_divisor(500_000_000, 100_000), MAX_CLOCK_DIVISOR);
+    }
+
+    proptest! {
+        /// The chosen clock never exceeds the target, and the next smaller divisor would.
+        #[test]
+        fn divisor_is_tight(
+            base_hz in 1_000_000u32..1_000_000_000,
+            target_hz in 100_000u32..100_000_000,
+        ) {
+            let divisor = clock_divisor(base_hz, target_hz);
+
+            if divisor < MAX_CLOCK_DIVISOR {
+                prop_assert!(divided_clock(base_hz, divisor) <= target_hz);
+            }
+            if divisor > 1 {
+                prop_assert!(2 * (divisor as u64 - 1) * (target_hz as u64) < base_hz as u64);
+            } else if divisor == 1 {
+                prop_assert!(base_hz > target_hz);
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! ChaCha20, and a random number generator built on it.
+//!
+//! The block function follows RFC 8439. The generator uses fast key erasure: every request for
+//! output also replaces the key, so that a later compromise of the state does not reveal earlier
+//! output.
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// "expand 32-byte k"
+const CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+/// Nonce for output blocks.
+const OUTPUT_NONCE: [u32; 3] = [0, 0, 0];
+
+/// Nonce for mixing input into the key, to separate it from output blocks.
+const MIX_NONCE: [u32; 3] = [0, 0, 1];
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Size of a key, in bytes.
+pub const KEY_SIZE: usize = 32;
+
+/// Size of a block, in bytes.
+pub const BLOCK_SIZE: usize = 64;
+
+/// A cryptographically secure random number generator.
+///
+/// It is only as good as the entropy that is mixed into it.
+pub struct ChaChaRng {
+    key: [u32; 8],
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+fn block_bytes(key: &[u32; 8], counter: u32, nonce: &[u32; 3]) -> [u8; BLOCK_SIZE] {
+    let mut bytes = [0; BLOCK_SIZE];
+
+    for (chunk, word) in bytes.chunks_exact_mut(4).zip(block(key, counter, nonce)) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+
+    bytes
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// The ChaCha20 block function.
+pub fn block(key: &[u32; 8], counter: u32, nonce: &[u32; 3]) -> [u32; 16] {
+    let mut initial = [0; 16];
+    initial[..4].copy_from_slice(&CONSTANTS);
+    initial[4..12].copy_from_slice(key);
+    initial[12] = counter;
+    initial[13..].copy_from_slice(nonce);
+
+    let mut state = initial;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    for (word, initial) in state.iter_mut().zip(initial) {
+        *word = word.wrapping_add(initial);
+    }
+
+    state
+}
+
+impl ChaChaRng {
+    /// Create an instance with an all-zero key. Its output is predictable until entropy is mixed
+    /// in.
+    pub const fn new() -> Self {
+        Self { key: [0; 8] }
+    }
+
+    /// Mix input into the key. The input does not need to be uniformly random, each bit of
+    /// entropy in it counts.
+    pub fn mix(&mut self, input: &[u8]) {
+        for chunk in input.chunks(KEY_SIZE) {
+            let mut padded = [0; KEY_SIZE];
+            padded[..chunk.len()].copy_from_slice(chunk);
+
+            for (word, bytes) in self.key.iter_mut().zip(padded.chunks_exact(4)) {
+                *word ^= u32::from_le_bytes(bytes.try_into().unwrap());
+            }
+
+            // The length goes into the counter, so that trailing zeros are not lost in padding.
+            let block = block(&self.key, chunk.len() as u32, &MIX_NONCE);
+            self.key.copy_from_slice(&block[..8]);
+        }
+    }
+
+    /// Fill `buf` with random bytes, and replace the key.
+    pub fn fill_bytes(&mut self, buf: &mut [u8]) {
+        // The first half of block 0 becomes the next key. Output starts in its second half.
+        let first = block_bytes(&self.key, 0, &OUTPUT_NONCE);
+        let (next_key, first_output) = first.split_at(KEY_SIZE);
+
+        let (head, tail) = buf.split_at_mut(buf.len().min(first_output.len()));
+        head.copy_from_slice(&first_output[..head.len()]);
+
+        for (counter, chunk) in (1..).zip(tail.chunks_mut(BLOCK_SIZE)) {
+            let block = block_bytes(&self.key, counter, &OUTPUT_NONCE);
+            chunk.copy_from_slice(&block[..chunk.len()]);
+        }
+
+        for (word, bytes) in self.key.iter_mut().zip(next_key.chunks_exact(4)) {
+            *word = u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+    }
+
+    /// A random `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+
+        u64::from_le_bytes(bytes)
+    }
+}
+
+impl Default for ChaChaRng {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// RFC 8439, section 2.3.2.
+    #[test]
+    fn block_matches_test_vector() {
+        let key = [
+            0x0302_0100,
+            0x0706_0504,
+            0x0b0a_0908,
+            0x0f0e_0d0c,
+            0x1312_1110,
+            0x1716_1514,
+            0x1b1a_1918,
+            0x1f1e_1d1c,
+        ];
+        let nonce = [0x0900_0000, 0x4a00_0000, 0x0000_0000];
+
+        assert_eq!(
+            block(&key, 1, &nonce),
+            [
+                0xe4e7_f110,
+                0x1559_3bd1,
+                0x1fdd_0f50,
+                0xc471_20a3,
+                0xc7f4_d1c7,
+                0x0368_c033,
+                0x9aaa_2204,
+                0x4e6c_d4c3,
+                0x4664_82d2,
+                0x09aa_9f07,
+                0x05d7_c214,
+                0xa202_8bd9,
+                0xd19c_12b5,
+                0xb94e_16de,
+                0xe883_d0cb,
+                0x4e3c_50a2,
+            ]
+        );
+    }
+
+    #[test]
+    fn output_depends_on_mixed_input() {
+        let mut a = ChaChaRng::new();
+        let mut b = ChaChaRng::new();
+        a.mix(b"seed");
+        b.mix(b"seed\0");
+
+        assert_ne!(a.next_u64(), b.next_u64());
+    }
+
+    proptest! {
+        #[test]
+        fn output_never_repeats(seed in proptest::collection::vec(any::<u8>(), 0..64), len in 1..200usize) {
+            let mut rng = ChaChaRng::new();
+            rng.mix(&seed);
+
+            let mut first = vec![0; len];
+            let mut second = vec![0; len];
+            rng.fill_bytes(&mut first);
+            rng.fill_bytes(&mut second);
+
+            // The key is replaced after each request, so the same stream is never handed out
+            // twice.
+            prop_assert_ne!(first, second);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Kernel command line.
+//!
+//! Arguments are separated by whitespace and are either `name=value` or a bare `name`. Double
+//! quotes group whitespace into a value, e.g. `name="a b"`, and are not part of it.
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// An argument of the command line.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Arg<'a> {
+    /// The name, up to the first `=`.
+    pub name: &'a str,
+
+    /// The value after the first `=`, without quotes. `None` for a bare name.
+    pub value: Option<&'a str>,
+}
+
+/// Iterator over the arguments of a command line.
+pub struct Args<'a> {
+    rest: &'a str,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn strip_quotes(s: &str) -> &str {
+    let s = s.strip_prefix('"').unwrap_or(s);
+
+    s.strip_suffix('"').unwrap_or(s)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// The arguments of a command line.
+pub fn args(line: &str) -> Args<'_> {
+    Args { rest: line }
+}
+
+impl<'a> Iterator for Args<'a> {
+    type Item = Arg<'a>;
+
+    fn next(&mut self) -> Option<Arg<'a>> {
+        self.rest = self.rest.trim_start();
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        let mut in_quotes = false;
+        let end = self
+            .rest
+            .char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    in_quotes = !in_quotes;
+                }
+                c.is_whitespace() && !in_quotes
+            })
+            .map_or(self.rest.len(), |(i, _)| i);
+
+        let (arg, rest) = self.rest.split_at(end);
+        self.rest = rest;
+
+        Some(match arg.split_once('=') {
+            Some((name, value)) => Arg {
+                name,
+                value: Some(strip_quotes(value)),
+            },
+            None => Arg {
+                name: arg,
+                value: None,
+            },
+        })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn arg<'a>(name: &'a str, value: Option<&'a str>) -> Arg<'a> {
+        Arg { name, value }
+    }
+
+    #[test]
+    fn arguments_are_split() {
+        let args: Vec<_> = args("  loglevel=debug quiet\tconsole=mini_uart,pl011 a==b  ").collect();
+
+        assert_eq!(
+            args,
+            [
+                arg("loglevel", Some("debug")),
+                arg("quiet", None),
+                arg("console", Some("mini_uart,pl011")),
+                arg("a", Some("=b")),
+            ]
+        );
+    }
+
+    #[test]
+    fn quotes_group_whitespace() {
+        let args: Vec<_> = args(r#"motd="hello world" x="" y=1"#).collect();
+
+        assert_eq!(
+            args,
+            [
+                arg("motd", Some("hello world")),
+                arg("x", Some("")),
+                arg("y", Some("1")),
+            ]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn arguments_round_trip(
+            pairs in proptest::collection::vec(("[a-z_.]{1,10}", "[a-z0-9,]{0,10}"), 0..10),
+        ) {
+            let line: Vec<String> =
+                pairs.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+            let line = line.join(" ");
+
+            let args: Vec<_> = args(&line).collect();
+
+            prop_assert_eq!(args.len(), pairs.len());
+            for (arg, (name, value)) in args.iter().zip(&pairs) {
+                prop_assert_eq!(arg.name, name.as_str());
+                prop_assert_eq!(arg.value, Some(value.as_str()));
+            }
+        }
+    }
+}
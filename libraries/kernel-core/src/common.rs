@@ -0,0 +1,202 @@
+//! General purpose code.
+
+/// Check if a value is aligned to a given size.
+#[inline(always)]
+pub const fn is_aligned(value: usize, alignment: usize) -> bool {
+    assert!(alignment.is_power_of_two());
+
+    (value & (alignment - 1)) == 0
+}
+
+/// Align down.
+#[inline(always)]
+pub const fn align_down(value: usize, alignment: usize) -> usize {
+    assert!(alignment.is_power_of_two());
+
+    value & !(alignment - 1)
+}
+
+/// Align up.
+#[inline(always)]
+pub const fn align_up(value: usize, alignment: usize) -> usize {
+    assert!(alignment.is_power_of_two());
+
+    (value + alignment - 1) & !(alignment - 1)
+}
+
+/// Convert a size into human readable format.
+pub const fn size_human_readable_ceil(size: usize) -> (usize, &'static str) {
+    const KIB: usize = 1024;
+    const MIB: usize = 1024 * 1024;
+    const GIB: usize = 1024 * 1024 * 1024;
+
+    if (size / GIB) > 0 {
+        (size.div_ceil(GIB), "GiB")
+    } else if (size / MIB) > 0 {
+        (size.div_ceil(MIB), "MiB")
+    } else if (size / KIB) > 0 {
+        (size.div_ceil(KIB), "KiB")
+    } else {
+        (size, "Byte")
+    }
+}
+
+/// A fixed-size FIFO of bytes.
+pub struct RingBuffer<const N: usize> {
+    data: [u8; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> RingBuffer<N> {
+    /// Create an instance.
+    pub const fn new() -> Self {
+        Self {
+            data: [0; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Check if the buffer is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Check if the buffer is full.
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Append a byte. Returns false if the buffer is full.
+    pub fn try_push(&mut self, byte: u8) -> bool {
+        if self.is_full() {
+            return false;
+        }
+
+        self.data[(self.head + self.len) % N] = byte;
+        self.len += 1;
+
+        true
+    }
+
+    /// Remove the oldest byte.
+    pub fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let byte = self.data[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+
+        Some(byte)
+    }
+
+    /// Remove all bytes.
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+}
+
+impl<const N: usize> Default for RingBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::VecDeque;
+
+    /// Operations on a [RingBuffer].
+    #[derive(Clone, Debug)]
+    enum Op {
+        Push(u8),
+        Pop,
+        Clear,
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            4 => any::<u8>().prop_map(Op::Push),
+            3 => Just(Op::Pop),
+            1 => Just(Op::Clear),
+        ]
+    }
+
+    proptest! {
+        /// Aligning down gives the closest aligned value that is not greater.
+        #[test]
+        fn align_down_is_closest_aligned(value in any::<usize>(), shift in 0..32u32) {
+            let alignment = 1 << shift;
+            let aligned = align_down(value, alignment);
+
+            prop_assert!(is_aligned(aligned, alignment));
+            prop_assert!(aligned <= value);
+            prop_assert!(value - aligned < alignment);
+        }
+
+        /// Aligning up gives the closest aligned value that is not smaller.
+        #[test]
+        fn align_up_is_closest_aligned(value in 0..usize::MAX >> 1, shift in 0..32u32) {
+            let alignment = 1 << shift;
+            let aligned = align_up(value, alignment);
+
+            prop_assert!(is_aligned(aligned, alignment));
+            prop_assert!(aligned >= value);
+            prop_assert!(aligned - value < alignment);
+            prop_assert_eq!(is_aligned(value, alignment), aligned == value);
+        }
+
+        /// The human readable size is rounded up to the next full unit.
+        #[test]
+        fn size_human_readable_is_ceiled(size in any::<usize>()) {
+            let (value, unit) = size_human_readable_ceil(size);
+            let unit_size: u128 = match unit {
+                "Byte" => 1,
+                "KiB" => 1 << 10,
+                "MiB" => 1 << 20,
+                "GiB" => 1 << 30,
+                _ => unreachable!(),
+            };
+
+            prop_assert!(value as u128 * unit_size >= size as u128);
+            prop_assert!(size == 0 || (value as u128 - 1) * unit_size < size as u128);
+            prop_assert!(unit == "GiB" || value <= 1024);
+        }
+
+        /// A ring buffer behaves like an unbounded FIFO, except that pushing to a full one fails.
+        #[test]
+        fn ring_buffer_is_fifo(ops in prop::collection::vec(op(), 0..256)) {
+            let mut buffer = RingBuffer::<16>::new();
+            let mut model = VecDeque::new();
+
+            for op in ops {
+                match op {
+                    Op::Push(byte) => {
+                        prop_assert_eq!(buffer.try_push(byte), model.len() < 16);
+                        if model.len() < 16 {
+                            model.push_back(byte);
+                        }
+                    }
+                    Op::Pop => prop_assert_eq!(buffer.pop(), model.pop_front()),
+                    Op::Clear => {
+                        buffer.clear();
+                        model.clear();
+                    }
+                }
+
+                prop_assert_eq!(buffer.is_empty(), model.is_empty());
+                prop_assert_eq!(buffer.is_full(), model.len() == 16);
+            }
+        }
+    }
+}
@@ -0,0 +1,4 @@
+//! Console support.
+
+pub mod ansi;
+pub mod psf;
@@ -0,0 +1,465 @@
+//! ANSI escape sequences.
+//!
+//! Parses the output that is written to a console into printable characters and the subset of
+//! control functions that the kernel's own output uses: Colors, cursor movement and erasing.
+//! Unsupported sequences are consumed and dropped.
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const ESC: char = '\x1b';
+
+/// Parameters beyond this number are ignored.
+const MAX_PARAMS: usize = 8;
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum State {
+    Ground,
+    Escape,
+    ControlSequence,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// One of the 16 colors of the ANSI palette. Index 0 to 7 are the normal colors, 8 to 15 their
+/// bright variants.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Color(u8);
+
+/// How text is rendered.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Attributes {
+    /// Text color.
+    pub foreground: Color,
+
+    /// Background color.
+    pub background: Color,
+
+    /// Bold text is shown with the bright variant of the foreground color.
+    pub bold: bool,
+
+    /// Swap the foreground and background colors.
+    pub reverse: bool,
+}
+
+/// Which part of the screen or line to erase, relative to the cursor.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EraseMode {
+    ToEnd,
+    ToStart,
+    All,
+}
+
+/// What the console shall do.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Action {
+    /// Show a character at the cursor and advance it.
+    Print(char),
+
+    /// `\n`: Move the cursor to the start of the next line, scrolling if needed.
+    LineFeed,
+
+    /// `\r`: Move the cursor to the start of the line.
+    CarriageReturn,
+
+    /// `\x08`: Move the cursor one column to the left.
+    Backspace,
+
+    /// `\t`: Move the cursor to the next tab stop.
+    Tab,
+
+    /// Move the cursor up by the given number of lines.
+    CursorUp(usize),
+
+    /// Move the cursor down by the given number of lines.
+    CursorDown(usize),
+
+    /// Move the cursor right by the given number of columns.
+    CursorForward(usize),
+
+    /// Move the cursor left by the given number of columns.
+    CursorBack(usize),
+
+    /// Move the cursor to a zero-based position.
+    CursorPosition {
+        /// Zero-based line.
+        row: usize,
+
+        /// Zero-based column.
+        column: usize,
+    },
+
+    /// Erase part of the screen.
+    EraseDisplay(EraseMode),
+
+    /// Erase part of the cursor's line.
+    EraseLine(EraseMode),
+
+    /// Use new attributes for the following characters.
+    SetAttributes(Attributes),
+}
+
+/// Turns the characters written to a console into [`Action`]s.
+pub struct Parser {
+    state: State,
+    params: [usize; MAX_PARAMS],
+    num_params: usize,
+    private: bool,
+    attributes: Attributes,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl EraseMode {
+    fn from_param(param: usize) -> Option<Self> {
+        match param {
+            0 => Some(Self::ToEnd),
+            1 => Some(Self::ToStart),
+            2 => Some(Self::All),
+            _ => None,
+        }
+    }
+}
+
+impl Parser {
+    fn ground(&mut self, c: char) -> Option<Action> {
+        match c {
+            ESC => {
+                self.state = State::Escape;
+                None
+            }
+            '\n' => Some(Action::LineFeed),
+            '\r' => Some(Action::CarriageReturn),
+            '\x08' => Some(Action::Backspace),
+            '\t' => Some(Action::Tab),
+            c if c.is_control() => None,
+            c => Some(Action::Print(c)),
+        }
+    }
+
+    fn escape(&mut self, c: char) -> Option<Action> {
+        if c == '[' {
+            self.state = State::ControlSequence;
+            self.params = [0; MAX_PARAMS];
+            self.num_params = 0;
+            self.private = false;
+        } else {
+            // Other escape sequences are not supported.
+            self.state = State::Ground;
+        }
+
+        None
+    }
+
+    fn control_sequence(&mut self, c: char) -> Option<Action> {
+        match c {
+            '0'..='9' => {
+                let index = self.num_params.max(1) - 1;
+                self.num_params = self.num_params.max(1);
+                if index < MAX_PARAMS {
+                    let digit = c as usize - '0' as usize;
+                    self.params[index] =
+                        self.params[index].saturating_mul(10).saturating_add(digit);
+                }
+                None
+            }
+            ';' => {
+                self.num_params = self.num_params.max(1) + 1;
+                None
+            }
+            '<'..='?' => {
+                self.private = true;
+                None
+            }
+            '\x40'..='\x7E' => {
+                self.state = State::Ground;
+                if self.private {
+                    None
+                } else {
+                    self.dispatch(c)
+                }
+            }
+            // Intermediate bytes are not supported, but do not end the sequence.
+            '\x20'..='\x2F' => None,
+            _ => {
+                self.state = State::Ground;
+                None
+            }
+        }
+    }
+
+    /// The parameter at `index`, with 0 and omitted parameters replaced by `default`.
+    fn param_or(&self, index: usize, default: usize) -> usize {
+        match self.params().get(index) {
+            Some(&x) if x != 0 => x,
+            _ => default,
+        }
+    }
+
+    fn params(&self) -> &[usize] {
+        &self.params[..self.num_params.min(MAX_PARAMS)]
+    }
+
+    fn dispatch(&mut self, final_byte: char) -> Option<Action> {
+        let action = match final_byte {
+            'A' => Action::CursorUp(self.param_or(0, 1)),
+            'B' => Action::CursorDown(self.param_or(0, 1)),
+            'C' => Action::CursorForward(self.param_or(0, 1)),
+            'D' => Action::CursorBack(self.param_or(0, 1)),
+            'H' | 'f' => Action::CursorPosition {
+                row: self.param_or(0, 1) - 1,
+                column: self.param_or(1, 1) - 1,
+            },
+            'J' => Action::EraseDisplay(EraseMode::from_param(self.param_or(0, 0))?),
+            'K' => Action::EraseLine(EraseMode::from_param(self.param_or(0, 0))?),
+            'm' => {
+                self.select_graphic_rendition();
+                Action::SetAttributes(self.attributes)
+            }
+            _ => return None,
+        };
+
+        Some(action)
+    }
+
+    fn select_graphic_rendition(&mut self) {
+        // No parameters mean reset.
+        if self.params().is_empty() {
+            self.attributes = Attributes::DEFAULT;
+            return;
+        }
+
+        for i in 0..self.params().len() {
+            let attr = &mut self.attributes;
+            match self.params[i] {
+                0 => *attr = Attributes::DEFAULT,
+                1 => attr.bold = true,
+                7 => attr.reverse = true,
+                22 => attr.bold = false,
+                27 => attr.reverse = false,
+                x @ 30..=37 => attr.foreground = Color((x - 30) as u8),
+                39 => attr.foreground = Attributes::DEFAULT.foreground,
+                x @ 40..=47 => attr.background = Color((x - 40) as u8),
+                49 => attr.background = Attributes::DEFAULT.background,
+                x @ 90..=97 => attr.foreground = Color((x - 90 + 8) as u8),
+                x @ 100..=107 => attr.background = Color((x - 100 + 8) as u8),
+                _ => (),
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Color {
+    /// Black, the default background.
+    pub const BLACK: Self = Self(0);
+
+    /// Light gray, the default foreground.
+    pub const WHITE: Self = Self(7);
+
+    /// The index into the palette, 0 to 15.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// The bright variant of a normal color.
+    pub fn bright(self) -> Self {
+        Self(self.0 | 8)
+    }
+
+    /// The color as `0xRRGGBB`, using the palette of the VGA text mode.
+    pub fn rgb(self) -> u32 {
+        const PALETTE: [u32; 16] = [
+            0x00_00_00, 0xAA_00_00, 0x00_AA_00, 0xAA_55_00, 0x00_00_AA, 0xAA_00_AA, 0x00_AA_AA,
+            0xAA_AA_AA, 0x55_55_55, 0xFF_55_55, 0x55_FF_55, 0xFF_FF_55, 0x55_55_FF, 0xFF_55_FF,
+            0x55_FF_FF, 0xFF_FF_FF,
+        ];
+
+        PALETTE[self.index()]
+    }
+}
+
+impl Attributes {
+    /// Light gray on black.
+    pub const DEFAULT: Self = Self {
+        foreground: Color::WHITE,
+        background: Color::BLACK,
+        bold: false,
+        reverse: false,
+    };
+
+    /// The colors to draw with, as foreground and background.
+    pub fn colors(&self) -> (Color, Color) {
+        let foreground = if self.bold {
+            self.foreground.bright()
+        } else {
+            self.foreground
+        };
+
+        if self.reverse {
+            (self.background, foreground)
+        } else {
+            (foreground, self.background)
+        }
+    }
+}
+
+impl Parser {
+    /// Create an instance.
+    pub const fn new() -> Self {
+        Self {
+            state: State::Ground,
+            params: [0; MAX_PARAMS],
+            num_params: 0,
+            private: false,
+            attributes: Attributes::DEFAULT,
+        }
+    }
+
+    /// Feed the next character. Returns the action that it completes, if any.
+    pub fn advance(&mut self, c: char) -> Option<Action> {
+        match self.state {
+            State::Ground => self.ground(c),
+            State::Escape => self.escape(c),
+            State::ControlSequence => self.control_sequence(c),
+        }
+    }
+
+    /// The attributes set by the sequences so far.
+    pub fn attributes(&self) -> Attributes {
+        self.attributes
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn parse(input: &str) -> Vec<Action> {
+        let mut parser = Parser::new();
+
+        input.chars().filter_map(|c| parser.advance(c)).collect()
+    }
+
+    /// Plain text and the common control characters.
+    #[test]
+    fn text_and_controls() {
+        assert_eq!(
+            parse("a\r\n\tb\x08\x07"),
+            [
+                Action::Print('a'),
+                Action::CarriageReturn,
+                Action::LineFeed,
+                Action::Tab,
+                Action::Print('b'),
+                Action::Backspace,
+            ]
+        );
+    }
+
+    /// Colors, bold and reset.
+    #[test]
+    fn select_graphic_rendition() {
+        let red_bold = Attributes {
+            foreground: Color(1),
+            bold: true,
+            ..Attributes::DEFAULT
+        };
+
+        assert_eq!(
+            parse("\x1b[31;1mx\x1b[0m"),
+            [
+                Action::SetAttributes(red_bold),
+                Action::Print('x'),
+                Action::SetAttributes(Attributes::DEFAULT),
+            ]
+        );
+        assert_eq!(red_bold.colors(), (Color(9), Color::BLACK));
+
+        let bright_on_blue = Attributes {
+            foreground: Color(10),
+            background: Color(4),
+            ..Attributes::DEFAULT
+        };
+        assert_eq!(
+            parse("\x1b[92;44m"),
+            [Action::SetAttributes(bright_on_blue)]
+        );
+        assert_eq!(
+            parse("\x1b[92;44m\x1b[m"),
+            [
+                Action::SetAttributes(bright_on_blue),
+                Action::SetAttributes(Attributes::DEFAULT),
+            ]
+        );
+    }
+
+    /// The sequences of the shell's line editor.
+    #[test]
+    fn cursor_and_erase() {
+        assert_eq!(
+            parse("\x1b[K\x1b[12D\x1b[A\x1b[2J\x1b[H\x1b[3;4H"),
+            [
+                Action::EraseLine(EraseMode::ToEnd),
+                Action::CursorBack(12),
+                Action::CursorUp(1),
+                Action::EraseDisplay(EraseMode::All),
+                Action::CursorPosition { row: 0, column: 0 },
+                Action::CursorPosition { row: 2, column: 3 },
+            ]
+        );
+    }
+
+    /// Unsupported sequences are swallowed completely.
+    #[test]
+    fn unsupported_sequences_are_dropped() {
+        assert_eq!(parse("\x1b[?25lx"), [Action::Print('x')]);
+        assert_eq!(parse("\x1b[5ny"), [Action::Print('y')]);
+        assert_eq!(parse("\x1bcz"), [Action::Print('z')]);
+        assert_eq!(parse("\x1b[9Ka"), [Action::Print('a')]);
+    }
+
+    proptest! {
+        /// Text without escapes and control characters is printed as is.
+        #[test]
+        fn plain_text_is_printed(text in "[^\\x00-\\x1f\\x7f-\\x9f]*") {
+            let expected: Vec<_> = text.chars().map(Action::Print).collect();
+
+            prop_assert_eq!(parse(&text), expected);
+        }
+
+        /// Arbitrary input never panics, and the parser recovers at the next plain character.
+        #[test]
+        fn arbitrary_input_recovers(garbage in any::<String>()) {
+            let mut parser = Parser::new();
+            for c in garbage.chars() {
+                parser.advance(c);
+            }
+
+            // Terminate a pending sequence. 'm' also ends a sequence in its parameters.
+            parser.advance('m');
+            parser.advance('m');
+            prop_assert_eq!(parser.advance('x'), Some(Action::Print('x')));
+        }
+    }
+}
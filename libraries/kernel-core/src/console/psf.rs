@@ -0,0 +1,284 @@
+//! PC Screen Font.
+//!
+//! Bitmap fonts in the format of the Linux console. Versions 1 and 2 are supported. Unicode tables
+//! are ignored, glyphs are looked up by code point.
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE_512: u8 = 0x01;
+const PSF1_HEADER_SIZE: usize = 4;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+const PSF2_HEADER_SIZE: usize = 32;
+
+/// The misc-fixed 8x13 font of X11, which is in the public domain, for the code points of Latin-1.
+static DEFAULT_FONT_DATA: &[u8] = include_bytes!("fonts/misc-fixed-8x13.psf");
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A parsed font. Borrows the font file.
+#[derive(Copy, Clone)]
+pub struct Font<'a> {
+    glyphs: &'a [u8],
+    num_glyphs: usize,
+    bytes_per_glyph: usize,
+    width: usize,
+    height: usize,
+}
+
+/// The bitmap of a single character.
+#[derive(Copy, Clone)]
+pub struct Glyph<'a> {
+    rows: &'a [u8],
+    bytes_per_row: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn read_u32(data: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ]) as usize
+}
+
+impl<'a> Font<'a> {
+    fn parse_psf1(data: &'a [u8]) -> Result<Self, &'static str> {
+        if data.len() < PSF1_HEADER_SIZE {
+            return Err("PSF1 header is truncated");
+        }
+
+        let num_glyphs = if data[2] & PSF1_MODE_512 != 0 {
+            512
+        } else {
+            256
+        };
+        let height = data[3] as usize;
+
+        Self::new(&data[PSF1_HEADER_SIZE..], num_glyphs, height, 8, height)
+    }
+
+    fn parse_psf2(data: &'a [u8]) -> Result<Self, &'static str> {
+        if data.len() < PSF2_HEADER_SIZE {
+            return Err("PSF2 header is truncated");
+        }
+
+        let header_size = read_u32(data, 8);
+        let num_glyphs = read_u32(data, 16);
+        let bytes_per_glyph = read_u32(data, 20);
+        let height = read_u32(data, 24);
+        let width = read_u32(data, 28);
+
+        if header_size < PSF2_HEADER_SIZE || header_size > data.len() {
+            return Err("PSF2 header size is invalid");
+        }
+
+        Self::new(
+            &data[header_size..],
+            num_glyphs,
+            bytes_per_glyph,
+            width,
+            height,
+        )
+    }
+
+    fn new(
+        glyph_data: &'a [u8],
+        num_glyphs: usize,
+        bytes_per_glyph: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, &'static str> {
+        if num_glyphs == 0 || width == 0 || height == 0 {
+            return Err("Font has no glyphs");
+        }
+        let min_bytes_per_glyph = width
+            .div_ceil(8)
+            .checked_mul(height)
+            .ok_or("Font is too large")?;
+        if bytes_per_glyph < min_bytes_per_glyph {
+            return Err("Font glyphs are smaller than their dimensions");
+        }
+
+        let size = num_glyphs
+            .checked_mul(bytes_per_glyph)
+            .ok_or("Font is too large")?;
+        if glyph_data.len() < size {
+            return Err("Font glyphs are truncated");
+        }
+
+        Ok(Self {
+            glyphs: &glyph_data[..size],
+            num_glyphs,
+            bytes_per_glyph,
+            width,
+            height,
+        })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl<'a> Font<'a> {
+    /// Parse a PSF1 or PSF2 font file.
+    pub fn parse(data: &'a [u8]) -> Result<Self, &'static str> {
+        if data.starts_with(&PSF2_MAGIC) {
+            Self::parse_psf2(data)
+        } else if data.starts_with(&PSF1_MAGIC) {
+            Self::parse_psf1(data)
+        } else {
+            Err("Not a PSF font")
+        }
+    }
+
+    /// Width of a glyph in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height of a glyph in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The glyph of a character. Characters that the font does not cover are shown as `?`, or as
+    /// the first glyph if the font lacks that, too.
+    pub fn glyph(&self, c: char) -> Glyph<'a> {
+        let index = [c as usize, '?' as usize]
+            .into_iter()
+            .find(|&x| x < self.num_glyphs)
+            .unwrap_or(0);
+        let start = index * self.bytes_per_glyph;
+
+        Glyph {
+            rows: &self.glyphs[start..start + self.bytes_per_glyph],
+            bytes_per_row: self.width.div_ceil(8),
+        }
+    }
+}
+
+impl Glyph<'_> {
+    /// Whether the pixel at column `x` of row `y` is set. The most significant bit is the leftmost
+    /// pixel.
+    pub fn is_set(&self, x: usize, y: usize) -> bool {
+        let byte = self.rows[y * self.bytes_per_row + x / 8];
+
+        byte & (0x80 >> (x % 8)) != 0
+    }
+}
+
+/// The font that the kernel ships with.
+pub fn default_font() -> Font<'static> {
+    Font::parse(DEFAULT_FONT_DATA).unwrap()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn glyph_rows(font: &Font, c: char) -> Vec<String> {
+        let glyph = font.glyph(c);
+
+        (0..font.height())
+            .map(|y| {
+                (0..font.width())
+                    .map(|x| if glyph.is_set(x, y) { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The shipped font parses and has the expected dimensions.
+    #[test]
+    fn default_font_is_8x13() {
+        let font = default_font();
+
+        assert_eq!(font.width(), 8);
+        assert_eq!(font.height(), 13);
+    }
+
+    /// Printable characters have pixels, the space has none.
+    #[test]
+    fn default_font_glyphs_are_drawn() {
+        let font = default_font();
+
+        for c in '!'..='~' {
+            assert!(glyph_rows(&font, c).concat().contains('#'), "{:?}", c);
+        }
+        assert!(!glyph_rows(&font, ' ').concat().contains('#'));
+    }
+
+    /// Characters beyond the font are replaced.
+    #[test]
+    fn uncovered_characters_are_replaced() {
+        let font = default_font();
+
+        assert_eq!(glyph_rows(&font, '\u{20AC}'), glyph_rows(&font, '?'));
+    }
+
+    /// A hand-made PSF2 font with a glyph that is wider than a byte.
+    #[test]
+    fn psf2_wide_glyphs() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&PSF2_MAGIC);
+        for field in [0, 32, 0, 1, 4, 2, 10] {
+            data.extend_from_slice(&(field as u32).to_le_bytes());
+        }
+        data.extend_from_slice(&[0b1000_0000, 0b0100_0000, 0b0000_0000, 0b0000_0000]);
+
+        let font = Font::parse(&data).unwrap();
+        let glyph = font.glyph('\0');
+
+        assert_eq!((font.width(), font.height()), (10, 2));
+        assert!(glyph.is_set(0, 0));
+        assert!(glyph.is_set(9, 0));
+        assert!(!glyph.is_set(1, 0));
+        assert!(!glyph.is_set(0, 1));
+    }
+
+    /// Broken headers are rejected.
+    #[test]
+    fn broken_fonts_are_rejected() {
+        assert!(Font::parse(&[]).is_err());
+        assert!(Font::parse(b"not a font").is_err());
+        assert!(Font::parse(&[0x36, 0x04, 0x00, 16, 0xFF]).is_err());
+        assert!(Font::parse(&PSF2_MAGIC).is_err());
+    }
+
+    proptest! {
+        /// Parsing never panics, and a parsed font hands out glyphs for any character.
+        #[test]
+        fn parse_arbitrary_data(
+            prefix in prop_oneof![Just(PSF1_MAGIC.to_vec()), Just(PSF2_MAGIC.to_vec())],
+            rest in proptest::collection::vec(any::<u8>(), 0..256),
+            c in any::<char>(),
+        ) {
+            let data = [prefix, rest].concat();
+
+            if let Ok(font) = Font::parse(&data) {
+                let glyph = font.glyph(c);
+                for y in 0..font.height() {
+                    for x in 0..font.width() {
+                        glyph.is_set(x, y);
+                    }
+                }
+            }
+        }
+    }
+}
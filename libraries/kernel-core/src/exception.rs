@@ -0,0 +1,3 @@
+//! Exception handling.
+
+pub mod asynchronous;
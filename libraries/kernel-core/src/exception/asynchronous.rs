@@ -0,0 +1,57 @@
+//! Asynchronous exception handling.
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Wrapper struct for a bitmask indicating pending IRQ numbers.
+///
+/// Iterates over the numbers of the set bits, lowest first.
+pub struct PendingIRQs {
+    bitmask: u64,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl PendingIRQs {
+    /// Create an instance.
+    pub fn new(bitmask: u64) -> Self {
+        Self { bitmask }
+    }
+}
+
+impl Iterator for PendingIRQs {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bitmask == 0 {
+            return None;
+        }
+
+        let next = self.bitmask.trailing_zeros() as usize;
+        self.bitmask &= self.bitmask.wrapping_sub(1);
+        Some(next)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        /// Exactly the set bits are yielded, in ascending order.
+        #[test]
+        fn yields_set_bits_in_order(bitmask in any::<u64>()) {
+            let expected: Vec<usize> = (0..64).filter(|i| bitmask & (1 << i) != 0).collect();
+
+            prop_assert_eq!(PendingIRQs::new(bitmask).collect::<Vec<_>>(), expected);
+        }
+    }
+}
@@ -0,0 +1,318 @@
+//! Flattened device trees, as handed over by the firmware.
+//!
+//! Only what is needed to read single properties, e.g. `/chosen/bootargs`. All numbers are big
+//! endian.
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// Byte offsets of the header fields.
+const OFFSET_TOTAL_SIZE: usize = 4;
+const OFFSET_STRUCT: usize = 8;
+const OFFSET_STRINGS: usize = 12;
+const OFFSET_STRINGS_SIZE: usize = 32;
+const OFFSET_STRUCT_SIZE: usize = 36;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The magic number at the start of the header.
+pub const MAGIC: u32 = 0xd00d_feed;
+
+/// Size of the header, which contains the size of the whole tree.
+pub const HEADER_SIZE: usize = 40;
+
+/// A device tree blob.
+pub struct Fdt<'a> {
+    structs: &'a [u8],
+    strings: &'a [u8],
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn be32(data: &[u8], offset: usize) -> Result<u32, &'static str> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .ok_or("Device tree is truncated")?;
+
+    Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// The NUL terminated string at `offset`.
+fn c_str(data: &[u8], offset: usize) -> Result<&str, &'static str> {
+    let rest = data.get(offset..).ok_or("Device tree is truncated")?;
+    let len = rest
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or("Device tree string is not terminated")?;
+
+    core::str::from_utf8(&rest[..len]).map_err(|_| "Device tree string is not UTF-8")
+}
+
+fn align4(offset: usize) -> usize {
+    offset.next_multiple_of(4)
+}
+
+/// Whether a node name matches a path component. The unit address may be omitted, so that
+/// `memory` matches `memory@0`.
+fn node_name_matches(node_name: &str, component: &str) -> bool {
+    node_name == component
+        || (!component.contains('@') && node_name.split('@').next() == Some(component))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// The size of the whole tree, from its header.
+pub fn total_size(header: &[u8]) -> Result<usize, &'static str> {
+    if be32(header, 0)? != MAGIC {
+        return Err("Invalid device tree magic");
+    }
+
+    Ok(be32(header, OFFSET_TOTAL_SIZE)? as usize)
+}
+
+/// The string value of a property, without the terminating NUL.
+pub fn property_str(value: &[u8]) -> Result<&str, &'static str> {
+    c_str(value, 0)
+}
+
+impl<'a> Fdt<'a> {
+    /// Check the header and locate the structure and strings blocks.
+    pub fn new(data: &'a [u8]) -> Result<Self, &'static str> {
+        let total_size = total_size(data)?;
+        let data = data.get(..total_size).ok_or("Device tree is truncated")?;
+
+        let block = |offset, size| -> Result<&'a [u8], &'static str> {
+            let start = be32(data, offset)? as usize;
+            let size = be32(data, size)? as usize;
+
+            start
+                .checked_add(size)
+                .and_then(|end| data.get(start..end))
+                .ok_or("Device tree block out of bounds")
+        };
+
+        Ok(Self {
+            structs: block(OFFSET_STRUCT, OFFSET_STRUCT_SIZE)?,
+            strings: block(OFFSET_STRINGS, OFFSET_STRINGS_SIZE)?,
+        })
+    }
+
+    /// The value of a property of the node at `node_path`, e.g. `/chosen`.
+    pub fn property(&self, node_path: &str, name: &str) -> Result<Option<&'a [u8]>, &'static str> {
+        let structs = self.structs;
+        let mut components = node_path.split('/').filter(|c| !c.is_empty());
+        let num_components = components.clone().count();
+
+        // Depth of the current node, with 1 being the root node, and the number of path
+        // components that the current node and its parents match.
+        let mut depth = 0;
+        let mut matched = 0;
+        let mut component = components.next();
+        let mut pos = 0;
+
+        loop {
+            let token = be32(structs, pos)?;
+            pos += 4;
+
+            match token {
+                FDT_BEGIN_NODE => {
+                    let node_name = c_str(structs, pos)?;
+                    pos = align4(pos + node_name.len() + 1);
+
+                    if depth >= 1
+                        && matched == depth - 1
+                        && component.is_some_and(|c| node_name_matches(node_name, c))
+                    {
+                        matched += 1;
+                        component = components.next();
+                    }
+                    depth += 1;
+                }
+                FDT_END_NODE => {
+                    if depth == 0 {
+                        return Err("Unbalanced device tree nodes");
+                    }
+                    if depth >= 2 && matched == depth - 1 {
+                        // Leaving a matched node. Its siblings can not match anymore.
+                        return Ok(None);
+                    }
+                    depth -= 1;
+                }
+                FDT_PROP => {
+                    let len = be32(structs, pos)? as usize;
+                    let name_offset = be32(structs, pos + 4)? as usize;
+                    let value = structs
+                        .get(pos + 8..pos + 8 + len)
+                        .ok_or("Device tree is truncated")?;
+                    pos = align4(pos + 8 + len);
+
+                    if depth >= 1
+                        && matched == depth - 1
+                        && matched == num_components
+                        && c_str(self.strings, name_offset)? == name
+                    {
+                        return Ok(Some(value));
+                    }
+                }
+                FDT_NOP => (),
+                FDT_END => return Ok(None),
+                _ => return Err("Invalid device tree token"),
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the blocks of a tree.
+    #[derive(Default)]
+    struct Builder {
+        structs: Vec<u8>,
+        strings: Vec<u8>,
+    }
+
+    impl Builder {
+        fn token(&mut self, token: u32) -> &mut Self {
+            self.structs.extend_from_slice(&token.to_be_bytes());
+            self
+        }
+
+        fn begin(&mut self, name: &str) -> &mut Self {
+            self.token(FDT_BEGIN_NODE);
+            self.structs.extend_from_slice(name.as_bytes());
+            self.structs.push(0);
+            self.structs.resize(align4(self.structs.len()), 0);
+            self
+        }
+
+        fn end(&mut self) -> &mut Self {
+            self.token(FDT_END_NODE)
+        }
+
+        fn prop(&mut self, name: &str, value: &[u8]) -> &mut Self {
+            let name_offset = self.strings.len() as u32;
+            self.strings.extend_from_slice(name.as_bytes());
+            self.strings.push(0);
+
+            self.token(FDT_PROP)
+                .token(value.len() as u32)
+                .token(name_offset);
+            self.structs.extend_from_slice(value);
+            self.structs.resize(align4(self.structs.len()), 0);
+            self
+        }
+
+        fn build(&mut self) -> Vec<u8> {
+            self.token(FDT_END);
+
+            let struct_offset = HEADER_SIZE + 16;
+            let strings_offset = struct_offset + self.structs.len();
+            let total_size = strings_offset + self.strings.len();
+
+            let mut data = Vec::new();
+            for field in [
+                MAGIC,
+                total_size as u32,
+                struct_offset as u32,
+                strings_offset as u32,
+                HEADER_SIZE as u32,
+                17,
+                16,
+                0,
+                self.strings.len() as u32,
+                self.structs.len() as u32,
+            ] {
+                data.extend_from_slice(&field.to_be_bytes());
+            }
+            // Empty memory reservation map.
+            data.extend_from_slice(&[0; 16]);
+            data.extend_from_slice(&self.structs);
+            data.extend_from_slice(&self.strings);
+
+            data
+        }
+    }
+
+    fn sample() -> Vec<u8> {
+        Builder::default()
+            .begin("")
+            .prop("model", b"Raspberry Pi 3\0")
+            .begin("memory@0")
+            .prop("reg", &[0; 8])
+            .end()
+            .begin("soc")
+            .begin("chosen")
+            .prop("bootargs", b"wrong\0")
+            .end()
+            .end()
+            .begin("chosen")
+            .token(FDT_NOP)
+            .prop("stdout-path", b"serial0\0")
+            .prop("bootargs", b"loglevel=debug\0")
+            .end()
+            .end()
+            .build()
+    }
+
+    #[test]
+    fn properties_are_found() {
+        let data = sample();
+        let fdt = Fdt::new(&data).unwrap();
+
+        let bootargs = fdt.property("/chosen", "bootargs").unwrap().unwrap();
+        assert_eq!(property_str(bootargs), Ok("loglevel=debug"));
+        assert_eq!(
+            property_str(fdt.property("/", "model").unwrap().unwrap()),
+            Ok("Raspberry Pi 3")
+        );
+        assert_eq!(fdt.property("/memory", "reg").unwrap().unwrap().len(), 8);
+        assert_eq!(
+            fdt.property("/soc/chosen", "bootargs").unwrap(),
+            Some(&b"wrong\0"[..])
+        );
+    }
+
+    #[test]
+    fn missing_properties_are_none() {
+        let data = sample();
+        let fdt = Fdt::new(&data).unwrap();
+
+        assert_eq!(fdt.property("/chosen", "initrd-start").unwrap(), None);
+        assert_eq!(fdt.property("/aliases", "serial0").unwrap(), None);
+        assert_eq!(fdt.property("/", "bootargs").unwrap(), None);
+    }
+
+    #[test]
+    fn broken_trees_are_rejected() {
+        let data = sample();
+
+        assert_eq!(total_size(&data), Ok(data.len()));
+        assert!(Fdt::new(&data[..data.len() - 1]).is_err());
+        assert!(Fdt::new(&[0; 64]).is_err());
+
+        let mut data = data;
+        // Cut the structure block short, in the middle of the tree.
+        data[OFFSET_STRUCT_SIZE..OFFSET_STRUCT_SIZE + 4].copy_from_slice(&24u32.to_be_bytes());
+        let fdt = Fdt::new(&data).unwrap();
+        assert!(fdt.property("/chosen", "bootargs").is_err());
+    }
+}
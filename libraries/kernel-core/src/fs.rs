@@ -0,0 +1,5 @@
+//! Filesystems.
+
+pub mod cpio;
+pub mod fat;
+pub mod path;
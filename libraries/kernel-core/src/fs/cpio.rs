@@ -0,0 +1,261 @@
+//! cpio archives in the portable "newc" format.
+//!
+//! Each entry is a header of ASCII hex fields, followed by the NUL terminated path and the data,
+//! both padded to four bytes. The archive ends with an entry named `TRAILER!!!`.
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const HEADER_SIZE: usize = 110;
+const FIELD_SIZE: usize = 8;
+const TRAILER: &str = "TRAILER!!!";
+
+/// Field indices, after the magic.
+const FIELD_MODE: usize = 1;
+const FIELD_FILE_SIZE: usize = 6;
+const FIELD_NAME_SIZE: usize = 11;
+
+const MODE_TYPE_MASK: u32 = 0o170000;
+const MODE_DIRECTORY: u32 = 0o040000;
+const MODE_FILE: u32 = 0o100000;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The magic number that every header starts with.
+pub const MAGIC: &[u8; 6] = b"070701";
+
+/// The type of an entry.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EntryKind {
+    /// A regular file.
+    File,
+
+    /// A directory.
+    Directory,
+
+    /// Anything else, e.g. a symbolic link or a device node.
+    Other,
+}
+
+/// An entry of an archive.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Entry<'a> {
+    /// The path, without a leading `./` or `/`. Empty for the root directory.
+    pub path: &'a str,
+
+    /// The type.
+    pub kind: EntryKind,
+
+    /// The contents of a file.
+    pub data: &'a [u8],
+}
+
+/// Iterator over the entries of an archive. Stops after the first error.
+pub struct Entries<'a> {
+    archive: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn align4(offset: usize) -> usize {
+    offset.next_multiple_of(4)
+}
+
+fn field(header: &[u8], index: usize) -> Result<usize, &'static str> {
+    let start = MAGIC.len() + index * FIELD_SIZE;
+    let digits = core::str::from_utf8(&header[start..start + FIELD_SIZE])
+        .map_err(|_| "Invalid cpio header")?;
+
+    usize::from_str_radix(digits, 16).map_err(|_| "Invalid cpio header")
+}
+
+impl<'a> Entries<'a> {
+    fn parse_next(&mut self) -> Result<Option<Entry<'a>>, &'static str> {
+        let header = self
+            .archive
+            .get(self.offset..)
+            .and_then(|rest| rest.get(..HEADER_SIZE))
+            .ok_or("cpio archive is truncated")?;
+        if !header.starts_with(MAGIC) {
+            return Err("Invalid cpio magic");
+        }
+
+        let mode = field(header, FIELD_MODE)? as u32;
+        let file_size = field(header, FIELD_FILE_SIZE)?;
+        let name_size = field(header, FIELD_NAME_SIZE)?;
+
+        let name_start = self.offset + HEADER_SIZE;
+        let name = name_start
+            .checked_add(name_size)
+            .and_then(|name_end| self.archive.get(name_start..name_end))
+            .ok_or("cpio archive is truncated")?;
+        let Some((0, name)) = name.split_last() else {
+            return Err("cpio path is not terminated");
+        };
+        let name = core::str::from_utf8(name).map_err(|_| "cpio path is not UTF-8")?;
+
+        let data_start = align4(name_start + name_size);
+        let data = data_start
+            .checked_add(file_size)
+            .and_then(|data_end| self.archive.get(data_start..data_end))
+            .ok_or("cpio archive is truncated")?;
+        self.offset = align4(data_start + file_size);
+
+        if name == TRAILER {
+            return Ok(None);
+        }
+
+        let kind = match mode & MODE_TYPE_MASK {
+            MODE_FILE => EntryKind::File,
+            MODE_DIRECTORY => EntryKind::Directory,
+            _ => EntryKind::Other,
+        };
+        let path = name.trim_start_matches("./").trim_start_matches('/');
+
+        Ok(Some(Entry {
+            path: if path == "." { "" } else { path },
+            kind,
+            data,
+        }))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// The entries of an archive, up to the trailer.
+pub fn entries(archive: &[u8]) -> Entries<'_> {
+    Entries {
+        archive,
+        offset: 0,
+        done: false,
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Result<Entry<'a>, &'static str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let entry = self.parse_next().transpose();
+        if !matches!(entry, Some(Ok(_))) {
+            self.done = true;
+        }
+
+        entry
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn append(archive: &mut Vec<u8>, name: &str, mode: u32, data: &[u8]) {
+        archive.extend_from_slice(MAGIC);
+        let fields = [
+            1,
+            mode as usize,
+            0,
+            0,
+            1,
+            0,
+            data.len(),
+            0,
+            0,
+            0,
+            0,
+            name.len() + 1,
+            0,
+        ];
+        for value in fields {
+            archive.extend_from_slice(format!("{:08X}", value).as_bytes());
+        }
+        archive.extend_from_slice(name.as_bytes());
+        archive.push(0);
+        archive.resize(align4(archive.len()), 0);
+        archive.extend_from_slice(data);
+        archive.resize(align4(archive.len()), 0);
+    }
+
+    fn archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        append(&mut archive, ".", MODE_DIRECTORY | 0o755, &[]);
+        for (name, data) in files {
+            append(&mut archive, name, MODE_FILE | 0o644, data);
+        }
+        append(&mut archive, TRAILER, 0, &[]);
+
+        archive
+    }
+
+    #[test]
+    fn entries_are_parsed() {
+        let mut data = archive(&[("./etc/motd", b"Hello\n"), ("bin/test", b"")]);
+        // Trailing padding, e.g. from the section the archive is stored in.
+        data.extend_from_slice(&[0; 64]);
+
+        let entries: Vec<_> = entries(&data).collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].path, "");
+        assert_eq!(entries[0].kind, EntryKind::Directory);
+        assert_eq!(entries[1].path, "etc/motd");
+        assert_eq!(entries[1].kind, EntryKind::File);
+        assert_eq!(entries[1].data, b"Hello\n");
+        assert_eq!(entries[2].path, "bin/test");
+    }
+
+    #[test]
+    fn broken_archives_are_rejected() {
+        let data = archive(&[("file", b"contents")]);
+
+        for len in [0, 50, 120, data.len() - 4] {
+            assert!(entries(&data[..len]).any(|entry| entry.is_err()));
+        }
+        assert_eq!(entries(&[0; 256]).next(), Some(Err("Invalid cpio magic")));
+    }
+
+    proptest! {
+        #[test]
+        fn files_round_trip(
+            files in proptest::collection::vec(
+                ("[a-z]{1,10}(/[a-z]{1,10}){0,2}", proptest::collection::vec(any::<u8>(), 0..100)),
+                0..8,
+            ),
+        ) {
+            let files: Vec<(&str, &[u8])> =
+                files.iter().map(|(name, data)| (name.as_str(), data.as_slice())).collect();
+            let data = archive(&files);
+
+            let entries: Vec<_> = entries(&data).skip(1).collect::<Result<_, _>>().unwrap();
+
+            prop_assert_eq!(entries.len(), files.len());
+            for (entry, (name, contents)) in entries.iter().zip(files) {
+                prop_assert_eq!(entry.path, name);
+                prop_assert_eq!(entry.data, contents);
+            }
+        }
+
+        /// Parsing arbitrary data never panics.
+        #[test]
+        fn arbitrary_data(data in proptest::collection::vec(any::<u8>(), 0..512)) {
+            for _ in entries(&data) {}
+        }
+    }
+}
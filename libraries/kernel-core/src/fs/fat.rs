@@ -0,0 +1,1037 @@
+//! FAT32.
+//!
+//! Directories with long file names, reading and writing of files, and allocation of clusters.
+//! Whether a volume is FAT32 is decided by its boot sector alone, not by its number of clusters.
+//! There is no clock, so timestamps are not maintained.
+
+mod dir;
+
+use crate::block::interface::BlockDevice;
+use alloc::{string::String, vec, vec::Vec};
+use dir::{ShortName, Slot, SLOT_SIZE};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+const MIN_SECTOR_SIZE: usize = 512;
+
+const FSINFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FSINFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+const FSINFO_FREE_COUNT_OFFSET: usize = 488;
+const FSINFO_NEXT_FREE_OFFSET: usize = 492;
+const FSINFO_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+const FIRST_CLUSTER: u32 = 2;
+const CLUSTER_MASK: u32 = 0x0FFF_FFFF;
+const CLUSTER_FREE: u32 = 0;
+const CLUSTER_END_OF_CHAIN: u32 = 0x0FFF_FFFF;
+const CLUSTER_MIN_END_OF_CHAIN: u32 = 0x0FFF_FFF8;
+
+/// Directories must not grow beyond this many slots.
+const MAX_DIR_SLOTS: usize = 65536;
+
+/// Candidates for numbered short names, `~1` to `~999999`.
+const MAX_SHORT_NAME_NUMBER: usize = 999_999;
+
+/// The sector of the FAT that was read last.
+struct FatSector {
+    number: Option<u64>,
+    data: Vec<u8>,
+}
+
+/// The slots of a directory, together with the clusters that hold them.
+struct DirSlots {
+    clusters: Vec<u32>,
+    slots: Vec<Slot>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A mounted FAT32 volume.
+pub struct FileSystem<D> {
+    device: D,
+    sector_size: usize,
+    sectors_per_cluster: u32,
+    fat_start: u64,
+    sectors_per_fat: u64,
+    num_fats: u32,
+    data_start: u64,
+    num_clusters: u32,
+    root_cluster: u32,
+    fsinfo_sector: Option<u64>,
+    next_free: u32,
+    fsinfo_invalidated: bool,
+}
+
+/// A file or directory.
+#[derive(Clone, Debug)]
+pub struct DirEntry {
+    name: String,
+    attributes: u8,
+    first_cluster: u32,
+    size: u32,
+
+    /// The sector and byte offset of the short entry. The root directory has none.
+    location: Option<(u64, usize)>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Split a path into the path of its parent and its last component.
+fn split_path(path: &str) -> Result<(&str, &str), &'static str> {
+    let path = path.trim_end_matches('/');
+
+    match path.rsplit_once('/') {
+        Some((parent, name)) => Ok((parent, name)),
+        None if !path.is_empty() => Ok(("", path)),
+        None => Err("Invalid path"),
+    }
+}
+
+impl FatSector {
+    fn new(sector_size: usize) -> Self {
+        Self {
+            number: None,
+            data: vec![0; sector_size],
+        }
+    }
+}
+
+impl DirEntry {
+    fn from_raw(raw: dir::RawEntry, location: (u64, usize)) -> Self {
+        Self {
+            name: raw.name,
+            attributes: raw.attributes,
+            first_cluster: raw.first_cluster,
+            size: raw.size,
+            location: Some(location),
+        }
+    }
+}
+
+impl<D: BlockDevice> FileSystem<D> {
+    fn cluster_size(&self) -> usize {
+        self.sectors_per_cluster as usize * self.sector_size
+    }
+
+    fn cluster_sector(&self, cluster: u32) -> u64 {
+        self.data_start + (cluster - FIRST_CLUSTER) as u64 * self.sectors_per_cluster as u64
+    }
+
+    fn is_valid_cluster(&self, cluster: u32) -> bool {
+        (FIRST_CLUSTER..FIRST_CLUSTER + self.num_clusters).contains(&cluster)
+    }
+
+    fn read_sector(&self, sector: u64, buf: &mut [u8]) -> Result<(), &'static str> {
+        self.device.read_blocks(sector, buf)
+    }
+
+    fn write_sector(&self, sector: u64, buf: &[u8]) -> Result<(), &'static str> {
+        self.device.write_blocks(sector, buf)
+    }
+
+    fn fat_entry(&self, cluster: u32, cache: &mut FatSector) -> Result<u32, &'static str> {
+        let offset = cluster as usize * 4;
+        let sector = self.fat_start + (offset / self.sector_size) as u64;
+
+        if cache.number != Some(sector) {
+            cache.number = None;
+            self.read_sector(sector, &mut cache.data)?;
+            cache.number = Some(sector);
+        }
+
+        Ok(read_u32(&cache.data, offset % self.sector_size) & CLUSTER_MASK)
+    }
+
+    /// Update an entry in all copies of the FAT.
+    fn set_fat_entry(&mut self, cluster: u32, value: u32) -> Result<(), &'static str> {
+        let offset = cluster as usize * 4;
+        let mut sector = vec![0; self.sector_size];
+
+        for fat in 0..self.num_fats as u64 {
+            let number =
+                self.fat_start + fat * self.sectors_per_fat + (offset / self.sector_size) as u64;
+            self.read_sector(number, &mut sector)?;
+
+            // The upper four bits are reserved and must be preserved.
+            let entry = &mut sector[offset % self.sector_size..][..4];
+            let old = read_u32(entry, 0);
+            entry.copy_from_slice(&((old & !CLUSTER_MASK) | (value & CLUSTER_MASK)).to_le_bytes());
+
+            self.write_sector(number, &sector)?;
+        }
+
+        self.invalidate_fsinfo()
+    }
+
+    /// The free cluster count of the FSInfo sector goes stale with the first change to the FAT.
+    /// Mark it as unknown, so that other systems recount.
+    fn invalidate_fsinfo(&mut self) -> Result<(), &'static str> {
+        let Some(fsinfo_sector) = self.fsinfo_sector else {
+            return Ok(());
+        };
+        if self.fsinfo_invalidated {
+            return Ok(());
+        }
+
+        let mut sector = vec![0; self.sector_size];
+        self.read_sector(fsinfo_sector, &mut sector)?;
+        sector[FSINFO_FREE_COUNT_OFFSET..][..4].copy_from_slice(&FSINFO_UNKNOWN.to_le_bytes());
+        self.write_sector(fsinfo_sector, &sector)?;
+        self.fsinfo_invalidated = true;
+
+        Ok(())
+    }
+
+    /// The clusters of the chain that starts at `first_cluster`.
+    fn chain(&self, first_cluster: u32) -> Result<Vec<u32>, &'static str> {
+        let mut clusters = Vec::new();
+        if first_cluster == CLUSTER_FREE {
+            return Ok(clusters);
+        }
+
+        let mut cache = FatSector::new(self.sector_size);
+        let mut cluster = first_cluster;
+        loop {
+            if !self.is_valid_cluster(cluster) || clusters.len() >= self.num_clusters as usize {
+                return Err("FAT chain is corrupt");
+            }
+            clusters.push(cluster);
+
+            cluster = self.fat_entry(cluster, &mut cache)?;
+            if cluster >= CLUSTER_MIN_END_OF_CHAIN {
+                return Ok(clusters);
+            }
+        }
+    }
+
+    /// Allocate a cluster and terminate the chain with it. Appended to `last` if given.
+    fn allocate_cluster(&mut self, last: Option<u32>) -> Result<u32, &'static str> {
+        let mut cache = FatSector::new(self.sector_size);
+        let start = self.next_free - FIRST_CLUSTER;
+
+        for i in 0..self.num_clusters {
+            let cluster = FIRST_CLUSTER + (start + i) % self.num_clusters;
+            if self.fat_entry(cluster, &mut cache)? != CLUSTER_FREE {
+                continue;
+            }
+
+            self.set_fat_entry(cluster, CLUSTER_END_OF_CHAIN)?;
+            if let Some(last) = last {
+                self.set_fat_entry(last, cluster)?;
+            }
+            self.next_free = if cluster + 1 < FIRST_CLUSTER + self.num_clusters {
+                cluster + 1
+            } else {
+                FIRST_CLUSTER
+            };
+
+            return Ok(cluster);
+        }
+
+        Err("No space left on device")
+    }
+
+    /// Extend a chain to `count` clusters, and return the clusters. Clusters that were allocated
+    /// are released again if the volume runs full.
+    fn extend_chain(
+        &mut self,
+        mut clusters: Vec<u32>,
+        count: usize,
+    ) -> Result<Vec<u32>, &'static str> {
+        let old_len = clusters.len();
+
+        while clusters.len() < count {
+            match self.allocate_cluster(clusters.last().copied()) {
+                Ok(cluster) => clusters.push(cluster),
+                Err(e) => {
+                    if old_len > 0 && clusters.len() > old_len {
+                        self.set_fat_entry(clusters[old_len - 1], CLUSTER_END_OF_CHAIN)?;
+                    }
+                    self.free_clusters(&clusters[old_len..])?;
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(clusters)
+    }
+
+    fn free_clusters(&mut self, clusters: &[u32]) -> Result<(), &'static str> {
+        for &cluster in clusters {
+            self.set_fat_entry(cluster, CLUSTER_FREE)?;
+        }
+
+        Ok(())
+    }
+
+    /// The sector and byte offset of a position in a file.
+    fn file_position(&self, clusters: &[u32], position: u64) -> Result<(u64, usize), &'static str> {
+        let cluster_size = self.cluster_size() as u64;
+        let cluster = *clusters
+            .get((position / cluster_size) as usize)
+            .ok_or("FAT chain is shorter than the file")?;
+        let offset = (position % cluster_size) as usize;
+
+        Ok((
+            self.cluster_sector(cluster) + (offset / self.sector_size) as u64,
+            offset % self.sector_size,
+        ))
+    }
+
+    fn zero_cluster(&self, cluster: u32) -> Result<(), &'static str> {
+        self.write_sector(self.cluster_sector(cluster), &vec![0; self.cluster_size()])
+    }
+
+    fn dir_slots(&self, first_cluster: u32) -> Result<DirSlots, &'static str> {
+        let clusters = self.chain(first_cluster)?;
+        let mut data = vec![0; self.cluster_size()];
+        let mut slots = Vec::new();
+
+        for &cluster in &clusters {
+            self.read_sector(self.cluster_sector(cluster), &mut data)?;
+            slots.extend(
+                data.chunks_exact(SLOT_SIZE)
+                    .map(|slot| -> Slot { slot.try_into().unwrap() }),
+            );
+        }
+
+        Ok(DirSlots { clusters, slots })
+    }
+
+    /// The sector and byte offset of a slot of a directory.
+    fn slot_location(&self, clusters: &[u32], slot: usize) -> (u64, usize) {
+        let offset = slot * SLOT_SIZE;
+        let cluster = clusters[offset / self.cluster_size()];
+        let offset = offset % self.cluster_size();
+
+        (
+            self.cluster_sector(cluster) + (offset / self.sector_size) as u64,
+            offset % self.sector_size,
+        )
+    }
+
+    /// Modify the slot at a location.
+    fn update_slot(
+        &self,
+        (sector, offset): (u64, usize),
+        f: impl FnOnce(&mut Slot),
+    ) -> Result<(), &'static str> {
+        let mut data = vec![0; self.sector_size];
+        self.read_sector(sector, &mut data)?;
+
+        let mut slot: Slot = data[offset..offset + SLOT_SIZE].try_into().unwrap();
+        f(&mut slot);
+        data[offset..offset + SLOT_SIZE].copy_from_slice(&slot);
+
+        self.write_sector(sector, &data)
+    }
+
+    /// Write the first cluster and the size of an entry back to its directory.
+    fn update_entry(&self, entry: &DirEntry) -> Result<(), &'static str> {
+        let location = entry.location.ok_or("Entry has no directory slot")?;
+
+        self.update_slot(location, |slot| {
+            dir::set_cluster_and_size(slot, entry.first_cluster, entry.size)
+        })
+    }
+
+    fn open_dir(&self, path: &str) -> Result<DirEntry, &'static str> {
+        let dir = self.open(path)?;
+        if !dir.is_dir() {
+            return Err("Not a directory");
+        }
+
+        Ok(dir)
+    }
+
+    /// The first cluster of a directory, as it is referred to by `..` entries.
+    fn dot_dot_cluster(&self, dir: &DirEntry) -> u32 {
+        if dir.first_cluster == self.root_cluster {
+            CLUSTER_FREE
+        } else {
+            dir.first_cluster
+        }
+    }
+
+    fn short_name_for(
+        &self,
+        name: &str,
+        entries: &[dir::RawEntry],
+    ) -> Result<ShortName, &'static str> {
+        let is_taken = |short_name: &ShortName| entries.iter().any(|e| &e.short_name == short_name);
+
+        if let Some(short_name) = dir::exact_short_name(name).filter(|s| !is_taken(s)) {
+            return Ok(short_name);
+        }
+
+        (1..=MAX_SHORT_NAME_NUMBER)
+            .map(|n| dir::numbered_short_name(name, n))
+            .find(|short_name| !is_taken(short_name))
+            .ok_or("No short name available")
+    }
+
+    fn create_entry(&mut self, path: &str, attributes: u8) -> Result<DirEntry, &'static str> {
+        let (parent_path, name) = split_path(path)?;
+        if !dir::is_valid_name(name) {
+            return Err("Invalid file name");
+        }
+
+        let parent = self.open_dir(parent_path)?;
+        let DirSlots {
+            mut clusters,
+            mut slots,
+        } = self.dir_slots(parent.first_cluster)?;
+        let entries = dir::parse(&slots);
+        if entries.iter().any(|e| dir::names_equal(&e.name, name)) {
+            return Err("File exists");
+        }
+        let short_name = self.short_name_for(name, &entries)?;
+
+        // Find the first run of free slots that is long enough. It may run past the end of the
+        // directory, which is then extended.
+        let num_slots = dir::encode(name, &short_name, attributes, 0).len();
+        let mut start = 0;
+        for (i, slot) in slots.iter().enumerate() {
+            if i - start >= num_slots {
+                break;
+            }
+            if slot[0] != dir::SLOT_END && slot[0] != dir::SLOT_FREE {
+                start = i + 1;
+            }
+        }
+        if start + num_slots > MAX_DIR_SLOTS {
+            return Err("Directory is full");
+        }
+        if start + num_slots > slots.len() {
+            let slots_per_cluster = self.cluster_size() / SLOT_SIZE;
+            let count = (start + num_slots).div_ceil(slots_per_cluster);
+            let old_len = clusters.len();
+
+            clusters = self.extend_chain(clusters, count)?;
+            for &cluster in &clusters[old_len..] {
+                self.zero_cluster(cluster)?;
+            }
+            slots.resize(count * slots_per_cluster, [0; SLOT_SIZE]);
+        }
+
+        let first_cluster = if attributes & dir::ATTR_DIRECTORY != 0 {
+            let cluster = self.extend_chain(Vec::new(), 1)?[0];
+            self.zero_cluster(cluster)?;
+
+            let dots = dir::dot_entries(cluster, self.dot_dot_cluster(&parent));
+            let mut data = vec![0; self.sector_size];
+            data[..2 * SLOT_SIZE].copy_from_slice(dots.as_flattened());
+            self.write_sector(self.cluster_sector(cluster), &data)?;
+
+            cluster
+        } else {
+            CLUSTER_FREE
+        };
+
+        let new_slots = dir::encode(name, &short_name, attributes, first_cluster);
+        for (i, new_slot) in new_slots.iter().enumerate() {
+            let location = self.slot_location(&clusters, start + i);
+            self.update_slot(location, |slot| *slot = *new_slot)?;
+        }
+
+        Ok(DirEntry {
+            name: String::from(name),
+            attributes,
+            first_cluster,
+            size: 0,
+            location: Some(self.slot_location(&clusters, start + new_slots.len() - 1)),
+        })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl DirEntry {
+    /// The name. The long name if there is one.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.attributes & dir::ATTR_DIRECTORY != 0
+    }
+
+    /// The size in bytes. Always 0 for directories.
+    pub fn size(&self) -> u64 {
+        self.size as u64
+    }
+}
+
+impl<D: BlockDevice> FileSystem<D> {
+    /// Mount the FAT32 volume of a block device.
+    pub fn mount(device: D) -> Result<Self, &'static str> {
+        let sector_size = device.block_size();
+        if sector_size < MIN_SECTOR_SIZE {
+            return Err("Block size too small for FAT");
+        }
+
+        let mut sector = vec![0; sector_size];
+        device.read_blocks(0, &mut sector)?;
+        if sector[510..512] != BOOT_SIGNATURE {
+            return Err("No FAT boot sector");
+        }
+
+        let bytes_per_sector = read_u16(&sector, 11) as usize;
+        let sectors_per_cluster = sector[13] as u32;
+        let reserved_sectors = read_u16(&sector, 14) as u64;
+        let num_fats = sector[16] as u32;
+        let root_entry_count = read_u16(&sector, 17);
+        let total_sectors_16 = read_u16(&sector, 19) as u64;
+        let sectors_per_fat_16 = read_u16(&sector, 22);
+        let total_sectors_32 = read_u32(&sector, 32) as u64;
+        let sectors_per_fat = read_u32(&sector, 36) as u64;
+        let root_cluster = read_u32(&sector, 44);
+        let fsinfo_sector = read_u16(&sector, 48) as u64;
+
+        if bytes_per_sector != sector_size {
+            return Err("FAT sector size differs from the block size");
+        }
+        if !sectors_per_cluster.is_power_of_two() || reserved_sectors == 0 || num_fats == 0 {
+            return Err("Invalid FAT boot sector");
+        }
+        if root_entry_count != 0 || sectors_per_fat_16 != 0 || sectors_per_fat == 0 {
+            return Err("Not a FAT32 volume");
+        }
+
+        let total_sectors = if total_sectors_16 != 0 {
+            total_sectors_16
+        } else {
+            total_sectors_32
+        };
+        if total_sectors > device.num_blocks() {
+            return Err("FAT volume exceeds the device");
+        }
+
+        let data_start = reserved_sectors + num_fats as u64 * sectors_per_fat;
+        if data_start >= total_sectors {
+            return Err("Invalid FAT boot sector");
+        }
+        let fat_capacity = sectors_per_fat * (sector_size / 4) as u64 - FIRST_CLUSTER as u64;
+        let num_clusters = ((total_sectors - data_start) / sectors_per_cluster as u64)
+            .min(fat_capacity)
+            .min((CLUSTER_MIN_END_OF_CHAIN - FIRST_CLUSTER) as u64)
+            as u32;
+
+        let mut fs = Self {
+            device,
+            sector_size,
+            sectors_per_cluster,
+            fat_start: reserved_sectors,
+            sectors_per_fat,
+            num_fats,
+            data_start,
+            num_clusters,
+            root_cluster,
+            fsinfo_sector: None,
+            next_free: FIRST_CLUSTER,
+            fsinfo_invalidated: false,
+        };
+        if !fs.is_valid_cluster(root_cluster) {
+            return Err("Invalid FAT root cluster");
+        }
+
+        // The FSInfo sector is optional, and only a hint.
+        if fsinfo_sector != 0 && fsinfo_sector < reserved_sectors {
+            fs.read_sector(fsinfo_sector, &mut sector)?;
+            if read_u32(&sector, 0) == FSINFO_LEAD_SIGNATURE
+                && read_u32(&sector, 484) == FSINFO_STRUCT_SIGNATURE
+                && sector[510..512] == BOOT_SIGNATURE
+            {
+                fs.fsinfo_sector = Some(fsinfo_sector);
+
+                let next_free = read_u32(&sector, FSINFO_NEXT_FREE_OFFSET);
+                if fs.is_valid_cluster(next_free) {
+                    fs.next_free = next_free;
+                }
+            }
+        }
+
+        Ok(fs)
+    }
+
+    /// The size of the volume in bytes.
+    pub fn size(&self) -> u64 {
+        self.num_clusters as u64 * self.cluster_size() as u64
+    }
+
+    /// The root directory.
+    pub fn root(&self) -> DirEntry {
+        DirEntry {
+            name: String::new(),
+            attributes: dir::ATTR_DIRECTORY,
+            first_cluster: self.root_cluster,
+            size: 0,
+            location: None,
+        }
+    }
+
+    /// Look up a file or directory. Components are separated by `/` and compared ignoring case.
+    pub fn open(&self, path: &str) -> Result<DirEntry, &'static str> {
+        let mut entry = self.root();
+
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            entry = self
+                .read_dir(&entry)?
+                .into_iter()
+                .find(|e| dir::names_equal(e.name(), name))
+                .ok_or("No such file or directory")?;
+        }
+
+        Ok(entry)
+    }
+
+    /// The entries of a directory, except for `.` and `..`.
+    pub fn read_dir(&self, dir: &DirEntry) -> Result<Vec<DirEntry>, &'static str> {
+        if !dir.is_dir() {
+            return Err("Not a directory");
+        }
+
+        let DirSlots { clusters, slots } = self.dir_slots(dir.first_cluster)?;
+
+        Ok(dir::parse(&slots)
+            .into_iter()
+            .map(|raw| {
+                let location = self.slot_location(&clusters, raw.slot);
+                DirEntry::from_raw(raw, location)
+            })
+            .collect())
+    }
+
+    /// Read from a file at `offset`, and return the number of bytes read. Less than the length of
+    /// `buf` at the end of the file.
+    pub fn read(
+        &self,
+        file: &DirEntry,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, &'static str> {
+        if file.is_dir() {
+            return Err("Is a directory");
+        }
+        if offset >= file.size() {
+            return Ok(0);
+        }
+
+        let len = buf.len().min((file.size() - offset) as usize);
+        let clusters = self.chain(file.first_cluster)?;
+        let mut sector = vec![0; self.sector_size];
+
+        let mut done = 0;
+        while done < len {
+            let position = offset + done as u64;
+            let (sector_number, within) = self.file_position(&clusters, position)?;
+            let n = (self.sector_size - within).min(len - done);
+
+            self.read_sector(sector_number, &mut sector)?;
+            buf[done..done + n].copy_from_slice(&sector[within..within + n]);
+            done += n;
+        }
+
+        Ok(len)
+    }
+
+    /// Write to a file at `offset`, growing it as needed. `offset` must not lie beyond the end of
+    /// the file.
+    pub fn write(
+        &mut self,
+        file: &mut DirEntry,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<usize, &'static str> {
+        if file.is_dir() {
+            return Err("Is a directory");
+        }
+        if offset > file.size() {
+            return Err("Write would leave a hole");
+        }
+        let end = offset
+            .checked_add(data.len() as u64)
+            .filter(|&end| end <= u32::MAX as u64)
+            .ok_or("File too large")?;
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        let clusters = self.chain(file.first_cluster)?;
+        let count = end.div_ceil(self.cluster_size() as u64) as usize;
+        let clusters = self.extend_chain(clusters, count)?;
+        if file.first_cluster == CLUSTER_FREE {
+            file.first_cluster = clusters[0];
+        }
+
+        let mut sector = vec![0; self.sector_size];
+        let mut done = 0;
+        while done < data.len() {
+            let position = offset + done as u64;
+            let (sector_number, within) = self.file_position(&clusters, position)?;
+            let n = (self.sector_size - within).min(data.len() - done);
+
+            if n < self.sector_size {
+                self.read_sector(sector_number, &mut sector)?;
+            }
+            sector[within..within + n].copy_from_slice(&data[done..done + n]);
+            self.write_sector(sector_number, &sector)?;
+            done += n;
+        }
+
+        file.size = file.size.max(end as u32);
+        self.update_entry(file)?;
+
+        Ok(data.len())
+    }
+
+    /// Shrink a file to `len` bytes, and release the clusters it no longer needs.
+    pub fn truncate(&mut self, file: &mut DirEntry, len: u64) -> Result<(), &'static str> {
+        if file.is_dir() {
+            return Err("Is a directory");
+        }
+        if len > file.size() {
+            return Err("Truncate would grow the file");
+        }
+
+        let clusters = self.chain(file.first_cluster)?;
+        let keep = len.div_ceil(self.cluster_size() as u64) as usize;
+        if keep < clusters.len() {
+            if keep == 0 {
+                file.first_cluster = CLUSTER_FREE;
+            } else {
+                self.set_fat_entry(clusters[keep - 1], CLUSTER_END_OF_CHAIN)?;
+            }
+            self.free_clusters(&clusters[keep..])?;
+        }
+
+        file.size = len as u32;
+        self.update_entry(file)
+    }
+
+    /// Create an empty file. The parent directory must exist.
+    pub fn create(&mut self, path: &str) -> Result<DirEntry, &'static str> {
+        self.create_entry(path, dir::ATTR_ARCHIVE)
+    }
+
+    /// Create an empty directory. The parent directory must exist.
+    pub fn create_dir(&mut self, path: &str) -> Result<DirEntry, &'static str> {
+        self.create_entry(path, dir::ATTR_DIRECTORY)
+    }
+
+    /// Remove a file or an empty directory.
+    pub fn remove(&mut self, path: &str) -> Result<(), &'static str> {
+        let (parent_path, name) = split_path(path)?;
+        let parent = self.open_dir(parent_path)?;
+        let DirSlots { clusters, slots } = self.dir_slots(parent.first_cluster)?;
+        let raw = dir::parse(&slots)
+            .into_iter()
+            .find(|e| dir::names_equal(&e.name, name))
+            .ok_or("No such file or directory")?;
+
+        let slot_range = raw.first_slot..=raw.slot;
+        let location = self.slot_location(&clusters, raw.slot);
+        let entry = DirEntry::from_raw(raw, location);
+        if entry.is_dir() && !self.read_dir(&entry)?.is_empty() {
+            return Err("Directory not empty");
+        }
+
+        for i in slot_range {
+            let location = self.slot_location(&clusters, i);
+            self.update_slot(location, |slot| slot[0] = dir::SLOT_FREE)?;
+        }
+        let clusters = self.chain(entry.first_cluster)?;
+        self.free_clusters(&clusters)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::ram_disk::RamDisk;
+    use proptest::prelude::*;
+
+    const NUM_BLOCKS: usize = 4096;
+    const RESERVED_SECTORS: usize = 32;
+    const SECTORS_PER_FAT: usize = NUM_BLOCKS / 128;
+    const NUM_CLUSTERS: usize = NUM_BLOCKS - RESERVED_SECTORS - 2 * SECTORS_PER_FAT;
+
+    /// Format a RAM disk with a FAT32 volume of one sector per cluster.
+    fn mkfs() -> RamDisk {
+        let disk = RamDisk::new(NUM_BLOCKS);
+        let mut data = disk.data.borrow_mut();
+
+        let boot = &mut data[0..512];
+        boot[0..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+        boot[3..11].copy_from_slice(b"MSWIN4.1");
+        boot[11..13].copy_from_slice(&512u16.to_le_bytes());
+        boot[13] = 1;
+        boot[14..16].copy_from_slice(&(RESERVED_SECTORS as u16).to_le_bytes());
+        boot[16] = 2;
+        boot[21] = 0xF8;
+        boot[32..36].copy_from_slice(&(NUM_BLOCKS as u32).to_le_bytes());
+        boot[36..40].copy_from_slice(&(SECTORS_PER_FAT as u32).to_le_bytes());
+        boot[44..48].copy_from_slice(&2u32.to_le_bytes());
+        boot[48..50].copy_from_slice(&1u16.to_le_bytes());
+        boot[510..512].copy_from_slice(&BOOT_SIGNATURE);
+
+        let fsinfo = &mut data[512..1024];
+        fsinfo[0..4].copy_from_slice(&FSINFO_LEAD_SIGNATURE.to_le_bytes());
+        fsinfo[484..488].copy_from_slice(&FSINFO_STRUCT_SIGNATURE.to_le_bytes());
+        fsinfo[488..492].copy_from_slice(&(NUM_CLUSTERS as u32 - 1).to_le_bytes());
+        fsinfo[492..496].copy_from_slice(&3u32.to_le_bytes());
+        fsinfo[510..512].copy_from_slice(&BOOT_SIGNATURE);
+
+        for fat in 0..2 {
+            let start = (RESERVED_SECTORS + fat * SECTORS_PER_FAT) * 512;
+            for (i, entry) in [0x0FFF_FFF8u32, 0x0FFF_FFFF, CLUSTER_END_OF_CHAIN]
+                .iter()
+                .enumerate()
+            {
+                data[start + i * 4..][..4].copy_from_slice(&entry.to_le_bytes());
+            }
+        }
+
+        drop(data);
+        disk
+    }
+
+    fn free_clusters(fs: &FileSystem<&RamDisk>) -> usize {
+        let mut cache = FatSector::new(512);
+
+        (FIRST_CLUSTER..FIRST_CLUSTER + fs.num_clusters)
+            .filter(|&cluster| fs.fat_entry(cluster, &mut cache).unwrap() == CLUSTER_FREE)
+            .count()
+    }
+
+    fn read_all(fs: &FileSystem<&RamDisk>, path: &str) -> Vec<u8> {
+        let file = fs.open(path).unwrap();
+        let mut data = vec![0; file.size() as usize + 1];
+        let len = fs.read(&file, 0, &mut data).unwrap();
+        data.truncate(len);
+
+        data
+    }
+
+    #[test]
+    fn mount() {
+        let disk = mkfs();
+        let fs = FileSystem::mount(&disk).unwrap();
+
+        assert_eq!(fs.num_clusters as usize, NUM_CLUSTERS);
+        assert_eq!(fs.next_free, 3);
+        assert!(fs.read_dir(&fs.root()).unwrap().is_empty());
+
+        disk.data.borrow_mut()[510] = 0;
+        assert!(FileSystem::mount(&disk).is_err());
+    }
+
+    #[test]
+    fn files_are_written_and_read() {
+        let disk = mkfs();
+        let mut fs = FileSystem::mount(&disk).unwrap();
+        let content: Vec<u8> = (0..2000).map(|i| i as u8).collect();
+
+        let mut file = fs.create("/Long file name.txt").unwrap();
+        fs.write(&mut file, 0, &content[..700]).unwrap();
+        fs.write(&mut file, 700, &content[700..]).unwrap();
+
+        assert_eq!(read_all(&fs, "Long file name.txt"), content);
+        assert_eq!(fs.chain(file.first_cluster).unwrap().len(), 4);
+        assert_eq!(free_clusters(&fs), NUM_CLUSTERS - 5);
+
+        let mut buf = [0; 10];
+        assert_eq!(fs.read(&file, 1995, &mut buf), Ok(5));
+        assert_eq!(buf[..5], content[1995..]);
+        assert_eq!(
+            fs.write(&mut file, 2001, b"x"),
+            Err("Write would leave a hole")
+        );
+
+        // The FSInfo free count is no longer trusted.
+        assert_eq!(read_u32(&disk.data.borrow()[512..], 488), FSINFO_UNKNOWN);
+    }
+
+    #[test]
+    fn short_names_are_generated() {
+        let disk = mkfs();
+        let mut fs = FileSystem::mount(&disk).unwrap();
+
+        fs.create("Long file name.txt").unwrap();
+        fs.create("Long file name 2.txt").unwrap();
+        fs.create("CONFIG.TXT").unwrap();
+
+        let slots = fs.dir_slots(fs.root_cluster).unwrap().slots;
+        let short_names: Vec<ShortName> = dir::parse(&slots).iter().map(|e| e.short_name).collect();
+
+        assert_eq!(
+            short_names,
+            [*b"LONGFI~1TXT", *b"LONGFI~2TXT", *b"CONFIG  TXT"]
+        );
+        assert_eq!(fs.create("config.txt").unwrap_err(), "File exists");
+    }
+
+    #[test]
+    fn lookup_ignores_case() {
+        let disk = mkfs();
+        let mut fs = FileSystem::mount(&disk).unwrap();
+
+        fs.create_dir("Boot").unwrap();
+        fs.create("boot/Config.txt").unwrap();
+
+        assert_eq!(fs.open("/BOOT/CONFIG.TXT").unwrap().name(), "Config.txt");
+        assert!(fs.open("boot/missing").is_err());
+        assert_eq!(fs.open("boot/config.txt/x").unwrap_err(), "Not a directory");
+    }
+
+    #[test]
+    fn truncate_releases_clusters() {
+        let disk = mkfs();
+        let mut fs = FileSystem::mount(&disk).unwrap();
+        let free = free_clusters(&fs);
+
+        let mut file = fs.create("data").unwrap();
+        fs.write(&mut file, 0, &[0xAA; 3000]).unwrap();
+        assert_eq!(free_clusters(&fs), free - 6);
+
+        fs.truncate(&mut file, 1000).unwrap();
+        assert_eq!(free_clusters(&fs), free - 2);
+        assert_eq!(read_all(&fs, "data"), [0xAA; 1000]);
+
+        fs.truncate(&mut file, 0).unwrap();
+        assert_eq!(free_clusters(&fs), free);
+        assert_eq!(fs.open("data").unwrap().size(), 0);
+    }
+
+    #[test]
+    fn directories_grow() {
+        let disk = mkfs();
+        let mut fs = FileSystem::mount(&disk).unwrap();
+
+        fs.create_dir("dir").unwrap();
+        for i in 0..100 {
+            fs.create(&format!("dir/file number {}", i)).unwrap();
+        }
+
+        let entries = fs.read_dir(&fs.open("dir").unwrap()).unwrap();
+        assert_eq!(entries.len(), 100);
+        assert_eq!(entries[42].name(), "file number 42");
+
+        let dir = fs.open("dir").unwrap();
+        assert!(fs.chain(dir.first_cluster).unwrap().len() > 1);
+    }
+
+    #[test]
+    fn nested_directories() {
+        let disk = mkfs();
+        let mut fs = FileSystem::mount(&disk).unwrap();
+
+        fs.create_dir("a").unwrap();
+        fs.create_dir("a/b").unwrap();
+        let mut file = fs.create("a/b/c.txt").unwrap();
+        fs.write(&mut file, 0, b"nested").unwrap();
+
+        assert_eq!(read_all(&fs, "a/b/c.txt"), b"nested");
+
+        // `..` of a directory in the root refers to cluster 0.
+        let a = fs.open("a").unwrap();
+        let b = fs.open("a/b").unwrap();
+        let a_slots = fs.dir_slots(a.first_cluster).unwrap().slots;
+        let b_slots = fs.dir_slots(b.first_cluster).unwrap().slots;
+        assert_eq!(read_u16(&a_slots[1], 26), 0);
+        assert_eq!(read_u16(&b_slots[1], 26) as u32, a.first_cluster);
+    }
+
+    #[test]
+    fn files_are_removed() {
+        let disk = mkfs();
+        let mut fs = FileSystem::mount(&disk).unwrap();
+        let free = free_clusters(&fs);
+
+        fs.create_dir("dir").unwrap();
+        let mut file = fs.create("dir/A long name").unwrap();
+        fs.write(&mut file, 0, &[1; 1500]).unwrap();
+
+        assert_eq!(fs.remove("dir").unwrap_err(), "Directory not empty");
+        fs.remove("dir/a long name").unwrap();
+        fs.remove("dir").unwrap();
+
+        assert!(fs.read_dir(&fs.root()).unwrap().is_empty());
+        assert_eq!(free_clusters(&fs), free);
+    }
+
+    #[test]
+    fn full_volume() {
+        let disk = mkfs();
+        let mut fs = FileSystem::mount(&disk).unwrap();
+        let free = free_clusters(&fs);
+
+        let mut file = fs.create("big").unwrap();
+        let too_big = vec![0; (free + 1) * 512];
+        assert_eq!(
+            fs.write(&mut file, 0, &too_big),
+            Err("No space left on device")
+        );
+        assert_eq!(free_clusters(&fs), free);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        /// Writes and truncations match a file in memory.
+        #[test]
+        fn file_matches_model(
+            ops in proptest::collection::vec(
+                (any::<bool>(), 0.0..=1.0f64, proptest::collection::vec(any::<u8>(), 0..1500)),
+                1..12,
+            ),
+        ) {
+            let disk = mkfs();
+            let mut fs = FileSystem::mount(&disk).unwrap();
+            let mut file = fs.create("file").unwrap();
+            let mut model = Vec::new();
+
+            for (truncate, position, data) in ops {
+                let offset = (model.len() as f64 * position) as usize;
+                if truncate {
+                    fs.truncate(&mut file, offset as u64).unwrap();
+                    model.truncate(offset);
+                } else {
+                    fs.write(&mut file, offset as u64, &data).unwrap();
+                    let end = offset + data.len();
+                    if model.len() < end {
+                        model.resize(end, 0);
+                    }
+                    model[offset..end].copy_from_slice(&data);
+                }
+
+                prop_assert_eq!(read_all(&fs, "file"), model.clone());
+            }
+
+            let clusters = model.len().div_ceil(512);
+            prop_assert_eq!(free_clusters(&fs), NUM_CLUSTERS - 1 - clusters);
+        }
+    }
+}
@@ -0,0 +1,448 @@
+//! Directory entries.
+//!
+//! A directory is an array of 32 byte slots. A file is described by one short entry with an 8.3
+//! name, preceded by the long file name entries that carry its full name in UTF-16.
+
+use alloc::{format, string::String, vec, vec::Vec};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_LONG_NAME: u8 = 0x0F;
+const ATTR_LONG_NAME_MASK: u8 = 0x3F;
+
+/// Marks the long name entry that comes first on disk, and carries the end of the name.
+const LFN_LAST: u8 = 0x40;
+const LFN_ORDINAL_MASK: u8 = 0x1F;
+const LFN_CHARS: usize = 13;
+const LFN_CHAR_OFFSETS: [usize; LFN_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+const LFN_MAX_UNITS: usize = 255;
+
+/// Lower case flags for the base and the extension of a short name.
+const NTRES_LOWER_BASE: u8 = 0x08;
+const NTRES_LOWER_EXT: u8 = 0x10;
+
+/// The first byte of a short name that starts with 0xE5, which marks free slots.
+const KANJI_E5: u8 = 0x05;
+
+/// There is no clock, so all timestamps are the start of the FAT epoch.
+const DATE_1980_01_01: u16 = (1 << 5) | 1;
+
+/// Special characters that short names may contain, besides letters and digits.
+const SHORT_NAME_SPECIALS: &[u8] = b"$%'-_@~`!(){}^#&";
+
+struct LongName {
+    first_slot: usize,
+    units: Vec<u16>,
+    checksum: u8,
+    next_ordinal: u8,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+pub const SLOT_SIZE: usize = 32;
+
+/// A slot that was never used. All slots after it are unused, too.
+pub const SLOT_END: u8 = 0x00;
+
+/// A slot whose entry was deleted.
+pub const SLOT_FREE: u8 = 0xE5;
+
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+
+pub type Slot = [u8; SLOT_SIZE];
+
+/// The 8.3 name of a short entry, padded with spaces.
+pub type ShortName = [u8; 11];
+
+/// A file or directory of a directory.
+pub struct RawEntry {
+    pub name: String,
+    pub short_name: ShortName,
+    pub attributes: u8,
+    pub first_cluster: u32,
+    pub size: u32,
+
+    /// Index of the first slot of the entry, including its long name, among the slots of the
+    /// directory.
+    pub first_slot: usize,
+
+    /// Index of the short entry among the slots of the directory.
+    pub slot: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn read_u16(slot: &Slot, offset: usize) -> u16 {
+    u16::from_le_bytes([slot[offset], slot[offset + 1]])
+}
+
+fn write_u16(slot: &mut Slot, offset: usize, value: u16) {
+    slot[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn is_short_name_char(c: u8) -> bool {
+    c.is_ascii_uppercase() || c.is_ascii_digit() || SHORT_NAME_SPECIALS.contains(&c)
+}
+
+/// The name of a short entry as it is displayed.
+fn short_name_display(slot: &Slot) -> String {
+    let mut base: Vec<u8> = slot[0..8].to_vec();
+    let mut ext: Vec<u8> = slot[8..11].to_vec();
+    if base[0] == KANJI_E5 {
+        base[0] = SLOT_FREE;
+    }
+    if slot[12] & NTRES_LOWER_BASE != 0 {
+        base.make_ascii_lowercase();
+    }
+    if slot[12] & NTRES_LOWER_EXT != 0 {
+        ext.make_ascii_lowercase();
+    }
+
+    let trim = |part: &[u8]| -> String {
+        let len = part.iter().rposition(|&c| c != b' ').map_or(0, |i| i + 1);
+        part[..len].iter().map(|&c| char::from(c)).collect()
+    };
+
+    let (base, ext) = (trim(&base), trim(&ext));
+    if ext.is_empty() {
+        base
+    } else {
+        format!("{}.{}", base, ext)
+    }
+}
+
+fn decode_long_name(units: &[u16]) -> String {
+    let len = units
+        .iter()
+        .position(|&u| u == 0x0000 || u == 0xFFFF)
+        .unwrap_or(units.len());
+
+    char::decode_utf16(units[..len].iter().copied())
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+fn short_entry(short_name: &ShortName, attributes: u8, first_cluster: u32) -> Slot {
+    let mut slot = [0; SLOT_SIZE];
+
+    slot[0..11].copy_from_slice(short_name);
+    slot[11] = attributes;
+    write_u16(&mut slot, 16, DATE_1980_01_01);
+    write_u16(&mut slot, 18, DATE_1980_01_01);
+    write_u16(&mut slot, 24, DATE_1980_01_01);
+    set_cluster_and_size(&mut slot, first_cluster, 0);
+
+    slot
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// The checksum of a short name, which ties the long name entries to their short entry.
+pub fn checksum(short_name: &ShortName) -> u8 {
+    short_name
+        .iter()
+        .fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c))
+}
+
+/// Collect the entries of a directory, except for `.` and `..`.
+pub fn parse(slots: &[Slot]) -> Vec<RawEntry> {
+    let mut entries = Vec::new();
+    let mut long_name: Option<LongName> = None;
+
+    for (i, slot) in slots.iter().enumerate() {
+        match slot[0] {
+            SLOT_END => break,
+            SLOT_FREE => {
+                long_name = None;
+                continue;
+            }
+            _ => (),
+        }
+
+        if slot[11] & ATTR_LONG_NAME_MASK == ATTR_LONG_NAME {
+            let ordinal = slot[0] & LFN_ORDINAL_MASK;
+            if slot[0] & LFN_LAST != 0 {
+                long_name = Some(LongName {
+                    first_slot: i,
+                    units: vec![0xFFFF; ordinal as usize * LFN_CHARS],
+                    checksum: slot[13],
+                    next_ordinal: ordinal,
+                });
+            }
+
+            long_name = long_name.filter(|name| {
+                ordinal != 0 && ordinal == name.next_ordinal && slot[13] == name.checksum
+            });
+            if let Some(name) = &mut long_name {
+                let start = (ordinal as usize - 1) * LFN_CHARS;
+                for (j, offset) in LFN_CHAR_OFFSETS.iter().enumerate() {
+                    name.units[start + j] = read_u16(slot, *offset);
+                }
+                name.next_ordinal -= 1;
+            }
+            continue;
+        }
+
+        let long_name = long_name.take();
+        if slot[11] & ATTR_VOLUME_ID != 0 || slot[0] == b'.' {
+            continue;
+        }
+
+        let short_name: ShortName = slot[0..11].try_into().unwrap();
+        let (name, first_slot) = match long_name {
+            Some(name) if name.next_ordinal == 0 && name.checksum == checksum(&short_name) => {
+                (decode_long_name(&name.units), name.first_slot)
+            }
+            _ => (short_name_display(slot), i),
+        };
+
+        entries.push(RawEntry {
+            name,
+            short_name,
+            attributes: slot[11],
+            first_cluster: (read_u16(slot, 20) as u32) << 16 | read_u16(slot, 26) as u32,
+            size: u32::from_le_bytes(slot[28..32].try_into().unwrap()),
+            first_slot,
+            slot: i,
+        });
+    }
+
+    entries
+}
+
+/// Whether a long file name may be stored.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.ends_with(['.', ' '])
+        && name.encode_utf16().count() <= LFN_MAX_UNITS
+        && !name
+            .chars()
+            .any(|c| c < ' ' || c == '\x7F' || "\"*/:<>?\\|".contains(c))
+}
+
+/// Whether two names are the same, ignoring case.
+pub fn names_equal(a: &str, b: &str) -> bool {
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .eq(b.chars().flat_map(char::to_lowercase))
+}
+
+/// The short name of a name that needs no long name, i.e. an upper case 8.3 name.
+pub fn exact_short_name(name: &str) -> Option<ShortName> {
+    let (base, ext) = name.split_once('.').unwrap_or((name, ""));
+
+    if base.is_empty()
+        || base.len() > 8
+        || ext.len() > 3
+        || !base.bytes().chain(ext.bytes()).all(is_short_name_char)
+    {
+        return None;
+    }
+
+    let mut short_name = [b' '; 11];
+    short_name[..base.len()].copy_from_slice(base.as_bytes());
+    short_name[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+
+    Some(short_name)
+}
+
+/// The `n`th candidate for the short name of a name that needs a long name, in the style of
+/// `LONGFI~1.TXT`.
+pub fn numbered_short_name(name: &str, n: usize) -> ShortName {
+    let to_short = |part: &str| -> Vec<u8> {
+        part.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .flat_map(char::to_uppercase)
+            .map(|c| match u8::try_from(c) {
+                Ok(c) if is_short_name_char(c) => c,
+                _ => b'_',
+            })
+            .collect()
+    };
+
+    let name = name.trim_start_matches('.');
+    let (base, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+    let (base, ext) = (to_short(base), to_short(ext));
+    let tail = format!("~{}", n);
+    let base_len = base.len().min(8 - tail.len());
+
+    let mut short_name = [b' '; 11];
+    short_name[..base_len].copy_from_slice(&base[..base_len]);
+    short_name[base_len..base_len + tail.len()].copy_from_slice(tail.as_bytes());
+    let ext_len = ext.len().min(3);
+    short_name[8..8 + ext_len].copy_from_slice(&ext[..ext_len]);
+
+    short_name
+}
+
+/// The slots of a new entry, in their order on disk. Without a long name if `name` is an exact
+/// short name.
+pub fn encode(name: &str, short_name: &ShortName, attributes: u8, first_cluster: u32) -> Vec<Slot> {
+    let mut slots = Vec::new();
+
+    if exact_short_name(name).as_ref() != Some(short_name) {
+        let units: Vec<u16> = name.encode_utf16().collect();
+        let num_entries = units.len().div_ceil(LFN_CHARS);
+        let sum = checksum(short_name);
+
+        for ordinal in (1..=num_entries).rev() {
+            let mut slot = [0; SLOT_SIZE];
+            slot[0] = ordinal as u8 | if ordinal == num_entries { LFN_LAST } else { 0 };
+            slot[11] = ATTR_LONG_NAME;
+            slot[13] = sum;
+
+            for (j, offset) in LFN_CHAR_OFFSETS.iter().enumerate() {
+                let unit = match ((ordinal - 1) * LFN_CHARS + j).cmp(&units.len()) {
+                    core::cmp::Ordering::Less => units[(ordinal - 1) * LFN_CHARS + j],
+                    core::cmp::Ordering::Equal => 0x0000,
+                    core::cmp::Ordering::Greater => 0xFFFF,
+                };
+                write_u16(&mut slot, *offset, unit);
+            }
+            slots.push(slot);
+        }
+    }
+
+    slots.push(short_entry(short_name, attributes, first_cluster));
+
+    slots
+}
+
+/// The short entries `.` and `..` that start a new directory.
+pub fn dot_entries(cluster: u32, parent_cluster: u32) -> [Slot; 2] {
+    let mut dot = *b"           ";
+    dot[0] = b'.';
+    let mut dot_dot = dot;
+    dot_dot[1] = b'.';
+
+    [
+        short_entry(&dot, ATTR_DIRECTORY, cluster),
+        short_entry(&dot_dot, ATTR_DIRECTORY, parent_cluster),
+    ]
+}
+
+/// Update the first cluster and the size of a short entry.
+pub fn set_cluster_and_size(slot: &mut Slot, first_cluster: u32, size: u32) {
+    write_u16(slot, 20, (first_cluster >> 16) as u16);
+    write_u16(slot, 26, first_cluster as u16);
+    slot[28..32].copy_from_slice(&size.to_le_bytes());
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn short_names() {
+        assert_eq!(exact_short_name("README.TXT"), Some(*b"README  TXT"));
+        assert_eq!(exact_short_name("BOOT"), Some(*b"BOOT       "));
+        assert_eq!(exact_short_name("readme.txt"), None);
+        assert_eq!(exact_short_name("LONGNAME1.TXT"), None);
+        assert_eq!(exact_short_name("A.B.C"), None);
+
+        assert_eq!(
+            numbered_short_name("Long file.name.txt", 1),
+            *b"LONGFI~1TXT"
+        );
+        assert_eq!(numbered_short_name(".config", 12), *b"CONFI~12   ");
+        assert_eq!(numbered_short_name("ä+b", 3), *b"__B~3      ");
+    }
+
+    #[test]
+    fn names_are_validated() {
+        assert!(is_valid_name("Some file.txt"));
+        assert!(is_valid_name("ünïcödé"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name(".."));
+        assert!(!is_valid_name("a/b"));
+        assert!(!is_valid_name("trailing."));
+        assert!(!is_valid_name(&"x".repeat(256)));
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert!(names_equal("Config.TXT", "config.txt"));
+        assert!(!names_equal("config.txt", "config.tx"));
+    }
+
+    /// The checksum of the specification's example.
+    #[test]
+    fn short_name_checksum() {
+        assert_eq!(checksum(b"README  TXT"), 0x73);
+    }
+
+    #[test]
+    fn lower_case_short_names() {
+        let mut slots = encode("KERNEL8.IMG", b"KERNEL8 IMG", ATTR_ARCHIVE, 0);
+        slots[0][12] = NTRES_LOWER_BASE | NTRES_LOWER_EXT;
+        slots.push([0; SLOT_SIZE]);
+
+        assert_eq!(parse(&slots)[0].name, "kernel8.img");
+    }
+
+    #[test]
+    fn deleted_and_orphaned_entries_are_skipped() {
+        let mut slots = encode("A long name.txt", b"ALONGN~1TXT", ATTR_ARCHIVE, 5);
+        slots.extend(encode("B long name.txt", b"BLONGN~1TXT", ATTR_ARCHIVE, 6));
+        // Delete the first file, and lose the first long name entry of the second one.
+        slots[0][0] = SLOT_FREE;
+        slots[1][0] = SLOT_FREE;
+        slots[2][0] = SLOT_FREE;
+        slots[3][0] = SLOT_FREE;
+
+        let entries = parse(&slots);
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "BLONGN~1.TXT");
+        assert_eq!(entries[0].first_cluster, 6);
+        assert_eq!(entries[0].first_slot, 5);
+        assert_eq!(entries[0].slot, 5);
+    }
+
+    proptest! {
+        /// Long names survive encoding and parsing.
+        #[test]
+        fn long_names_round_trip(name in "[^\"*/:<>?\\\\|\\x00-\\x1F\\x7F]{1,100}[a-z0-9]", cluster in any::<u32>()) {
+            prop_assume!(is_valid_name(&name));
+            let short_name = numbered_short_name(&name, 1);
+
+            let mut slots = encode(&name, &short_name, ATTR_ARCHIVE, cluster);
+            slots.push([0; SLOT_SIZE]);
+            let entries = parse(&slots);
+
+            prop_assert_eq!(entries.len(), 1);
+            prop_assert_eq!(&entries[0].name, &name);
+            prop_assert_eq!(entries[0].first_cluster, cluster);
+            prop_assert_eq!(entries[0].first_slot, 0);
+            prop_assert_eq!(entries[0].slot, slots.len() - 2);
+        }
+
+        /// Parsing arbitrary slots never panics.
+        #[test]
+        fn parse_arbitrary_slots(data in proptest::collection::vec(any::<u8>(), 0..1024)) {
+            let slots: Vec<Slot> = data
+                .chunks_exact(SLOT_SIZE)
+                .map(|chunk| chunk.try_into().unwrap())
+                .collect();
+
+            parse(&slots);
+        }
+    }
+}
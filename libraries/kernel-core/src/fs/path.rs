@@ -0,0 +1,119 @@
+//! Paths.
+//!
+//! Paths are absolute and separated by `/`. A missing leading `/` is implied, as there is no
+//! working directory.
+
+use alloc::{string::String, vec::Vec};
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// The canonical form of a path: starting with `/`, without empty, `.` and `..` components, and
+/// without a trailing `/`. `..` at the root stays at the root.
+pub fn normalize(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+
+    let mut normalized = String::with_capacity(path.len() + 1);
+    for component in &components {
+        normalized.push('/');
+        normalized.push_str(component);
+    }
+    if normalized.is_empty() {
+        normalized.push('/');
+    }
+
+    normalized
+}
+
+/// Split a normalized path into the path of its parent and its last component. `None` for the
+/// root.
+pub fn split(path: &str) -> Option<(&str, &str)> {
+    let (parent, name) = path.rsplit_once('/')?;
+    if name.is_empty() {
+        return None;
+    }
+
+    Some((if parent.is_empty() { "/" } else { parent }, name))
+}
+
+/// If the normalized `path` lies within the normalized `prefix`, the rest of it, without a
+/// leading `/`.
+pub fn strip_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    if prefix == "/" {
+        return path.strip_prefix('/');
+    }
+
+    match path.strip_prefix(prefix)? {
+        "" => Some(""),
+        rest => rest.strip_prefix('/'),
+    }
+}
+
+/// The components of a normalized path.
+pub fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|component| !component.is_empty())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn paths_are_normalized() {
+        assert_eq!(normalize(""), "/");
+        assert_eq!(normalize("/"), "/");
+        assert_eq!(normalize("boot/config.txt"), "/boot/config.txt");
+        assert_eq!(normalize("//dev/./uart0/"), "/dev/uart0");
+        assert_eq!(normalize("/a/b/../../.."), "/");
+        assert_eq!(normalize("/a/../b/./c/.."), "/b");
+    }
+
+    #[test]
+    fn paths_are_split() {
+        assert_eq!(split("/"), None);
+        assert_eq!(split("/dev"), Some(("/", "dev")));
+        assert_eq!(split("/dev/uart0"), Some(("/dev", "uart0")));
+    }
+
+    #[test]
+    fn prefixes_are_whole_components() {
+        assert_eq!(strip_prefix("/dev/uart0", "/dev"), Some("uart0"));
+        assert_eq!(strip_prefix("/dev", "/dev"), Some(""));
+        assert_eq!(strip_prefix("/devices", "/dev"), None);
+        assert_eq!(strip_prefix("/dev", "/"), Some("dev"));
+        assert_eq!(strip_prefix("/", "/"), Some(""));
+    }
+
+    proptest! {
+        #[test]
+        fn normalized_paths_are_canonical(path in "[a./]{0,20}") {
+            let normalized = normalize(&path);
+
+            prop_assert!(normalized.starts_with('/'));
+            prop_assert!(normalized == "/" || !normalized.ends_with('/'));
+            prop_assert!(components(&normalized).all(|c| c != "." && c != ".."));
+            prop_assert!(!normalized.contains("//"));
+            prop_assert_eq!(normalize(&normalized), normalized.clone());
+
+            if let Some((parent, name)) = split(&normalized) {
+                prop_assert_eq!(strip_prefix(&normalized, parent), Some(name));
+            }
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! Architecture and board independent parts of the kernel.
+//!
+//! Kept free of hardware access, so that the crate builds for the host as well and its tests can
+//! run there with `cargo test`.
+
+#![cfg_attr(not(test), no_std)]
+#![feature(step_trait)]
+
+extern crate alloc;
+
+pub mod block;
+pub mod chacha;
+pub mod cmdline;
+pub mod common;
+pub mod console;
+pub mod exception;
+pub mod fdt;
+pub mod fs;
+pub mod memory;
@@ -0,0 +1,184 @@
+//! Memory addresses.
+
+pub mod mmu;
+
+use crate::common;
+use core::{
+    fmt,
+    marker::PhantomData,
+    ops::{Add, Sub},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Metadata trait for marking the type of an address.
+pub trait AddressType: Copy + Clone + PartialOrd + PartialEq + Ord + Eq {}
+
+/// Zero-sized type to mark a physical address.
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq)]
+pub enum Physical {}
+
+/// Zero-sized type to mark a virtual address.
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq)]
+pub enum Virtual {}
+
+/// Generic address type.
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq)]
+pub struct Address<ATYPE: AddressType> {
+    value: usize,
+    _address_type: PhantomData<fn() -> ATYPE>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl AddressType for Physical {}
+impl AddressType for Virtual {}
+
+impl<ATYPE: AddressType> Address<ATYPE> {
+    /// Create an instance.
+    pub const fn new(value: usize) -> Self {
+        Self {
+            value,
+            _address_type: PhantomData,
+        }
+    }
+
+    /// Convert to usize.
+    pub const fn as_usize(self) -> usize {
+        self.value
+    }
+
+    /// Align down, e.g. to the page size.
+    #[must_use]
+    pub const fn align_down(self, alignment: usize) -> Self {
+        let aligned = common::align_down(self.value, alignment);
+
+        Self::new(aligned)
+    }
+
+    /// Align up, e.g. to the page size.
+    #[must_use]
+    pub const fn align_up(self, alignment: usize) -> Self {
+        let aligned = common::align_up(self.value, alignment);
+
+        Self::new(aligned)
+    }
+
+    /// Checks if the address is aligned.
+    pub const fn is_aligned(&self, alignment: usize) -> bool {
+        common::is_aligned(self.value, alignment)
+    }
+
+    /// Return the address' offset into the aligned block that contains it, e.g. into its page.
+    pub const fn offset_into(&self, alignment: usize) -> usize {
+        self.value - common::align_down(self.value, alignment)
+    }
+}
+
+impl<ATYPE: AddressType> Add<usize> for Address<ATYPE> {
+    type Output = Self;
+
+    #[inline(always)]
+    fn add(self, rhs: usize) -> Self::Output {
+        match self.value.checked_add(rhs) {
+            None => panic!("Overflow on Address::add"),
+            Some(x) => Self::new(x),
+        }
+    }
+}
+
+impl<ATYPE: AddressType> Sub<usize> for Address<ATYPE> {
+    type Output = Self;
+
+    #[inline(always)]
+    fn sub(self, rhs: usize) -> Self::Output {
+        match self.value.checked_sub(rhs) {
+            None => panic!("Overflow on Address::sub"),
+            Some(x) => Self::new(x),
+        }
+    }
+}
+
+impl<ATYPE: AddressType> Sub<Address<ATYPE>> for Address<ATYPE> {
+    type Output = Self;
+
+    #[inline(always)]
+    fn sub(self, rhs: Address<ATYPE>) -> Self::Output {
+        match self.value.checked_sub(rhs.value) {
+            None => panic!("Overflow on Address::sub"),
+            Some(x) => Self::new(x),
+        }
+    }
+}
+
+impl fmt::Display for Address<Physical> {
+    // Don't expect to see physical addresses greater than 40 bit.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let q3: u8 = ((self.value >> 32) & 0xff) as u8;
+        let q2: u16 = ((self.value >> 16) & 0xffff) as u16;
+        let q1: u16 = (self.value & 0xffff) as u16;
+
+        write!(f, "0x")?;
+        write!(f, "{:02x}_", q3)?;
+        write!(f, "{:04x}_", q2)?;
+        write!(f, "{:04x}", q1)
+    }
+}
+
+impl fmt::Display for Address<Virtual> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let q4: u16 = ((self.value >> 48) & 0xffff) as u16;
+        let q3: u16 = ((self.value >> 32) & 0xffff) as u16;
+        let q2: u16 = ((self.value >> 16) & 0xffff) as u16;
+        let q1: u16 = (self.value & 0xffff) as u16;
+
+        write!(f, "0x")?;
+        write!(f, "{:04x}_", q4)?;
+        write!(f, "{:04x}_", q3)?;
+        write!(f, "{:04x}_", q2)?;
+        write!(f, "{:04x}", q1)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const PAGE_SIZE: usize = 64 * 1024;
+
+    proptest! {
+        /// Aligning down to the page and adding the offset into the page gives the address back.
+        #[test]
+        fn page_align_and_offset_agree(value in any::<usize>()) {
+            let addr = Address::<Virtual>::new(value);
+            let page_start = addr.align_down(PAGE_SIZE);
+
+            prop_assert!(page_start.is_aligned(PAGE_SIZE));
+            prop_assert_eq!(page_start + addr.offset_into(PAGE_SIZE), addr);
+            prop_assert_eq!(addr.is_aligned(PAGE_SIZE), addr.offset_into(PAGE_SIZE) == 0);
+        }
+
+        /// Aligning up ends on the next page start, unless the address is page aligned already.
+        #[test]
+        fn align_up_page_is_next_page(value in 0..usize::MAX >> 1) {
+            let addr = Address::<Physical>::new(value);
+            let next = addr.align_up(PAGE_SIZE);
+
+            prop_assert!(next.is_aligned(PAGE_SIZE));
+            if addr.is_aligned(PAGE_SIZE) {
+                prop_assert_eq!(next, addr);
+            } else {
+                prop_assert_eq!(next - addr.align_down(PAGE_SIZE), Address::new(PAGE_SIZE));
+            }
+        }
+    }
+}
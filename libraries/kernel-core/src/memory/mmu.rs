@@ -0,0 +1,37 @@
+//! Memory Management Unit.
+
+pub mod mapping_record;
+mod types;
+
+pub use types::*;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Describes the characteristics of a translation granule.
+///
+/// The page aware types, e.g. [PageAddress], take the granule's size as `PAGE_SIZE` parameter. The
+/// granule itself is chosen by the kernel's BSP.
+pub struct TranslationGranule<const GRANULE_SIZE: usize>;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl<const GRANULE_SIZE: usize> TranslationGranule<GRANULE_SIZE> {
+    /// The granule's size.
+    pub const SIZE: usize = Self::size_checked();
+
+    /// The granule's mask.
+    pub const MASK: usize = Self::SIZE - 1;
+
+    /// The granule's shift, aka log2(size).
+    pub const SHIFT: usize = Self::SIZE.trailing_zeros() as usize;
+
+    const fn size_checked() -> usize {
+        assert!(GRANULE_SIZE.is_power_of_two());
+
+        GRANULE_SIZE
+    }
+}
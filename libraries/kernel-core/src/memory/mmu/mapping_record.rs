@@ -0,0 +1,261 @@
+//! A record of mapped pages.
+//!
+//! Device mappings of the same physical region are shared between drivers. Instead of a new
+//! entry, the driver is added as another user of the existing one.
+
+use super::{AttributeFields, MemAttributes, MemoryRegion};
+use crate::memory::{Address, Physical, Virtual};
+use alloc::{vec, vec::Vec};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Type describing a virtual memory mapping, in pages of `PAGE_SIZE`.
+#[allow(missing_docs)]
+pub struct MappingRecordEntry<const PAGE_SIZE: usize> {
+    pub users: Vec<&'static str>,
+    pub phys_start_addr: Address<Physical>,
+    pub virt_start_addr: Address<Virtual>,
+    pub num_pages: usize,
+    pub attribute_fields: AttributeFields,
+}
+
+/// The recorded mappings, sorted by their virtual start address.
+pub struct MappingRecord<const PAGE_SIZE: usize> {
+    inner: Vec<MappingRecordEntry<PAGE_SIZE>>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl<const PAGE_SIZE: usize> MappingRecordEntry<PAGE_SIZE> {
+    fn new(
+        name: &'static str,
+        virt_region: &MemoryRegion<Virtual, PAGE_SIZE>,
+        phys_region: &MemoryRegion<Physical, PAGE_SIZE>,
+        attr: &AttributeFields,
+    ) -> Self {
+        Self {
+            users: vec![name],
+            phys_start_addr: phys_region.start_addr(),
+            virt_start_addr: virt_region.start_addr(),
+            num_pages: phys_region.num_pages(),
+            attribute_fields: *attr,
+        }
+    }
+}
+
+impl<const PAGE_SIZE: usize> MappingRecord<PAGE_SIZE> {
+    fn sort(&mut self) {
+        if !self.inner.is_sorted_by_key(|item| item.virt_start_addr) {
+            self.inner.sort_unstable_by_key(|item| item.virt_start_addr)
+        }
+    }
+
+    fn find_duplicate(
+        &mut self,
+        phys_region: &MemoryRegion<Physical, PAGE_SIZE>,
+    ) -> Option<&mut MappingRecordEntry<PAGE_SIZE>> {
+        self.inner
+            .iter_mut()
+            .filter(|x| x.attribute_fields.mem_attributes == MemAttributes::Device)
+            .find(|x| {
+                if x.phys_start_addr != phys_region.start_addr() {
+                    return false;
+                }
+
+                if x.num_pages != phys_region.num_pages() {
+                    return false;
+                }
+
+                true
+            })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl<const PAGE_SIZE: usize> MappingRecordEntry<PAGE_SIZE> {
+    /// The size of the mapping in bytes.
+    pub fn size(&self) -> usize {
+        self.num_pages * PAGE_SIZE
+    }
+}
+
+impl<const PAGE_SIZE: usize> MappingRecord<PAGE_SIZE> {
+    /// Create an instance.
+    pub const fn new() -> Self {
+        Self { inner: Vec::new() }
+    }
+
+    /// Add an entry.
+    pub fn add(
+        &mut self,
+        name: &'static str,
+        virt_region: &MemoryRegion<Virtual, PAGE_SIZE>,
+        phys_region: &MemoryRegion<Physical, PAGE_SIZE>,
+        attr: &AttributeFields,
+    ) {
+        self.inner.push(MappingRecordEntry::new(
+            name,
+            virt_region,
+            phys_region,
+            attr,
+        ));
+
+        self.sort();
+    }
+
+    /// Look for a device mapping of exactly the physical region. If there is one, add `new_user`
+    /// to it and return its virtual start address.
+    pub fn find_and_insert_mmio_duplicate(
+        &mut self,
+        phys_region: &MemoryRegion<Physical, PAGE_SIZE>,
+        new_user: &'static str,
+    ) -> Option<Address<Virtual>> {
+        let dup = self.find_duplicate(phys_region)?;
+
+        dup.users.push(new_user);
+
+        Some(dup.virt_start_addr)
+    }
+
+    /// Iterate over the entries, in order of their virtual start address.
+    pub fn iter(&self) -> impl Iterator<Item = &MappingRecordEntry<PAGE_SIZE>> {
+        self.inner.iter()
+    }
+}
+
+impl<const PAGE_SIZE: usize> Default for MappingRecord<PAGE_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::mmu::{AccessPermissions, PageAddress};
+    use proptest::prelude::*;
+
+    const PAGE_SIZE: usize = 64 * 1024;
+
+    type MappingRecord = super::MappingRecord<PAGE_SIZE>;
+    type MappingRecordEntry = super::MappingRecordEntry<PAGE_SIZE>;
+
+    const DEVICE: AttributeFields = AttributeFields {
+        mem_attributes: MemAttributes::Device,
+        acc_perms: AccessPermissions::ReadWrite,
+        execute_never: true,
+    };
+
+    const RAM: AttributeFields = AttributeFields {
+        mem_attributes: MemAttributes::CacheableDRAM,
+        acc_perms: AccessPermissions::ReadWrite,
+        execute_never: true,
+    };
+
+    fn region<ATYPE: crate::memory::AddressType>(
+        start_page: usize,
+        num_pages: usize,
+    ) -> MemoryRegion<ATYPE, PAGE_SIZE> {
+        MemoryRegion::new(
+            PageAddress::from(start_page * PAGE_SIZE),
+            PageAddress::from((start_page + num_pages) * PAGE_SIZE),
+        )
+    }
+
+    /// A device mapping of the same physical region is shared.
+    #[test]
+    fn device_duplicate_gets_merged() {
+        let mut record = MappingRecord::new();
+        record.add("UART", &region(100, 1), &region(7, 1), &DEVICE);
+
+        assert_eq!(
+            record.find_and_insert_mmio_duplicate(&region(7, 1), "GPIO"),
+            Some(region::<Virtual>(100, 1).start_addr())
+        );
+
+        let entry = record.iter().next().unwrap();
+        assert_eq!(entry.users, ["UART", "GPIO"]);
+        assert_eq!(record.iter().count(), 1);
+    }
+
+    /// Only device mappings of exactly the same physical region are shared.
+    #[test]
+    fn non_duplicates_are_not_merged() {
+        let mut record = MappingRecord::new();
+        record.add("UART", &region(100, 2), &region(7, 2), &DEVICE);
+        record.add("Heap", &region(200, 1), &region(20, 1), &RAM);
+
+        assert_eq!(
+            record.find_and_insert_mmio_duplicate(&region(7, 1), "A"),
+            None
+        );
+        assert_eq!(
+            record.find_and_insert_mmio_duplicate(&region(8, 2), "B"),
+            None
+        );
+        assert_eq!(
+            record.find_and_insert_mmio_duplicate(&region(20, 1), "C"),
+            None
+        );
+
+        assert!(record.iter().all(|entry| entry.users.len() == 1));
+    }
+
+    proptest! {
+        /// Entries stay sorted by virtual start address, whatever order they are added in.
+        #[test]
+        fn entries_are_sorted(starts in prop::collection::vec(0..1024usize, 0..32)) {
+            let mut record = MappingRecord::new();
+            for start in &starts {
+                record.add("X", &region(*start, 1), &region(*start, 1), &RAM);
+            }
+
+            let virt_starts: Vec<_> = record.iter().map(|entry| entry.virt_start_addr).collect();
+            prop_assert_eq!(virt_starts.len(), starts.len());
+            prop_assert!(virt_starts.windows(2).all(|w| w[0] <= w[1]));
+        }
+
+        /// Merging adds exactly one user to exactly one matching device entry.
+        #[test]
+        fn merging_adds_one_user(
+            mappings in prop::collection::vec((0..16usize, 1..4usize, any::<bool>()), 1..16),
+            query in (0..16usize, 1..4usize),
+        ) {
+            let mut record = MappingRecord::new();
+            for (i, (phys_start, num_pages, is_device)) in mappings.iter().enumerate() {
+                let attr = if *is_device { DEVICE } else { RAM };
+                record.add("X", &region(i * 8, *num_pages), &region(*phys_start, *num_pages), &attr);
+            }
+            let users_before: usize = record.iter().map(|entry| entry.users.len()).sum();
+
+            let matches_query = |entry: &MappingRecordEntry| {
+                entry.attribute_fields.mem_attributes == MemAttributes::Device
+                    && entry.phys_start_addr == region::<Physical>(query.0, query.1).start_addr()
+                    && entry.num_pages == query.1
+            };
+            let has_match = record.iter().any(matches_query);
+
+            let result = record.find_and_insert_mmio_duplicate(&region(query.0, query.1), "New");
+            let users_after: usize = record.iter().map(|entry| entry.users.len()).sum();
+
+            prop_assert_eq!(result.is_some(), has_match);
+            prop_assert_eq!(users_after, users_before + has_match as usize);
+            if let Some(virt_start_addr) = result {
+                let entry = record.iter().find(|entry| entry.virt_start_addr == virt_start_addr).unwrap();
+                prop_assert!(matches_query(entry));
+                prop_assert_eq!(entry.users.last(), Some(&"New"));
+            }
+        }
+    }
+}
@@ -0,0 +1,466 @@
+//! Memory Management Unit types.
+
+use super::TranslationGranule;
+use crate::{
+    common,
+    memory::{Address, AddressType, Physical},
+};
+use core::{convert::From, iter::Step, num::NonZeroUsize, ops::Range};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A wrapper type around [Address] that ensures alignment to pages of `PAGE_SIZE`.
+#[derive(Copy, Clone, Debug, Eq, PartialOrd, PartialEq)]
+pub struct PageAddress<ATYPE: AddressType, const PAGE_SIZE: usize> {
+    inner: Address<ATYPE>,
+}
+
+/// A type that describes a region of memory in quantities of pages.
+#[derive(Copy, Clone, Debug, Eq, PartialOrd, PartialEq)]
+pub struct MemoryRegion<ATYPE: AddressType, const PAGE_SIZE: usize> {
+    start: PageAddress<ATYPE, PAGE_SIZE>,
+    end_exclusive: PageAddress<ATYPE, PAGE_SIZE>,
+}
+
+/// Architecture agnostic memory attributes.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialOrd, PartialEq)]
+pub enum MemAttributes {
+    CacheableDRAM,
+    Device,
+    WriteCombining,
+}
+
+/// Architecture agnostic access permissions.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialOrd, PartialEq)]
+pub enum AccessPermissions {
+    ReadOnly,
+    ReadWrite,
+}
+
+/// Collection of memory attributes.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialOrd, PartialEq)]
+pub struct AttributeFields {
+    pub mem_attributes: MemAttributes,
+    pub acc_perms: AccessPermissions,
+    pub execute_never: bool,
+}
+
+/// An MMIO descriptor for use in device drivers.
+#[derive(Copy, Clone)]
+pub struct MMIODescriptor {
+    start_addr: Address<Physical>,
+    end_addr_exclusive: Address<Physical>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+//------------------------------------------------------------------------------
+// PageAddress
+//------------------------------------------------------------------------------
+impl<ATYPE: AddressType, const PAGE_SIZE: usize> PageAddress<ATYPE, PAGE_SIZE> {
+    /// The page size, checked to be a power of two.
+    const SIZE: usize = TranslationGranule::<PAGE_SIZE>::SIZE;
+
+    /// The largest value that can be represented by this type.
+    pub const MAX: Self = PageAddress {
+        inner: Address::new(usize::MAX).align_down(Self::SIZE),
+    };
+
+    /// Unwraps the value.
+    pub fn into_inner(self) -> Address<ATYPE> {
+        self.inner
+    }
+
+    /// Calculates the offset from the page address.
+    ///
+    /// `count` is in units of [PageAddress]. For example, a count of 2 means `result = self + 2 *
+    /// page_size`.
+    pub fn checked_offset(self, count: isize) -> Option<Self> {
+        if count == 0 {
+            return Some(self);
+        }
+
+        let delta = count.unsigned_abs().checked_mul(Self::SIZE)?;
+        let result = if count.is_positive() {
+            self.inner.as_usize().checked_add(delta)?
+        } else {
+            self.inner.as_usize().checked_sub(delta)?
+        };
+
+        Some(Self {
+            inner: Address::new(result),
+        })
+    }
+}
+
+impl<ATYPE: AddressType, const PAGE_SIZE: usize> From<usize> for PageAddress<ATYPE, PAGE_SIZE> {
+    fn from(addr: usize) -> Self {
+        assert!(
+            common::is_aligned(addr, Self::SIZE),
+            "Input usize not page aligned"
+        );
+
+        Self {
+            inner: Address::new(addr),
+        }
+    }
+}
+
+impl<ATYPE: AddressType, const PAGE_SIZE: usize> From<Address<ATYPE>>
+    for PageAddress<ATYPE, PAGE_SIZE>
+{
+    fn from(addr: Address<ATYPE>) -> Self {
+        assert!(
+            addr.is_aligned(Self::SIZE),
+            "Input Address not page aligned"
+        );
+
+        Self { inner: addr }
+    }
+}
+
+impl<ATYPE: AddressType, const PAGE_SIZE: usize> Step for PageAddress<ATYPE, PAGE_SIZE> {
+    fn steps_between(start: &Self, end: &Self) -> Option<usize> {
+        if start > end {
+            return None;
+        }
+
+        // Since start <= end, do unchecked arithmetic.
+        Some(
+            (end.inner.as_usize() - start.inner.as_usize())
+                >> TranslationGranule::<PAGE_SIZE>::SHIFT,
+        )
+    }
+
+    fn forward_checked(start: Self, count: usize) -> Option<Self> {
+        start.checked_offset(count as isize)
+    }
+
+    fn backward_checked(start: Self, count: usize) -> Option<Self> {
+        start.checked_offset(-(count as isize))
+    }
+}
+
+//------------------------------------------------------------------------------
+// MemoryRegion
+//------------------------------------------------------------------------------
+impl<ATYPE: AddressType, const PAGE_SIZE: usize> MemoryRegion<ATYPE, PAGE_SIZE> {
+    /// Create an instance.
+    pub fn new(
+        start: PageAddress<ATYPE, PAGE_SIZE>,
+        end_exclusive: PageAddress<ATYPE, PAGE_SIZE>,
+    ) -> Self {
+        assert!(start <= end_exclusive);
+
+        Self {
+            start,
+            end_exclusive,
+        }
+    }
+
+    fn as_range(&self) -> Range<PageAddress<ATYPE, PAGE_SIZE>> {
+        self.into_iter()
+    }
+
+    /// Returns the start page address.
+    pub fn start_page_addr(&self) -> PageAddress<ATYPE, PAGE_SIZE> {
+        self.start
+    }
+
+    /// Returns the start address.
+    pub fn start_addr(&self) -> Address<ATYPE> {
+        self.start.into_inner()
+    }
+
+    /// Returns the exclusive end page address.
+    pub fn end_exclusive_page_addr(&self) -> PageAddress<ATYPE, PAGE_SIZE> {
+        self.end_exclusive
+    }
+
+    /// Returns the exclusive end page address.
+    pub fn end_inclusive_page_addr(&self) -> PageAddress<ATYPE, PAGE_SIZE> {
+        self.end_exclusive.checked_offset(-1).unwrap()
+    }
+
+    /// Checks if self contains an address.
+    pub fn contains(&self, addr: Address<ATYPE>) -> bool {
+        let page_addr = PageAddress::from(addr.align_down(PAGE_SIZE));
+        self.as_range().contains(&page_addr)
+    }
+
+    /// Checks if there is an overlap with another memory region, i.e. if they share a page.
+    ///
+    /// This includes a region that encloses the other one. Empty regions do not overlap with
+    /// anything.
+    pub fn overlaps(&self, other_region: &Self) -> bool {
+        if self.num_pages() == 0 || other_region.num_pages() == 0 {
+            return false;
+        }
+
+        self.start < other_region.end_exclusive && other_region.start < self.end_exclusive
+    }
+
+    /// Returns the number of pages contained in this region.
+    pub fn num_pages(&self) -> usize {
+        PageAddress::steps_between(&self.start, &self.end_exclusive).unwrap()
+    }
+
+    /// Returns the size in bytes of this region.
+    pub fn size(&self) -> usize {
+        // Invariant: start <= end_exclusive, so do unchecked arithmetic.
+        let end_exclusive = self.end_exclusive.into_inner().as_usize();
+        let start = self.start.into_inner().as_usize();
+
+        end_exclusive - start
+    }
+
+    /// Splits the MemoryRegion like:
+    ///
+    /// --------------------------------------------------------------------------------
+    /// |   |   |   |   |   |   |   |   |   |   |   |   |   |   |   |   |   |   |
+    /// --------------------------------------------------------------------------------
+    ///   ^                               ^                                       ^
+    ///   |                               |                                       |
+    ///   left_start     left_end_exclusive                                       |
+    ///                                                                           |
+    ///                                   ^                                       |
+    ///                                   |                                       |
+    ///                                   right_start           right_end_exclusive
+    ///
+    /// Left region is returned to the caller. Right region is the new region for this struct.
+    pub fn take_first_n_pages(&mut self, num_pages: NonZeroUsize) -> Result<Self, &'static str> {
+        let count: usize = num_pages.into();
+
+        let left_end_exclusive = self.start.checked_offset(count as isize);
+        let left_end_exclusive = match left_end_exclusive {
+            None => return Err("Overflow while calculating left_end_exclusive"),
+            Some(x) => x,
+        };
+
+        if left_end_exclusive > self.end_exclusive {
+            return Err("Not enough free pages");
+        }
+
+        let allocation = Self {
+            start: self.start,
+            end_exclusive: left_end_exclusive,
+        };
+        self.start = left_end_exclusive;
+
+        Ok(allocation)
+    }
+}
+
+impl<ATYPE: AddressType, const PAGE_SIZE: usize> IntoIterator for MemoryRegion<ATYPE, PAGE_SIZE> {
+    type Item = PageAddress<ATYPE, PAGE_SIZE>;
+    type IntoIter = Range<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Range {
+            start: self.start,
+            end: self.end_exclusive,
+        }
+    }
+}
+
+impl<const PAGE_SIZE: usize> From<MMIODescriptor> for MemoryRegion<Physical, PAGE_SIZE> {
+    fn from(desc: MMIODescriptor) -> Self {
+        let start = PageAddress::from(desc.start_addr.align_down(PAGE_SIZE));
+        let end_exclusive = PageAddress::from(desc.end_addr_exclusive().align_up(PAGE_SIZE));
+
+        Self {
+            start,
+            end_exclusive,
+        }
+    }
+}
+
+//------------------------------------------------------------------------------
+// MMIODescriptor
+//------------------------------------------------------------------------------
+
+impl MMIODescriptor {
+    /// Create an instance.
+    pub const fn new(start_addr: Address<Physical>, size: usize) -> Self {
+        assert!(size > 0);
+        let end_addr_exclusive = Address::new(start_addr.as_usize() + size);
+
+        Self {
+            start_addr,
+            end_addr_exclusive,
+        }
+    }
+
+    /// Return the start address.
+    pub const fn start_addr(&self) -> Address<Physical> {
+        self.start_addr
+    }
+
+    /// Return the exclusive end address.
+    pub fn end_addr_exclusive(&self) -> Address<Physical> {
+        self.end_addr_exclusive
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Virtual;
+    use proptest::prelude::*;
+
+    const PAGE_SIZE: usize = 64 * 1024;
+
+    fn page(index: usize) -> PageAddress<Virtual, PAGE_SIZE> {
+        PageAddress::from(index * PAGE_SIZE)
+    }
+
+    /// Regions of up to 64 pages, starting below page `max_start`.
+    fn region(max_start: usize) -> impl Strategy<Value = MemoryRegion<Virtual, PAGE_SIZE>> {
+        (0..max_start, 0..64usize)
+            .prop_map(|(start, num_pages)| MemoryRegion::new(page(start), page(start + num_pages)))
+    }
+
+    /// Sanity of [PageAddress] methods.
+    #[test]
+    fn pageaddress_type_method_sanity() {
+        let page_addr = page(2);
+
+        assert_eq!(page_addr.checked_offset(-2), Some(page(0)));
+        assert_eq!(page_addr.checked_offset(2), Some(page(4)));
+        assert_eq!(page(0).checked_offset(0), Some(page(0)));
+        assert_eq!(page(0).checked_offset(-1), None);
+        assert_eq!(
+            PageAddress::<Virtual, PAGE_SIZE>::MAX.checked_offset(1),
+            None
+        );
+
+        assert_eq!(PageAddress::steps_between(&page(0), &page(3)), Some(3));
+    }
+
+    /// Sanity of [MemoryRegion] methods.
+    #[test]
+    fn memoryregion_type_method_sanity() {
+        let zero_region = MemoryRegion::new(page(0), page(0));
+        assert_eq!(zero_region.num_pages(), 0);
+        assert_eq!(zero_region.size(), 0);
+
+        let one_region = MemoryRegion::new(page(0), page(1));
+        assert_eq!(one_region.num_pages(), 1);
+        assert_eq!(one_region.size(), PAGE_SIZE);
+
+        let mut three_region = MemoryRegion::new(page(0), page(3));
+        assert!(three_region.contains(page(0).into_inner()));
+        assert!(!three_region.contains(page(3).into_inner()));
+        assert!(three_region.overlaps(&one_region));
+        assert!(!three_region.overlaps(&zero_region));
+
+        let allocation = three_region
+            .take_first_n_pages(NonZeroUsize::new(2).unwrap())
+            .unwrap();
+        assert_eq!(allocation.num_pages(), 2);
+        assert_eq!(three_region.num_pages(), 1);
+
+        for (i, alloc) in allocation.into_iter().enumerate() {
+            assert_eq!(alloc.into_inner().as_usize(), i * PAGE_SIZE);
+        }
+
+        assert_eq!(
+            three_region.take_first_n_pages(NonZeroUsize::new(2).unwrap()),
+            Err("Not enough free pages")
+        );
+        assert_eq!(three_region.num_pages(), 1);
+    }
+
+    /// A region overlaps with the regions that it encloses, and the other way round.
+    #[test]
+    fn memoryregion_overlaps_enclosing_region() {
+        let outer = MemoryRegion::new(page(0), page(5));
+        let inner = MemoryRegion::new(page(1), page(3));
+
+        assert!(outer.overlaps(&inner));
+        assert!(inner.overlaps(&outer));
+        assert!(outer.overlaps(&outer));
+    }
+
+    proptest! {
+        /// Moving a page address and back again ends where it started, unless the address space
+        /// is left on the way.
+        #[test]
+        fn checked_offset_is_reversible(
+            start in 0..=usize::MAX / PAGE_SIZE,
+            count in -(1isize << 40)..(1isize << 40),
+        ) {
+            let target = start as i128 + count as i128;
+
+            match page(start).checked_offset(count) {
+                Some(moved) => {
+                    prop_assert_eq!(moved.into_inner().as_usize() as i128, target * PAGE_SIZE as i128);
+                    prop_assert_eq!(moved.checked_offset(-count), Some(page(start)));
+                }
+                None => prop_assert!(target < 0 || target > (usize::MAX / PAGE_SIZE) as i128),
+            }
+        }
+
+        /// Taking pages splits a region into two adjacent regions that together are the original
+        /// one. Taking too many pages leaves the region untouched.
+        #[test]
+        fn take_first_n_pages_splits_region(original in region(1 << 20), n in 1..80usize) {
+            let mut rest = original;
+
+            match rest.take_first_n_pages(NonZeroUsize::new(n).unwrap()) {
+                Ok(taken) => {
+                    prop_assert!(n <= original.num_pages());
+                    prop_assert_eq!(taken.num_pages(), n);
+                    prop_assert_eq!(taken.start_page_addr(), original.start_page_addr());
+                    prop_assert_eq!(taken.end_exclusive_page_addr(), rest.start_page_addr());
+                    prop_assert_eq!(rest.end_exclusive_page_addr(), original.end_exclusive_page_addr());
+                    prop_assert_eq!(taken.size() + rest.size(), original.size());
+                }
+                Err(_) => {
+                    prop_assert!(n > original.num_pages());
+                    prop_assert_eq!(rest, original);
+                }
+            }
+        }
+
+        /// Number of pages, size and the page iterator agree.
+        #[test]
+        fn num_pages_matches_size_and_iterator(region in region(1 << 20)) {
+            prop_assert_eq!(region.size(), region.num_pages() * PAGE_SIZE);
+            prop_assert_eq!(region.into_iter().count(), region.num_pages());
+        }
+
+        /// An address is contained if and only if it lies between start and exclusive end.
+        #[test]
+        fn contains_matches_bounds(
+            region in region(1 << 20),
+            delta in -2 * PAGE_SIZE as isize..70 * PAGE_SIZE as isize,
+        ) {
+            let addr = region.start_addr().as_usize().saturating_add_signed(delta);
+            let inside = addr >= region.start_addr().as_usize()
+                && addr < region.end_exclusive_page_addr().into_inner().as_usize();
+
+            prop_assert_eq!(region.contains(Address::new(addr)), inside);
+        }
+
+        /// Regions overlap if and only if they share a page.
+        #[test]
+        fn overlaps_matches_shared_pages(a in region(128), b in region(128)) {
+            let shared = a.into_iter().any(|page_addr| b.into_iter().any(|x| x == page_addr));
+
+            prop_assert_eq!(a.overlaps(&b), shared);
+            prop_assert_eq!(b.overlaps(&a), shared);
+        }
+    }
+}
@@ -38,4 +38,26 @@ end
 def patch_num_symbols(kernel_elf)
     num_packed = [kernel_elf.num_symbols].pack('Q<*') # "Q" == uint64_t, "<" == little endian
     File.binwrite(kernel_elf.path, num_packed, kernel_elf.num_kernel_symbols_offset_in_file)
-end
\ No newline at end of file
+end
+
+def patch_line_info(kernel_elf, section)
+    debug_line = kernel_elf.section_data('.debug_line')
+    raise 'No .debug_line section. Build the kernel with debug info.' if debug_line.nil?
+
+    line_table = DwarfLineTable.new(debug_line,
+                                    kernel_elf.section_data('.debug_line_str'),
+                                    kernel_elf.section_data('.debug_str'))
+    blob = LineInfoBlob.new(line_table, kernel_elf.code_range).to_binary
+
+    raise "Line info (#{blob.bytesize} Byte) does not fit into #{section}" if blob.bytesize > kernel_elf.section_size(section)
+
+    File.binwrite(kernel_elf.path, blob, kernel_elf.section_offset_in_file(section))
+end
+
+def patch_initramfs(kernel_elf, section, dir)
+    blob = CpioArchive.new(dir).to_binary
+
+    raise "Initramfs (#{blob.bytesize} Byte) does not fit into #{section}" if blob.bytesize > kernel_elf.section_size(section)
+
+    File.binwrite(kernel_elf.path, blob, kernel_elf.section_offset_in_file(section))
+end
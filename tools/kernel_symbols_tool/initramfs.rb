@@ -0,0 +1,60 @@
+# frozen_string_literal: true
+
+# Packs a directory into a cpio archive in the "newc" format.
+class CpioArchive
+    MAGIC = '070701'
+    TRAILER = 'TRAILER!!!'
+
+    MODE_DIRECTORY = 0o040000
+    MODE_FILE = 0o100000
+
+    def initialize(dir)
+        @dir = dir
+    end
+
+    def to_binary
+        out = String.new(encoding: Encoding::BINARY)
+
+        paths.each_with_index do |path, index|
+            full_path = File.join(@dir, path)
+            stat = File.stat(full_path)
+
+            if stat.directory?
+                append(out, index + 1, path, MODE_DIRECTORY | 0o755, 2, '')
+            else
+                mode = MODE_FILE | (stat.mode & 0o777)
+                append(out, index + 1, path, mode, 1, File.binread(full_path))
+            end
+        end
+        append(out, 0, TRAILER, 0, 1, '')
+
+        out
+    end
+
+    private
+
+    # Sorted, so that directories precede their contents. Symbolic links and special files are
+    # skipped.
+    def paths
+        Dir.glob('**/*', File::FNM_DOTMATCH, base: @dir).sort.select do |path|
+            full_path = File.join(@dir, path)
+            next false if %w[. ..].include?(File.basename(path)) || File.symlink?(full_path)
+
+            File.directory?(full_path) || File.file?(full_path)
+        end
+    end
+
+    def append(out, ino, name, mode, nlink, data)
+        name = name.b
+        fields = [ino, mode, 0, 0, nlink, 0, data.bytesize, 0, 0, 0, 0, name.bytesize + 1, 0]
+
+        out << MAGIC << fields.map { |field| format('%08X', field) }.join << name << "\0"
+        pad(out)
+        out << data.b
+        pad(out)
+    end
+
+    def pad(out)
+        out << ("\0" * (-out.bytesize % 4))
+    end
+end
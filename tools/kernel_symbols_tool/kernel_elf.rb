@@ -47,9 +47,14 @@ class KernelELF
 
     public
 
+    # The kernel binary-searches the table, so it must be sorted by address. Aliases that cover the
+    # exact same range are reduced to a single entry.
     def symbols
-        non_zero_symbols = @symtab_section.symbols.reject { |sym| sym.header.st_size.zero? }
-        non_zero_symbols.sort_by { |sym| sym.header.st_value }
+        @symbols ||= begin
+            non_zero_symbols = @symtab_section.symbols.reject { |sym| sym.header.st_size.zero? }
+            sorted = non_zero_symbols.sort_by { |sym| [sym.header.st_value, sym.header.st_size, sym.name] }
+            sorted.uniq { |sym| [sym.header.st_value, sym.header.st_size] }
+        end
     end
 
     def num_symbols
@@ -71,4 +76,30 @@ class KernelELF
     def num_kernel_symbols_offset_in_file
         virt_addr_to_file_offset(num_kernel_symbols_virt_addr)
     end
+
+    # Returns nil if the section does not exist.
+    def section_data(name)
+        @elf.section_by_name(name)&.data
+    end
+
+    def section_size(name)
+        section = @elf.section_by_name(name)
+        raise "Section \"#{name}\" not found" if section.nil?
+
+        section.header.sh_size.to_i
+    end
+
+    def section_offset_in_file(name)
+        section = @elf.section_by_name(name)
+        raise "Section \"#{name}\" not found" if section.nil?
+
+        virt_addr_to_file_offset(section.header.sh_addr.to_i)
+    end
+
+    def code_range
+        text = @elf.section_by_name('.text')
+        start = text.header.sh_addr.to_i
+
+        start...(start + text.header.sh_size.to_i)
+    end
 end
\ No newline at end of file
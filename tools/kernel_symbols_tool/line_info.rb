@@ -0,0 +1,369 @@
+# frozen_string_literal: true
+
+# Reader for binary strings, with the primitive encodings used by DWARF.
+class DwarfReader
+    attr_accessor :pos
+
+    def initialize(data, pos = 0)
+        @data = data
+        @pos = pos
+    end
+
+    def u8
+        read(1, 'C')
+    end
+
+    def u16
+        read(2, 'S<')
+    end
+
+    def u32
+        read(4, 'L<')
+    end
+
+    def u64
+        read(8, 'Q<')
+    end
+
+    def i8
+        read(1, 'c')
+    end
+
+    def uint(size)
+        case size
+        when 1 then u8
+        when 2 then u16
+        when 4 then u32
+        when 8 then u64
+        else raise "Unsupported integer size #{size}"
+        end
+    end
+
+    def uleb128
+        result = 0
+        shift = 0
+        loop do
+            byte = u8
+            result |= (byte & 0x7f) << shift
+            shift += 7
+            return result if (byte & 0x80).zero?
+        end
+    end
+
+    def sleb128
+        result = 0
+        shift = 0
+        loop do
+            byte = u8
+            result |= (byte & 0x7f) << shift
+            shift += 7
+            next unless (byte & 0x80).zero?
+
+            result -= (1 << shift) if (byte & 0x40).positive?
+            return result
+        end
+    end
+
+    def cstring
+        terminator = @data.index("\0", @pos)
+        raise 'Unterminated string' if terminator.nil?
+
+        str = @data.byteslice(@pos, terminator - @pos).force_encoding(Encoding::UTF_8)
+        @pos = terminator + 1
+        str
+    end
+
+    def skip(size)
+        @pos += size
+    end
+
+    private
+
+    def read(size, format)
+        raise 'Read beyond end of section' if @pos + size > @data.bytesize
+
+        value = @data.byteslice(@pos, size).unpack1(format)
+        @pos += size
+        value
+    end
+end
+
+# Decoder for the line number programs in .debug_line. Supports DWARF versions 2 to 5.
+#
+# See chapter 6.2 "Line Number Information" of the DWARF 5 standard.
+class DwarfLineTable
+    DW_LNS_COPY = 1
+    DW_LNS_ADVANCE_PC = 2
+    DW_LNS_ADVANCE_LINE = 3
+    DW_LNS_SET_FILE = 4
+    DW_LNS_CONST_ADD_PC = 8
+    DW_LNS_FIXED_ADVANCE_PC = 9
+
+    DW_LNE_END_SEQUENCE = 1
+    DW_LNE_SET_ADDRESS = 2
+    DW_LNE_DEFINE_FILE = 3
+
+    DW_LNCT_PATH = 1
+    DW_LNCT_DIRECTORY_INDEX = 2
+
+    DW_FORM_BLOCK = 0x09
+    DW_FORM_DATA1 = 0x0b
+    DW_FORM_DATA2 = 0x05
+    DW_FORM_DATA4 = 0x06
+    DW_FORM_DATA8 = 0x07
+    DW_FORM_DATA16 = 0x1e
+    DW_FORM_LINE_STRP = 0x1f
+    DW_FORM_STRING = 0x08
+    DW_FORM_STRP = 0x0e
+    DW_FORM_UDATA = 0x0f
+
+    # A row of the line table. `line` is nil for the end of a sequence.
+    Row = Struct.new(:addr, :file, :line)
+
+    # Returns an array of sequences. Each is an array of rows, terminated by an end of sequence row.
+    attr_reader :sequences
+
+    def initialize(debug_line, debug_line_str, debug_str)
+        @debug_line_str = debug_line_str
+        @debug_str = debug_str
+        @sequences = []
+
+        reader = DwarfReader.new(debug_line)
+        decode_unit(reader) while reader.pos < debug_line.bytesize
+    end
+
+    private
+
+    def decode_unit(reader)
+        unit_length = reader.u32
+        offset_size = 4
+        if unit_length == 0xffff_ffff
+            unit_length = reader.u64
+            offset_size = 8
+        end
+        unit_end = reader.pos + unit_length
+
+        header = decode_header(reader, offset_size)
+        decode_program(reader, header, unit_end)
+
+        reader.pos = unit_end
+    end
+
+    # rubocop:disable Metrics/AbcSize
+    def decode_header(reader, offset_size)
+        h = { version: reader.u16, offset_size: offset_size }
+        if h[:version] >= 5
+            h[:address_size] = reader.u8
+            reader.u8 # segment_selector_size
+        end
+
+        header_length = reader.uint(offset_size)
+        program_start = reader.pos + header_length
+
+        h[:min_inst_length] = reader.u8
+        reader.u8 if h[:version] >= 4 # maximum_operations_per_instruction
+        reader.u8 # default_is_stmt
+        h[:line_base] = reader.i8
+        h[:line_range] = reader.u8
+        h[:opcode_base] = reader.u8
+        h[:standard_opcode_lengths] = Array.new(h[:opcode_base] - 1) { reader.u8 }
+
+        h[:files] = h[:version] >= 5 ? decode_files_v5(reader, offset_size) : decode_files_v4(reader)
+
+        reader.pos = program_start
+        h
+    end
+    # rubocop:enable Metrics/AbcSize
+
+    # Before DWARF 5, directory and file indices are 1-based and index 0 is the compilation unit's
+    # directory. Files in it are kept relative.
+    def decode_files_v4(reader)
+        dirs = [nil]
+        loop do
+            dir = reader.cstring
+            break if dir.empty?
+
+            dirs << dir
+        end
+
+        files = [nil]
+        loop do
+            name = reader.cstring
+            break if name.empty?
+
+            dir_index = reader.uleb128
+            reader.uleb128 # modification time
+            reader.uleb128 # file length
+            files << join_path(dirs[dir_index], name)
+        end
+        files
+    end
+
+    # Since DWARF 5, indices are 0-based and entry 0 describes the compilation unit itself. Paths
+    # below its directory are made relative, like for older versions.
+    def decode_files_v5(reader, offset_size)
+        dirs = decode_entries(reader, offset_size).map { |entry| entry[DW_LNCT_PATH] }
+        comp_dir = "#{dirs.first}/"
+
+        decode_entries(reader, offset_size).map do |entry|
+            dir = dirs[entry[DW_LNCT_DIRECTORY_INDEX] || 0]
+            dir = dir == dirs.first ? nil : dir&.delete_prefix(comp_dir)
+            join_path(dir, entry[DW_LNCT_PATH].delete_prefix(comp_dir))
+        end
+    end
+
+    def decode_entries(reader, offset_size)
+        format = Array.new(reader.u8) { [reader.uleb128, reader.uleb128] }
+
+        Array.new(reader.uleb128) do
+            format.to_h { |content_type, form| [content_type, read_form(reader, form, offset_size)] }
+        end
+    end
+
+    def read_form(reader, form, offset_size)
+        case form
+        when DW_FORM_STRING then reader.cstring
+        when DW_FORM_LINE_STRP then string_at(@debug_line_str, reader.uint(offset_size))
+        when DW_FORM_STRP then string_at(@debug_str, reader.uint(offset_size))
+        when DW_FORM_UDATA then reader.uleb128
+        when DW_FORM_DATA1 then reader.u8
+        when DW_FORM_DATA2 then reader.u16
+        when DW_FORM_DATA4 then reader.u32
+        when DW_FORM_DATA8 then reader.u64
+        when DW_FORM_DATA16 then reader.skip(16)
+        when DW_FORM_BLOCK then reader.skip(reader.uleb128)
+        else raise "Unsupported form 0x#{form.to_s(16)} in line table header"
+        end
+    end
+
+    def string_at(section, offset)
+        raise 'String section missing' if section.nil?
+
+        DwarfReader.new(section, offset).cstring
+    end
+
+    def join_path(dir, name)
+        return name if dir.nil? || name.start_with?('/')
+
+        "#{dir}/#{name}"
+    end
+
+    # rubocop:disable Metrics/AbcSize
+    # rubocop:disable Metrics/CyclomaticComplexity
+    # rubocop:disable Metrics/MethodLength
+    def decode_program(reader, header, unit_end)
+        files = header[:files]
+        state = { addr: 0, file: 1, line: 1 }
+        sequence = []
+
+        emit = lambda do
+            sequence << Row.new(state[:addr], files[state[:file]], state[:line])
+        end
+
+        advance = lambda do |operation_advance|
+            state[:addr] += operation_advance * header[:min_inst_length]
+        end
+
+        while reader.pos < unit_end
+            opcode = reader.u8
+
+            if opcode >= header[:opcode_base]
+                adjusted = opcode - header[:opcode_base]
+                advance.call(adjusted / header[:line_range])
+                state[:line] += header[:line_base] + (adjusted % header[:line_range])
+                emit.call
+                next
+            end
+
+            case opcode
+            when 0
+                len = reader.uleb128
+                sub_start = reader.pos
+                case reader.u8
+                when DW_LNE_END_SEQUENCE
+                    sequence << Row.new(state[:addr], nil, nil)
+                    @sequences << sequence
+                    sequence = []
+                    state = { addr: 0, file: 1, line: 1 }
+                when DW_LNE_SET_ADDRESS
+                    state[:addr] = reader.uint(len - 1)
+                when DW_LNE_DEFINE_FILE
+                    files << reader.cstring
+                end
+                reader.pos = sub_start + len
+            when DW_LNS_COPY then emit.call
+            when DW_LNS_ADVANCE_PC then advance.call(reader.uleb128)
+            when DW_LNS_ADVANCE_LINE then state[:line] += reader.sleb128
+            when DW_LNS_SET_FILE then state[:file] = reader.uleb128
+            when DW_LNS_CONST_ADD_PC then advance.call((255 - header[:opcode_base]) / header[:line_range])
+            when DW_LNS_FIXED_ADVANCE_PC then state[:addr] += reader.u16
+            else
+                # Skip the operands of all other standard opcodes.
+                header[:standard_opcode_lengths][opcode - 1].times { reader.uleb128 }
+            end
+        end
+    end
+    # rubocop:enable Metrics/AbcSize
+    # rubocop:enable Metrics/CyclomaticComplexity
+    # rubocop:enable Metrics/MethodLength
+end
+
+# Generates the compact line table that is patched into the kernel. See the `line_info` module of
+# the `debug-symbol-types` library for the layout.
+class LineInfoBlob
+    MAGIC = 0x454e_494c
+
+    # Sequences outside of `code_range` belong to code that was discarded by the linker.
+    def initialize(line_table, code_range)
+        @base_addr = code_range.first
+        @files = {}
+        @rows = []
+
+        line_table.sequences
+                  .select { |sequence| code_range.cover?(sequence.first.addr) }
+                  .sort_by { |sequence| sequence.first.addr }
+                  .each { |sequence| add_sequence(sequence) }
+    end
+
+    def to_binary
+        strings = @files.keys.join.b
+        header = [MAGIC, @rows.size, @files.size, strings.bytesize, @base_addr].pack('L<L<L<L<Q<')
+        rows = @rows.map { |offset, file, line| [offset, file, line].pack('L<L<L<') }.join
+
+        offset = 0
+        files = @files.keys.map do |name|
+            entry = [offset, name.bytesize].pack('L<L<')
+            offset += name.bytesize
+            entry
+        end.join
+
+        header + rows + files + strings
+    end
+
+    private
+
+    # Rust keeps sources of the standard library and dependencies under long, machine specific
+    # prefixes. Only keep the part that identifies the crate.
+    def shorten(file)
+        file.sub(%r{\A/rustc/[0-9a-f]+/}, '')
+            .sub(%r{\A.*/registry/src/[^/]+/}, '')
+    end
+
+    def file_index(file)
+        @files[shorten(file || '??')] ||= @files.size
+    end
+
+    # Consecutive rows for the same line are merged. The end of a sequence becomes a row with line
+    # zero, unless the next sequence starts right there.
+    def add_sequence(sequence)
+        sequence.each do |row|
+            offset = row.addr - @base_addr
+            @rows.pop if !@rows.empty? && @rows.last[0] == offset
+
+            entry = row.line.nil? ? [offset, 0, 0] : [offset, file_index(row.file), row.line]
+            next if !@rows.empty? && @rows.last[1..] == entry[1..]
+
+            @rows << entry
+        end
+    end
+end
@@ -7,10 +7,14 @@ require 'colorize'
 require 'elftools'
 
 require_relative 'kernel_elf'
+require_relative 'line_info'
+require_relative 'initramfs'
 require_relative 'cmds'
 
 KERNEL_SYMBOLS_SECTION = '.kernel_symbols'
 NUM_KERNEL_SYMBOLS = 'NUM_KERNEL_SYMBOLS'
+KERNEL_LINE_INFO_SECTION = '.kernel_line_info'
+KERNEL_INITRAMFS_SECTION = '.kernel_initramfs'
 
 cmd = ARGV[0]
 
@@ -38,6 +42,18 @@ when '--patch_data'
 
     patch_symbol_data(kernel_elf, symbols_blob_path)
     patch_num_symbols(kernel_elf)
+when '--patch_line_info'
+    print 'Patching'.rjust(12).green.bold
+    puts ' Source line info into ELF'
+
+    patch_line_info(kernel_elf, KERNEL_LINE_INFO_SECTION)
+when '--patch_initramfs'
+    initramfs_dir = ARGV[2]
+
+    print 'Patching'.rjust(12).green.bold
+    puts " Initramfs archive of #{initramfs_dir} into ELF"
+
+    patch_initramfs(kernel_elf, KERNEL_INITRAMFS_SECTION, initramfs_dir)
 else
     raise
 end
\ No newline at end of file